@@ -5,7 +5,7 @@
 //! actual contract execution all behave identically, preventing economic exploits and
 //! inconsistencies.
 
-use crate::types::U256;
+use crate::types::{CurveKind, U256, BASIS_POINTS, MINIMUM_LIQUIDITY, PRICE_PRECISION};
 use anyhow::{anyhow, Result};
 
 /// Calculates the output amount for a swap, given input amount and reserves.
@@ -31,6 +31,9 @@ pub fn calculate_swap_out(
     if reserve_in == 0 || reserve_out == 0 {
         return Err(anyhow!("Insufficient liquidity"));
     }
+    if fee_bps > BASIS_POINTS {
+        return Err(anyhow!("Fee cannot exceed 100%"));
+    }
 
     let amount_in_u256 = U256::from(amount_in);
     let reserve_in_u256 = U256::from(reserve_in);
@@ -46,7 +49,253 @@ pub fn calculate_swap_out(
     }
 
     let amount_out = numerator / denominator;
-    Ok(amount_out.try_into()?)
+    amount_out.try_into().map_err(|_| anyhow!("Swap output exceeds u128"))
+}
+
+/// Calculates the input amount needed to receive exactly `amount_out` from a swap.
+/// This inverts `calculate_swap_out`, rounding up so the returned input always buys at
+/// least `amount_out`.
+///
+/// # Arguments
+/// * `amount_out` - The desired amount of the output token.
+/// * `reserve_in` - The reserve of the input token in the pool.
+/// * `reserve_out` - The reserve of the output token in the pool.
+/// * `fee_bps` - The swap fee in basis points (e.g., 30 for 0.3%).
+///
+/// # Returns
+/// The smallest input amount whose output is at least `amount_out`.
+pub fn calculate_swap_in(
+    amount_out: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    fee_bps: u128,
+) -> Result<u128> {
+    if amount_out == 0 {
+        return Err(anyhow!("Output amount cannot be zero"));
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(anyhow!("Insufficient liquidity"));
+    }
+    if amount_out >= reserve_out {
+        return Err(anyhow!("Output {} must be below the output reserve {}", amount_out, reserve_out));
+    }
+    if fee_bps >= BASIS_POINTS {
+        return Err(anyhow!("Fee rate {} leaves nothing to swap", fee_bps));
+    }
+
+    // amount_in = reserve_in * amount_out / ((reserve_out - amount_out) * (1 - fee)), rounded up
+    let numerator = U256::from(reserve_in) * U256::from(amount_out) * U256::from(BASIS_POINTS);
+    let denominator = U256::from(reserve_out - amount_out) * U256::from(BASIS_POINTS - fee_bps);
+    let amount_in = numerator.div_ceil(denominator);
+
+    amount_in.try_into().map_err(|_| anyhow!("Swap input exceeds u128"))
+}
+
+/// Checks that a swap kept the pool's constant product: the reserves after the swap, with
+/// only the fee-adjusted input counted, must multiply to at least what they did before.
+/// A factory that pays out more than `calculate_swap_out` allows fails the check.
+///
+/// # Arguments
+/// * `reserve_in_before` - The pool's reserve of the input token before the swap.
+/// * `reserve_out_before` - The pool's reserve of the output token before the swap.
+/// * `amount_in` - The amount of the input token swapped.
+/// * `amount_out` - The amount of the output token paid out.
+/// * `fee_bps` - The swap fee in basis points (e.g., 30 for 0.3%).
+///
+/// # Returns
+/// Whether `(reserve_in + amount_in_after_fee) * (reserve_out - amount_out)` is at least
+/// `reserve_in * reserve_out`. Paying out the whole output reserve or more never is.
+pub fn verify_constant_product(
+    reserve_in_before: u128,
+    reserve_out_before: u128,
+    amount_in: u128,
+    amount_out: u128,
+    fee_bps: u128,
+) -> bool {
+    if amount_out >= reserve_out_before || fee_bps > BASIS_POINTS {
+        return false;
+    }
+
+    // Scaled by BASIS_POINTS on both sides so the fee-adjusted input is not rounded
+    let amount_in_with_fee = U256::from(amount_in) * U256::from(BASIS_POINTS - fee_bps);
+    let reserve_in_after = U256::from(reserve_in_before) * U256::from(BASIS_POINTS) + amount_in_with_fee;
+    let k_after = reserve_in_after * U256::from(reserve_out_before - amount_out);
+    let k_before = U256::from(reserve_in_before) * U256::from(reserve_out_before) * U256::from(BASIS_POINTS);
+    k_after >= k_before
+}
+
+/// Reduces `amount` by a slippage tolerance, multiplying in U256 before dividing so large
+/// amounts neither overflow nor lose precision.
+///
+/// # Arguments
+/// * `amount` - The expected amount.
+/// * `slippage_bps` - The tolerated shortfall in basis points (e.g., 50 for 0.5%).
+///
+/// # Returns
+/// The smallest acceptable amount, rounded down.
+pub fn apply_slippage(amount: u128, slippage_bps: u128) -> Result<u128> {
+    if slippage_bps > BASIS_POINTS {
+        return Err(anyhow!("Slippage tolerance cannot exceed 100%"));
+    }
+
+    let minimum = U256::from(amount) * U256::from(BASIS_POINTS - slippage_bps) / U256::from(BASIS_POINTS);
+    minimum.try_into().map_err(|_| anyhow!("Slippage-adjusted amount exceeds u128"))
+}
+
+/// Calculates how much of a single-sided deposit to swap through the pool it is deposited
+/// into, so that the unswapped remainder and the swap proceeds match the post-swap ratio.
+///
+/// Solves `(amount_in - s) / swap_out(s) = (reserve_in + s) / (reserve_out - swap_out(s))` for
+/// `s` on a constant product pool, which with `g = 1 - fee` gives
+/// `s = (sqrt(r^2 (1 + g)^2 + 4 g r amount_in) - r (1 + g)) / 2g`.
+///
+/// # Arguments
+/// * `amount_in` - The full amount of the deposited token.
+/// * `reserve_in` - The pool's reserve of the deposited token.
+/// * `fee_bps` - The swap fee in basis points (e.g., 30 for 0.3%).
+///
+/// # Returns
+/// The amount of the deposited token to swap for the other side. An empty pool or a fee
+/// that takes the whole swap leaves nothing worth swapping, so the result is zero.
+pub fn optimal_swap_fraction(amount_in: u128, reserve_in: u128, fee_bps: u128) -> u128 {
+    if amount_in == 0 || reserve_in == 0 || fee_bps >= BASIS_POINTS {
+        return 0;
+    }
+
+    // `s` scales linearly with the reserve and amount together, so shift both below 2^112,
+    // where every term below fits U256, and shift the result back
+    let shift = (u128::BITS - reserve_in.max(amount_in).leading_zeros()).saturating_sub(112);
+    let (amount, reserve) = (U256::from(amount_in >> shift), U256::from(reserve_in >> shift));
+
+    // Scaled by BASIS_POINTS: g = G / BP, so s = (sqrt(r^2 (BP + G)^2 + 4 G BP r A) - r (BP + G)) / 2G
+    let retained = U256::from(BASIS_POINTS - fee_bps);
+    let scaled_reserve = reserve * (U256::from(BASIS_POINTS) + retained);
+    let discriminant = scaled_reserve * scaled_reserve
+        + U256::from(4u8) * retained * U256::from(BASIS_POINTS) * reserve * amount;
+    let swap_amount = (integer_sqrt(discriminant) - scaled_reserve) / (U256::from(2u8) * retained);
+
+    // Rounding can only shave the root, so the swap never exceeds the input
+    (swap_amount.to::<u128>() << shift).min(amount_in)
+}
+
+/// Calculates the output amount for a swap on a two-coin StableSwap pool.
+/// This follows Curve's invariant, which trades close to 1:1 around balance and
+/// falls back towards constant product as the pool becomes imbalanced.
+///
+/// # Arguments
+/// * `amount_in` - The amount of the input token.
+/// * `reserve_in` - The reserve of the input token in the pool.
+/// * `reserve_out` - The reserve of the output token in the pool.
+/// * `fee_bps` - The swap fee in basis points, charged on the input.
+/// * `amp` - The amplification coefficient; higher values flatten the curve.
+///
+/// # Returns
+/// The calculated output amount of the target token.
+pub fn calculate_swap_out_stable(
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    fee_bps: u128,
+    amp: u128,
+) -> Result<u128> {
+    if amount_in == 0 {
+        return Err(anyhow!("Input amount cannot be zero"));
+    }
+    if reserve_in == 0 || reserve_out == 0 {
+        return Err(anyhow!("Insufficient liquidity"));
+    }
+    if amp == 0 {
+        return Err(anyhow!("Amplification coefficient cannot be zero"));
+    }
+    if fee_bps > BASIS_POINTS {
+        return Err(anyhow!("Fee cannot exceed 100%"));
+    }
+
+    let amount_in_with_fee =
+        U256::from(amount_in) * U256::from(BASIS_POINTS - fee_bps) / U256::from(BASIS_POINTS);
+    let reserve_in_u256 = U256::from(reserve_in);
+    let reserve_out_u256 = U256::from(reserve_out);
+
+    // A * n^n for n = 2 coins
+    let ann = U256::from(amp) * U256::from(4);
+    let d = stable_invariant(reserve_in_u256, reserve_out_u256, ann)?;
+    let new_reserve_out = stable_balance_out(reserve_in_u256 + amount_in_with_fee, d, ann)?;
+
+    // Round against the trader by one unit, as the invariant is solved approximately
+    let amount_out = reserve_out_u256
+        .saturating_sub(new_reserve_out)
+        .saturating_sub(U256::from(1));
+    amount_out.try_into().map_err(|_| anyhow!("Swap output exceeds u128"))
+}
+
+/// Calculates the swap output along the given pool curve.
+pub fn calculate_swap_out_for_curve(
+    amount_in: u128,
+    reserve_in: u128,
+    reserve_out: u128,
+    fee_bps: u128,
+    curve: CurveKind,
+) -> Result<u128> {
+    match curve {
+        CurveKind::ConstantProduct => calculate_swap_out(amount_in, reserve_in, reserve_out, fee_bps),
+        CurveKind::Stable { amp } => calculate_swap_out_stable(amount_in, reserve_in, reserve_out, fee_bps, amp),
+    }
+}
+
+/// Solves the StableSwap invariant D for balances `x` and `y` using Newton's method.
+fn stable_invariant(x: U256, y: U256, ann: U256) -> Result<U256> {
+    let overflow = || anyhow!("Overflow in stable swap invariant");
+    let two = U256::from(2);
+    let sum = x + y;
+    let mut d = sum;
+
+    for _ in 0..255 {
+        // D_P = D^3 / (4 * x * y), built up to keep intermediates small
+        let d_p = d.checked_mul(d).ok_or_else(overflow)? / (x * two);
+        let d_p = d_p.checked_mul(d).ok_or_else(overflow)? / (y * two);
+        let previous = d;
+
+        let numerator = (ann.checked_mul(sum).ok_or_else(overflow)? + d_p * two)
+            .checked_mul(d)
+            .ok_or_else(overflow)?;
+        let denominator = (ann - U256::from(1)).checked_mul(d).ok_or_else(overflow)? + d_p * U256::from(3);
+        d = numerator / denominator;
+
+        let delta = if d > previous { d - previous } else { previous - d };
+        if delta <= U256::from(1) {
+            return Ok(d);
+        }
+    }
+
+    Err(anyhow!("Stable swap invariant did not converge"))
+}
+
+/// Solves for the output-side balance that keeps invariant `d` given input-side balance `x`.
+fn stable_balance_out(x: U256, d: U256, ann: U256) -> Result<U256> {
+    let overflow = || anyhow!("Overflow in stable swap balance");
+    let two = U256::from(2);
+
+    // c = D^3 / (4 * x * Ann), b = x + D / Ann
+    let c = d.checked_mul(d).ok_or_else(overflow)? / (x * two);
+    let c = c.checked_mul(d).ok_or_else(overflow)? / (ann * two);
+    let b = x + d / ann;
+    let mut y = d;
+
+    for _ in 0..255 {
+        let previous = y;
+        let numerator = y.checked_mul(y).ok_or_else(overflow)? + c;
+        let denominator = (y * two + b)
+            .checked_sub(d)
+            .ok_or_else(|| anyhow!("Stable swap balance underflow"))?;
+        y = numerator / denominator;
+
+        let delta = if y > previous { y - previous } else { previous - y };
+        if delta <= U256::from(1) {
+            return Ok(y);
+        }
+    }
+
+    Err(anyhow!("Stable swap balance did not converge"))
 }
 
 /// Calculates the number of LP tokens to mint for a given liquidity provision.
@@ -59,7 +308,8 @@ pub fn calculate_swap_out(
 /// * `total_supply` - The current total supply of LP tokens.
 ///
 /// # Returns
-/// The number of LP tokens to be minted.
+/// The number of LP tokens to be minted. Products are taken in `U256`, so only a result
+/// that does not fit `u128` is an error.
 pub fn calculate_lp_tokens_minted(
     amount_a: u128,
     amount_b: u128,
@@ -67,21 +317,208 @@ pub fn calculate_lp_tokens_minted(
     reserve_b: u128,
     total_supply: u128,
 ) -> Result<u128> {
-    if total_supply == 0 {
+    let lp_tokens = if total_supply == 0 {
         // First liquidity provider, LP tokens are geometric mean of amounts
-        let lp_tokens = integer_sqrt(U256::from(amount_a) * U256::from(amount_b));
-        Ok(lp_tokens.try_into()?)
+        integer_sqrt(U256::from(amount_a) * U256::from(amount_b))
     } else {
+        if reserve_a == 0 || reserve_b == 0 {
+            return Err(anyhow!("Cannot mint LP tokens against an empty reserve"));
+        }
         // Subsequent provider, LP tokens are proportional to the lesser of the two amounts
         let lp_from_a = U256::from(amount_a) * U256::from(total_supply) / U256::from(reserve_a);
         let lp_from_b = U256::from(amount_b) * U256::from(total_supply) / U256::from(reserve_b);
-        let min_lp = if lp_from_a < lp_from_b { lp_from_a } else { lp_from_b };
-        Ok(min_lp.try_into()?)
+        lp_from_a.min(lp_from_b)
+    };
+    lp_tokens
+        .try_into()
+        .map_err(|_| anyhow!("LP tokens minted exceed u128"))
+}
+
+/// Calculates the LP token supply of a pool bootstrapped with the given reserves.
+///
+/// The supply is the geometric mean of the reserves, computed with `U256` intermediates so
+/// that reserves near `u128::MAX` cannot overflow. The `MINIMUM_LIQUIDITY` locked at
+/// bootstrap stays part of the supply, so pools that cannot cover it are rejected.
+///
+/// # Arguments
+/// * `reserve_a` - The initial reserve of token A.
+/// * `reserve_b` - The initial reserve of token B.
+///
+/// # Returns
+/// The total supply of LP tokens after bootstrap.
+pub fn calculate_initial_total_supply(reserve_a: u128, reserve_b: u128) -> Result<u128> {
+    let total_supply = integer_sqrt(U256::from(reserve_a) * U256::from(reserve_b));
+    if total_supply < U256::from(MINIMUM_LIQUIDITY) {
+        return Err(anyhow!("Initial liquidity must exceed the minimum locked liquidity"));
     }
+    total_supply
+        .try_into()
+        .map_err(|_| anyhow!("Initial total supply exceeds u128"))
+}
+
+/// Calculates the LP tokens the creator of a new pool receives for its initial deposit.
+///
+/// The pool's supply starts at the geometric mean of the deposit, of which
+/// `MINIMUM_LIQUIDITY` stays locked in the pool for good.
+///
+/// # Arguments
+/// * `amount_a` - The initial deposit of token A.
+/// * `amount_b` - The initial deposit of token B.
+///
+/// # Returns
+/// The LP tokens minted to the creator.
+pub fn calculate_initial_lp_tokens(amount_a: u128, amount_b: u128) -> Result<u128> {
+    Ok(calculate_initial_total_supply(amount_a, amount_b)? - MINIMUM_LIQUIDITY)
+}
+
+/// Quotes how much of each token a deposit consumes at the pool's current ratio.
+///
+/// Mirrors the factory's `AddLiquidity`: token B is matched to all of token A when the
+/// ratio allows it, otherwise token A is matched to all of token B, and the side in excess
+/// of the ratio is left for the caller as dust.
+///
+/// # Arguments
+/// * `amount_a_desired` - The amount of token A offered.
+/// * `amount_b_desired` - The amount of token B offered.
+/// * `reserve_a` - The current reserve of token A.
+/// * `reserve_b` - The current reserve of token B.
+///
+/// # Returns
+/// A tuple containing the amounts of token A and token B actually deposited.
+pub fn quote_add_liquidity(
+    amount_a_desired: u128,
+    amount_b_desired: u128,
+    reserve_a: u128,
+    reserve_b: u128,
+) -> Result<(u128, u128)> {
+    if reserve_a == 0 || reserve_b == 0 {
+        // An empty pool takes whatever ratio the first provider sets
+        return Ok((amount_a_desired, amount_b_desired));
+    }
+    let amount_b_optimal = U256::from(amount_a_desired) * U256::from(reserve_b) / U256::from(reserve_a);
+    if amount_b_optimal <= U256::from(amount_b_desired) {
+        let amount_b_used = amount_b_optimal.try_into().map_err(|_| anyhow!("Deposit amount exceeds u128"))?;
+        Ok((amount_a_desired, amount_b_used))
+    } else {
+        let amount_a_optimal = U256::from(amount_b_desired) * U256::from(reserve_a) / U256::from(reserve_b);
+        let amount_a_used = amount_a_optimal.try_into().map_err(|_| anyhow!("Deposit amount exceeds u128"))?;
+        Ok((amount_a_used, amount_b_desired))
+    }
+}
+
+/// Calculates the underlying token amounts an LP position is currently worth.
+///
+/// The position's value is its pro-rata share of each reserve, so it grows as swap fees
+/// accumulate in the pool.
+///
+/// # Arguments
+/// * `lp_amount` - The amount of LP tokens held.
+/// * `reserve_a` - The current reserve of token A.
+/// * `reserve_b` - The current reserve of token B.
+/// * `total_supply` - The current total supply of LP tokens.
+///
+/// # Returns
+/// A tuple containing the amounts of token A and token B backing the position.
+pub fn calculate_lp_share(
+    lp_amount: u128,
+    reserve_a: u128,
+    reserve_b: u128,
+    total_supply: u128,
+) -> Result<(u128, u128)> {
+    if total_supply == 0 {
+        return Err(anyhow!("Pool has no LP supply"));
+    }
+    if lp_amount > total_supply {
+        return Err(anyhow!("LP amount {} exceeds total supply {}", lp_amount, total_supply));
+    }
+    let amount_a = U256::from(lp_amount) * U256::from(reserve_a) / U256::from(total_supply);
+    let amount_b = U256::from(lp_amount) * U256::from(reserve_b) / U256::from(total_supply);
+    Ok((
+        amount_a.try_into().map_err(|_| anyhow!("LP share exceeds u128"))?,
+        amount_b.try_into().map_err(|_| anyhow!("LP share exceeds u128"))?,
+    ))
+}
+
+/// Calculates the share of a pool, in basis points, owned by newly minted LP tokens.
+///
+/// # Arguments
+/// * `lp_minted` - The amount of LP tokens minted by the deposit.
+/// * `total_supply` - The pool's LP supply before the deposit.
+///
+/// # Returns
+/// `lp_minted * BASIS_POINTS / (total_supply + lp_minted)`, rounded down.
+pub fn calculate_pool_share_bps(lp_minted: u128, total_supply: u128) -> Result<u128> {
+    let supply_after = U256::from(total_supply) + U256::from(lp_minted);
+    if supply_after.is_zero() {
+        return Err(anyhow!("Pool has no LP supply"));
+    }
+    let share = U256::from(lp_minted) * U256::from(BASIS_POINTS) / supply_after;
+    share.try_into().map_err(|_| anyhow!("Pool share exceeds u128"))
+}
+
+/// Calculates how far a trade's effective price strays from a reference price, in basis points.
+///
+/// # Arguments
+/// * `amount_in` - The amount of the input token.
+/// * `amount_out` - The amount of the output token received.
+/// * `reference_price` - Expected output per unit of input, scaled by 1e18.
+///
+/// # Returns
+/// The absolute deviation of the effective price from the reference in basis points.
+pub fn calculate_price_deviation(amount_in: u128, amount_out: u128, reference_price: u128) -> Result<u128> {
+    if amount_in == 0 || reference_price == 0 {
+        return Err(anyhow!("Cannot compare prices with zero input or reference"));
+    }
+    let effective_price = U256::from(amount_out) * U256::from(PRICE_PRECISION) / U256::from(amount_in);
+    let reference_price = U256::from(reference_price);
+    let difference = if effective_price > reference_price {
+        effective_price - reference_price
+    } else {
+        reference_price - effective_price
+    };
+    let deviation_bps = difference * U256::from(BASIS_POINTS) / reference_price;
+    Ok(deviation_bps.try_into().unwrap_or(u128::MAX))
+}
+
+/// Calculates the part of an LP position's pro-rata share that swap fees have added.
+///
+/// A pool's supply starts at the geometric mean of its reserves and deposits mint in
+/// proportion, so without fees the square root of the reserve product stays equal to the
+/// supply. Fees grow the reserves without minting, and the share's excess over what the
+/// supply alone accounts for is the fee appreciation since the pool was created.
+///
+/// # Arguments
+/// * `lp_amount` - The amount of LP tokens held.
+/// * `reserve_a` - The current reserve of token A.
+/// * `reserve_b` - The current reserve of token B.
+/// * `total_supply` - The current total supply of LP tokens.
+///
+/// # Returns
+/// A tuple containing the amounts of token A and token B the position has earned in fees.
+pub fn calculate_lp_fee_share(
+    lp_amount: u128,
+    reserve_a: u128,
+    reserve_b: u128,
+    total_supply: u128,
+) -> Result<(u128, u128)> {
+    let (amount_a, amount_b) = calculate_lp_share(lp_amount, reserve_a, reserve_b, total_supply)?;
+    let root_k = integer_sqrt(U256::from(reserve_a) * U256::from(reserve_b));
+    if root_k <= U256::from(total_supply) {
+        return Ok((0, 0));
+    }
+    let fee_part = |amount: u128| -> Result<u128> {
+        let principal: u128 = (U256::from(amount) * U256::from(total_supply) / root_k).try_into()?;
+        Ok(amount - principal)
+    };
+    Ok((fee_part(amount_a)?, fee_part(amount_b)?))
 }
 
 /// Calculates the price impact of a trade in basis points.
 ///
+/// Impact is measured against the mid-price before the trade:
+/// `1 - (amount_out / amount_in) / (reserve_out / reserve_in)`. Comparing against the
+/// post-trade price instead would understate it. Fees paid are included in the impact.
+///
 /// # Arguments
 /// * `amount_in` - The amount of the input token.
 /// * `reserve_in` - The reserve of the input token before the trade.
@@ -89,45 +526,54 @@ pub fn calculate_lp_tokens_minted(
 /// * `reserve_out` - The reserve of the output token before the trade.
 ///
 /// # Returns
-/// The price impact in basis points (e.g., 100 for 1%).
+/// The price impact in basis points (e.g., 100 for 1%), always within `[0, BASIS_POINTS]`.
+/// A pool with no output reserve has no price to measure against and is an error.
 pub fn calculate_price_impact(
     amount_in: u128,
     reserve_in: u128,
     amount_out: u128,
     reserve_out: u128,
 ) -> Result<u128> {
-    if reserve_in == 0 || reserve_out == 0 {
-        return Ok(10000); // 100% impact if no liquidity
+    if reserve_out == 0 {
+        return Err(anyhow!("Cannot measure price impact against an empty output reserve"));
+    }
+    if reserve_in == 0 {
+        return Ok(BASIS_POINTS); // 100% impact if no input-side liquidity
     }
 
-    let amount_in_u256 = U256::from(amount_in);
-    let reserve_in_u256 = U256::from(reserve_in);
-    let reserve_out_u256 = U256::from(reserve_out);
-
-    // Ideal amount out without slippage (mid-price), ignoring fees for impact calculation
-    let ideal_out = (amount_in_u256 * reserve_out_u256) / reserve_in_u256;
-    let actual_out = U256::from(amount_out);
+    // Output at the mid-price, scaled by reserve_in so no precision is lost to division
+    let ideal_out_scaled = U256::from(amount_in) * U256::from(reserve_out);
+    let actual_out_scaled = U256::from(amount_out) * U256::from(reserve_in);
 
-    if ideal_out.is_zero() {
-        return Ok(10000); // Cannot calculate impact if ideal output is zero
+    if ideal_out_scaled.is_zero() {
+        return Ok(BASIS_POINTS); // Cannot calculate impact if ideal output is zero
     }
     
     // The difference between the ideal output and the actual output
-    let impact_diff = if ideal_out > actual_out {
-        ideal_out - actual_out
-    } else {
-        U256::from(0)
-    };
+    let impact_diff = ideal_out_scaled.saturating_sub(actual_out_scaled);
 
     // Price impact as a percentage of the ideal output
-    let impact_bps = (impact_diff * U256::from(10000)) / ideal_out;
+    let impact_bps = (impact_diff * U256::from(BASIS_POINTS)) / ideal_out_scaled;
 
-    Ok(impact_bps.try_into().unwrap_or(10000))
+    Ok(impact_bps.try_into().unwrap_or(BASIS_POINTS).min(BASIS_POINTS))
+}
+
+/// Combine per-hop price impacts (bps) into the impact of the whole path.
+///
+/// Each hop keeps `BASIS_POINTS - impact` of the value left by the previous hops. Hop impacts
+/// are clamped to 100%, so the retained fraction never goes negative and the result always
+/// lies in `[0, BASIS_POINTS]`.
+pub fn compound_price_impact(hop_impacts_bps: &[u128]) -> u128 {
+    let basis_points = U256::from(BASIS_POINTS);
+    let remaining_fraction = hop_impacts_bps.iter().fold(basis_points, |remaining, impact| {
+        remaining * U256::from(BASIS_POINTS - (*impact).min(BASIS_POINTS)) / basis_points
+    });
+    BASIS_POINTS - remaining_fraction.min(basis_points).to::<u128>()
 }
 
 
 /// Integer square root implementation for U256, using Babylonian method.
-fn integer_sqrt(n: U256) -> U256 {
+pub(crate) fn integer_sqrt(n: U256) -> U256 {
     if n.is_zero() {
         return U256::from(0);
     }
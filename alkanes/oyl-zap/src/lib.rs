@@ -18,24 +18,183 @@ pub mod pool_provider;
 pub mod route_finder;
 pub mod zap_calculator;
 
+use pool_provider::{CachedPoolProvider, FlashSwapPoolProvider, PoolProvider};
+use route_finder::RouteFinder;
+use types::{decode_token_list, encode_token_list, ensure_distinct_pair, parse_flag, ensure_distinct_tokens, FactorySwapLayout, HopBreakdown, LpValueEstimate, PoolReserves, QuoteOptions, RouteInfo, StoredPool, ZapConfig, QuoteRecord, ZapError, ZapParams, ZapQuote, ZapReceipt, ZapSimulation, U256, MAX_QUOTE_BATCH, MAX_TOKEN_DECIMALS, QUOTE_HISTORY_LENGTH, RATIO_HISTORY_LENGTH};
+use zap_calculator::ZapCalculator;
+
 // Re-export constants for tests
-pub use types::{DEFAULT_FEE_AMOUNT_PER_1000, MAX_HOPS, BASIS_POINTS, MINIMUM_LIQUIDITY};
-
-// Helper function for integer square root
-fn integer_sqrt(n: u128) -> u128 {
-    if n == 0 {
-        return 0;
-    }
-    
-    let mut x = n;
-    let mut y = (x + 1) / 2;
-    
-    while y < x {
-        x = y;
-        y = (x + n / x) / 2;
-    }
-    
-    x
+pub use types::{DEFAULT_FEE_AMOUNT_PER_1000, DEFAULT_FEE_BPS, MAX_HOPS, BASIS_POINTS, MINIMUM_LIQUIDITY};
+
+/// A token pair in canonical order, lowest `(block, tx)` first, so `(A, B)` and `(B, A)`
+/// name the same pool. `AlkaneId` has no `Ord` of its own, so the fields are compared.
+pub fn canonical_pool_key(token_a: AlkaneId, token_b: AlkaneId) -> (AlkaneId, AlkaneId) {
+    if (token_a.block, token_a.tx) <= (token_b.block, token_b.tx) {
+        (token_a, token_b)
+    } else {
+        (token_b, token_a)
+    }
+}
+
+/// Storage key for data kept per pool under `prefix`, the same whichever order the pair
+/// is given in
+pub fn pool_storage_key(prefix: &str, token_a: &AlkaneId, token_b: &AlkaneId) -> Vec<u8> {
+    let (first, second) = canonical_pool_key(*token_a, *token_b);
+    format!("{}/{}:{}/{}:{}", prefix, first.block, first.tx, second.block, second.tx).into_bytes()
+}
+
+/// Sum the amount of `token` contained in a transfer parcel.
+pub fn received_amount(parcel: &AlkaneTransferParcel, token: &AlkaneId) -> u128 {
+    parcel
+        .0
+        .iter()
+        .filter(|transfer| transfer.id == *token)
+        .fold(0u128, |total, transfer| total.saturating_add(transfer.value))
+}
+
+/// Amounts of each token a deposit took, from how far the zap's own balances of them fell
+/// across the call. Anything the factory refunds lands back in those balances, so this
+/// holds whatever layout it gives its response.
+pub fn consumed_liquidity_amounts(held_before: (u128, u128), held_after: (u128, u128)) -> (u128, u128) {
+    (held_before.0.saturating_sub(held_after.0), held_before.1.saturating_sub(held_after.1))
+}
+
+/// Build the caller's response to an `AddLiquidity` call: the minted LP tokens plus the
+/// `dust_a`/`dust_b` the pool did not consume.
+pub fn settle_add_liquidity(
+    liquidity_result: CallResponse,
+    token_a: AlkaneId,
+    token_b: AlkaneId,
+    dust_a: u128,
+    dust_b: u128,
+) -> CallResponse {
+    let mut response = CallResponse::default();
+    response.alkanes.0.extend(
+        liquidity_result
+            .alkanes
+            .0
+            .into_iter()
+            .filter(|transfer| transfer.id != token_a && transfer.id != token_b),
+    );
+    for (token, dust) in [(token_a, dust_a), (token_b, dust_b)] {
+        if dust > 0 {
+            response.alkanes.0.push(AlkaneTransfer { id: token, value: dust });
+        }
+    }
+    response.data = liquidity_result.data;
+    response
+}
+
+/// Opcodes handled by `OylZapMessage`, reported by `GetVersion` for capability discovery.
+/// Keep in sync with the message enum; `test_supported_opcodes_match_message_enum` fails
+/// when they drift apart.
+pub const SUPPORTED_OPCODES: &[u128] = &[0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 50];
+
+/// Semantic version of the contract as (major, minor, patch), taken from the crate version
+pub fn contract_version() -> (u128, u128, u128) {
+    (
+        env!("CARGO_PKG_VERSION_MAJOR").parse().unwrap_or(0),
+        env!("CARGO_PKG_VERSION_MINOR").parse().unwrap_or(0),
+        env!("CARGO_PKG_VERSION_PATCH").parse().unwrap_or(0),
+    )
+}
+
+/// Packed `GetVersion` payload: major, minor and patch followed by each supported opcode,
+/// all as little-endian u128 values.
+pub fn version_data() -> Vec<u8> {
+    let (major, minor, patch) = contract_version();
+    let mut data = Vec::with_capacity((3 + SUPPORTED_OPCODES.len()) * 16);
+    data.extend_from_slice(&major.to_le_bytes());
+    data.extend_from_slice(&minor.to_le_bytes());
+    data.extend_from_slice(&patch.to_le_bytes());
+    for opcode in SUPPORTED_OPCODES {
+        data.extend_from_slice(&opcode.to_le_bytes());
+    }
+    data
+}
+
+/// Whether `err` only says a pair has no pool or route, rather than that quoting it failed
+pub fn is_unroutable(err: &anyhow::Error) -> bool {
+    matches!(
+        err.downcast_ref::<ZapError>(),
+        Some(ZapError::RouteNotFound { .. } | ZapError::PoolNotFound { .. })
+    )
+}
+
+/// Single-hop leg from the zap input to one target token, or the identity leg when they match
+fn direct_leg_route(input_token: AlkaneId, target: AlkaneId) -> RouteInfo {
+    if input_token == target {
+        RouteInfo::new(vec![input_token], 0)
+    } else {
+        RouteInfo::new(vec![input_token, target], 0)
+    }
+}
+
+/// Adapts a zap contract's pool queries to the `PoolProvider` interface used by routing.
+pub struct ZapPoolProvider<'a, Z: ZapBase + ?Sized> {
+    zap: &'a Z,
+}
+
+impl<'a, Z: ZapBase + ?Sized> ZapPoolProvider<'a, Z> {
+    pub fn new(zap: &'a Z) -> Self {
+        Self { zap }
+    }
+}
+
+impl<'a, Z: ZapBase + ?Sized> PoolProvider for ZapPoolProvider<'a, Z> {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<PoolReserves> {
+        if let Some(pool) = self.zap.stored_pool(token_a, token_b)? {
+            return Ok(pool.to_reserves(token_a, token_b));
+        }
+
+        let (reserve_a, reserve_b, fee_rate) = self.zap.get_pool_reserves_impl(token_a, token_b)?;
+        let pool_id = self.zap.find_pool_id(token_a, token_b)?;
+        let total_supply = self.zap.pool_total_supply(pool_id)?;
+
+        Ok(PoolReserves::new(
+            token_a,
+            token_b,
+            reserve_a,
+            reserve_b,
+            total_supply,
+            fee_rate,
+        ))
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        // The factory offers no adjacency query, so only pools registered through `AddPool`
+        // are known beyond direct pools and base tokens
+        self.zap.get_connected_tokens_impl(token)
+    }
+}
+
+/// Storage flag marking a zap as mid-execution, so a contract it calls cannot reenter it
+pub trait ReentrancyLock {
+    fn is_locked(&self) -> Result<bool>;
+    fn set_locked(&self, locked: bool) -> Result<()>;
+}
+
+/// Holds a `ReentrancyLock` for the rest of an opcode and releases it when dropped, so the
+/// flag clears on every early return and error path as well as on success
+pub struct ReentrancyGuard<'a, L: ReentrancyLock + ?Sized> {
+    lock: &'a L,
+}
+
+impl<'a, L: ReentrancyLock + ?Sized> ReentrancyGuard<'a, L> {
+    pub fn acquire(lock: &'a L) -> Result<Self> {
+        if lock.is_locked()? {
+            return Err(ZapError::Reentrancy.into());
+        }
+        lock.set_locked(true)?;
+        Ok(Self { lock })
+    }
+}
+
+impl<'a, L: ReentrancyLock + ?Sized> Drop for ReentrancyGuard<'a, L> {
+    fn drop(&mut self) {
+        // Drop cannot propagate an error; a failed write reverts the whole call anyway
+        let _ = self.lock.set_locked(false);
+    }
 }
 
 #[derive(MessageDispatch)]
@@ -69,6 +228,7 @@ pub enum OylZapMessage {
         target_token_a: AlkaneId,
         target_token_b: AlkaneId,
         max_slippage_bps: u128,
+        allow_pool_creation: u128,
     },
     #[opcode(4)]
     ExecuteZap {
@@ -79,6 +239,9 @@ pub enum OylZapMessage {
         min_lp_tokens: u128,
         deadline: u128,
         max_slippage_bps: u128,
+        allow_pool_creation: u128,
+        expected_lp_tokens: u128,
+        max_price_impact_bps: u128,
     },
     #[opcode(5)]
     GetBestRoute {
@@ -91,17 +254,425 @@ pub enum OylZapMessage {
         token_a: AlkaneId,
         token_b: AlkaneId,
     },
+    #[opcode(7)]
+    SetMinLpSafetyMargin {
+        margin_bps: u128,
+    },
+    #[opcode(8)]
+    ZapOut {
+        lp_token: AlkaneId,
+        lp_amount: u128,
+        output_token: AlkaneId,
+        min_output: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    },
+    #[opcode(9)]
+    GetVersion {},
+    #[opcode(10)]
+    ZapIntoWeighted {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_tokens: Vec<AlkaneId>,
+        weights_bps: Vec<u128>,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    },
+    #[opcode(11)]
+    RecoverStrandedSwap {
+        token: AlkaneId,
+        amount: u128,
+    },
+    #[opcode(12)]
+    SetMaxRatioVolatility {
+        max_volatility_bps: u128,
+    },
+    #[opcode(13)]
+    EstimateLpValue {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        lp_amount: u128,
+    },
+    #[opcode(14)]
+    GetZapQuoteV2 {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+        deadline: u128,
+    },
+    #[opcode(15)]
+    GetBestRouteBatch {
+        queries: Vec<u128>,
+    },
+    #[opcode(16)]
+    SetReferencePrice {
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+        reference_price: u128,
+        tolerance_bps: u128,
+    },
+    #[opcode(17)]
+    AddBaseToken {
+        token: AlkaneId,
+    },
+    #[opcode(18)]
+    RemoveBaseToken {
+        token: AlkaneId,
+    },
+    #[opcode(19)]
+    SetFlashSwapConfig {
+        supports_flash: u128,
+        flash_fee_bps: u128,
+    },
+    #[opcode(20)]
+    GetFlashZapQuote {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    },
+    #[opcode(21)]
+    GetConfig {},
+    #[opcode(22)]
+    SimulateZap {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+        max_price_impact_bps: u128,
+    },
+    #[opcode(23)]
+    MigrateLp {
+        source_lp: AlkaneId,
+        source_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    },
+    #[opcode(24)]
+    GetLpTokenId {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+    },
+    #[opcode(25)]
+    GetZapQuoteBatch {
+        input_token: AlkaneId,
+        input_amount: u128,
+        pairs: Vec<AlkaneId>,
+        max_slippage_bps: u128,
+    },
+    #[opcode(26)]
+    SetMaxReserveDeviation {
+        max_deviation_bps: u128,
+    },
+    #[opcode(27)]
+    GetPoolId {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+    },
+    #[opcode(28)]
+    Rebalance {
+        from_lp: AlkaneId,
+        from_lp_amount: u128,
+        target_token_c: AlkaneId,
+        target_token_d: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    },
+    #[opcode(29)]
+    SetDefaultSlippage {
+        bps: u128,
+    },
+    #[opcode(30)]
+    CheckRoutable {
+        token: AlkaneId,
+    },
+    #[opcode(31)]
+    SetFeeOnTransfer {
+        token: AlkaneId,
+        fee_on_transfer: u128,
+    },
+    #[opcode(32)]
+    ZapPartial {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+        fraction_bps: u128,
+    },
+    #[opcode(33)]
+    ZapForExactLp {
+        input_token: AlkaneId,
+        max_input: u128,
+        lp_amount_out: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        deadline: u128,
+        max_slippage_bps: u128,
+    },
+    #[opcode(34)]
+    GetConnectedTokens {
+        token: AlkaneId,
+    },
+    #[opcode(35)]
+    SetPaused {
+        paused: u128,
+    },
+    #[opcode(36)]
+    ExecuteZapExact {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        amount_a_min: u128,
+        amount_b_min: u128,
+        min_lp_tokens: u128,
+        deadline: u128,
+    },
+    #[opcode(37)]
+    PreviewDust {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    },
+    #[opcode(38)]
+    SetMaxReserveKDrop {
+        max_drop_bps: u128,
+    },
+    #[opcode(39)]
+    GetPoolUpdateHeight {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+    },
+    #[opcode(40)]
+    GetQuoteHistory {
+        limit: u128,
+    },
+    #[opcode(41)]
+    SetMaxZapInput {
+        amount: u128,
+    },
+    #[opcode(42)]
+    GetSpotPrice {
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+    },
+    #[opcode(43)]
+    GetPoolShare {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    },
+    #[opcode(44)]
+    GetWorstCaseQuote {
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        adversary_amount: u128,
+    },
+    #[opcode(45)]
+    ExplainRoute {
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+    },
+    #[opcode(46)]
+    Sweep {
+        token: AlkaneId,
+        amount: u128,
+        to_vout: u32,
+    },
+    #[opcode(47)]
+    SetTokenDecimals {
+        token: AlkaneId,
+        decimals: u128,
+    },
+    #[opcode(48)]
+    GetTokenDecimals {
+        token: AlkaneId,
+    },
     #[opcode(50)]
     Forward {},
 }
 
-pub trait ZapBase: AuthenticatedResponder {
+pub trait ZapBase: AuthenticatedResponder + ReentrancyLock {
     // Helper methods that need to be implemented
-    fn get_pool_reserves_impl(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<(u128, u128)>;
-    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128) -> Result<u128>;
+    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128, fee_rate: u128) -> Result<u128>;
     fn execute_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse>;
+    fn simulate_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse>;
     fn add_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128, amount_a_min: u128, amount_b_min: u128, deadline: u128) -> Result<CallResponse>;
+    fn create_pool(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128) -> Result<CallResponse>;
+    fn add_liquidity_weighted(&self, tokens: Vec<AlkaneId>, amounts: Vec<u128>, amount_mins: Vec<u128>, deadline: u128) -> Result<CallResponse>;
     fn find_pool_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<AlkaneId>;
+    fn pool_lp_token_id(&self, pool_id: AlkaneId) -> Result<AlkaneId>;
+    fn min_lp_safety_margin_bps(&self) -> Result<u128>;
+    fn pool_tokens(&self, pool_id: AlkaneId) -> Result<(AlkaneId, AlkaneId)>;
+    fn remove_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, liquidity: u128, amount_a_min: u128, amount_b_min: u128, deadline: u128) -> Result<CallResponse>;
+    fn pool_total_supply(&self, pool_id: AlkaneId) -> Result<u128>;
+    fn live_pool_reserves(&self, pool_id: AlkaneId, token_a: AlkaneId) -> Result<(u128, u128, Option<u128>)>;
+    fn oyl_factory_id(&self) -> Result<AlkaneId>;
+    fn base_tokens(&self) -> Result<Vec<AlkaneId>>;
+    fn pool_ratio_history(&self, pool_id: AlkaneId) -> Result<Vec<U256>>;
+    fn record_pool_ratio(&self, pool_id: AlkaneId, ratio: U256) -> Result<()>;
+    fn max_ratio_volatility_bps(&self) -> Result<u128>;
+    fn max_reserve_deviation_bps(&self) -> Result<u128>;
+    fn max_reserve_k_drop_bps(&self) -> Result<u128>;
+    fn max_zap_input(&self) -> Result<u128>;
+    fn default_slippage_bps(&self) -> Result<u128>;
+    fn reference_price(&self, token_in: AlkaneId, token_out: AlkaneId) -> Result<Option<(u128, u128)>>;
+    fn is_fee_on_transfer(&self, token: AlkaneId) -> Result<bool>;
+    fn token_decimals(&self, token: AlkaneId) -> Result<Option<u8>>;
+    fn stored_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<StoredPool>>;
+    fn store_pool(&self, pool: &StoredPool) -> Result<()>;
+    fn get_connected_tokens_impl(&self, token: AlkaneId) -> Result<Vec<AlkaneId>>;
+    fn flash_swap_fee_bps(&self) -> Result<Option<u128>>;
+    fn registered_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<AlkaneId>>;
+    fn register_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId, lp_token_id: AlkaneId) -> Result<()>;
+    fn registered_lp_token_pair(&self, lp_token_id: AlkaneId) -> Result<Option<(AlkaneId, AlkaneId)>>;
+    fn cached_route(&self, from_token: AlkaneId, to_token: AlkaneId) -> Result<Option<RouteInfo>>;
+    fn cache_route(&self, from_token: AlkaneId, to_token: AlkaneId, route: &RouteInfo) -> Result<()>;
+    fn is_paused(&self) -> Result<bool>;
+    fn record_quote(&self, record: &QuoteRecord) -> Result<()>;
+    fn quote_history(&self, limit: u128) -> Result<Vec<QuoteRecord>>;
+
+    /// Reserves of a pair oriented so `token_a`'s comes first, with its fee rate. A pool
+    /// registered through `AddPool` is served from storage without calling out; any other
+    /// is resolved through the factory and read live from the pool.
+    fn get_pool_reserves_impl(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<(u128, u128, u128)> {
+        if let Some(pool) = self.stored_pool(token_a, token_b)? {
+            let (reserve_a, reserve_b) = pool.reserves_for(token_a);
+            return Ok((reserve_a, reserve_b, pool.fee_rate));
+        }
+        
+        let (reserve_a, reserve_b, reported_fee) = self.live_pool_reserves(self.find_pool_id(token_a, token_b)?, token_a)?;
+        // Pools that do not report a fee rate charge the protocol default
+        Ok((reserve_a, reserve_b, reported_fee.unwrap_or(DEFAULT_FEE_BPS)))
+    }
+
+    /// Refuse to move funds while the owner has paused zaps. Quotes and other reads stay
+    /// available, as does recovering stranded swap outputs.
+    fn ensure_not_paused(&self) -> Result<()> {
+        if self.is_paused()? {
+            return Err(ZapError::Paused.into());
+        }
+        Ok(())
+    }
+
+    /// Refuse an input above the owner's per-zap cap, which bounds a zap's size however deep
+    /// the pools it trades against are. The cap is in input-token units, so LP amounts
+    /// handed back to unzap, migrate or rebalance are not held to it.
+    fn ensure_within_max_input(&self, input_amount: u128) -> Result<()> {
+        let max = self.max_zap_input()?;
+        if max != 0 && input_amount > max {
+            return Err(ZapError::InputTooLarge { amount: input_amount, max }.into());
+        }
+        Ok(())
+    }
+
+    /// The zap's own balances of a pair's tokens
+    fn held_balances(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<(u128, u128)> {
+        let myself = self.context()?.myself;
+        Ok((self.balance(&myself, &token_a), self.balance(&myself, &token_b)))
+    }
+
+    /// LP token minted for a pair, asked of the pool once and served from the registry after
+    fn lp_token_id_for_pair(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<AlkaneId> {
+        if let Some(lp_token_id) = self.registered_lp_token_id(token_a, token_b)? {
+            return Ok(lp_token_id);
+        }
+        let lp_token_id = self.pool_lp_token_id(self.find_pool_id(token_a, token_b)?)?;
+        self.register_lp_token_id(token_a, token_b, lp_token_id)?;
+        Ok(lp_token_id)
+    }
+
+    /// Pair behind an LP token: the registry's answer for LP tokens the zap has minted into,
+    /// otherwise the LP token itself is taken to be the pool, as it is for pools minting their
+    /// own shares
+    fn lp_token_pair(&self, lp_token_id: AlkaneId) -> Result<(AlkaneId, AlkaneId)> {
+        match self.registered_lp_token_pair(lp_token_id)? {
+            Some(pair) => Ok(pair),
+            None => self.pool_tokens(lp_token_id),
+        }
+    }
+
+    /// Report the LP token for a pair without writing to the registry
+    fn get_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let lp_token_id = match self.registered_lp_token_id(token_a, token_b)? {
+            Some(lp_token_id) => lp_token_id,
+            None => self.pool_lp_token_id(self.find_pool_id(token_a, token_b)?)?,
+        };
+        
+        response.data.extend_from_slice(&lp_token_id.block.to_le_bytes());
+        response.data.extend_from_slice(&lp_token_id.tx.to_le_bytes());
+        Ok(response)
+    }
+
+    /// Report the pool for a pair, or an empty payload when the factory has none
+    fn get_pool_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        if let Ok(pool_id) = self.find_pool_id(token_a, token_b) {
+            response.data.extend_from_slice(&pool_id.block.to_le_bytes());
+            response.data.extend_from_slice(&pool_id.tx.to_le_bytes());
+        }
+        Ok(response)
+    }
+
+    /// Report how many base tokens `token` can reach within `MAX_HOPS`, as a u128, so
+    /// integrators can check a token is routable before offering it
+    fn check_routable(&self, token: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let pool_provider = ZapPoolProvider::new(self);
+        let reachable = RouteFinder::new(self.oyl_factory_id()?, &pool_provider)
+            .with_base_tokens(self.base_tokens()?)
+            .reachable_base_tokens(token)?;
+        
+        response.data = (reachable.len() as u128).to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Counterparties of `token` across pools registered through `AddPool`, encoded with
+    /// `encode_token_list`
+    fn get_connected_tokens(&self, token: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = encode_token_list(&self.get_connected_tokens_impl(token)?);
+        Ok(response)
+    }
+
+    /// Price of `token_a` in `token_b` scaled by 1e18, for display rather than execution
+    fn get_spot_price(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        let pool_provider = ZapPoolProvider::new(self);
+        let price = RouteFinder::new(self.oyl_factory_id()?, &pool_provider)
+            .with_base_tokens(self.base_tokens()?)
+            .spot_price(token_a, token_b)?;
+        response.data = price.to_le_bytes().to_vec();
+        Ok(response)
+    }
 
     fn initialize(&self, factory_id: AlkaneId, base_tokens: Vec<AlkaneId>) -> Result<CallResponse> {
         let context = self.context()?;
@@ -120,8 +691,30 @@ pub trait ZapBase: AuthenticatedResponder {
         fee_rate: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
-        // In a real implementation, this would store pool data
-        // For now, just return success
+        self.only_owner()?;
+
+        if token_a == token_b {
+            return Err(anyhow!("Pool tokens must differ"));
+        }
+        if fee_rate > BASIS_POINTS {
+            return Err(anyhow!("Fee rate {} exceeds {} bps", fee_rate, BASIS_POINTS));
+        }
+
+        // Resolve the pool once so later quotes and swaps can skip the factory lookup. The
+        // reserves are kept only as the baseline for the reserve sanity check.
+        let pool_id = self.find_pool_id(token_a, token_b)?;
+        // Registering its LP token lets positions in the pool be unwound by LP id
+        self.register_lp_token_id(token_a, token_b, self.pool_lp_token_id(pool_id)?)?;
+        self.store_pool(&StoredPool::new(
+            pool_id,
+            token_a,
+            token_b,
+            reserve_a,
+            reserve_b,
+            total_supply,
+            fee_rate,
+        ))?;
+
         Ok(CallResponse::forward(&context.incoming_alkanes))
     }
 
@@ -134,147 +727,1240 @@ pub trait ZapBase: AuthenticatedResponder {
         total_supply: u128,
     ) -> Result<CallResponse> {
         let context = self.context()?;
-        // In a real implementation, this would update stored pool data
+        self.only_owner()?;
+
+        let mut pool = self
+            .stored_pool(token_a, token_b)?
+            .ok_or(ZapError::PoolNotFound { token_a, token_b })?;
+        
+        // Refuse reserves no real pool could have moved to, e.g. from a faulty feed
+        let updated_reserves = PoolReserves::new(token_a, token_b, reserve_a, reserve_b, total_supply, pool.fee_rate);
+        ZapCalculator::check_reserve_update(
+            &pool.to_reserves(token_a, token_b),
+            &updated_reserves,
+            self.max_reserve_k_drop_bps()?,
+        )?;
+        
+        pool.set_reserves(token_a, reserve_a, reserve_b, total_supply);
+        self.store_pool(&pool)?;
+
         Ok(CallResponse::forward(&context.incoming_alkanes))
     }
 
-    fn get_zap_quote(
-        &self,
-        input_token: AlkaneId,
-        input_amount: u128,
-        target_token_a: AlkaneId,
-        target_token_b: AlkaneId,
-        max_slippage_bps: u128,
-    ) -> Result<CallResponse> {
+    /// Return tokens stranded in the contract, whether by a multi-call orchestration that
+    /// stopped between its swap and liquidity steps or by a parcel that reached the contract
+    /// outside any call. Single-call zaps revert atomically instead.
+    ///
+    /// Holding the reentrancy lock keeps a recovery from running inside an in-flight zap, and
+    /// the parcel sent with this call is not counted as stranded.
+    fn recover_stranded_swap(&self, token: AlkaneId, amount: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
         let context = self.context()?;
-        let mut response = CallResponse::forward(&context.incoming_alkanes);
         
-        // Get pool reserves for the target pair (call implementation method directly)
-        let (reserve_a, reserve_b) = self.get_pool_reserves_impl(target_token_a, target_token_b)?;
-        
-        // Calculate optimal split (50/50 for simplicity, could be optimized)
-        let split_amount = input_amount / 2;
-        
-        // Calculate swap outputs for each half
-        let mut amount_a_out = 0u128;
-        let mut amount_b_out = 0u128;
-        
-        if input_token == target_token_a {
-            amount_a_out = split_amount;
-            // Swap other half to token_b
-            let (reserve_in, reserve_out) = self.get_pool_reserves_impl(input_token, target_token_b)?;
-            amount_b_out = self.calculate_swap_output(split_amount, reserve_in, reserve_out)?;
-        } else if input_token == target_token_b {
-            amount_b_out = split_amount;
-            // Swap other half to token_a
-            let (reserve_in, reserve_out) = self.get_pool_reserves_impl(input_token, target_token_a)?;
-            amount_a_out = self.calculate_swap_output(split_amount, reserve_in, reserve_out)?;
-        } else {
-            // Need to swap both halves
-            let (reserve_in_a, reserve_out_a) = self.get_pool_reserves_impl(input_token, target_token_a)?;
-            amount_a_out = self.calculate_swap_output(split_amount, reserve_in_a, reserve_out_a)?;
-            
-            let (reserve_in_b, reserve_out_b) = self.get_pool_reserves_impl(input_token, target_token_b)?;
-            amount_b_out = self.calculate_swap_output(split_amount, reserve_in_b, reserve_out_b)?;
+        if amount == 0 {
+            return Err(anyhow!("Recovery amount cannot be zero"));
         }
         
-        // Calculate expected LP tokens (simplified)
-        let total_supply = reserve_a + reserve_b; // Simplified, should get actual total supply
-        let expected_lp = if total_supply == 0 {
-            integer_sqrt(amount_a_out * amount_b_out)
-        } else {
-            std::cmp::min(
-                amount_a_out * total_supply / reserve_a,
-                amount_b_out * total_supply / reserve_b
-            )
-        };
-        
-        // Apply slippage
-        let min_lp_tokens = expected_lp * (10000 - max_slippage_bps) / 10000;
+        let incoming = received_amount(&context.incoming_alkanes, &token);
+        let held = self.balance(&context.myself, &token).saturating_sub(incoming);
+        if held < amount {
+            return Err(anyhow!("Insufficient stranded balance: {} < {}", held, amount));
+        }
         
-        // Pack quote data
-        let mut data = Vec::new();
-        data.extend_from_slice(&split_amount.to_le_bytes()); // split_amount
-        data.extend_from_slice(&amount_a_out.to_le_bytes()); // expected_token_a
-        data.extend_from_slice(&amount_b_out.to_le_bytes()); // expected_token_b
-        data.extend_from_slice(&expected_lp.to_le_bytes());  // expected_lp_tokens
-        data.extend_from_slice(&min_lp_tokens.to_le_bytes()); // min_lp_tokens
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.alkanes.0.push(AlkaneTransfer { id: token, value: amount });
         
-        response.data = data;
         Ok(response)
     }
 
-    fn execute_zap(
+    /// Send `amount` of a token sitting in the contract outside any call back out, under the
+    /// same owner, reentrancy and incoming-parcel rules as `recover_stranded_swap`.
+    ///
+    /// A contract cannot pick the output its transfers land on; they follow the calling
+    /// protostone's pointer. `to_vout` is the output the owner points the call at, echoed as a
+    /// little-endian u32 in the response data so indexers can attribute the sweep.
+    fn sweep(&self, token: AlkaneId, amount: u128, to_vout: u32) -> Result<CallResponse> {
+        let mut response = self.recover_stranded_swap(token, amount)?;
+        response.data = to_vout.to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Build the full quote shared by `GetZapQuote` and `GetZapQuoteV2`
+    fn quote_zap(
         &self,
         input_token: AlkaneId,
         input_amount: u128,
         target_token_a: AlkaneId,
         target_token_b: AlkaneId,
-        min_lp_tokens: u128,
-        deadline: u128,
         max_slippage_bps: u128,
-    ) -> Result<CallResponse> {
+    ) -> Result<ZapQuote> {
+        let pool_provider = ZapPoolProvider::new(self);
+        self.quote_zap_via(&pool_provider, input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)
+    }
+
+    /// Build a quote against pool data from `pool_provider`. A zero `max_slippage_bps` uses
+    /// the stored default.
+    fn quote_zap_via<P: PoolProvider>(
+        &self,
+        pool_provider: &P,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    ) -> Result<ZapQuote> {
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        let max_slippage_bps = self.resolve_slippage(max_slippage_bps)?;
+        let factory_id = self.oyl_factory_id()?;
+        let base_tokens = self.base_tokens()?;
+        
+        // Find routes to both target tokens without trading against the target pool, and
+        // away from any pool priced off its configured reference
+        let mut route_finder = RouteFinder::new(factory_id, pool_provider).with_base_tokens(base_tokens);
+        for target in [target_token_a, target_token_b] {
+            if let Some((reference_price, tolerance_bps)) = self.reference_price(input_token, target)? {
+                route_finder = route_finder.with_reference_price(input_token, target, reference_price, tolerance_bps);
+            }
+        }
+        let (route_a, route_b) =
+            route_finder.find_zap_routes(input_token, input_amount, target_token_a, target_token_b)?;
+        
+        // Get pool reserves for the target pair
+        let target_pool_reserves = pool_provider.get_pool_reserves(target_token_a, target_token_b)?;
+        
+        // Refuse to quote against a pool whose ratio is moving rapidly. Ratios are taken in
+        // canonical order so zaps naming the pair either way share one history.
+        let pool_id = self.find_pool_id(target_token_a, target_token_b)?;
+        ZapCalculator::check_ratio_volatility(
+            &target_pool_reserves.oriented(canonical_pool_key(target_token_a, target_token_b).0),
+            &self.pool_ratio_history(pool_id)?,
+            self.max_ratio_volatility_bps()?,
+        )?;
+        
+        // Calculate the optimal split and LP estimate, comparing ratios at a common scale
+        // when both targets have decimals recorded
+        let options = match (self.token_decimals(target_token_a)?, self.token_decimals(target_token_b)?) {
+            (Some(decimals_a), Some(decimals_b)) => QuoteOptions::new().with_target_decimals(decimals_a, decimals_b),
+            _ => QuoteOptions::new(),
+        };
+        let params = ZapParams::new(input_token, input_amount, target_token_a, target_token_b, 0, 0)
+            .with_max_slippage(max_slippage_bps);
+        ZapCalculator::generate_zap_quote_with_options(
+            &params,
+            route_a,
+            route_b,
+            &target_pool_reserves,
+            &RouteFinder::new(factory_id, pool_provider),
+            &options,
+        )
+        .map(|quote| quote.with_computed_at_height(u128::from(self.height())))
+    }
+
+    /// Quote a zap into a pair with no pool yet, priced as the deposit that creates the pool
+    fn quote_new_pool_zap(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    ) -> Result<ZapQuote> {
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        // Refused before routing, which could not reach the other target without the new pool
+        if input_token == target_token_a || input_token == target_token_b {
+            return Err(ZapError::IdenticalTokens { token: input_token }.into());
+        }
+        let max_slippage_bps = self.resolve_slippage(max_slippage_bps)?;
+        let factory_id = self.oyl_factory_id()?;
+        let pool_provider = ZapPoolProvider::new(self);
+        let (route_a, route_b) = RouteFinder::new(factory_id, &pool_provider)
+            .with_base_tokens(self.base_tokens()?)
+            .find_zap_routes(input_token, input_amount, target_token_a, target_token_b)?;
+        let params = ZapParams::new(input_token, input_amount, target_token_a, target_token_b, 0, 0)
+            .with_max_slippage(max_slippage_bps);
+        ZapCalculator::generate_new_pool_zap_quote(&params, route_a, route_b, &RouteFinder::new(factory_id, &pool_provider))
+        .map(|quote| quote.with_computed_at_height(u128::from(self.height())))
+    }
+
+    /// Whether a pool exists for the pair, telling a missing pool apart from a failed lookup
+    fn target_pool_exists(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<bool> {
+        match self.find_pool_id(token_a, token_b) {
+            Ok(_) => Ok(true),
+            Err(err) => match err.downcast_ref::<ZapError>() {
+                Some(ZapError::PoolNotFound { .. }) => Ok(false),
+                _ => Err(err),
+            },
+        }
+    }
+
+    fn get_zap_quote(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+        allow_pool_creation: u128,
+    ) -> Result<CallResponse> {
+        // Checked before the pool lookup, so a degenerate pair is never quoted as a pool to create
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        // Only a caller who opted in is quoted a missing pool as the zap that creates it
+        let quote = if parse_flag(allow_pool_creation, "allow_pool_creation")?
+            && !self.target_pool_exists(target_token_a, target_token_b)?
+        {
+            self.quote_new_pool_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?
+        } else {
+            self.quote_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?
+        };
+        
+        // Pack quote data in the original fixed layout; newer fields are only in GetZapQuoteV2
+        let mut data = Vec::with_capacity(80);
+        data.extend_from_slice(&quote.split_amount_a.to_le_bytes()); // split_amount
+        data.extend_from_slice(&quote.route_a.expected_output.to_le_bytes()); // expected_token_a
+        data.extend_from_slice(&quote.route_b.expected_output.to_le_bytes()); // expected_token_b
+        data.extend_from_slice(&quote.expected_lp_tokens.to_le_bytes()); // expected_lp_tokens
+        data.extend_from_slice(&quote.minimum_lp_tokens.to_le_bytes()); // min_lp_tokens
+        
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Up to `limit` of the most recent quotes zaps executed against, oldest first, packed
+    /// as consecutive fixed-width records
+    fn get_quote_history(&self, limit: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let records = self.quote_history(limit)?;
+        let mut data = Vec::with_capacity(records.len() * QuoteRecord::ENCODED_LEN);
+        for record in &records {
+            data.extend_from_slice(&record.encode());
+        }
+        response.data = data;
+        Ok(response)
+    }
+
+    /// The target tokens a zap is expected to leave unconsumed once `add_liquidity` applies
+    /// the pool ratio, packed as token A then token B
+    fn preview_dust(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let quote = self.quote_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?;
+        
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&quote.expected_dust_a.to_le_bytes());
+        data.extend_from_slice(&quote.expected_dust_b.to_le_bytes());
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Expected LP tokens from a zap and the share of the target pool they would own, in bps
+    /// of the supply after minting
+    fn get_pool_share(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let quote = self.quote_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?;
+        let total_supply = ZapPoolProvider::new(self)
+            .get_pool_reserves(target_token_a, target_token_b)?
+            .total_supply;
+        let share_bps = amm_logic::calculate_pool_share_bps(quote.expected_lp_tokens, total_supply)?;
+        
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&quote.expected_lp_tokens.to_le_bytes());
+        data.extend_from_slice(&share_bps.to_le_bytes());
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Expected LP tokens from a zap and the LP it would mint if an adversary first swapped
+    /// `adversary_amount` through each route's first pool, a floor for `min_lp_tokens`
+    fn get_worst_case_quote(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        adversary_amount: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let quote = self.quote_zap(input_token, input_amount, target_token_a, target_token_b, 0)?;
+        let pool_provider = ZapPoolProvider::new(self);
+        let target_pool_reserves = pool_provider.get_pool_reserves(target_token_a, target_token_b)?;
+        let worst_case_lp_tokens = ZapCalculator::calculate_worst_case_lp_tokens(
+            &quote,
+            &target_pool_reserves,
+            adversary_amount,
+            &RouteFinder::new(self.oyl_factory_id()?, &pool_provider),
+        )?;
+        
+        let mut data = Vec::with_capacity(32);
+        data.extend_from_slice(&quote.expected_lp_tokens.to_le_bytes());
+        data.extend_from_slice(&worst_case_lp_tokens.to_le_bytes());
+        response.data = data;
+        Ok(response)
+    }
+
+    /// Full quote including both route paths, see `ZapQuote::serialize`. `deadline` is a block
+    /// height (zero for none); past it the quote is still returned, flagged stale.
+    fn get_zap_quote_v2(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let quote = self
+            .quote_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?
+            .with_stale(self.is_deadline_passed(deadline));
+        response.data = quote.serialize();
+        Ok(response)
+    }
+
+    /// Quote the same input into each target pair, given as consecutive token ids. Pairs with
+    /// no pool or route come back as zero-LP sentinels rather than failing the batch; the
+    /// response is `ZapQuote::serialize_batch`.
+    fn get_zap_quote_batch(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        pairs: Vec<AlkaneId>,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        if !pairs.len().is_multiple_of(2) {
+            return Err(anyhow!("Quote batch pairs must be groups of 2 tokens"));
+        }
+        if pairs.len() / 2 > MAX_QUOTE_BATCH {
+            return Err(anyhow!("Quote batch of {} exceeds the limit of {}", pairs.len() / 2, MAX_QUOTE_BATCH));
+        }
+        
+        // One pool snapshot serves every pair in the batch
+        let pool_provider = ZapPoolProvider::new(self);
+        let cached_provider = CachedPoolProvider::new(&pool_provider);
+        let mut quotes: Vec<ZapQuote> = Vec::with_capacity(pairs.len() / 2);
+        for pair in pairs.chunks_exact(2) {
+            let (target_token_a, target_token_b) = (pair[0], pair[1]);
+            let quote = match self.quote_zap_via(&cached_provider, input_token, input_amount, target_token_a, target_token_b, max_slippage_bps) {
+                Err(err) if is_unroutable(&err) => ZapQuote::new(input_token, input_amount, target_token_a, target_token_b),
+                quote => quote?,
+            };
+            quotes.push(quote);
+        }
+        
+        response.data = ZapQuote::serialize_batch(&quotes);
+        Ok(response)
+    }
+
+    /// Quote a zap whose swaps settle as one atomic flash swap through the factory, priced
+    /// at the factory's flash fee. Encoded like `GetZapQuoteV2`.
+    fn get_flash_zap_quote(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let flash_fee_bps = self
+            .flash_swap_fee_bps()?
+            .ok_or_else(|| anyhow!("Factory does not support flash swaps"))?;
+        let pool_provider = ZapPoolProvider::new(self);
+        let flash_provider = FlashSwapPoolProvider::new(&pool_provider, flash_fee_bps);
+        let quote = self.quote_zap_via(
+            &flash_provider,
+            input_token,
+            input_amount,
+            target_token_a,
+            target_token_b,
+            max_slippage_bps,
+        )?;
+        response.data = quote.serialize();
+        Ok(response)
+    }
+
+    /// Zap the incoming input into the target pool. A nonzero `expected_lp_tokens`, the
+    /// expectation of the caller's quote, re-quotes first and refuses when current reserves
+    /// would mint more than `max_slippage_bps` less; a nonzero `max_price_impact_bps` refuses
+    /// legs whose combined price impact exceeds it.
+    #[allow(clippy::too_many_arguments)]
+    fn execute_zap(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+        allow_pool_creation: u128,
+        expected_lp_tokens: u128,
+        max_price_impact_bps: u128,
+    ) -> Result<CallResponse> {
+        let allow_pool_creation = parse_flag(allow_pool_creation, "allow_pool_creation")?;
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
+        let max_slippage_bps = self.resolve_slippage(max_slippage_bps)?;
+        
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
+        }
+        
+        let input_amount = self.received_input(input_token, input_amount)?;
+        let params = ZapParams::new(input_token, input_amount, target_token_a, target_token_b, min_lp_tokens, deadline)
+            .with_max_slippage(max_slippage_bps);
+        // Only a caller who opted in bootstraps a missing pool; otherwise the zap fails on it
+        // as `PoolNotFound` before any swap
+        if allow_pool_creation && !self.target_pool_exists(target_token_a, target_token_b)? {
+            return self.zap_into_new_pool(&params, max_price_impact_bps);
+        }
+        self.zap_input(&params, None, None, expected_lp_tokens, max_price_impact_bps)
+    }
+
+    /// Zap with the caller's absolute deposit minimums passed straight to `add_liquidity`
+    /// in place of ones derived from a slippage tolerance
+    #[allow(clippy::too_many_arguments)]
+    fn execute_zap_exact(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        amount_a_min: u128,
+        amount_b_min: u128,
+        min_lp_tokens: u128,
+        deadline: u128,
+    ) -> Result<CallResponse> {
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
+        let max_slippage_bps = self.resolve_slippage(0)?;
+        
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
+        }
+        
+        let input_amount = self.received_input(input_token, input_amount)?;
+        let params = ZapParams::new(input_token, input_amount, target_token_a, target_token_b, min_lp_tokens, deadline)
+            .with_max_slippage(max_slippage_bps);
+        self.zap_input(&params, None, Some((amount_a_min, amount_b_min)), 0, 0)
+    }
+
+    /// Zap only `fraction_bps` of the incoming parcel and forward the rest of it back to the
+    /// caller alongside the LP tokens
+    #[allow(clippy::too_many_arguments)]
+    fn zap_partial(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+        fraction_bps: u128,
+    ) -> Result<CallResponse> {
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
+        if fraction_bps == 0 || fraction_bps > BASIS_POINTS {
+            return Err(anyhow!("Zap fraction {} bps must be within (0, {}]", fraction_bps, BASIS_POINTS));
+        }
+        let max_slippage_bps = self.resolve_slippage(max_slippage_bps)?;
+        
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
+        }
+        
+        let received = self.received_input(input_token, input_amount)?;
+        let zap_amount: u128 = (U256::from(received) * U256::from(fraction_bps) / U256::from(BASIS_POINTS))
+            .try_into()
+            .map_err(|_| anyhow!("Zap fraction exceeds u128"))?;
+        if zap_amount == 0 {
+            return Err(ZapError::ZeroInputAmount.into());
+        }
+        
+        let params = ZapParams::new(input_token, zap_amount, target_token_a, target_token_b, min_lp_tokens, deadline)
+            .with_max_slippage(max_slippage_bps);
+        let mut response = self.zap_input(&params, None, None, 0, 0)?;
+        let remainder = received - zap_amount;
+        if remainder > 0 {
+            response.alkanes.0.push(AlkaneTransfer { id: input_token, value: remainder });
+        }
+        Ok(response)
+    }
+
+    /// Zap just enough of the incoming parcel, up to `max_input`, to mint `lp_amount_out` LP
+    /// tokens, forwarding the unused input back to the caller
+    #[allow(clippy::too_many_arguments)]
+    fn zap_for_exact_lp(
+        &self,
+        input_token: AlkaneId,
+        max_input: u128,
+        lp_amount_out: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        deadline: u128,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
+        let max_slippage_bps = self.resolve_slippage(max_slippage_bps)?;
+        
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
+        }
+        
+        let received = self.received_input(input_token, max_input)?;
+        
+        // Back-solve the input each quoted leg needs from the target pool's current LP supply
+        let quote = self.quote_zap(input_token, received, target_token_a, target_token_b, max_slippage_bps)?;
+        let pool_provider = ZapPoolProvider::new(self);
+        let target_pool_reserves = pool_provider.get_pool_reserves(target_token_a, target_token_b)?;
+        let (split_a, split_b) = ZapCalculator::calculate_split_for_exact_lp(
+            lp_amount_out,
+            &quote.route_a,
+            &quote.route_b,
+            &target_pool_reserves,
+            &RouteFinder::new(self.oyl_factory_id()?, &pool_provider),
+        )?;
+        let required = split_a
+            .checked_add(split_b)
+            .ok_or_else(|| anyhow!("Input for exact LP output exceeds u128"))?;
+        if required > received {
+            return Err(ZapError::InsufficientInput { required, max: received }.into());
+        }
+        
+        let min_lp_tokens = amm_logic::apply_slippage(lp_amount_out, max_slippage_bps)?;
+        let params = ZapParams::new(input_token, required, target_token_a, target_token_b, min_lp_tokens, deadline)
+            .with_max_slippage(max_slippage_bps);
+        let mut response = self.zap_input(&params, Some(quote.with_split(split_a, split_b)), None, 0, 0)?;
+        let remainder = received - required;
+        if remainder > 0 {
+            response.alkanes.0.push(AlkaneTransfer { id: input_token, value: remainder });
+        }
+        Ok(response)
+    }
+
+    /// Check the incoming parcel carries the input of any zap, LP tokens included, and return
+    /// how much of it arrived. A fee-on-transfer input arrives short of what the caller sent,
+    /// so the zap works with the amount it actually received.
+    fn received_input(&self, input_token: AlkaneId, input_amount: u128) -> Result<u128> {
         let context = self.context()?;
+        if context.incoming_alkanes.0.is_empty() {
+            return Err(ZapError::NoInputTokens.into());
+        }
+        
+        let input_transfer = &context.incoming_alkanes.0[0];
+        if self.is_fee_on_transfer(input_token)? {
+            if input_transfer.id != input_token || input_transfer.value == 0 || input_transfer.value > input_amount {
+                return Err(ZapError::InputMismatch.into());
+            }
+            Ok(input_transfer.value)
+        } else {
+            if input_transfer.id != input_token || input_transfer.value != input_amount {
+                return Err(ZapError::InputMismatch.into());
+            }
+            Ok(input_amount)
+        }
+    }
+
+    /// Swap `params.input_amount` of an already received input into both targets and deposit
+    /// them, returning the LP tokens and dust with a `ZapReceipt` as the response data. The
+    /// swaps follow the routes and split of `quote`, quoted afresh when none is given, and
+    /// `amount_mins` overrides the deposit minimums otherwise derived from the slippage tolerance.
+    fn zap_input(
+        &self,
+        params: &ZapParams,
+        quote: Option<ZapQuote>,
+        amount_mins: Option<(u128, u128)>,
+        expected_lp_tokens: u128,
+        max_price_impact_bps: u128,
+    ) -> Result<CallResponse> {
+        let ZapParams {
+            input_token,
+            input_amount,
+            target_token_a,
+            target_token_b,
+            min_lp_tokens,
+            deadline,
+            max_slippage_bps,
+        } = *params;
+        self.ensure_within_max_input(input_amount)?;
+        
+        // Quote against current reserves and refuse if they moved against the caller's quote.
+        // An expectation of zero opts out.
+        let quote = match quote {
+            Some(quote) => quote,
+            None => self.quote_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?,
+        };
+        if expected_lp_tokens != 0 {
+            ZapCalculator::check_quote_drift(expected_lp_tokens, quote.expected_lp_tokens, max_slippage_bps)?;
+        }
+        
+        // Fail before the first swap rather than run out of fuel with some hops already executed
+        let required_fuel = ZapCalculator::estimate_fuel_for_quote(&quote);
+        let available_fuel = self.fuel() as u128;
+        if available_fuel < required_fuel {
+            return Err(ZapError::InsufficientFuel { available: available_fuel, required: required_fuel }.into());
+        }
+        
+        // Refuse to swap through any stored pool the zap touches if it has been pushed away
+        // from its last recorded reserves
+        for (token_a, token_b) in [
+            (input_token, target_token_a),
+            (input_token, target_token_b),
+            (target_token_a, target_token_b),
+        ] {
+            if token_a == token_b {
+                continue;
+            }
+            if let Some(stored) = self.stored_pool(token_a, token_b)? {
+                self.check_reserves_sane(token_a, token_b, &stored)?;
+            }
+        }
+        
+        // Record the pre-zap target pool ratio for volatility checks on later quotes
+        let pool_id = self.find_pool_id(target_token_a, target_token_b)?;
+        let (reserve_a, reserve_b, fee_rate) = self.get_pool_reserves_impl(target_token_a, target_token_b)?;
+        let target_pool_reserves = PoolReserves::new(target_token_a, target_token_b, reserve_a, reserve_b, 0, fee_rate)
+            .oriented(canonical_pool_key(target_token_a, target_token_b).0);
+        if let Ok(ratio) = ZapCalculator::get_pool_ratio(&target_pool_reserves) {
+            self.record_pool_ratio(pool_id, ratio)?;
+        }
+        
+        // Step 1: Execute swaps to get both target tokens
+        let (amount_a, amount_b) = self.swap_zap_legs(params, &quote, max_price_impact_bps, false)?;
+        
+        // Step 2: Add liquidity with the obtained tokens. The pool receives less of a
+        // fee-on-transfer token than is sent, and that shortfall also caps how much of the other
+        // token it takes, so such pairs are bounded by the LP minimum alone. Minimums the
+        // caller set explicitly are used as given.
+        let (amount_a_min, amount_b_min) = if let Some((amount_a_min, amount_b_min)) = amount_mins {
+            let legs = [
+                (amount_a, amount_a_min, quote.route_a.expected_output),
+                (amount_b, amount_b_min, quote.route_b.expected_output),
+            ];
+            for (realized, minimum, expected) in legs {
+                if realized < minimum {
+                    return Err(ZapError::SlippageExceeded {
+                        realized,
+                        minimum,
+                        suggested_bps: ZapCalculator::suggest_slippage_bps(expected, realized),
+                    }
+                    .into());
+                }
+            }
+            (amount_a_min, amount_b_min)
+        } else if self.is_fee_on_transfer(target_token_a)? || self.is_fee_on_transfer(target_token_b)? {
+            (0, 0)
+        } else {
+            (
+                amm_logic::apply_slippage(amount_a, max_slippage_bps)?,
+                amm_logic::apply_slippage(amount_b, max_slippage_bps)?,
+            )
+        };
+        
+        let held_before = self.held_balances(target_token_a, target_token_b)?;
+        let liquidity_result = self.add_liquidity(
+            target_token_a,
+            target_token_b,
+            amount_a,
+            amount_b,
+            amount_a_min,
+            amount_b_min,
+            deadline,
+        )?;
+        let (consumed_a, consumed_b) =
+            consumed_liquidity_amounts(held_before, self.held_balances(target_token_a, target_token_b)?);
+        let (dust_a, dust_b) = (amount_a.saturating_sub(consumed_a), amount_b.saturating_sub(consumed_b));
+        
+        // Validate minimum LP tokens received. The LP token is resolved through the pool
+        // rather than assumed to share the pool contract's id.
+        let lp_token_id = self.lp_token_id_for_pair(target_token_a, target_token_b)?;
+        let lp_tokens_received = received_amount(&liquidity_result.alkanes, &lp_token_id);
+        
+        // Apply the opt-in protocol safety margin, never loosening past the caller's slippage
+        let effective_min_lp_tokens = ZapCalculator::apply_min_lp_safety_margin(
+            min_lp_tokens,
+            self.min_lp_safety_margin_bps()?,
+            max_slippage_bps,
+        )?;
+        
+        if lp_tokens_received < effective_min_lp_tokens {
+            // Suggest a tolerance against the caller's quote, or the one their minimum implies
+            let expected_lp = if expected_lp_tokens != 0 {
+                expected_lp_tokens
+            } else {
+                ZapCalculator::implied_expected_amount(min_lp_tokens, max_slippage_bps)
+            };
+            return Err(ZapError::SlippageExceeded {
+                realized: lp_tokens_received,
+                minimum: effective_min_lp_tokens,
+                suggested_bps: ZapCalculator::suggest_slippage_bps(expected_lp, lp_tokens_received),
+            }
+            .into());
+        }
+        
+        // Keep the quote this zap executed against for indexers. Quotes themselves are
+        // read-only, so only executions are recorded.
+        self.record_quote(&QuoteRecord {
+            input_token,
+            input_amount,
+            expected_lp_tokens: quote.expected_lp_tokens,
+            price_impact: quote.price_impact,
+            height: u128::from(self.height()),
+        })?;
+        
+        // Return the LP tokens along with whatever the pool ratio left unconsumed, and a
+        // receipt of the zap as the response data
+        let receipt = ZapReceipt {
+            input_token,
+            input_amount,
+            path_a: quote.route_a.path,
+            path_b: quote.route_b.path,
+            output_a: amount_a,
+            output_b: amount_b,
+            lp_minted: lp_tokens_received,
+            dust_a,
+            dust_b,
+        };
+        let mut response = settle_add_liquidity(liquidity_result, target_token_a, target_token_b, dust_a, dust_b);
+        response.data = receipt.encode();
+        Ok(response)
+    }
+
+    /// Swap an already received input into both targets of a pair with no pool and create the
+    /// pool with everything the legs return, so no dust is left. The legs follow the routes
+    /// and even split of `quote_new_pool_zap`, as there is no ratio to match.
+    fn zap_into_new_pool(&self, params: &ZapParams, max_price_impact_bps: u128) -> Result<CallResponse> {
+        let ZapParams { input_token, input_amount, target_token_a, target_token_b, min_lp_tokens, max_slippage_bps, .. } = *params;
+        self.ensure_within_max_input(input_amount)?;
+        let quote = self.quote_new_pool_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?;
+        
+        let required_fuel = ZapCalculator::estimate_fuel_for_quote(&quote);
+        let available_fuel = self.fuel() as u128;
+        if available_fuel < required_fuel {
+            return Err(ZapError::InsufficientFuel { available: available_fuel, required: required_fuel }.into());
+        }
+        
+        let (amount_a, amount_b) = self.swap_zap_legs(params, &quote, max_price_impact_bps, false)?;
+        
+        let creation_result = self.create_pool(target_token_a, target_token_b, amount_a, amount_b)?;
+        let lp_token_id = self.lp_token_id_for_pair(target_token_a, target_token_b)?;
+        let lp_tokens_received = received_amount(&creation_result.alkanes, &lp_token_id);
+        
+        let effective_min_lp_tokens = ZapCalculator::apply_min_lp_safety_margin(
+            min_lp_tokens,
+            self.min_lp_safety_margin_bps()?,
+            max_slippage_bps,
+        )?;
+        if lp_tokens_received < effective_min_lp_tokens {
+            let expected_lp = ZapCalculator::implied_expected_amount(min_lp_tokens, max_slippage_bps);
+            return Err(ZapError::SlippageExceeded {
+                realized: lp_tokens_received,
+                minimum: effective_min_lp_tokens,
+                suggested_bps: ZapCalculator::suggest_slippage_bps(expected_lp, lp_tokens_received),
+            }
+            .into());
+        }
+        
+        self.record_quote(&QuoteRecord {
+            input_token,
+            input_amount,
+            expected_lp_tokens: quote.expected_lp_tokens,
+            price_impact: quote.price_impact,
+            height: u128::from(self.height()),
+        })?;
+        let receipt = ZapReceipt {
+            input_token,
+            input_amount,
+            path_a: quote.route_a.path,
+            path_b: quote.route_b.path,
+            output_a: amount_a,
+            output_b: amount_b,
+            lp_minted: lp_tokens_received,
+            dust_a: 0,
+            dust_b: 0,
+        };
+        let mut response = settle_add_liquidity(creation_result, target_token_a, target_token_b, 0, 0);
+        response.data = receipt.encode();
+        Ok(response)
+    }
+
+    /// Refuse to trade against a pool whose live ratio has moved more than the configured
+    /// deviation from the reserves last recorded for it by `UpdatePoolReserves`
+    fn check_reserves_sane(&self, token_a: AlkaneId, token_b: AlkaneId, stored: &StoredPool) -> Result<()> {
+        let max_deviation_bps = self.max_reserve_deviation_bps()?;
+        if max_deviation_bps == 0 {
+            return Ok(());
+        }
+        // Ask the pool itself, so the recorded reserves are never compared with themselves
+        let (reserve_a, reserve_b, _) = self.live_pool_reserves(stored.pool_id, token_a)?;
+        let fresh_reserves = PoolReserves::new(token_a, token_b, reserve_a, reserve_b, 0, stored.fee_rate);
+        ZapCalculator::check_reserve_deviation(&stored.to_reserves(token_a, token_b), &fresh_reserves, max_deviation_bps)
+    }
+
+    /// The caller's slippage tolerance, or the stored default when they pass zero
+    fn resolve_slippage(&self, max_slippage_bps: u128) -> Result<u128> {
+        if max_slippage_bps == 0 {
+            return self.default_slippage_bps();
+        }
+        Ok(max_slippage_bps)
+    }
+
+    /// Whether `deadline` has passed at the current block. `u128::from` keeps the height
+    /// conversion lossless, so a wider height type fails to compile rather than truncating.
+    fn is_deadline_passed(&self, deadline: u128) -> bool {
+        types::is_deadline_passed(deadline, u128::from(self.height()))
+    }
+
+    /// Swap the input into each target token along the routes and split of `quote`, refusing up
+    /// front when the legs' combined price impact exceeds the ceiling. A dry run prices the swaps
+    /// through `simulate_swap`.
+    fn swap_zap_legs(
+        &self,
+        params: &ZapParams,
+        quote: &ZapQuote,
+        max_price_impact_bps: u128,
+        dry_run: bool,
+    ) -> Result<(u128, u128)> {
+        let ZapParams { input_token, deadline, max_slippage_bps, .. } = *params;
+        let (route_a, route_b) = (&quote.route_a, &quote.route_b);
+        let (split_a, split_b) = (quote.split_amount_a, quote.split_amount_b);
+        let pool_provider = ZapPoolProvider::new(self);
+        let route_finder = RouteFinder::new(self.oyl_factory_id()?, &pool_provider);
+        
+        // Enforce the price impact ceiling before any swap or liquidity call is made
+        if max_price_impact_bps != 0 {
+            let price_impact = ZapCalculator::calculate_overall_price_impact(
+                route_a,
+                route_b,
+                split_a,
+                split_b,
+                &route_finder,
+            )?;
+            if price_impact > max_price_impact_bps {
+                return Err(ZapError::PriceImpactExceeded { impact: price_impact, max: max_price_impact_bps }.into());
+            }
+        }
+        
+        // Each leg's swap must return its expected output less the caller's slippage, so the
+        // factory reverts a sandwiched hop instead of handing back whatever is left. A
+        // fee-on-transfer input reaches the pool short by an unknown fee, so its legs are left
+        // to the LP minimum.
+        let input_fee_on_transfer = self.is_fee_on_transfer(input_token)?;
+        let leg_min_out = |route: &RouteInfo, split_amount: u128| -> Result<u128> {
+            if input_fee_on_transfer {
+                return Ok(0);
+            }
+            let minimums = ZapCalculator::hop_min_outputs(split_amount, route, max_slippage_bps, &route_finder)?;
+            Ok(minimums.last().copied().unwrap_or(0))
+        };
+        
+        // Identity legs are contributed directly; the rest swap, a before b. Debug builds also
+        // check each single-hop swap kept the pool's constant product, catching a factory that
+        // pays out more than its reserves allow.
+        let swap_leg = |route: &RouteInfo, split_amount: u128| -> Result<u128> {
+            if route.hop_count() == 0 {
+                return Ok(split_amount);
+            }
+            let min_out = leg_min_out(route, split_amount)?;
+            let reserves_before = if cfg!(debug_assertions) && !dry_run && route.hop_count() == 1 {
+                Some(self.get_pool_reserves_impl(route.path[0], route.path[1])?)
+            } else {
+                None
+            };
+            let swap_result = if dry_run {
+                self.simulate_swap(route.path.clone(), split_amount, min_out, deadline)?
+            } else {
+                self.execute_swap(route.path.clone(), split_amount, min_out, deadline)?
+            };
+            let amount_out = swap_result.alkanes.0.first().map_or(0, |transfer| transfer.value);
+            if let Some((reserve_in, reserve_out, fee_rate)) = reserves_before {
+                if !amm_logic::verify_constant_product(reserve_in, reserve_out, split_amount, amount_out, fee_rate) {
+                    return Err(anyhow!(
+                        "Swap of {} along {:?} returned {}, breaking the pool's constant product",
+                        split_amount,
+                        route.path,
+                        amount_out
+                    ));
+                }
+            }
+            Ok(amount_out)
+        };
+        let amount_a = swap_leg(route_a, split_a)?;
+        let amount_b = swap_leg(route_b, split_b)?;
+        
+        Ok((amount_a, amount_b))
+    }
+
+    /// Dry run of `execute_zap`: the swaps are priced with static calls and the deposit
+    /// against the fetched target pool reserves, so no state is written. Returns the
+    /// realized LP tokens, consumed amounts and dust as a `ZapSimulation`.
+    fn simulate_zap(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+        max_price_impact_bps: u128,
+    ) -> Result<CallResponse> {
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        if input_amount == 0 {
+            return Err(ZapError::ZeroInputAmount.into());
+        }
+        
+        let max_slippage_bps = self.resolve_slippage(max_slippage_bps)?;
+        let params = ZapParams::new(input_token, input_amount, target_token_a, target_token_b, 0, 0)
+            .with_max_slippage(max_slippage_bps);
+        let quote = self.quote_zap(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)?;
+        let (amount_a, amount_b) = self.swap_zap_legs(&params, &quote, max_price_impact_bps, true)?;
+        
+        // A leg swapping one target token straight into the other trades against the target
+        // pool, so the deposit meets the reserves that swap leaves behind
+        let pool_id = self.find_pool_id(target_token_a, target_token_b)?;
+        let (mut reserve_a, mut reserve_b, fee_rate) = self.get_pool_reserves_impl(target_token_a, target_token_b)?;
+        if input_token == target_token_a && quote.route_b.hop_count() == 1 {
+            reserve_a = reserve_a.saturating_add(input_amount - amount_a);
+            reserve_b = reserve_b.saturating_sub(amount_b);
+        } else if input_token == target_token_b && quote.route_a.hop_count() == 1 {
+            reserve_b = reserve_b.saturating_add(input_amount - amount_b);
+            reserve_a = reserve_a.saturating_sub(amount_a);
+        }
+        
+        let (consumed_a, consumed_b) = amm_logic::quote_add_liquidity(amount_a, amount_b, reserve_a, reserve_b)?;
+        let deposit_reserves = PoolReserves::new(
+            target_token_a,
+            target_token_b,
+            reserve_a,
+            reserve_b,
+            self.pool_total_supply(pool_id)?,
+            fee_rate,
+        );
+        let lp_tokens = ZapCalculator::calculate_expected_lp_tokens(consumed_a, consumed_b, &deposit_reserves)?;
+        
+        response.data = ZapSimulation {
+            lp_tokens,
+            consumed_a,
+            consumed_b,
+            dust_a: amount_a - consumed_a,
+            dust_b: amount_b - consumed_b,
+        }
+        .encode();
+        Ok(response)
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn zap_into_weighted(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_tokens: Vec<AlkaneId>,
+        weights_bps: Vec<u128>,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
+        
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
+        }
+        
+        let input_amount = self.received_input(input_token, input_amount)?;
+        self.ensure_within_max_input(input_amount)?;
+        
+        // Split the input proportionally to the pool weights
+        let splits = ZapCalculator::calculate_weighted_split(input_amount, &target_tokens, &weights_bps)?;
+        
+        // Step 1: Swap each non-identity leg into its target token. A fee-on-transfer input
+        // reaches each pool short by an unknown fee, so its legs are left to the LP minimum.
+        let input_fee_on_transfer = self.is_fee_on_transfer(input_token)?;
+        let mut amounts = Vec::with_capacity(target_tokens.len());
+        for (target_token, split_amount) in target_tokens.iter().zip(splits) {
+            if *target_token == input_token {
+                amounts.push(split_amount);
+                continue;
+            }
+            
+            let amount_out_min = if input_fee_on_transfer {
+                0
+            } else {
+                let (reserve_in, reserve_out, fee_rate) = self.get_pool_reserves_impl(input_token, *target_token)?;
+                let expected_out = self.calculate_swap_output(split_amount, reserve_in, reserve_out, fee_rate)?;
+                amm_logic::apply_slippage(expected_out, max_slippage_bps)?
+            };
+            
+            let swap_result = self.execute_swap(vec![input_token, *target_token], split_amount, amount_out_min, deadline)?;
+            amounts.push(received_amount(&swap_result.alkanes, target_token));
+        }
+        
+        // Step 2: Add weighted liquidity with the obtained tokens
+        let amount_mins = amounts
+            .iter()
+            .map(|amount| amm_logic::apply_slippage(*amount, max_slippage_bps))
+            .collect::<Result<Vec<_>>>()?;
+        let liquidity_result = self.add_liquidity_weighted(target_tokens.clone(), amounts, amount_mins, deadline)?;
+        
+        // Anything returned besides the pool's own tokens is the minted LP position
+        let lp_tokens_received = liquidity_result
+            .alkanes
+            .0
+            .iter()
+            .filter(|transfer| !target_tokens.contains(&transfer.id))
+            .fold(0u128, |total, transfer| total.saturating_add(transfer.value));
+        
+        let effective_min_lp_tokens = ZapCalculator::apply_min_lp_safety_margin(
+            min_lp_tokens,
+            self.min_lp_safety_margin_bps()?,
+            max_slippage_bps,
+        )?;
+        
+        if lp_tokens_received < effective_min_lp_tokens {
+            let expected_lp = ZapCalculator::implied_expected_amount(min_lp_tokens, max_slippage_bps);
+            return Err(ZapError::SlippageExceeded {
+                realized: lp_tokens_received,
+                minimum: effective_min_lp_tokens,
+                suggested_bps: ZapCalculator::suggest_slippage_bps(expected_lp, lp_tokens_received),
+            }
+            .into());
+        }
+        
+        Ok(liquidity_result)
+    }
+
+    fn zap_out(
+        &self,
+        lp_token: AlkaneId,
+        lp_amount: u128,
+        output_token: AlkaneId,
+        min_output: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
+        let context = self.context()?;
+        
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
+        }
+        
+        let lp_amount = self.received_input(lp_token, lp_amount)?;
+        
+        let (token_a, token_b) = self.lp_token_pair(lp_token)?;
+        if output_token != token_a && output_token != token_b {
+            return Err(anyhow!("Output token must be one of the pool tokens"));
+        }
+        
+        // Step 1: Burn the LP tokens for both pool tokens
+        let removal_result = self.remove_liquidity(token_a, token_b, lp_amount, 0, 0, deadline)?;
+        let amount_a = received_amount(&removal_result.alkanes, &token_a);
+        let amount_b = received_amount(&removal_result.alkanes, &token_b);
+        
+        // Step 2: Swap the other pool token into the output token
+        let (swap_token, swap_amount, mut output_amount) = if output_token == token_a {
+            (token_b, amount_b, amount_a)
+        } else {
+            (token_a, amount_a, amount_b)
+        };
+        
+        let mut dust = Vec::new();
+        if swap_amount > 0 {
+            let (reserve_in, reserve_out, fee_rate) = self.get_pool_reserves_impl(swap_token, output_token)?;
+            let expected_out = self.calculate_swap_output(swap_amount, reserve_in, reserve_out, fee_rate)?;
+            let amount_out_min = amm_logic::apply_slippage(expected_out, max_slippage_bps)?;
+            
+            let swap_result = self.execute_swap(vec![swap_token, output_token], swap_amount, amount_out_min, deadline)?;
+            output_amount += received_amount(&swap_result.alkanes, &output_token);
+            
+            // Anything the swap hands back besides the output token is dust for the caller
+            dust.extend(swap_result.alkanes.0.into_iter().filter(|transfer| transfer.id != output_token));
+        }
+        
+        if output_amount < min_output {
+            let expected_output = ZapCalculator::implied_expected_amount(min_output, max_slippage_bps);
+            return Err(ZapError::SlippageExceeded {
+                realized: output_amount,
+                minimum: min_output,
+                suggested_bps: ZapCalculator::suggest_slippage_bps(expected_output, output_amount),
+            }
+            .into());
+        }
+        
+        // Return the output token, any dust, and any extra incoming alkanes to the caller
+        let mut response = CallResponse::default();
+        response.alkanes.0.push(AlkaneTransfer { id: output_token, value: output_amount });
+        response.alkanes.0.extend(dust);
+        response.alkanes.0.extend(context.incoming_alkanes.0[1..].iter().cloned());
+        response.data = output_amount.to_le_bytes().to_vec();
+        
+        Ok(response)
+    }
+
+    /// Move an LP position into another pool in one call: burn the source LP tokens, swap
+    /// each withdrawn token the target pair lacks into the target token not already covered,
+    /// and add liquidity. A token shared by both pools is contributed without a swap.
+    #[allow(clippy::too_many_arguments)]
+    fn migrate_lp(
+        &self,
+        source_lp: AlkaneId,
+        source_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        let params = ZapParams::new(source_lp, source_amount, target_token_a, target_token_b, min_lp_tokens, deadline)
+            .with_max_slippage(max_slippage_bps);
+        self.reposition_lp(&params, false)
+    }
+
+    /// `migrate_lp` for pairs without a direct pool between the withdrawn and target tokens:
+    /// each swap follows the best route the route finder discovers. Any failed step errors
+    /// the whole call, so the caller never ends up holding the withdrawn tokens.
+    #[allow(clippy::too_many_arguments)]
+    fn rebalance(
+        &self,
+        from_lp: AlkaneId,
+        from_lp_amount: u128,
+        target_token_c: AlkaneId,
+        target_token_d: AlkaneId,
+        min_lp_tokens: u128,
+        deadline: u128,
+        max_slippage_bps: u128,
+    ) -> Result<CallResponse> {
+        let params = ZapParams::new(from_lp, from_lp_amount, target_token_c, target_token_d, min_lp_tokens, deadline)
+            .with_max_slippage(max_slippage_bps);
+        self.reposition_lp(&params, true)
+    }
+
+    /// Burn `params.input_amount` of the `params.input_token` LP position and deposit the
+    /// proceeds into the target pair. `routed` swaps along discovered routes instead of
+    /// requiring a direct pool from each withdrawn token to its target.
+    fn reposition_lp(&self, params: &ZapParams, routed: bool) -> Result<CallResponse> {
+        let ZapParams {
+            input_token: source_lp,
+            input_amount: source_amount,
+            target_token_a,
+            target_token_b,
+            min_lp_tokens,
+            deadline,
+            max_slippage_bps,
+        } = *params;
+        ensure_distinct_pair(target_token_a, target_token_b)?;
+        self.ensure_not_paused()?;
+        let _lock = ReentrancyGuard::acquire(self)?;
         
-        // Basic deadline check
-        if deadline != 0 && self.height() as u128 > deadline {
-            return Err(anyhow!("Transaction deadline has passed"));
+        if self.is_deadline_passed(deadline) {
+            return Err(ZapError::DeadlineExpired.into());
         }
         
-        // Validate input amount from incoming alkanes
-        if context.incoming_alkanes.0.is_empty() {
-            return Err(anyhow!("No input tokens provided"));
-        }
+        let source_amount = self.received_input(source_lp, source_amount)?;
         
-        let input_transfer = &context.incoming_alkanes.0[0];
-        if input_transfer.id != input_token || input_transfer.value != input_amount {
-            return Err(anyhow!("Input token mismatch"));
+        let (source_token_a, source_token_b) = self.lp_token_pair(source_lp)?;
+        if canonical_pool_key(source_token_a, source_token_b)
+            == canonical_pool_key(target_token_a, target_token_b)
+        {
+            return Err(anyhow!("Source and target pools must differ"));
         }
         
-        // Calculate optimal split (50/50 for simplicity)
-        let split_amount = input_amount / 2;
+        // Step 1: Burn the source LP tokens for both of its tokens
+        let removal_result = self.remove_liquidity(source_token_a, source_token_b, source_amount, 0, 0, deadline)?;
+        let withdrawn = [
+            (source_token_a, received_amount(&removal_result.alkanes, &source_token_a)),
+            (source_token_b, received_amount(&removal_result.alkanes, &source_token_b)),
+        ];
         
-        // Step 1: Execute swaps to get both target tokens
+        // Step 2: Shared tokens count directly; the rest swap into whichever target is uncovered
+        let shares_a = withdrawn.iter().any(|(token, _)| *token == target_token_a);
+        let shares_b = withdrawn.iter().any(|(token, _)| *token == target_token_b);
+        let mut unclaimed_targets: Vec<AlkaneId> = [(target_token_a, shares_a), (target_token_b, shares_b)]
+            .into_iter()
+            .filter(|(_, shared)| !shared)
+            .map(|(target, _)| target)
+            .collect();
+        
+        let pool_provider = ZapPoolProvider::new(self);
+        let route_finder = RouteFinder::new(self.oyl_factory_id()?, &pool_provider)
+            .with_base_tokens(self.base_tokens()?);
         let mut amount_a = 0u128;
         let mut amount_b = 0u128;
-        
-        if input_token == target_token_a {
-            amount_a = split_amount;
-            // Swap other half to token_b
-            let swap_path = vec![input_token, target_token_b];
-            let swap_result = self.execute_swap(swap_path, split_amount, 0, deadline)?;
-            // Extract amount_b from swap result
-            if !swap_result.alkanes.0.is_empty() {
-                amount_b = swap_result.alkanes.0[0].value;
-            }
-        } else if input_token == target_token_b {
-            amount_b = split_amount;
-            // Swap other half to token_a
-            let swap_path = vec![input_token, target_token_a];
-            let swap_result = self.execute_swap(swap_path, split_amount, 0, deadline)?;
-            // Extract amount_a from swap result
-            if !swap_result.alkanes.0.is_empty() {
-                amount_a = swap_result.alkanes.0[0].value;
-            }
-        } else {
-            // Need to swap both halves
-            let swap_path_a = vec![input_token, target_token_a];
-            let swap_result_a = self.execute_swap(swap_path_a, split_amount, 0, deadline)?;
-            if !swap_result_a.alkanes.0.is_empty() {
-                amount_a = swap_result_a.alkanes.0[0].value;
+        for (token, amount) in withdrawn {
+            let target = if token == target_token_a || token == target_token_b {
+                token
+            } else {
+                unclaimed_targets.remove(0)
+            };
+            
+            let mut received = amount;
+            if target != token && amount > 0 {
+                let route = if routed {
+                    route_finder.find_best_route(token, target, amount)?
+                } else {
+                    direct_leg_route(token, target)
+                };
+                let minimums = ZapCalculator::hop_min_outputs(amount, &route, max_slippage_bps, &route_finder)?;
+                let amount_out_min = minimums.last().copied().unwrap_or(0);
+                let swap_result = self.execute_swap(route.path, amount, amount_out_min, deadline)?;
+                received = received_amount(&swap_result.alkanes, &target);
             }
             
-            let swap_path_b = vec![input_token, target_token_b];
-            let swap_result_b = self.execute_swap(swap_path_b, split_amount, 0, deadline)?;
-            if !swap_result_b.alkanes.0.is_empty() {
-                amount_b = swap_result_b.alkanes.0[0].value;
+            if target == target_token_a {
+                amount_a += received;
+            } else {
+                amount_b += received;
             }
         }
         
-        // Step 2: Add liquidity with the obtained tokens
-        let amount_a_min = amount_a * (10000 - max_slippage_bps) / 10000;
-        let amount_b_min = amount_b * (10000 - max_slippage_bps) / 10000;
-        
+        // Step 3: Add liquidity to the target pool. The withdrawn ratio rarely matches the
+        // target pool's, so the minimums cover what the pool will take at its current reserves
+        // rather than everything offered; the rest comes back as dust.
+        let (reserve_a, reserve_b, _) = self.get_pool_reserves_impl(target_token_a, target_token_b)?;
+        let (expected_a, expected_b) = amm_logic::quote_add_liquidity(amount_a, amount_b, reserve_a, reserve_b)?;
+        let amount_a_min = amm_logic::apply_slippage(expected_a, max_slippage_bps)?;
+        let amount_b_min = amm_logic::apply_slippage(expected_b, max_slippage_bps)?;
+        let held_before = self.held_balances(target_token_a, target_token_b)?;
         let liquidity_result = self.add_liquidity(
             target_token_a,
             target_token_b,
@@ -284,28 +1970,34 @@ pub trait ZapBase: AuthenticatedResponder {
             amount_b_min,
             deadline,
         )?;
+        let (consumed_a, consumed_b) =
+            consumed_liquidity_amounts(held_before, self.held_balances(target_token_a, target_token_b)?);
         
-        // Validate minimum LP tokens received
-        let mut lp_tokens_received = 0u128;
-        for transfer in &liquidity_result.alkanes.0 {
-            // LP token should be the pool token
-            if let Ok(pool_id) = self.find_pool_id(target_token_a, target_token_b) {
-                if transfer.id == pool_id {
-                    lp_tokens_received = transfer.value;
-                    break;
-                }
+        let lp_token_id = self.lp_token_id_for_pair(target_token_a, target_token_b)?;
+        let lp_tokens_received = received_amount(&liquidity_result.alkanes, &lp_token_id);
+        let effective_min_lp_tokens = ZapCalculator::apply_min_lp_safety_margin(
+            min_lp_tokens,
+            self.min_lp_safety_margin_bps()?,
+            max_slippage_bps,
+        )?;
+        if lp_tokens_received < effective_min_lp_tokens {
+            let expected_lp = ZapCalculator::implied_expected_amount(min_lp_tokens, max_slippage_bps);
+            return Err(ZapError::SlippageExceeded {
+                realized: lp_tokens_received,
+                minimum: effective_min_lp_tokens,
+                suggested_bps: ZapCalculator::suggest_slippage_bps(expected_lp, lp_tokens_received),
             }
+            .into());
         }
         
-        if lp_tokens_received < min_lp_tokens {
-            return Err(anyhow!(
-                "Insufficient LP tokens received: {} < {}",
-                lp_tokens_received,
-                min_lp_tokens
-            ));
-        }
-        
-        Ok(liquidity_result)
+        // Return the new LP tokens along with whatever the pool ratio left unconsumed
+        Ok(settle_add_liquidity(
+            liquidity_result,
+            target_token_a,
+            target_token_b,
+            amount_a.saturating_sub(consumed_a),
+            amount_b.saturating_sub(consumed_b),
+        ))
     }
 
     fn get_best_route(
@@ -314,15 +2006,78 @@ pub trait ZapBase: AuthenticatedResponder {
         to_token: AlkaneId,
         amount_in: u128,
     ) -> Result<CallResponse> {
+        ensure_distinct_pair(from_token, to_token)?;
         let context = self.context()?;
         let mut response = CallResponse::forward(&context.incoming_alkanes);
         
-        let route_data = format!(
-            "BestRoute: from={:?}, to={:?}, amount_in={}",
-            from_token, to_token, amount_in
-        );
+        // The first search for a pair in a block is cached; later queries in the block re-price
+        // that path against current reserves instead of searching again
+        let pool_provider = ZapPoolProvider::new(self);
+        let route_finder = RouteFinder::new(self.oyl_factory_id()?, &pool_provider).with_base_tokens(self.base_tokens()?);
+        let route = match self.cached_route(from_token, to_token)? {
+            Some(cached) => route_finder.price_path(&cached.path, amount_in)?,
+            None => {
+                let route = route_finder.find_best_route(from_token, to_token, amount_in)?;
+                self.cache_route(from_token, to_token, &route)?;
+                route
+            }
+        };
+        
+        response.data = route.to_bytes();
+        Ok(response)
+    }
+
+    /// Per-hop breakdown of the route `GetBestRoute` would return, encoded with
+    /// `HopBreakdown::serialize_list`
+    fn explain_route(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+    ) -> Result<CallResponse> {
+        ensure_distinct_pair(from_token, to_token)?;
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let pool_provider = ZapPoolProvider::new(self);
+        let route_finder = RouteFinder::new(self.oyl_factory_id()?, &pool_provider).with_base_tokens(self.base_tokens()?);
+        let path = match self.cached_route(from_token, to_token)? {
+            Some(cached) => cached.path,
+            None => route_finder.find_best_route(from_token, to_token, amount_in)?.path,
+        };
+        
+        response.data = HopBreakdown::serialize_list(&route_finder.explain(&path, amount_in)?);
+        Ok(response)
+    }
+
+    /// Route many pairs at once. `queries` is flattened as `from.block, from.tx, to.block,
+    /// to.tx, amount_in` per pair; the response is `RouteInfo::serialize_batch`.
+    fn get_best_route_batch(&self, queries: Vec<u128>) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        if !queries.len().is_multiple_of(5) {
+            return Err(anyhow!("Route batch queries must be groups of 5 values"));
+        }
+        let queries: Vec<(AlkaneId, AlkaneId, u128)> = queries
+            .chunks_exact(5)
+            .map(|query| {
+                (
+                    AlkaneId { block: query[0], tx: query[1] },
+                    AlkaneId { block: query[2], tx: query[3] },
+                    query[4],
+                )
+            })
+            .collect();
+        
+        // One pool snapshot serves every query in the batch
+        let pool_provider = ZapPoolProvider::new(self);
+        let cached_provider = CachedPoolProvider::new(&pool_provider);
+        let routes = RouteFinder::new(self.oyl_factory_id()?, &cached_provider)
+            .with_base_tokens(self.base_tokens()?)
+            .find_best_routes(&queries)?;
         
-        response.data = route_data.as_bytes().to_vec();
+        response.data = RouteInfo::serialize_batch(&routes);
         Ok(response)
     }
 
@@ -339,6 +2094,36 @@ pub trait ZapBase: AuthenticatedResponder {
         Ok(response)
     }
 
+    fn get_version(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = version_data();
+        Ok(response)
+    }
+
+    fn estimate_lp_value(&self, token_a: AlkaneId, token_b: AlkaneId, lp_amount: u128) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        
+        let (reserve_a, reserve_b, total_supply) = match self.stored_pool(token_a, token_b)? {
+            Some(pool) => {
+                let (reserve_a, reserve_b) = pool.reserves_for(token_a);
+                (reserve_a, reserve_b, pool.total_supply)
+            }
+            None => {
+                let (reserve_a, reserve_b, _) = self.get_pool_reserves_impl(token_a, token_b)?;
+                (reserve_a, reserve_b, self.pool_total_supply(self.find_pool_id(token_a, token_b)?)?)
+            }
+        };
+        
+        let (amount_a, amount_b) = amm_logic::calculate_lp_share(lp_amount, reserve_a, reserve_b, total_supply)?;
+        let (fees_a, fees_b) = amm_logic::calculate_lp_fee_share(lp_amount, reserve_a, reserve_b, total_supply)?;
+        
+        response.data = LpValueEstimate { amount_a, amount_b, fees_a, fees_b }.encode();
+        
+        Ok(response)
+    }
+
     fn forward(&self) -> Result<CallResponse> {
         let context = self.context()?;
         Ok(CallResponse::forward(&context.incoming_alkanes))
@@ -350,26 +2135,169 @@ pub struct OylZap();
 
 impl AlkaneResponder for OylZap {}
 impl AuthenticatedResponder for OylZap {}
-impl ZapBase for OylZap {
-    fn get_pool_reserves_impl(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<(u128, u128)> {
-        OylZap::get_pool_reserves_impl(self, token_a, token_b)
+impl ReentrancyLock for OylZap {
+    fn is_locked(&self) -> Result<bool> {
+        let bytes = self.load("/locked".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(false);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()) == 1)
+    }
+
+    fn set_locked(&self, locked: bool) -> Result<()> {
+        self.store("/locked".as_bytes().to_vec(), u128::from(locked).to_le_bytes().to_vec());
+        Ok(())
     }
+}
 
-    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
-        OylZap::calculate_swap_output(self, amount_in, reserve_in, reserve_out)
+impl ZapBase for OylZap {
+    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128, fee_rate: u128) -> Result<u128> {
+        OylZap::calculate_swap_output(self, amount_in, reserve_in, reserve_out, fee_rate)
     }
 
     fn execute_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
         OylZap::execute_swap(self, path, amount_in, amount_out_min, deadline)
     }
 
+    fn simulate_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
+        OylZap::simulate_swap(self, path, amount_in, amount_out_min, deadline)
+    }
+
     fn add_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128, amount_a_min: u128, amount_b_min: u128, deadline: u128) -> Result<CallResponse> {
         OylZap::add_liquidity(self, token_a, token_b, amount_a, amount_b, amount_a_min, amount_b_min, deadline)
     }
 
+    fn create_pool(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128) -> Result<CallResponse> {
+        OylZap::create_pool(self, token_a, token_b, amount_a, amount_b)
+    }
+
+    fn add_liquidity_weighted(&self, tokens: Vec<AlkaneId>, amounts: Vec<u128>, amount_mins: Vec<u128>, deadline: u128) -> Result<CallResponse> {
+        OylZap::add_liquidity_weighted(self, tokens, amounts, amount_mins, deadline)
+    }
+
     fn find_pool_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<AlkaneId> {
         OylZap::find_pool_id(self, token_a, token_b)
     }
+
+    fn pool_lp_token_id(&self, pool_id: AlkaneId) -> Result<AlkaneId> {
+        OylZap::pool_lp_token_id(self, pool_id)
+    }
+
+    fn min_lp_safety_margin_bps(&self) -> Result<u128> {
+        OylZap::min_lp_safety_margin_bps(self)
+    }
+
+    fn pool_tokens(&self, pool_id: AlkaneId) -> Result<(AlkaneId, AlkaneId)> {
+        OylZap::pool_tokens(self, pool_id)
+    }
+
+    fn remove_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, liquidity: u128, amount_a_min: u128, amount_b_min: u128, deadline: u128) -> Result<CallResponse> {
+        OylZap::remove_liquidity(self, token_a, token_b, liquidity, amount_a_min, amount_b_min, deadline)
+    }
+
+    fn pool_total_supply(&self, pool_id: AlkaneId) -> Result<u128> {
+        OylZap::pool_total_supply(self, pool_id)
+    }
+
+    fn live_pool_reserves(&self, pool_id: AlkaneId, token_a: AlkaneId) -> Result<(u128, u128, Option<u128>)> {
+        OylZap::live_pool_reserves(self, pool_id, token_a)
+    }
+
+    fn oyl_factory_id(&self) -> Result<AlkaneId> {
+        OylZap::oyl_factory_id(self)
+    }
+
+    fn base_tokens(&self) -> Result<Vec<AlkaneId>> {
+        OylZap::base_tokens(self)
+    }
+
+    fn pool_ratio_history(&self, pool_id: AlkaneId) -> Result<Vec<U256>> {
+        OylZap::pool_ratio_history(self, pool_id)
+    }
+
+    fn record_pool_ratio(&self, pool_id: AlkaneId, ratio: U256) -> Result<()> {
+        OylZap::record_pool_ratio(self, pool_id, ratio)
+    }
+
+    fn max_ratio_volatility_bps(&self) -> Result<u128> {
+        OylZap::max_ratio_volatility_bps(self)
+    }
+
+    fn max_reserve_deviation_bps(&self) -> Result<u128> {
+        OylZap::max_reserve_deviation_bps(self)
+    }
+
+    fn max_reserve_k_drop_bps(&self) -> Result<u128> {
+        OylZap::max_reserve_k_drop_bps(self)
+    }
+
+    fn max_zap_input(&self) -> Result<u128> {
+        OylZap::max_zap_input(self)
+    }
+
+    fn default_slippage_bps(&self) -> Result<u128> {
+        OylZap::default_slippage_bps(self)
+    }
+
+    fn reference_price(&self, token_in: AlkaneId, token_out: AlkaneId) -> Result<Option<(u128, u128)>> {
+        OylZap::reference_price(self, token_in, token_out)
+    }
+
+    fn is_fee_on_transfer(&self, token: AlkaneId) -> Result<bool> {
+        OylZap::is_fee_on_transfer(self, token)
+    }
+
+    fn token_decimals(&self, token: AlkaneId) -> Result<Option<u8>> {
+        OylZap::token_decimals(self, token)
+    }
+
+    fn stored_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<StoredPool>> {
+        OylZap::stored_pool(self, token_a, token_b)
+    }
+
+    fn store_pool(&self, pool: &StoredPool) -> Result<()> {
+        OylZap::store_pool(self, pool)
+    }
+
+    fn get_connected_tokens_impl(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        OylZap::connected_tokens(self, token)
+    }
+
+    fn is_paused(&self) -> Result<bool> {
+        OylZap::is_paused(self)
+    }
+
+    fn flash_swap_fee_bps(&self) -> Result<Option<u128>> {
+        OylZap::flash_swap_fee_bps(self)
+    }
+
+    fn registered_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<AlkaneId>> {
+        OylZap::registered_lp_token_id(self, token_a, token_b)
+    }
+
+    fn register_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId, lp_token_id: AlkaneId) -> Result<()> {
+        OylZap::register_lp_token_id(self, token_a, token_b, lp_token_id)
+    }
+
+    fn registered_lp_token_pair(&self, lp_token_id: AlkaneId) -> Result<Option<(AlkaneId, AlkaneId)>> {
+        OylZap::registered_lp_token_pair(self, lp_token_id)
+    }
+
+    fn cached_route(&self, from_token: AlkaneId, to_token: AlkaneId) -> Result<Option<RouteInfo>> {
+        OylZap::cached_route(self, from_token, to_token)
+    }
+
+    fn cache_route(&self, from_token: AlkaneId, to_token: AlkaneId, route: &RouteInfo) -> Result<()> {
+        OylZap::cache_route(self, from_token, to_token, route)
+    }
+
+    fn record_quote(&self, record: &QuoteRecord) -> Result<()> {
+        OylZap::record_quote(self, record)
+    }
+
+    fn quote_history(&self, limit: u128) -> Result<Vec<QuoteRecord>> {
+        OylZap::quote_history(self, limit)
+    }
 }
 
 impl OylZap {
@@ -380,12 +2308,243 @@ impl OylZap {
         // Store the oyl-protocol factory ID for making AMM calls
         self.set_oyl_factory_id(&factory_id)?;
         
+        // Record which swap layout this factory version expects
+        let layout = self.probe_factory_swap_layout(&factory_id);
+        self.set_factory_swap_layout(layout)?;
+        
         // Store base tokens for routing
+        ensure_distinct_tokens(&base_tokens)?;
         self.set_base_tokens(&base_tokens)?;
         
+        // Mint the auth token that gates owner-only configuration opcodes
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.alkanes.0.push(self.deploy_auth_token(1)?);
+        
+        Ok(response)
+    }
+
+    fn set_min_lp_safety_margin(&self, margin_bps: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if margin_bps > BASIS_POINTS {
+            return Err(anyhow!("Safety margin cannot exceed 100%"));
+        }
+        
+        self.store("/min_lp_safety_margin_bps".as_bytes().to_vec(), margin_bps.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn set_max_ratio_volatility(&self, max_volatility_bps: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        self.store("/max_ratio_volatility_bps".as_bytes().to_vec(), max_volatility_bps.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn set_max_reserve_deviation(&self, max_deviation_bps: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        self.store("/max_reserve_deviation_bps".as_bytes().to_vec(), max_deviation_bps.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    fn set_max_reserve_k_drop(&self, max_drop_bps: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if max_drop_bps > BASIS_POINTS {
+            return Err(anyhow!("Reserve product drop cannot exceed 100%"));
+        }
+        
+        self.store("/max_reserve_k_drop_bps".as_bytes().to_vec(), max_drop_bps.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Cap the input a single zap may swap, however deep the pools; zero lifts the cap
+    fn set_max_zap_input(&self, amount: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        self.store("/max_zap_input".as_bytes().to_vec(), amount.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Block height a stored pool's reserves were last written at, zero if never
+    fn get_pool_update_height(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.pool_update_height(token_a, token_b).to_le_bytes().to_vec();
+        Ok(response)
+    }
+
+    /// Set the slippage tolerance used by quotes and zaps that pass zero
+    fn set_default_slippage(&self, bps: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if bps > BASIS_POINTS {
+            return Err(anyhow!("Default slippage cannot exceed 100%"));
+        }
+        
+        self.store("/default_slippage_bps".as_bytes().to_vec(), bps.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Flag a token that takes a fee on transfer, so zaps measure what they receive rather
+    /// than assume the amount sent
+    fn set_fee_on_transfer(&self, token: AlkaneId, fee_on_transfer: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if fee_on_transfer > 1 {
+            return Err(anyhow!("fee_on_transfer must be 0 or 1"));
+        }
+        
+        self.store(Self::fee_on_transfer_key(&token), fee_on_transfer.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Record the decimals of `token`; quotes into a pair whose tokens both have decimals
+    /// recorded compare the split's ratios at a common 18-decimal scale
+    fn set_token_decimals(&self, token: AlkaneId, decimals: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        let decimals = u8::try_from(decimals)
+            .ok()
+            .filter(|decimals| *decimals <= MAX_TOKEN_DECIMALS)
+            .ok_or_else(|| anyhow!("Token decimals cannot exceed {}", MAX_TOKEN_DECIMALS))?;
+        self.store(Self::token_decimals_key(&token), vec![decimals]);
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Decimals recorded for `token`, or empty data when none are recorded
+    fn get_token_decimals(&self, token: AlkaneId) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self
+            .token_decimals(token)?
+            .map(|decimals| u128::from(decimals).to_le_bytes().to_vec())
+            .unwrap_or_default();
+        Ok(response)
+    }
+
+    /// Pause (1) or resume (0) every zap that moves funds, as an emergency stop
+    fn set_paused(&self, paused: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if paused > 1 {
+            return Err(anyhow!("paused must be 0 or 1"));
+        }
+        
+        self.store("/paused".as_bytes().to_vec(), paused.to_le_bytes().to_vec());
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Set the oracle price (`token_out` per `token_in`, scaled by 1e18) that quoted routes
+    /// must stay within `tolerance_bps` of. A zero price clears the reference.
+    fn set_reference_price(
+        &self,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+        reference_price: u128,
+        tolerance_bps: u128,
+    ) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if tolerance_bps > BASIS_POINTS {
+            return Err(anyhow!("Reference price tolerance cannot exceed 100%"));
+        }
+        
+        let mut bytes = Vec::with_capacity(32);
+        bytes.extend_from_slice(&reference_price.to_le_bytes());
+        bytes.extend_from_slice(&tolerance_bps.to_le_bytes());
+        self.store(Self::reference_price_key(&token_in, &token_out), bytes);
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Record whether the factory settles flash swaps and the fee it charges for them
+    fn set_flash_swap_config(&self, supports_flash: u128, flash_fee_bps: u128) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        if supports_flash > 1 {
+            return Err(anyhow!("supports_flash must be 0 or 1"));
+        }
+        if flash_fee_bps > BASIS_POINTS {
+            return Err(anyhow!("Flash swap fee cannot exceed 100%"));
+        }
+        
+        let mut bytes = Vec::with_capacity(32);
+        bytes.extend_from_slice(&supports_flash.to_le_bytes());
+        bytes.extend_from_slice(&flash_fee_bps.to_le_bytes());
+        self.store("/factory_supports_flash".as_bytes().to_vec(), bytes);
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Add a routing base token; adding one that is already present changes nothing
+    fn add_base_token(&self, token: AlkaneId) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        let mut base_tokens = self.base_tokens()?;
+        if !base_tokens.contains(&token) {
+            base_tokens.push(token);
+            self.set_base_tokens(&base_tokens)?;
+        }
+        
+        Ok(CallResponse::forward(&context.incoming_alkanes))
+    }
+
+    /// Remove a routing base token; removing one that is absent changes nothing
+    fn remove_base_token(&self, token: AlkaneId) -> Result<CallResponse> {
+        self.only_owner()?;
+        let context = self.context()?;
+        
+        let mut base_tokens = self.base_tokens()?;
+        let count = base_tokens.len();
+        base_tokens.retain(|base_token| *base_token != token);
+        if base_tokens.len() != count {
+            self.set_base_tokens(&base_tokens)?;
+        }
+        
         Ok(CallResponse::forward(&context.incoming_alkanes))
     }
 
+    fn get_config(&self) -> Result<CallResponse> {
+        let context = self.context()?;
+        let mut response = CallResponse::forward(&context.incoming_alkanes);
+        response.data = self.config_bytes()?;
+        Ok(response)
+    }
+
+    /// Packed `GetConfig` payload; see `ZapConfig::encode` for the layout
+    pub fn config_bytes(&self) -> Result<Vec<u8>> {
+        let config = ZapConfig {
+            factory_id: self.oyl_factory_id()?,
+            base_tokens: self.base_tokens()?,
+            version: contract_version(),
+            default_slippage_bps: self.default_slippage_bps()?,
+        };
+        Ok(config.encode())
+    }
+
     // Storage functions
     fn oyl_factory_id(&self) -> Result<AlkaneId> {
         let bytes = self.load("/oyl_factory_id".as_bytes().to_vec());
@@ -406,6 +2565,35 @@ impl OylZap {
         Ok(())
     }
 
+    /// Ask the factory for its version. Factories that predate versioning do not answer and
+    /// use the original swap layout.
+    fn probe_factory_swap_layout(&self, factory_id: &AlkaneId) -> FactorySwapLayout {
+        let cellpack = Cellpack {
+            target: *factory_id,
+            inputs: vec![99], // GetVersion opcode
+        };
+        
+        match self.staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel()) {
+            Ok(response) if response.data.len() >= 16 => FactorySwapLayout::from_version(
+                u128::from_le_bytes(response.data[0..16].try_into().unwrap()),
+            ),
+            _ => FactorySwapLayout::V1,
+        }
+    }
+
+    fn factory_swap_layout(&self) -> Result<FactorySwapLayout> {
+        let bytes = self.load("/factory_swap_layout".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(FactorySwapLayout::V1);
+        }
+        Ok(FactorySwapLayout::from_version(u128::from_le_bytes(bytes[0..16].try_into().unwrap())))
+    }
+
+    fn set_factory_swap_layout(&self, layout: FactorySwapLayout) -> Result<()> {
+        self.store("/factory_swap_layout".as_bytes().to_vec(), layout.version().to_le_bytes().to_vec());
+        Ok(())
+    }
+
     fn base_tokens(&self) -> Result<Vec<AlkaneId>> {
         let bytes = self.load("/base_tokens".as_bytes().to_vec());
         if bytes.is_empty() {
@@ -433,20 +2621,328 @@ impl OylZap {
         Ok(())
     }
 
+    /// Safety margin (bps) applied to `min_lp_tokens` in `execute_zap`; zero when unset
+    fn min_lp_safety_margin_bps(&self) -> Result<u128> {
+        let bytes = self.load("/min_lp_safety_margin_bps".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(0);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()))
+    }
+
+    /// Maximum pool ratio movement (bps) tolerated when quoting; zero disables the check
+    fn max_ratio_volatility_bps(&self) -> Result<u128> {
+        let bytes = self.load("/max_ratio_volatility_bps".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(0);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()))
+    }
+
+    /// Maximum live ratio deviation (bps) from stored reserves tolerated when executing;
+    /// zero disables the check
+    fn max_reserve_deviation_bps(&self) -> Result<u128> {
+        let bytes = self.load("/max_reserve_deviation_bps".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(0);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()))
+    }
+
+    /// Maximum drop (bps) in a pool's reserve product tolerated from one `UpdatePoolReserves`
+    /// to the next; zero disables the check
+    fn max_reserve_k_drop_bps(&self) -> Result<u128> {
+        let bytes = self.load("/max_reserve_k_drop_bps".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(0);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()))
+    }
+
+    /// Largest input a single zap may swap; zero means unlimited
+    fn max_zap_input(&self) -> Result<u128> {
+        let bytes = self.load("/max_zap_input".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(0);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()))
+    }
+
+    /// Slippage tolerance (bps) applied when a caller passes zero; zero until configured
+    fn default_slippage_bps(&self) -> Result<u128> {
+        let bytes = self.load("/default_slippage_bps".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(0);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()))
+    }
+
+    fn reference_price_key(token_in: &AlkaneId, token_out: &AlkaneId) -> Vec<u8> {
+        format!(
+            "/reference_price/{}:{}/{}:{}",
+            token_in.block, token_in.tx, token_out.block, token_out.tx
+        )
+        .into_bytes()
+    }
+
+    /// Reference price and tolerance for routes from `token_in` to `token_out`, if configured
+    fn reference_price(&self, token_in: AlkaneId, token_out: AlkaneId) -> Result<Option<(u128, u128)>> {
+        let bytes = self.load(Self::reference_price_key(&token_in, &token_out));
+        if bytes.len() < 32 {
+            return Ok(None);
+        }
+        let reference_price = u128::from_le_bytes(bytes[0..16].try_into().unwrap());
+        let tolerance_bps = u128::from_le_bytes(bytes[16..32].try_into().unwrap());
+        Ok((reference_price > 0).then_some((reference_price, tolerance_bps)))
+    }
+
+    fn fee_on_transfer_key(token: &AlkaneId) -> Vec<u8> {
+        format!("/fee_on_transfer/{}:{}", token.block, token.tx).into_bytes()
+    }
+
+    /// Whether the owner has flagged `token` as taking a fee on transfer
+    fn is_fee_on_transfer(&self, token: AlkaneId) -> Result<bool> {
+        let bytes = self.load(Self::fee_on_transfer_key(&token));
+        if bytes.len() < 16 {
+            return Ok(false);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()) == 1)
+    }
+
+    fn token_decimals_key(token: &AlkaneId) -> Vec<u8> {
+        format!("/token_decimals/{}:{}", token.block, token.tx).into_bytes()
+    }
+
+    /// Decimals the owner has recorded for `token` with `SetTokenDecimals`, if any
+    fn token_decimals(&self, token: AlkaneId) -> Result<Option<u8>> {
+        let bytes = self.load(Self::token_decimals_key(&token));
+        Ok(bytes.first().copied())
+    }
+
+    /// Whether the owner has paused zaps with `SetPaused`
+    fn is_paused(&self) -> Result<bool> {
+        let bytes = self.load("/paused".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return Ok(false);
+        }
+        Ok(u128::from_le_bytes(bytes[0..16].try_into().unwrap()) == 1)
+    }
+
+    /// Flash swap fee when the factory is configured to support flash swaps
+    fn flash_swap_fee_bps(&self) -> Result<Option<u128>> {
+        let bytes = self.load("/factory_supports_flash".as_bytes().to_vec());
+        if bytes.len() < 32 {
+            return Ok(None);
+        }
+        let supports_flash = u128::from_le_bytes(bytes[0..16].try_into().unwrap());
+        let flash_fee_bps = u128::from_le_bytes(bytes[16..32].try_into().unwrap());
+        Ok((supports_flash == 1).then_some(flash_fee_bps))
+    }
+
+    fn ratio_history_key(pool_id: &AlkaneId) -> Vec<u8> {
+        format!("/ratio_history/{}:{}", pool_id.block, pool_id.tx).into_bytes()
+    }
+
+    fn pool_ratio_history(&self, pool_id: AlkaneId) -> Result<Vec<U256>> {
+        let bytes = self.load(Self::ratio_history_key(&pool_id));
+        Ok(bytes
+            .chunks_exact(32)
+            .map(U256::from_le_slice)
+            .collect())
+    }
+
+    fn record_pool_ratio(&self, pool_id: AlkaneId, ratio: U256) -> Result<()> {
+        let mut history = self.pool_ratio_history(pool_id)?;
+        ZapCalculator::push_ratio_observation(&mut history, ratio);
+        
+        let mut bytes = Vec::with_capacity(RATIO_HISTORY_LENGTH * 32);
+        for observation in &history {
+            bytes.extend_from_slice(&observation.to_le_bytes::<32>());
+        }
+        self.store(Self::ratio_history_key(&pool_id), bytes);
+        Ok(())
+    }
+
+    /// Pool metadata registered through `AddPool`, if any
+    fn stored_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<StoredPool>> {
+        let bytes = self.load(StoredPool::storage_key(&token_a, &token_b));
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        StoredPool::decode(&bytes).map(Some)
+    }
+
+    fn store_pool(&self, pool: &StoredPool) -> Result<()> {
+        self.store(StoredPool::storage_key(&pool.token_a, &pool.token_b), pool.encode());
+        self.store(
+            Self::pool_update_height_key(&pool.token_a, &pool.token_b),
+            u128::from(self.height()).to_le_bytes().to_vec(),
+        );
+        
+        // Persist the pool as an edge of the routing graph in both directions. Counterparties
+        // are only ever appended, so a pool stays routable after its reserves are refreshed.
+        for (token, other) in [(pool.token_a, pool.token_b), (pool.token_b, pool.token_a)] {
+            let mut connected = self.connected_tokens(token)?;
+            if connected.contains(&other) {
+                continue;
+            }
+            connected.push(other);
+            self.store(Self::adjacency_key(&token), encode_token_list(&connected));
+        }
+        Ok(())
+    }
+
+    fn pool_update_height_key(token_a: &AlkaneId, token_b: &AlkaneId) -> Vec<u8> {
+        let mut key = StoredPool::storage_key(token_a, token_b);
+        key.extend_from_slice(b"/updated_height");
+        key
+    }
+
+    /// Block height `store_pool` last wrote the pair's reserves at, zero if never
+    fn pool_update_height(&self, token_a: AlkaneId, token_b: AlkaneId) -> u128 {
+        let bytes = self.load(Self::pool_update_height_key(&token_a, &token_b));
+        if bytes.len() < 16 {
+            return 0;
+        }
+        u128::from_le_bytes(bytes[0..16].try_into().unwrap())
+    }
+
+    fn quote_history_slot_key(slot: u128) -> Vec<u8> {
+        format!("/quote_history/{}", slot).into_bytes()
+    }
+
+    /// Total quotes ever recorded; the next record goes in slot `count % QUOTE_HISTORY_LENGTH`
+    fn quote_history_count(&self) -> u128 {
+        let bytes = self.load("/quote_history/count".as_bytes().to_vec());
+        if bytes.len() < 16 {
+            return 0;
+        }
+        u128::from_le_bytes(bytes[0..16].try_into().unwrap())
+    }
+
+    fn record_quote(&self, record: &QuoteRecord) -> Result<()> {
+        let count = self.quote_history_count();
+        self.store(Self::quote_history_slot_key(count % QUOTE_HISTORY_LENGTH), record.encode());
+        self.store(
+            "/quote_history/count".as_bytes().to_vec(),
+            count.saturating_add(1).to_le_bytes().to_vec(),
+        );
+        Ok(())
+    }
+
+    fn quote_history(&self, limit: u128) -> Result<Vec<QuoteRecord>> {
+        let count = self.quote_history_count();
+        let retained = limit.min(count).min(QUOTE_HISTORY_LENGTH);
+        (count - retained..count)
+            .map(|index| QuoteRecord::decode(&self.load(Self::quote_history_slot_key(index % QUOTE_HISTORY_LENGTH))))
+            .collect()
+    }
+
+    fn adjacency_key(token: &AlkaneId) -> Vec<u8> {
+        format!("/adjacency/{}:{}", token.block, token.tx).into_bytes()
+    }
+
+    /// Tokens sharing a pool registered through `AddPool` with `token`, in registration order
+    fn connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        let bytes = self.load(Self::adjacency_key(&token));
+        if bytes.is_empty() {
+            return Ok(Vec::new());
+        }
+        decode_token_list(&bytes)
+    }
+
+    fn route_cache_key(from_token: &AlkaneId, to_token: &AlkaneId, height: u128) -> Vec<u8> {
+        format!(
+            "/route_cache/{}:{}/{}:{}/{}",
+            from_token.block, from_token.tx, to_token.block, to_token.tx, height
+        )
+        .into_bytes()
+    }
+
+    /// Best route between two tokens cached in the current block. Entries are keyed by height,
+    /// so one from an earlier block, whose reserves may since have moved, is never served.
+    fn cached_route(&self, from_token: AlkaneId, to_token: AlkaneId) -> Result<Option<RouteInfo>> {
+        let bytes = self.load(Self::route_cache_key(&from_token, &to_token, u128::from(self.height())));
+        if bytes.is_empty() {
+            return Ok(None);
+        }
+        RouteInfo::from_bytes(&bytes).map(Some)
+    }
+
+    /// Record `route` as the best route between two tokens in the current block
+    fn cache_route(&self, from_token: AlkaneId, to_token: AlkaneId, route: &RouteInfo) -> Result<()> {
+        self.store(Self::route_cache_key(&from_token, &to_token, u128::from(self.height())), route.to_bytes());
+        Ok(())
+    }
+
+    fn lp_token_id_key(token_a: &AlkaneId, token_b: &AlkaneId) -> Vec<u8> {
+        pool_storage_key("/lp_token_ids", token_a, token_b)
+    }
+
+    fn registered_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<AlkaneId>> {
+        let bytes = self.load(Self::lp_token_id_key(&token_a, &token_b));
+        if bytes.len() < 32 {
+            return Ok(None);
+        }
+        Ok(Some(AlkaneId {
+            block: u128::from_le_bytes(bytes[0..16].try_into().unwrap()),
+            tx: u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
+        }))
+    }
+
+    fn register_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId, lp_token_id: AlkaneId) -> Result<()> {
+        let mut bytes = Vec::with_capacity(32);
+        bytes.extend_from_slice(&lp_token_id.block.to_le_bytes());
+        bytes.extend_from_slice(&lp_token_id.tx.to_le_bytes());
+        self.store(Self::lp_token_id_key(&token_a, &token_b), bytes);
+        
+        // Index the other way too, so an LP token handed back can be traced to its pair
+        let (first, second) = canonical_pool_key(token_a, token_b);
+        let mut pair_bytes = Vec::with_capacity(64);
+        for id in [first, second] {
+            pair_bytes.extend_from_slice(&id.block.to_le_bytes());
+            pair_bytes.extend_from_slice(&id.tx.to_le_bytes());
+        }
+        self.store(Self::lp_token_pair_key(&lp_token_id), pair_bytes);
+        Ok(())
+    }
+
+    fn lp_token_pair_key(lp_token_id: &AlkaneId) -> Vec<u8> {
+        format!("/lp_token_pairs/{}:{}", lp_token_id.block, lp_token_id.tx).into_bytes()
+    }
+
+    fn registered_lp_token_pair(&self, lp_token_id: AlkaneId) -> Result<Option<(AlkaneId, AlkaneId)>> {
+        let bytes = self.load(Self::lp_token_pair_key(&lp_token_id));
+        if bytes.len() < 64 {
+            return Ok(None);
+        }
+        let id_at = |offset: usize| AlkaneId {
+            block: u128::from_le_bytes(bytes[offset..offset + 16].try_into().unwrap()),
+            tx: u128::from_le_bytes(bytes[offset + 16..offset + 32].try_into().unwrap()),
+        };
+        Ok(Some((id_at(0), id_at(32))))
+    }
+
     // Real AMM interaction functions
     fn find_pool_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<AlkaneId> {
+        if let Some(pool) = self.stored_pool(token_a, token_b)? {
+            return Ok(pool.pool_id);
+        }
+
         let factory_id = self.oyl_factory_id()?;
         
-        // Call oyl-protocol factory to find existing pool
+        // Call oyl-protocol factory to find existing pool, asking in canonical order so
+        // either ordering of the pair resolves the same pool
+        let (first, second) = canonical_pool_key(token_a, token_b);
         let cellpack = Cellpack {
             target: factory_id,
-            inputs: vec![2, token_a.block, token_a.tx, token_b.block, token_b.tx], // FindExistingPoolId opcode
+            inputs: vec![2, first.block, first.tx, second.block, second.tx], // FindExistingPoolId opcode
         };
 
         let response = self.staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
         
         if response.data.len() < 32 {
-            return Err(anyhow!("Pool not found for tokens {:?} and {:?}", token_a, token_b));
+            return Err(ZapError::PoolNotFound { token_a, token_b }.into());
         }
 
         Ok(AlkaneId {
@@ -455,9 +2951,73 @@ impl OylZap {
         })
     }
 
-    fn get_pool_reserves_impl(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<(u128, u128)> {
-        let pool_id = self.find_pool_id(token_a, token_b)?;
+    fn pool_lp_token_id(&self, pool_id: AlkaneId) -> Result<AlkaneId> {
+        // Ask the pool which token it mints for liquidity providers
+        let cellpack = Cellpack {
+            target: pool_id,
+            inputs: vec![1000], // GetLpTokenId opcode
+        };
+
+        // Pools that are their own LP token don't implement the lookup
+        let response = match self.staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel()) {
+            Ok(response) => response,
+            Err(_) => return Ok(pool_id),
+        };
+
+        if response.data.len() < 32 {
+            return Ok(pool_id);
+        }
+
+        Ok(AlkaneId {
+            block: u128::from_le_bytes(response.data[0..16].try_into().unwrap()),
+            tx: u128::from_le_bytes(response.data[16..32].try_into().unwrap()),
+        })
+    }
+
+    fn pool_tokens(&self, pool_id: AlkaneId) -> Result<(AlkaneId, AlkaneId)> {
+        // Call pool to get its token pair
+        let cellpack = Cellpack {
+            target: pool_id,
+            inputs: vec![999], // PoolDetails opcode
+        };
+
+        let response = self.staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
+        
+        if response.data.len() < 64 {
+            return Err(anyhow!("Failed to get pool tokens for {:?}", pool_id));
+        }
+
+        let token_a = AlkaneId {
+            block: u128::from_le_bytes(response.data[0..16].try_into().unwrap()),
+            tx: u128::from_le_bytes(response.data[16..32].try_into().unwrap()),
+        };
+        let token_b = AlkaneId {
+            block: u128::from_le_bytes(response.data[32..48].try_into().unwrap()),
+            tx: u128::from_le_bytes(response.data[48..64].try_into().unwrap()),
+        };
+        
+        Ok((token_a, token_b))
+    }
+
+    fn pool_total_supply(&self, pool_id: AlkaneId) -> Result<u128> {
+        // Call pool to get the LP token supply
+        let cellpack = Cellpack {
+            target: pool_id,
+            inputs: vec![101], // GetTotalSupply opcode
+        };
+
+        let response = self.staticcall(&cellpack, &AlkaneTransferParcel::default(), self.fuel())?;
         
+        if response.data.len() < 16 {
+            return Err(anyhow!("Failed to get pool total supply"));
+        }
+
+        Ok(u128::from_le_bytes(response.data[0..16].try_into().unwrap()))
+    }
+
+    /// Current reserves of `pool_id` oriented so `token_a`'s comes first, with the fee rate
+    /// when the pool reports one
+    fn live_pool_reserves(&self, pool_id: AlkaneId, token_a: AlkaneId) -> Result<(u128, u128, Option<u128>)> {
         // Call pool to get reserves
         let cellpack = Cellpack {
             target: pool_id,
@@ -470,51 +3030,51 @@ impl OylZap {
             return Err(anyhow!("Failed to get pool reserves"));
         }
 
-        let reserve_a = u128::from_le_bytes(response.data[0..16].try_into().unwrap());
-        let reserve_b = u128::from_le_bytes(response.data[16..32].try_into().unwrap());
+        let reserve_0 = u128::from_le_bytes(response.data[0..16].try_into().unwrap());
+        let reserve_1 = u128::from_le_bytes(response.data[16..32].try_into().unwrap());
+        let fee_rate = if response.data.len() >= 48 {
+            Some(u128::from_le_bytes(response.data[32..48].try_into().unwrap()))
+        } else {
+            None
+        };
         
-        Ok((reserve_a, reserve_b))
+        // Pools report reserves in their own token order; orient them to the caller's pair
+        let (pool_token_0, _) = self.pool_tokens(pool_id)?;
+        if pool_token_0 == token_a {
+            Ok((reserve_0, reserve_1, fee_rate))
+        } else {
+            Ok((reserve_1, reserve_0, fee_rate))
+        }
     }
 
-    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128) -> Result<u128> {
+    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128, fee_rate: u128) -> Result<u128> {
         if amount_in == 0 || reserve_in == 0 || reserve_out == 0 {
             return Ok(0);
         }
 
-        // AMM formula: amount_out = (amount_in * 997 * reserve_out) / (reserve_in * 1000 + amount_in * 997)
-        // Using 0.3% fee (997/1000)
-        let amount_in_with_fee = amount_in * 997;
-        let numerator = amount_in_with_fee * reserve_out;
-        let denominator = reserve_in * 1000 + amount_in_with_fee;
-        
-        Ok(numerator / denominator)
+        amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, fee_rate)
     }
 
     fn execute_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
-        let factory_id = self.oyl_factory_id()?;
-        
-        // Call oyl-protocol factory to execute swap
-        let cellpack = Cellpack {
-            target: factory_id,
-            inputs: vec![
-                13, // SwapExactTokensForTokens opcode
-                path.len() as u128,
-            ],
-        };
+        let (swap_cellpack, input_parcel) = self.swap_call(&path, amount_in, amount_out_min, deadline)?;
+        self.call(&swap_cellpack, &input_parcel, self.fuel())
+    }
 
-        // Add path tokens to inputs
-        let mut inputs = cellpack.inputs;
-        for token in &path {
-            inputs.push(token.block);
-            inputs.push(token.tx);
-        }
-        inputs.push(amount_in);
-        inputs.push(amount_out_min);
-        inputs.push(deadline);
+    /// Run the same factory swap as `execute_swap` as a static call, so nothing it does persists
+    fn simulate_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
+        let (swap_cellpack, input_parcel) = self.swap_call(&path, amount_in, amount_out_min, deadline)?;
+        self.staticcall(&swap_cellpack, &input_parcel, self.fuel())
+    }
 
+    fn swap_call(&self, path: &[AlkaneId], amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<(Cellpack, AlkaneTransferParcel)> {
+        let factory_id = self.oyl_factory_id()?;
+        
+        // Call oyl-protocol factory to execute swap, in the layout its version expects
         let swap_cellpack = Cellpack {
             target: factory_id,
-            inputs,
+            inputs: self
+                .factory_swap_layout()?
+                .swap_inputs(path, amount_in, amount_out_min, deadline),
         };
 
         // Create transfer parcel with input token
@@ -523,7 +3083,7 @@ impl OylZap {
             value: amount_in,
         }]);
 
-        self.call(&swap_cellpack, &input_parcel, self.fuel())
+        Ok((swap_cellpack, input_parcel))
     }
 
     fn add_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128, amount_a_min: u128, amount_b_min: u128, deadline: u128) -> Result<CallResponse> {
@@ -550,6 +3110,88 @@ impl OylZap {
 
         self.call(&cellpack, &liquidity_parcel, self.fuel())
     }
+
+    fn create_pool(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128) -> Result<CallResponse> {
+        let factory_id = self.oyl_factory_id()?;
+        
+        // Call oyl-protocol factory to create the pool, seeding it with the initial liquidity
+        let cellpack = Cellpack {
+            target: factory_id,
+            inputs: vec![
+                1, // CreateNewPool opcode
+                token_a.block, token_a.tx,
+                token_b.block, token_b.tx,
+                amount_a, amount_b,
+            ],
+        };
+
+        // Create transfer parcel with both tokens
+        let liquidity_parcel = AlkaneTransferParcel(vec![
+            AlkaneTransfer { id: token_a, value: amount_a },
+            AlkaneTransfer { id: token_b, value: amount_b },
+        ]);
+
+        self.call(&cellpack, &liquidity_parcel, self.fuel())
+    }
+
+    fn add_liquidity_weighted(&self, tokens: Vec<AlkaneId>, amounts: Vec<u128>, amount_mins: Vec<u128>, deadline: u128) -> Result<CallResponse> {
+        let factory_id = self.oyl_factory_id()?;
+        
+        // Call oyl-protocol factory to add liquidity to a multi-asset pool
+        let mut inputs = vec![
+            14, // AddLiquidityWeighted opcode
+            tokens.len() as u128,
+        ];
+        for token in &tokens {
+            inputs.push(token.block);
+            inputs.push(token.tx);
+        }
+        inputs.extend_from_slice(&amounts);
+        inputs.extend_from_slice(&amount_mins);
+        inputs.push(deadline);
+        
+        let cellpack = Cellpack {
+            target: factory_id,
+            inputs,
+        };
+
+        // Create transfer parcel with every pool token
+        let liquidity_parcel = AlkaneTransferParcel(
+            tokens
+                .iter()
+                .zip(amounts.iter())
+                .map(|(token, amount)| AlkaneTransfer { id: *token, value: *amount })
+                .collect(),
+        );
+
+        self.call(&cellpack, &liquidity_parcel, self.fuel())
+    }
+
+    fn remove_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, liquidity: u128, amount_a_min: u128, amount_b_min: u128, deadline: u128) -> Result<CallResponse> {
+        let factory_id = self.oyl_factory_id()?;
+        let lp_token_id = self.lp_token_id_for_pair(token_a, token_b)?;
+        
+        // Call oyl-protocol factory to burn LP tokens for the underlying pair
+        let cellpack = Cellpack {
+            target: factory_id,
+            inputs: vec![
+                12, // RemoveLiquidity opcode
+                token_a.block, token_a.tx,
+                token_b.block, token_b.tx,
+                liquidity,
+                amount_a_min, amount_b_min,
+                deadline,
+            ],
+        };
+
+        // Create transfer parcel with the LP tokens being burned
+        let lp_parcel = AlkaneTransferParcel(vec![AlkaneTransfer {
+            id: lp_token_id,
+            value: liquidity,
+        }]);
+
+        self.call(&cellpack, &lp_parcel, self.fuel())
+    }
 }
 
 declare_alkane! {
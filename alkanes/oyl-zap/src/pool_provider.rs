@@ -1,6 +1,8 @@
-use crate::types::PoolReserves;
+use crate::types::{PoolReserves, ZapError};
 use alkanes_support::id::AlkaneId;
 use anyhow::Result;
+use std::cell::RefCell;
+use std::collections::HashMap;
 
 /// A trait for providing pool data. This allows for decoupling the routing logic
 /// from the specific data source, making it easier to test with mock data or
@@ -11,4 +13,103 @@ pub trait PoolProvider {
 
     /// Get all tokens connected to a given token through existing pools.
     fn get_connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>>;
-}
\ No newline at end of file
+
+    /// Get the reserves for several pools at once, `None` for each pair without a pool.
+    /// Falls back to one lookup per pair; providers backed by the factory can override this
+    /// with a single multicall.
+    fn get_pool_reserves_batch(&self, pairs: &[(AlkaneId, AlkaneId)]) -> Result<Vec<Option<PoolReserves>>> {
+        Ok(pairs
+            .iter()
+            .map(|(token_a, token_b)| self.get_pool_reserves(*token_a, *token_b).ok())
+            .collect())
+    }
+}
+/// Memoizes another provider's answers so that many route searches in one call share a
+/// single snapshot of the pool graph instead of re-querying each pool.
+pub struct CachedPoolProvider<'a, P: PoolProvider> {
+    inner: &'a P,
+    reserves: RefCell<HashMap<(AlkaneId, AlkaneId), Option<PoolReserves>>>,
+    connections: RefCell<HashMap<AlkaneId, Vec<AlkaneId>>>,
+}
+
+impl<'a, P: PoolProvider> CachedPoolProvider<'a, P> {
+    pub fn new(inner: &'a P) -> Self {
+        Self {
+            inner,
+            reserves: RefCell::new(HashMap::new()),
+            connections: RefCell::new(HashMap::new()),
+        }
+    }
+}
+
+impl<'a, P: PoolProvider> PoolProvider for CachedPoolProvider<'a, P> {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<PoolReserves> {
+        let cached = self
+            .reserves
+            .borrow_mut()
+            .entry((token_a, token_b))
+            .or_insert_with(|| self.inner.get_pool_reserves(token_a, token_b).ok())
+            .clone();
+        cached.ok_or_else(|| ZapError::PoolNotFound { token_a, token_b }.into())
+    }
+
+    /// Answers cached pairs directly and asks the inner provider for the rest in one batch
+    fn get_pool_reserves_batch(&self, pairs: &[(AlkaneId, AlkaneId)]) -> Result<Vec<Option<PoolReserves>>> {
+        let mut missing: Vec<(AlkaneId, AlkaneId)> = Vec::new();
+        for pair in pairs {
+            if !self.reserves.borrow().contains_key(pair) && !missing.contains(pair) {
+                missing.push(*pair);
+            }
+        }
+        if !missing.is_empty() {
+            let fetched = self.inner.get_pool_reserves_batch(&missing)?;
+            let mut reserves = self.reserves.borrow_mut();
+            for (pair, pool) in missing.into_iter().zip(fetched) {
+                reserves.insert(pair, pool);
+            }
+        }
+
+        let reserves = self.reserves.borrow();
+        Ok(pairs.iter().map(|pair| reserves.get(pair).cloned().flatten()).collect())
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        if let Some(tokens) = self.connections.borrow().get(&token) {
+            return Ok(tokens.clone());
+        }
+        let tokens = self.inner.get_connected_tokens(token)?;
+        self.connections.borrow_mut().insert(token, tokens.clone());
+        Ok(tokens)
+    }
+}
+
+/// Prices every pool at the factory's flash-swap fee. A flash swap settles the whole route
+/// through the factory in one call, so the factory's fee applies in place of each pool's.
+pub struct FlashSwapPoolProvider<'a, P: PoolProvider> {
+    inner: &'a P,
+    fee_rate: u128,
+}
+
+impl<'a, P: PoolProvider> FlashSwapPoolProvider<'a, P> {
+    pub fn new(inner: &'a P, fee_rate: u128) -> Self {
+        Self { inner, fee_rate }
+    }
+}
+
+impl<'a, P: PoolProvider> PoolProvider for FlashSwapPoolProvider<'a, P> {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<PoolReserves> {
+        Ok(self.inner.get_pool_reserves(token_a, token_b)?.with_fee_rate(self.fee_rate))
+    }
+
+    fn get_pool_reserves_batch(&self, pairs: &[(AlkaneId, AlkaneId)]) -> Result<Vec<Option<PoolReserves>>> {
+        let batch = self.inner.get_pool_reserves_batch(pairs)?;
+        Ok(batch
+            .into_iter()
+            .map(|reserves| reserves.map(|reserves| reserves.with_fee_rate(self.fee_rate)))
+            .collect())
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        self.inner.get_connected_tokens(token)
+    }
+}
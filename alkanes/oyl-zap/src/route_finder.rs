@@ -1,15 +1,25 @@
-use crate::pool_provider::PoolProvider;
-use crate::types::{RouteInfo, U256, MAX_HOPS};
+use crate::pool_provider::{CachedPoolProvider, PoolProvider};
+use crate::types::{CurveKind, DefaultGasModel, GasModel, HopBreakdown, RouteInfo, RouteScoring, ZapError, U256, BASIS_POINTS, MAX_HOPS, MAX_ROUTES, MAX_ROUTE_BATCH, MAX_ROUTE_EXPANSIONS, MINIMUM_LIQUIDITY, PRICE_PRECISION, SPLIT_ROUTE_STEPS};
 use crate::amm_logic;
+use crate::canonical_pool_key;
 use alkanes_support::id::AlkaneId;
 use anyhow::{anyhow, Result};
-use std::collections::{HashSet, VecDeque};
+use std::collections::{BinaryHeap, HashMap, HashSet, VecDeque};
+use std::rc::Rc;
 
 pub struct RouteFinder<'a, P: PoolProvider> {
     pub oyl_factory_id: AlkaneId,
     pub common_base_tokens: Vec<AlkaneId>,
     pub pool_provider: &'a P,
     pub excluded_intermediate_tokens: HashSet<AlkaneId>,
+    pub excluded_pools: HashSet<(AlkaneId, AlkaneId)>,
+    pub reference_prices: HashMap<(AlkaneId, AlkaneId), (u128, u128)>, // (price, tolerance_bps)
+    pub ratio_preference_bps: u128,
+    pub scoring: RouteScoring,
+    pub min_pool_liquidity: u128,
+    pub gas_model: Rc<dyn GasModel>,
+    pub max_routes: usize,
+    pub max_expansions: usize,
 }
 
 impl<'a, P: PoolProvider> RouteFinder<'a, P> {
@@ -19,6 +29,14 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
             common_base_tokens: Vec::new(),
             pool_provider,
             excluded_intermediate_tokens: HashSet::new(),
+            excluded_pools: HashSet::new(),
+            reference_prices: HashMap::new(),
+            ratio_preference_bps: 0,
+            scoring: RouteScoring::default(),
+            min_pool_liquidity: MINIMUM_LIQUIDITY,
+            gas_model: Rc::new(DefaultGasModel),
+            max_routes: MAX_ROUTES,
+            max_expansions: MAX_ROUTE_EXPANSIONS,
         }
     }
 
@@ -33,34 +51,505 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
         self
     }
 
+    /// Exclude the pool between these tokens from being traded against in a route.
+    pub fn with_excluded_pool(mut self, token_a: AlkaneId, token_b: AlkaneId) -> Self {
+        self.excluded_pools.insert(canonical_pool_key(token_a, token_b));
+        self
+    }
+
+    /// Only accept routes from `from_token` to `to_token` whose effective price is within
+    /// `tolerance_bps` of `reference_price` (output per unit input, scaled by 1e18), so a
+    /// manipulated pool cannot be quoted against even when it is the only route.
+    pub fn with_reference_price(mut self, from_token: AlkaneId, to_token: AlkaneId, reference_price: u128, tolerance_bps: u128) -> Self {
+        self.reference_prices.insert((from_token, to_token), (reference_price, tolerance_bps));
+        self
+    }
+
+    /// When choosing zap routes, treat route pairs worth within `tolerance_bps` of the best pair
+    /// as comparable and take the one whose outputs sit closest to the target pool's ratio, so
+    /// large zaps leave less dust and disturb the pool less. Zero keeps the highest-output pair.
+    pub fn with_ratio_preference(mut self, tolerance_bps: u128) -> Self {
+        self.ratio_preference_bps = tolerance_bps;
+        self
+    }
+
+    /// Rank candidate routes with `scoring` instead of by raw expected output, e.g. so a large
+    /// zap avoids a thin pool that pays marginally more but moves its price far further.
+    pub fn with_scoring(mut self, scoring: RouteScoring) -> Self {
+        self.scoring = scoring;
+        self
+    }
+
+    /// Skip pools holding less than `min_pool_liquidity` of either token, where even a small
+    /// swap would move the price catastrophically. Defaults to `MINIMUM_LIQUIDITY`.
+    pub fn with_min_pool_liquidity(mut self, min_pool_liquidity: u128) -> Self {
+        self.min_pool_liquidity = min_pool_liquidity;
+        self
+    }
+
+    /// Estimate each route's gas with `gas_model` instead of the flat `DefaultGasModel`, e.g. to
+    /// charge stable-curve hops for their costlier invariant.
+    pub fn with_gas_model(mut self, gas_model: impl GasModel + 'static) -> Self {
+        self.gas_model = Rc::new(gas_model);
+        self
+    }
+
+    /// Stop enumerating candidate routes once `max_routes` are found, keeping the best of those
+    /// found so far, so a densely connected pool graph cannot exhaust fuel. Defaults to `MAX_ROUTES`.
+    pub fn with_max_routes(mut self, max_routes: usize) -> Self {
+        self.max_routes = max_routes;
+        self
+    }
+
+    /// Cap the partial paths the multi-hop and best-first searches each expand per query.
+    /// Defaults to `MAX_ROUTE_EXPANSIONS`.
+    pub fn with_max_expansions(mut self, max_expansions: usize) -> Self {
+        self.max_expansions = max_expansions;
+        self
+    }
+
+    /// Whether a pool's reserves on both sides meet `min_pool_liquidity`
+    fn has_min_liquidity(&self, reserve_in: u128, reserve_out: u128) -> bool {
+        reserve_in >= self.min_pool_liquidity && reserve_out >= self.min_pool_liquidity
+    }
+
+    /// Whether a path is free of cycles and never trades against an excluded pool.
+    pub fn is_route_allowed(&self, path: &[AlkaneId]) -> bool {
+        let mut seen = HashSet::new();
+        if !path.iter().all(|token| seen.insert(*token)) {
+            return false;
+        }
+        !path
+            .windows(2)
+            .any(|hop| self.excluded_pools.contains(&canonical_pool_key(hop[0], hop[1])))
+    }
+
+    /// Find the routes feeding both sides of a zap into the target pool.
+    ///
+    /// Neither route may trade against the target pool itself, whether directly or by passing
+    /// through the sibling target token, since that would move the reserves the zap deposits into.
+    /// A route for a target equal to the input token is a direct contribution, and the other
+    /// leg of such a single-sided zap may swap straight through the target pool, as the
+    /// single-sided split accounts for the reserves that swap leaves behind.
+    pub fn find_zap_routes(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+    ) -> Result<(RouteInfo, RouteInfo)> {
+        let half_amount = input_amount / 2;
+        // Both legs are priced against one snapshot, which also serves the pools they share
+        let snapshot = CachedPoolProvider::new(self.pool_provider);
+        let find_routes = |target: AlkaneId, sibling: AlkaneId| -> Result<Vec<RouteInfo>> {
+            if input_token == target {
+                return Ok(vec![RouteInfo::new(vec![input_token], half_amount)]);
+            }
+            let mut excluded_intermediate_tokens = self.excluded_intermediate_tokens.clone();
+            let mut excluded_pools = self.excluded_pools.clone();
+            if input_token != sibling {
+                excluded_intermediate_tokens.insert(sibling);
+                excluded_pools.insert(canonical_pool_key(target_token_a, target_token_b));
+            }
+
+            let route_finder = RouteFinder {
+                oyl_factory_id: self.oyl_factory_id,
+                common_base_tokens: self.common_base_tokens.clone(),
+                pool_provider: &snapshot,
+                excluded_intermediate_tokens,
+                excluded_pools,
+                reference_prices: self.reference_prices.clone(),
+                ratio_preference_bps: 0,
+                scoring: self.scoring,
+                min_pool_liquidity: self.min_pool_liquidity,
+                gas_model: self.gas_model.clone(),
+                max_routes: self.max_routes,
+                max_expansions: self.max_expansions,
+            };
+            if self.ratio_preference_bps == 0 {
+                return Ok(vec![route_finder.find_best_route(input_token, target, half_amount)?]);
+            }
+            let candidates = route_finder.find_route_candidates(input_token, target, half_amount)?;
+            if candidates.is_empty() {
+                return Err(ZapError::RouteNotFound { from_token: input_token, to_token: target }.into());
+            }
+            Ok(candidates)
+        };
+
+        let routes_a = find_routes(target_token_a, target_token_b)?;
+        let routes_b = find_routes(target_token_b, target_token_a)?;
+        let (route_a, mut route_b) = if routes_a.len() == 1 && routes_b.len() == 1 {
+            (routes_a[0].clone(), routes_b[0].clone())
+        } else {
+            self.select_ratio_stable_pair(routes_a, routes_b, target_token_a, target_token_b)?
+        };
+
+        // Both legs run in the same zap, so pools the first leg loaded are warm for the second.
+        // Their curves come from the snapshot the legs were priced against.
+        let snapshot_finder = self.with_pool_provider(&snapshot);
+        let mut touched = HashSet::new();
+        self.estimate_path_gas_with(&route_a.path, &snapshot_finder.path_curves(&route_a.path), &mut touched);
+        route_b.gas_estimate =
+            self.estimate_path_gas_with(&route_b.path, &snapshot_finder.path_curves(&route_b.path), &mut touched);
+        Ok((route_a, route_b))
+    }
+
+    /// Among route pairs worth within `ratio_preference_bps` of the best pair, valued in token B
+    /// at the target pool's price, pick the pair whose outputs deviate least from its ratio
+    fn select_ratio_stable_pair(
+        &self,
+        routes_a: Vec<RouteInfo>,
+        routes_b: Vec<RouteInfo>,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+    ) -> Result<(RouteInfo, RouteInfo)> {
+        let pool = self.pool_provider.get_pool_reserves(target_token_a, target_token_b)?;
+        let (reserve_a, reserve_b) = if pool.token_a == target_token_a {
+            (U256::from(pool.reserve_a), U256::from(pool.reserve_b))
+        } else {
+            (U256::from(pool.reserve_b), U256::from(pool.reserve_a))
+        };
+        if reserve_a.is_zero() || reserve_b.is_zero() {
+            return Err(anyhow!("Target pool has an empty reserve"));
+        }
+
+        // Value scaled by reserve_a to keep the comparison exact: out_a * reserve_b + out_b * reserve_a
+        let value = |route_a: &RouteInfo, route_b: &RouteInfo| {
+            U256::from(route_a.expected_output) * reserve_b + U256::from(route_b.expected_output) * reserve_a
+        };
+        let best_value = routes_a
+            .iter()
+            .flat_map(|route_a| routes_b.iter().map(move |route_b| value(route_a, route_b)))
+            .max()
+            .unwrap_or(U256::ZERO);
+        let floor = best_value * U256::from(BASIS_POINTS.saturating_sub(self.ratio_preference_bps)) / U256::from(BASIS_POINTS);
+
+        let mut best_pair: Option<(&RouteInfo, &RouteInfo, U256)> = None;
+        for route_a in &routes_a {
+            for route_b in &routes_b {
+                let pair_value = value(route_a, route_b);
+                if pair_value.is_zero() || pair_value < floor {
+                    continue;
+                }
+                // Deviation from the pool ratio relative to the pair's value
+                let held_a = U256::from(route_a.expected_output) * reserve_b;
+                let held_b = U256::from(route_b.expected_output) * reserve_a;
+                let imbalance = if held_a > held_b { held_a - held_b } else { held_b - held_a };
+                let deviation = imbalance * U256::from(PRICE_PRECISION) / pair_value;
+                if best_pair.as_ref().is_none_or(|(_, _, best)| deviation < *best) {
+                    best_pair = Some((route_a, route_b, deviation));
+                }
+            }
+        }
+
+        best_pair
+            .map(|(route_a, route_b, _)| (route_a.clone(), route_b.clone()))
+            .ok_or_else(|| anyhow!("No route pair found for target pool"))
+    }
+
     /// Find the best route from input token to target token
     pub fn find_best_route(
         &self,
         from_token: AlkaneId,
         to_token: AlkaneId,
         amount_in: u128,
+    ) -> Result<RouteInfo> {
+        self.find_route_candidates(from_token, to_token, amount_in)?
+            .into_iter()
+            .max_by(|a, b| self.scoring.compare(a, b))
+            .ok_or_else(|| ZapError::RouteNotFound { from_token, to_token }.into())
+    }
+
+    /// Best route by expected output net of its gas, where `gas_price` is the cost of one unit
+    /// of gas in `to_token`, scaled by `PRICE_PRECISION`.
+    ///
+    /// Each extra hop costs gas, so this can prefer a direct route over a multi-hop one that
+    /// only edges it out on gross output. Routes netting the same are ordered by
+    /// `RouteInfo::tie_break`, which favours fewer hops.
+    pub fn find_best_route_net(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+        gas_price: u128,
+    ) -> Result<RouteInfo> {
+        let net_output = |route: &RouteInfo| {
+            let gas_cost = U256::from(route.gas_estimate) * U256::from(gas_price) / U256::from(PRICE_PRECISION);
+            U256::from(route.expected_output).saturating_sub(gas_cost)
+        };
+        self.find_route_candidates(from_token, to_token, amount_in)?
+            .into_iter()
+            .max_by(|a, b| net_output(a).cmp(&net_output(b)).then_with(|| a.tie_break(b)))
+            .ok_or_else(|| ZapError::RouteNotFound { from_token, to_token }.into())
+    }
+
+    /// Best route among the direct pool and single hops through the base tokens, falling back
+    /// to `find_best_route` only when none of those is usable.
+    ///
+    /// This skips the graph search and the snapshot it is run against, so it agrees with
+    /// `find_best_route` whenever the best route is direct or goes through one base token. A
+    /// pair with a reference price always takes the full search, which applies its filter.
+    pub fn find_best_route_fast(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
     ) -> Result<RouteInfo> {
         if from_token == to_token {
-            return Err(anyhow!("Cannot route from token to itself"));
+            return Err(ZapError::SelfRoute.into());
         }
         if amount_in == 0 {
-            return Err(anyhow!("Input amount cannot be zero"));
+            return Err(ZapError::ZeroInputAmount.into());
         }
+        if self.reference_prices.contains_key(&(from_token, to_token)) {
+            return self.find_best_route(from_token, to_token, amount_in);
+        }
+
+        let fast_route = self
+            .collect_base_token_routes(from_token, to_token, amount_in)
+            .into_iter()
+            .filter(|route| self.is_route_allowed(&route.path))
+            .max_by(|a, b| self.scoring.compare(a, b));
+        match fast_route {
+            Some(route) => Ok(route),
+            None => self.find_best_route(from_token, to_token, amount_in),
+        }
+    }
 
-        let all_routes = self.find_all_routes(from_token, to_token, amount_in)?;
+    /// Best route whose price impact is at most `max_impact_bps`, or `None` when routes exist
+    /// but every one exceeds it. A pair with no route at all is still `RouteNotFound`, so
+    /// callers can tell missing liquidity from a trade that is merely too expensive.
+    pub fn find_route_within_impact(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+        max_impact_bps: u128,
+    ) -> Result<Option<RouteInfo>> {
+        let candidates = self.find_route_candidates(from_token, to_token, amount_in)?;
+        if candidates.is_empty() {
+            return Err(ZapError::RouteNotFound { from_token, to_token }.into());
+        }
+        Ok(candidates
+            .into_iter()
+            .filter(|route| route.price_impact <= max_impact_bps)
+            .max_by(|a, b| self.scoring.compare(a, b)))
+    }
+
+    /// Spread `amount_in` across up to `max_splits` routes that share no pool, each paired
+    /// with the amount allocated to it and priced for that amount.
+    ///
+    /// The amount is cut into `SPLIT_ROUTE_STEPS` chunks and each chunk goes to whichever
+    /// route gains the most output from it, so a large leg moves onto a second route once
+    /// the first one's impact makes it the worse marginal price. Routes left without an
+    /// allocation are dropped.
+    pub fn find_split_routes(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+        max_splits: usize,
+    ) -> Result<Vec<(RouteInfo, u128)>> {
+        let mut candidates = self.find_route_candidates(from_token, to_token, amount_in)?;
+        if candidates.is_empty() {
+            return Err(ZapError::RouteNotFound { from_token, to_token }.into());
+        }
+        candidates.sort_by(|a, b| self.scoring.compare(b, a));
+        
+        // Routes through a shared pool would move each other's price, so each pool is used once
+        let mut used_pools = HashSet::new();
+        let mut routes: Vec<RouteInfo> = Vec::new();
+        for route in candidates {
+            if routes.len() >= max_splits.max(1) {
+                break;
+            }
+            let pools: Vec<_> = route.path.windows(2).map(|hop| canonical_pool_key(hop[0], hop[1])).collect();
+            if pools.iter().any(|pool| used_pools.contains(pool)) {
+                continue;
+            }
+            used_pools.extend(pools);
+            routes.push(route);
+        }
         
-        all_routes
+        // Greedily hand each chunk to the route with the best marginal output
+        let chunk = (amount_in / SPLIT_ROUTE_STEPS).max(1);
+        let mut allocations = vec![0u128; routes.len()];
+        let mut outputs = vec![0u128; routes.len()];
+        let mut remaining = amount_in;
+        while remaining > 0 {
+            let step = if remaining < 2 * chunk { remaining } else { chunk };
+            let mut best: Option<(usize, u128, u128)> = None;
+            for (index, route) in routes.iter().enumerate() {
+                let Ok(priced) = self.price_path(&route.path, allocations[index] + step) else {
+                    continue;
+                };
+                let gain = priced.expected_output.saturating_sub(outputs[index]);
+                if best.is_none_or(|(_, best_gain, _)| gain > best_gain) {
+                    best = Some((index, gain, priced.expected_output));
+                }
+            }
+            let (index, _, output) = best.ok_or(ZapError::RouteNotFound { from_token, to_token })?;
+            allocations[index] += step;
+            outputs[index] = output;
+            remaining -= step;
+        }
+        
+        routes
+            .into_iter()
+            .zip(allocations)
+            .filter(|(_, allocation)| *allocation > 0)
+            .map(|(route, allocation)| Ok((self.price_path(&route.path, allocation)?, allocation)))
+            .collect()
+    }
+
+    /// Every allowed route from `from_token` to `to_token`, after reference price filtering
+    fn find_route_candidates(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+    ) -> Result<Vec<RouteInfo>> {
+        if from_token == to_token {
+            return Err(ZapError::SelfRoute.into());
+        }
+        if amount_in == 0 {
+            return Err(ZapError::ZeroInputAmount.into());
+        }
+
+        // Both searches run against one snapshot, so each pool is asked for at most once
+        let snapshot = CachedPoolProvider::new(self.pool_provider);
+        snapshot.get_pool_reserves_batch(&self.candidate_pairs(from_token, to_token))?;
+        let snapshot_finder = self.with_pool_provider(&snapshot);
+        let mut all_routes = snapshot_finder.collect_routes(from_token, to_token, amount_in)?;
+
+        // The BFS can skip the best path through a shared intermediate; supplement it
+        if let Ok(route) = snapshot_finder.find_best_route_dijkstra(from_token, to_token, amount_in) {
+            if !all_routes.iter().any(|existing| existing.path == route.path) {
+                all_routes.push(route);
+            }
+        }
+        
+        let mut candidates: Vec<RouteInfo> = all_routes
             .into_iter()
-            .max_by(|a, b| a.expected_output.cmp(&b.expected_output))
-            .ok_or_else(|| anyhow!("No route found from {:?} to {:?}", from_token, to_token))
+            .filter(|route| self.is_route_allowed(&route.path))
+            .collect();
+
+        // Drop routes priced too far from the reference, e.g. through a manipulated pool
+        if let Some(&(reference_price, tolerance_bps)) = self.reference_prices.get(&(from_token, to_token)) {
+            if reference_price > 0 && !candidates.is_empty() {
+                let mut closest_deviation_bps = u128::MAX;
+                let mut within_tolerance = Vec::with_capacity(candidates.len());
+                for route in candidates {
+                    let deviation_bps = amm_logic::calculate_price_deviation(amount_in, route.expected_output, reference_price)?;
+                    closest_deviation_bps = closest_deviation_bps.min(deviation_bps);
+                    if deviation_bps <= tolerance_bps {
+                        within_tolerance.push(route);
+                    }
+                }
+                if within_tolerance.is_empty() {
+                    return Err(ZapError::PriceDeviatesFromReference {
+                        deviation_bps: closest_deviation_bps,
+                        tolerance_bps,
+                    }
+                    .into());
+                }
+                candidates = within_tolerance;
+            }
+        }
+
+        Ok(candidates)
+    }
+
+    /// Find the best route for each `(from, to, amount_in)` query, with `None` for pairs that
+    /// cannot be routed. Wrap the provider in a `CachedPoolProvider` to share pool lookups
+    /// across the batch.
+    pub fn find_best_routes(&self, queries: &[(AlkaneId, AlkaneId, u128)]) -> Result<Vec<Option<RouteInfo>>> {
+        if queries.len() > MAX_ROUTE_BATCH {
+            return Err(anyhow!("Route batch of {} exceeds the limit of {}", queries.len(), MAX_ROUTE_BATCH));
+        }
+        Ok(queries
+            .iter()
+            .map(|(from_token, to_token, amount_in)| self.find_best_route(*from_token, *to_token, *amount_in).ok())
+            .collect())
+    }
+
+    /// Every direct, base-token and multi-hop route from `from_token` to `to_token`, each path
+    /// listed once and none revisiting a token or exceeding `MAX_HOPS`, best first by `scoring`.
+    /// Enumeration stops at `max_routes` routes or `max_expansions` expanded paths, whichever
+    /// comes first.
+    ///
+    /// The direct and base-token pools are fetched up front in one batch, and the search runs
+    /// against that snapshot so each pool is asked for at most once.
+    pub fn find_all_routes(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+    ) -> Result<Vec<RouteInfo>> {
+        let snapshot = CachedPoolProvider::new(self.pool_provider);
+        snapshot.get_pool_reserves_batch(&self.candidate_pairs(from_token, to_token))?;
+        self.with_pool_provider(&snapshot).collect_routes(from_token, to_token, amount_in)
     }
 
-    fn find_all_routes(
+    /// Pools the direct and base-token searches look up, in the order they ask for them
+    fn candidate_pairs(&self, from_token: AlkaneId, to_token: AlkaneId) -> Vec<(AlkaneId, AlkaneId)> {
+        let mut pairs = vec![(from_token, to_token)];
+        for base_token in &self.common_base_tokens {
+            if *base_token == from_token
+                || *base_token == to_token
+                || self.excluded_intermediate_tokens.contains(base_token)
+            {
+                continue;
+            }
+            pairs.push((from_token, *base_token));
+            pairs.push((*base_token, to_token));
+        }
+        pairs
+    }
+
+    /// This finder's configuration over a different source of pool data
+    fn with_pool_provider<'b, Q: PoolProvider>(&self, pool_provider: &'b Q) -> RouteFinder<'b, Q> {
+        RouteFinder {
+            oyl_factory_id: self.oyl_factory_id,
+            common_base_tokens: self.common_base_tokens.clone(),
+            pool_provider,
+            excluded_intermediate_tokens: self.excluded_intermediate_tokens.clone(),
+            excluded_pools: self.excluded_pools.clone(),
+            reference_prices: self.reference_prices.clone(),
+            ratio_preference_bps: self.ratio_preference_bps,
+            scoring: self.scoring,
+            min_pool_liquidity: self.min_pool_liquidity,
+            gas_model: self.gas_model.clone(),
+            max_routes: self.max_routes,
+            max_expansions: self.max_expansions,
+        }
+    }
+
+    fn collect_routes(
         &self,
         from_token: AlkaneId,
         to_token: AlkaneId,
         amount_in: u128,
     ) -> Result<Vec<RouteInfo>> {
+        let mut routes = self.collect_base_token_routes(from_token, to_token, amount_in);
+        
+        // Multi-hop routes
+        if let Ok(multi_hop_routes) = self.find_multi_hop_routes(from_token, to_token, amount_in) {
+            routes.extend(multi_hop_routes);
+        }
+
+        // The searches overlap, so the same path can surface more than once; keep its first copy
+        let mut seen_paths = HashSet::new();
+        routes.retain(|route| Self::is_well_formed_path(&route.path) && seen_paths.insert(route.path.clone()));
+        // Keep the best routes under the cap rather than the first ones found
+        routes.sort_by(|a, b| self.scoring.compare(b, a));
+        routes.truncate(self.max_routes);
+
+        Ok(routes)
+    }
+
+    /// The direct route and single hops through each base token, the routes most quotes take
+    fn collect_base_token_routes(&self, from_token: AlkaneId, to_token: AlkaneId, amount_in: u128) -> Vec<RouteInfo> {
         let mut routes = Vec::new();
 
         // Direct route
@@ -70,9 +559,17 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
             } else {
                 (reserves.reserve_b, reserves.reserve_a)
             };
-            if let Ok(amount_out) = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, 500) {
-                let impact = amm_logic::calculate_price_impact(amount_in, reserve_in, amount_out, reserve_out)?;
-                routes.push(RouteInfo::new(vec![from_token, to_token], amount_out).with_price_impact(impact));
+            if self.has_min_liquidity(reserve_in, reserve_out) {
+                if let Ok(amount_out) = amm_logic::calculate_swap_out_for_curve(amount_in, reserve_in, reserve_out, reserves.fee_for(from_token), reserves.curve) {
+                    // A pool whose impact cannot be measured is skipped, not fatal to routing
+                    if let Ok(impact) = amm_logic::calculate_price_impact(amount_in, reserve_in, amount_out, reserve_out) {
+                        routes.push(
+                            RouteInfo::new(vec![from_token, to_token], amount_out)
+                                .with_price_impact(impact)
+                                .with_gas_estimate(self.estimate_path_gas(&[from_token, to_token], &[reserves.curve])),
+                        );
+                    }
+                }
             }
         }
 
@@ -89,13 +586,14 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
                 routes.push(route);
             }
         }
-        
-        // Multi-hop routes
-        if let Ok(multi_hop_routes) = self.find_multi_hop_routes(from_token, to_token, amount_in) {
-            routes.extend(multi_hop_routes);
-        }
 
-        Ok(routes)
+        routes
+    }
+
+    /// Whether a path visits each token at most once and stays within `MAX_HOPS`
+    fn is_well_formed_path(path: &[AlkaneId]) -> bool {
+        let mut seen = HashSet::new();
+        path.len() <= MAX_HOPS + 1 && path.iter().all(|token| seen.insert(*token))
     }
 
     /// Find single-hop route through a base token
@@ -116,8 +614,11 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
         } else {
             (reserves1.reserve_b, reserves1.reserve_a)
         };
+        if !self.has_min_liquidity(reserve1_in, reserve1_out) {
+            return Err(anyhow!("Pool liquidity below minimum"));
+        }
 
-        let intermediate_amount = amm_logic::calculate_swap_out(amount_in, reserve1_in, reserve1_out, 500)?;
+        let intermediate_amount = amm_logic::calculate_swap_out_for_curve(amount_in, reserve1_in, reserve1_out, reserves1.fee_for(from_token), reserves1.curve)?;
 
         // Second hop: base_token -> to_token
         let reserves2 = self
@@ -129,21 +630,29 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
         } else {
             (reserves2.reserve_b, reserves2.reserve_a)
         };
+        if !self.has_min_liquidity(reserve2_in, reserve2_out) {
+            return Err(anyhow!("Pool liquidity below minimum"));
+        }
 
         let final_amount =
-            amm_logic::calculate_swap_out(intermediate_amount, reserve2_in, reserve2_out, 500)?;
+            amm_logic::calculate_swap_out_for_curve(intermediate_amount, reserve2_in, reserve2_out, reserves2.fee_for(base_token), reserves2.curve)?;
 
-        // Calculate combined price impact
-        let price_impact = self.calculate_path_price_impact(&[from_token, base_token, to_token], amount_in)?;
+        // Combine both hops' impact from the reserves already read
+        let price_impact = amm_logic::compound_price_impact(&[
+            amm_logic::calculate_price_impact(amount_in, reserve1_in, intermediate_amount, reserve1_out)?,
+            amm_logic::calculate_price_impact(intermediate_amount, reserve2_in, final_amount, reserve2_out)?,
+        ]);
+        let path = [from_token, base_token, to_token];
 
         Ok(
-            RouteInfo::new(vec![from_token, base_token, to_token], final_amount)
+            RouteInfo::new(path.to_vec(), final_amount)
                 .with_price_impact(price_impact)
-                .with_gas_estimate(100_000), // Estimated gas for two swaps
+                .with_gas_estimate(self.estimate_path_gas(&path, &[reserves1.curve, reserves2.curve])),
         )
     }
 
-    /// Find multi-hop routes using BFS
+    /// Find multi-hop routes using BFS, stopping once `max_routes` are found or
+    /// `max_expansions` partial paths have been expanded
     fn find_multi_hop_routes(
         &self,
         from_token: AlkaneId,
@@ -153,20 +662,25 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
         let mut routes = Vec::new();
         let mut queue = VecDeque::new();
         let mut visited = HashSet::new();
+        let mut expansions = 0;
 
         // Initialize with direct connections from from_token
         queue.push_back((vec![from_token], amount_in));
         visited.insert(from_token);
 
         while let Some((current_path, current_amount)) = queue.pop_front() {
+            if routes.len() >= self.max_routes || expansions >= self.max_expansions {
+                break;
+            }
             if current_path.len() > MAX_HOPS {
                 continue;
             }
+            expansions += 1;
 
             let current_token = *current_path.last().unwrap();
 
             // Get all tokens that have pools with current_token
-            if let Ok(connected_tokens) = self.pool_provider.get_connected_tokens(current_token) {
+            if let Ok(connected_tokens) = self.connected_tokens(current_token) {
                 for next_token in connected_tokens {
                     if visited.contains(&next_token) {
                         continue;
@@ -191,19 +705,22 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
                             (reserves.reserve_b, reserves.reserve_a)
                         };
 
+                        if !self.has_min_liquidity(reserve_in, reserve_out) {
+                            continue;
+                        }
+
                         if let Ok(amount_out) =
-                            amm_logic::calculate_swap_out(current_amount, reserve_in, reserve_out, 500)
+                            amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, reserves.fee_for(current_token), reserves.curve)
                         {
                             if next_token == to_token {
                                 // Found a complete route
-                                let price_impact =
-                                    self.calculate_path_price_impact(&new_path, amount_in)?;
-                                let gas_estimate = (new_path.len() - 1) as u128 * 50_000;
-
-                                let route = RouteInfo::new(new_path, amount_out)
-                                    .with_price_impact(price_impact)
-                                    .with_gas_estimate(gas_estimate);
+                                let Ok(route) = self.price_walked_path(&new_path, amount_in) else {
+                                    continue;
+                                };
                                 routes.push(route);
+                                if routes.len() >= self.max_routes {
+                                    break;
+                                }
                             } else {
                                 // Continue searching
                                 queue.push_back((new_path, amount_out));
@@ -218,14 +735,273 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
         Ok(routes)
     }
 
-    /// Calculate price impact for a complete path
-    fn calculate_path_price_impact(&self, path: &[AlkaneId], amount_in: u128) -> Result<u128> {
-        let mut remaining_fraction = U256::from(10000);
+    /// Find the highest-output route using a best-first search over pool edges.
+    ///
+    /// Unlike the BFS in `find_multi_hop_routes`, a token is re-expanded whenever it is reached
+    /// with a better partial output for the same hop count, so paths sharing an intermediate
+    /// token are not lost. Paths are bounded by `MAX_HOPS` and honor the excluded intermediates,
+    /// and at most `max_expansions` partial paths are expanded.
+    pub fn find_best_route_dijkstra(
+        &self,
+        from_token: AlkaneId,
+        to_token: AlkaneId,
+        amount_in: u128,
+    ) -> Result<RouteInfo> {
+        if from_token == to_token {
+            return Err(ZapError::SelfRoute.into());
+        }
+        if amount_in == 0 {
+            return Err(ZapError::ZeroInputAmount.into());
+        }
+
+        let mut queue = BinaryHeap::new();
+        let mut best_outputs: HashMap<(AlkaneId, usize), u128> = HashMap::new();
+        let mut best_route: Option<(Vec<AlkaneId>, u128)> = None;
+        let mut expansions = 0;
+
+        queue.push((amount_in, vec![from_token]));
+
+        while let Some((current_amount, current_path)) = queue.pop() {
+            let current_token = *current_path.last().unwrap();
+            let hops = current_path.len() - 1;
+
+            if current_token == to_token {
+                if best_route.as_ref().is_none_or(|(_, output)| current_amount > *output) {
+                    best_route = Some((current_path, current_amount));
+                }
+                continue;
+            }
+
+            if hops >= MAX_HOPS {
+                continue;
+            }
+
+            // Skip stale entries superseded by a better output at the same depth
+            if best_outputs
+                .get(&(current_token, hops))
+                .is_some_and(|best| current_amount < *best)
+            {
+                continue;
+            }
+
+            // Past the cap, queued paths that already reach the target are still considered
+            if expansions >= self.max_expansions {
+                continue;
+            }
+            expansions += 1;
+
+            let connected_tokens = match self.connected_tokens(current_token) {
+                Ok(tokens) => tokens,
+                Err(_) => continue,
+            };
+
+            for next_token in connected_tokens {
+                if current_path.contains(&next_token) {
+                    continue;
+                }
+
+                // Prevent routing through an excluded token, unless it's the final destination.
+                if self.excluded_intermediate_tokens.contains(&next_token) && next_token != to_token {
+                    continue;
+                }
+
+                if self.excluded_pools.contains(&canonical_pool_key(current_token, next_token)) {
+                    continue;
+                }
+
+                let amount_out = match self.hop_output(current_token, next_token, current_amount) {
+                    Ok(amount_out) if amount_out > 0 => amount_out,
+                    _ => continue,
+                };
+
+                let best = best_outputs.entry((next_token, hops + 1)).or_insert(0);
+                if amount_out <= *best {
+                    continue;
+                }
+                *best = amount_out;
+
+                let mut new_path = current_path.clone();
+                new_path.push(next_token);
+                queue.push((amount_out, new_path));
+            }
+        }
+
+        let (path, _) = best_route.ok_or(ZapError::RouteNotFound { from_token, to_token })?;
+        self.price_walked_path(&path, amount_in)
+    }
+
+    /// Price a known path for `amount_in` against current reserves, without searching for it
+    pub fn price_path(&self, path: &[AlkaneId], amount_in: u128) -> Result<RouteInfo> {
+        if path.len() < 2 || !Self::is_well_formed_path(path) {
+            return Err(anyhow!("Cannot price a malformed path"));
+        }
+        self.price_walked_path(path, amount_in)
+    }
+
+    /// Price `path` for `amount_in` in one walk, reading each pool once for its output, price
+    /// impact, curve and minimum liquidity
+    fn price_walked_path(&self, path: &[AlkaneId], amount_in: u128) -> Result<RouteInfo> {
+        let walk = self.walk_path(path, amount_in)?;
+        if !walk.iter().all(|(hop, _)| self.has_min_liquidity(hop.reserve_in, hop.reserve_out)) {
+            return Err(anyhow!("Pool liquidity below minimum"));
+        }
+        let expected_output = walk.last().map_or(amount_in, |(hop, _)| hop.amount_out);
+        let hop_impacts: Vec<u128> = walk.iter().map(|(hop, _)| hop.price_impact).collect();
+        let curves: Vec<CurveKind> = walk.iter().map(|(_, curve)| *curve).collect();
+
+        Ok(RouteInfo::new(path.to_vec(), expected_output)
+            .with_price_impact(amm_logic::compound_price_impact(&hop_impacts))
+            .with_gas_estimate(self.estimate_path_gas(path, &curves)))
+    }
+
+    /// Marginal price of `token_a` in `token_b`, scaled by 1e18, ignoring fees and trade size.
+    ///
+    /// Read from the direct pool when there is one, otherwise composed from the legs through
+    /// the first base token that connects the pair.
+    pub fn spot_price(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<u128> {
+        if token_a == token_b {
+            return Err(ZapError::SelfRoute.into());
+        }
+        if let Ok(price) = self.pool_spot_price(token_a, token_b) {
+            return Ok(price);
+        }
+        for base_token in &self.common_base_tokens {
+            if *base_token == token_a
+                || *base_token == token_b
+                || self.excluded_intermediate_tokens.contains(base_token)
+            {
+                continue;
+            }
+            let (Ok(first_leg), Ok(second_leg)) =
+                (self.pool_spot_price(token_a, *base_token), self.pool_spot_price(*base_token, token_b))
+            else {
+                continue;
+            };
+            let price = U256::from(first_leg) * U256::from(second_leg) / U256::from(PRICE_PRECISION);
+            return price.try_into().map_err(|_| anyhow!("Spot price exceeds u128"));
+        }
+        Err(ZapError::RouteNotFound { from_token: token_a, to_token: token_b }.into())
+    }
+
+    /// Price of `token_a` in `token_b` in the pool between them
+    fn pool_spot_price(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<u128> {
+        let reserves = self.pool_provider.get_pool_reserves(token_a, token_b)?;
+        // `get_price_ratio` prices the pool's second token in its first
+        let price = reserves.oriented(token_b).get_price_ratio()?;
+        price.try_into().map_err(|_| anyhow!("Spot price exceeds u128"))
+    }
+
+    /// Gas for every hop of `path` under `gas_model`, with every pool cold
+    fn estimate_path_gas(&self, path: &[AlkaneId], curves: &[CurveKind]) -> u128 {
+        self.estimate_path_gas_with(path, curves, &mut HashSet::new())
+    }
+
+    /// Gas for every hop of `path` under `gas_model`, given the curve of each hop's pool as
+    /// already read while pricing the path. A pool is cold unless it is already in `touched`,
+    /// which collects the pools the path loads. A hop without a known curve is costed as
+    /// constant product.
+    fn estimate_path_gas_with(&self, path: &[AlkaneId], curves: &[CurveKind], touched: &mut HashSet<(AlkaneId, AlkaneId)>) -> u128 {
+        path.windows(2)
+            .enumerate()
+            .map(|(index, hop)| {
+                let curve = curves.get(index).copied().unwrap_or(CurveKind::ConstantProduct);
+                let is_cold = touched.insert(canonical_pool_key(hop[0], hop[1]));
+                self.gas_model.cost_for_hop(curve, is_cold)
+            })
+            .fold(0u128, |total, cost| total.saturating_add(cost))
+    }
+
+    /// Curve of each hop's pool along `path`, constant product where a pool cannot be read
+    fn path_curves(&self, path: &[AlkaneId]) -> Vec<CurveKind> {
+        path.windows(2)
+            .map(|hop| {
+                self.pool_provider
+                    .get_pool_reserves(hop[0], hop[1])
+                    .map_or(CurveKind::ConstantProduct, |reserves| reserves.curve)
+            })
+            .collect()
+    }
+
+    /// Output of swapping `amount_in` across the pool between two tokens
+    fn hop_output(&self, token_in: AlkaneId, token_out: AlkaneId, amount_in: u128) -> Result<u128> {
+        let reserves = self.pool_provider.get_pool_reserves(token_in, token_out)?;
+        let (reserve_in, reserve_out) = if reserves.token_a == token_in {
+            (reserves.reserve_a, reserves.reserve_b)
+        } else {
+            (reserves.reserve_b, reserves.reserve_a)
+        };
+        if !self.has_min_liquidity(reserve_in, reserve_out) {
+            return Err(anyhow!("Pool liquidity below minimum"));
+        }
+        amm_logic::calculate_swap_out_for_curve(amount_in, reserve_in, reserve_out, reserves.fee_for(token_in), reserves.curve)
+    }
+
+    /// Base tokens reachable from `token` within `MAX_HOPS`, in configured order.
+    ///
+    /// Walks connected tokens and base-token pools breadth first, following only pools
+    /// with enough liquidity to route through, so no trade amount is needed.
+    pub fn reachable_base_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        let mut visited = HashSet::from([token]);
+        let mut frontier = vec![token];
+        for _ in 0..MAX_HOPS {
+            let mut next_frontier = Vec::new();
+            for current in frontier {
+                let mut neighbours = self.connected_tokens(current)?;
+                neighbours.extend(self.common_base_tokens.iter().copied());
+                for next in neighbours {
+                    if visited.contains(&next) {
+                        continue;
+                    }
+                    let Ok(reserves) = self.pool_provider.get_pool_reserves(current, next) else {
+                        continue;
+                    };
+                    if self.has_min_liquidity(reserves.reserve_a, reserves.reserve_b) {
+                        visited.insert(next);
+                        next_frontier.push(next);
+                    }
+                }
+            }
+            if next_frontier.is_empty() {
+                break;
+            }
+            frontier = next_frontier;
+        }
+
+        Ok(self
+            .common_base_tokens
+            .iter()
+            .filter(|base_token| **base_token != token && visited.contains(*base_token))
+            .copied()
+            .collect())
+    }
+
+    /// Tokens sharing a pool with `token`, ignoring any erroneous self-connection
+    /// reported by the provider. Sorted so the search visits them in the same order
+    /// whatever order the provider lists them in.
+    fn connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        let mut connected = self.pool_provider.get_connected_tokens(token)?;
+        connected.retain(|connected_token| *connected_token != token);
+        connected.sort_by_key(|connected_token| (connected_token.block, connected_token.tx));
+        connected.dedup();
+        Ok(connected)
+    }
+
+    /// Per-hop breakdown of trading `amount_in` along `path`: the reserves each hop trades
+    /// against, what goes in and comes out, and its price impact. Each hop's output is the
+    /// next hop's input.
+    pub fn explain(&self, path: &[AlkaneId], amount_in: u128) -> Result<Vec<HopBreakdown>> {
+        Ok(self.walk_path(path, amount_in)?.into_iter().map(|(hop, _)| hop).collect())
+    }
+
+    /// Each hop's breakdown along `path`, paired with the curve of the pool it trades against.
+    ///
+    /// Fails if any hop's pool cannot be priced (e.g. an empty output reserve).
+    fn walk_path(&self, path: &[AlkaneId], amount_in: u128) -> Result<Vec<(HopBreakdown, CurveKind)>> {
+        let mut hops = Vec::with_capacity(path.len().saturating_sub(1));
         let mut current_amount = amount_in;
 
-        for i in 0..path.len() - 1 {
-            let from_token = path[i];
-            let to_token = path[i + 1];
+        for hop in path.windows(2) {
+            let (from_token, to_token) = (hop[0], hop[1]);
 
             let reserves = self
                 .pool_provider
@@ -237,18 +1013,29 @@ impl<'a, P: PoolProvider> RouteFinder<'a, P> {
                 (reserves.reserve_b, reserves.reserve_a)
             };
 
-            let amount_out = amm_logic::calculate_swap_out(current_amount, reserve_in, reserve_out, 500)?;
-            let impact = amm_logic::calculate_price_impact(
+            let amount_out = amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, reserves.fee_for(from_token), reserves.curve)?;
+            let price_impact = amm_logic::calculate_price_impact(
                 current_amount,
                 reserve_in,
                 amount_out,
                 reserve_out,
             )?;
 
-            remaining_fraction = remaining_fraction * (U256::from(10000) - U256::from(impact)) / U256::from(10000);
+            hops.push((
+                HopBreakdown {
+                    token_in: from_token,
+                    token_out: to_token,
+                    reserve_in,
+                    reserve_out,
+                    amount_in: current_amount,
+                    amount_out,
+                    price_impact,
+                },
+                reserves.curve,
+            ));
             current_amount = amount_out;
         }
 
-        Ok((U256::from(10000) - remaining_fraction).try_into()?)
+        Ok(hops)
     }
 }
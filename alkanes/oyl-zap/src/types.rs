@@ -1,6 +1,7 @@
 use alkanes_support::id::AlkaneId;
 use anyhow::{anyhow, Result};
 use ruint::Uint;
+use std::cmp::Ordering;
 
 pub type U256 = Uint<256, 4>;
 
@@ -43,9 +44,224 @@ impl RouteInfo {
             self.path.len() - 1
         }
     }
+
+    /// Deterministic preference between routes that score the same: fewer hops first, then
+    /// the lexicographically smaller path. `Greater` means `self` is preferred.
+    pub fn tie_break(&self, other: &RouteInfo) -> Ordering {
+        let path_key = |route: &RouteInfo| route.path.iter().map(|token| (token.block, token.tx)).collect::<Vec<_>>();
+        other
+            .hop_count()
+            .cmp(&self.hop_count())
+            .then_with(|| path_key(other).cmp(&path_key(self)))
+    }
+
+    /// Append the route as a u32 path length, the path ids, then expected output, price
+    /// impact and gas estimate
+    pub(crate) fn encode_into(&self, out: &mut Vec<u8>) {
+        out.extend_from_slice(&(self.path.len() as u32).to_le_bytes());
+        for token in &self.path {
+            write_alkane_id(out, token);
+        }
+        out.extend_from_slice(&self.expected_output.to_le_bytes());
+        out.extend_from_slice(&self.price_impact.to_le_bytes());
+        out.extend_from_slice(&self.gas_estimate.to_le_bytes());
+    }
+
+    pub(crate) fn decode_from(reader: &mut ByteReader) -> Result<Self> {
+        let path_len = reader.read_u32()? as usize;
+        let mut path = Vec::with_capacity(path_len.min(MAX_HOPS + 1));
+        for _ in 0..path_len {
+            path.push(reader.read_alkane_id()?);
+        }
+        Ok(Self {
+            path,
+            expected_output: reader.read_u128()?,
+            price_impact: reader.read_u128()?,
+            gas_estimate: reader.read_u128()?,
+        })
+    }
 }
 
-#[derive(Debug, Clone)]
+impl RouteInfo {
+    /// Encode as the `GetBestRoute` payload: a u32 path length, each path token, then the
+    /// expected output, price impact and gas estimate
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + self.path.len() * 32 + 48);
+        self.encode_into(&mut out);
+        out
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let route = Self::decode_from(&mut reader)?;
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after route"));
+        }
+        Ok(route)
+    }
+
+    /// Encode one answer per batch query as a u32 count followed by each route; a route
+    /// with an empty path marks a query that could not be routed
+    pub fn serialize_batch(routes: &[Option<RouteInfo>]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(routes.len() as u32).to_le_bytes());
+        let no_route = RouteInfo::new(Vec::new(), 0);
+        for route in routes {
+            route.as_ref().unwrap_or(&no_route).encode_into(&mut out);
+        }
+        out
+    }
+
+    pub fn deserialize_batch(bytes: &[u8]) -> Result<Vec<Option<RouteInfo>>> {
+        let mut reader = ByteReader::new(bytes);
+        let count = reader.read_u32()? as usize;
+        let mut routes = Vec::with_capacity(count.min(MAX_ROUTE_BATCH));
+        for _ in 0..count {
+            let route = RouteInfo::decode_from(&mut reader)?;
+            routes.push(if route.path.is_empty() { None } else { Some(route) });
+        }
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after route batch"));
+        }
+        Ok(routes)
+    }
+}
+
+fn write_alkane_id(out: &mut Vec<u8>, id: &AlkaneId) {
+    out.extend_from_slice(&id.block.to_le_bytes());
+    out.extend_from_slice(&id.tx.to_le_bytes());
+}
+
+/// Encode a token list as a u32 count followed by each token, the `GetConnectedTokens` payload
+pub fn encode_token_list(tokens: &[AlkaneId]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(4 + tokens.len() * 32);
+    out.extend_from_slice(&(tokens.len() as u32).to_le_bytes());
+    for token in tokens {
+        write_alkane_id(&mut out, token);
+    }
+    out
+}
+
+pub fn decode_token_list(bytes: &[u8]) -> Result<Vec<AlkaneId>> {
+    let mut reader = ByteReader::new(bytes);
+    let count = reader.read_u32()? as usize;
+    let mut tokens = Vec::with_capacity(count.min(bytes.len() / 32));
+    for _ in 0..count {
+        tokens.push(reader.read_alkane_id()?);
+    }
+    if !reader.is_empty() {
+        return Err(anyhow!("Trailing bytes after token list"));
+    }
+    Ok(tokens)
+}
+
+/// One hop of a priced route, as `ExplainRoute` reports it. The pool is identified by the
+/// pair it trades, which is how pool providers key pools.
+#[derive(Debug, Clone, PartialEq)]
+pub struct HopBreakdown {
+    pub token_in: AlkaneId,
+    pub token_out: AlkaneId,
+    pub reserve_in: u128,
+    pub reserve_out: u128,
+    pub amount_in: u128,
+    pub amount_out: u128,
+    pub price_impact: u128, // in basis points (10000 = 100%)
+}
+
+impl HopBreakdown {
+    const ENCODED_LEN: usize = 64 + 5 * 16;
+
+    /// Encode hops as a u32 count followed by each hop's tokens, reserves, amounts and impact
+    pub fn serialize_list(hops: &[HopBreakdown]) -> Vec<u8> {
+        let mut out = Vec::with_capacity(4 + hops.len() * Self::ENCODED_LEN);
+        out.extend_from_slice(&(hops.len() as u32).to_le_bytes());
+        for hop in hops {
+            write_alkane_id(&mut out, &hop.token_in);
+            write_alkane_id(&mut out, &hop.token_out);
+            for value in [hop.reserve_in, hop.reserve_out, hop.amount_in, hop.amount_out, hop.price_impact] {
+                out.extend_from_slice(&value.to_le_bytes());
+            }
+        }
+        out
+    }
+
+    pub fn deserialize_list(bytes: &[u8]) -> Result<Vec<HopBreakdown>> {
+        let mut reader = ByteReader::new(bytes);
+        let count = reader.read_u32()? as usize;
+        let mut hops = Vec::with_capacity(count.min(MAX_HOPS));
+        for _ in 0..count {
+            hops.push(HopBreakdown {
+                token_in: reader.read_alkane_id()?,
+                token_out: reader.read_alkane_id()?,
+                reserve_in: reader.read_u128()?,
+                reserve_out: reader.read_u128()?,
+                amount_in: reader.read_u128()?,
+                amount_out: reader.read_u128()?,
+                price_impact: reader.read_u128()?,
+            });
+        }
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after hop breakdown"));
+        }
+        Ok(hops)
+    }
+}
+
+/// Little-endian cursor over serialized response data
+pub(crate) struct ByteReader<'a> {
+    bytes: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    pub(crate) fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes, offset: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self
+            .offset
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| anyhow!("Serialized data truncated at byte {}", self.offset))?;
+        let slice = &self.bytes[self.offset..end];
+        self.offset = end;
+        Ok(slice)
+    }
+
+    pub(crate) fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn read_u16(&mut self) -> Result<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u32(&mut self) -> Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_u128(&mut self) -> Result<u128> {
+        Ok(u128::from_le_bytes(self.take(16)?.try_into().unwrap()))
+    }
+
+    pub(crate) fn read_alkane_id(&mut self) -> Result<AlkaneId> {
+        Ok(AlkaneId {
+            block: self.read_u128()?,
+            tx: self.read_u128()?,
+        })
+    }
+
+    pub(crate) fn is_empty(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+
+    fn remaining(&self) -> &'a [u8] {
+        &self.bytes[self.offset..]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub struct ZapQuote {
     pub input_token: AlkaneId,
     pub input_amount: u128,
@@ -58,6 +274,13 @@ pub struct ZapQuote {
     pub expected_lp_tokens: u128,
     pub price_impact: u128,
     pub minimum_lp_tokens: u128,
+    pub slippage_sensitivity: u128, // LP tokens lost per SLIPPAGE_SENSITIVITY_STEP_BPS of slippage
+    pub expected_dust_a: u128, // target token A left over after add_liquidity
+    pub expected_dust_b: u128, // target token B left over after add_liquidity
+    pub expected_deposit_a: u128, // target token A deposited once add_liquidity applies the pool ratio
+    pub expected_deposit_b: u128, // target token B deposited once add_liquidity applies the pool ratio
+    pub computed_at_height: u128, // block height the quote was priced at
+    pub stale: bool, // requested past its deadline block, so not to be executed
 }
 
 impl ZapQuote {
@@ -79,6 +302,13 @@ impl ZapQuote {
             expected_lp_tokens: 0,
             price_impact: 0,
             minimum_lp_tokens: 0,
+            slippage_sensitivity: 0,
+            expected_dust_a: 0,
+            expected_dust_b: 0,
+            expected_deposit_a: 0,
+            expected_deposit_b: 0,
+            computed_at_height: 0,
+            stale: false,
         }
     }
 
@@ -105,6 +335,205 @@ impl ZapQuote {
         self
     }
 
+    pub fn with_slippage_sensitivity(mut self, slippage_sensitivity: u128) -> Self {
+        self.slippage_sensitivity = slippage_sensitivity;
+        self
+    }
+
+    pub fn with_expected_dust(mut self, expected_dust_a: u128, expected_dust_b: u128) -> Self {
+        self.expected_dust_a = expected_dust_a;
+        self.expected_dust_b = expected_dust_b;
+        self
+    }
+
+    pub fn with_expected_deposits(mut self, expected_deposit_a: u128, expected_deposit_b: u128) -> Self {
+        self.expected_deposit_a = expected_deposit_a;
+        self.expected_deposit_b = expected_deposit_b;
+        self
+    }
+
+    pub fn with_computed_at_height(mut self, computed_at_height: u128) -> Self {
+        self.computed_at_height = computed_at_height;
+        self
+    }
+
+    pub fn with_stale(mut self, stale: bool) -> Self {
+        self.stale = stale;
+        self
+    }
+
+    /// Whether the quote was flagged stale or more than `max_age` blocks have passed since it
+    /// was priced, so the reserves it assumed may have moved
+    pub fn is_stale(&self, current_height: u128, max_age: u128) -> bool {
+        self.stale || current_height.saturating_sub(self.computed_at_height) > max_age
+    }
+
+    /// Human-readable breakdown of the quote for logs and tooling
+    pub fn explain(&self) -> String {
+        let format_path = |path: &[AlkaneId]| {
+            path.iter()
+                .map(|token| format!("{}:{}", token.block, token.tx))
+                .collect::<Vec<_>>()
+                .join(" -> ")
+        };
+        let weighted_impact = |impact: u128, split: u128| {
+            impact.saturating_mul(split).checked_div(self.input_amount).unwrap_or(0)
+        };
+
+        let mut lines = Vec::new();
+        lines.push(format!(
+            "Zap quote: {} of {}:{} -> {}:{}/{}:{} LP",
+            self.input_amount,
+            self.input_token.block,
+            self.input_token.tx,
+            self.target_token_a.block,
+            self.target_token_a.tx,
+            self.target_token_b.block,
+            self.target_token_b.tx,
+        ));
+        lines.push(format!("Split: {} -> route A, {} -> route B", self.split_amount_a, self.split_amount_b));
+        lines.push(format!(
+            "Route A ({} hops): {} => {} out",
+            self.route_a.hop_count(),
+            format_path(&self.route_a.path),
+            self.route_a.expected_output
+        ));
+        lines.push(format!(
+            "Route B ({} hops): {} => {} out",
+            self.route_b.hop_count(),
+            format_path(&self.route_b.path),
+            self.route_b.expected_output
+        ));
+        lines.push(format!(
+            "LP tokens: {} expected, {} minimum, {} lost per {} bps of slippage",
+            self.expected_lp_tokens,
+            self.minimum_lp_tokens,
+            self.slippage_sensitivity,
+            SLIPPAGE_SENSITIVITY_STEP_BPS
+        ));
+        lines.push(format!(
+            "Expected deposit: {} of token A, {} of token B",
+            self.expected_deposit_a, self.expected_deposit_b
+        ));
+        lines.push(format!(
+            "Expected dust: {} of token A, {} of token B",
+            self.expected_dust_a, self.expected_dust_b
+        ));
+        lines.push(format!(
+            "Price impact: {} bps total (route A {} bps weighted {}, route B {} bps weighted {})",
+            self.price_impact,
+            self.route_a.price_impact,
+            weighted_impact(self.route_a.price_impact, self.split_amount_a),
+            self.route_b.price_impact,
+            weighted_impact(self.route_b.price_impact, self.split_amount_b),
+        ));
+
+        lines.join("\n")
+    }
+
+    /// Self-describing encoding returned by `GetZapQuoteV2`: a u16 format version and u32
+    /// total length, then the tokens, amounts and both routes with their full paths
+    pub fn serialize(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&ZAP_QUOTE_SERIALIZED_VERSION.to_le_bytes());
+        out.extend_from_slice(&0u32.to_le_bytes()); // total length, filled in below
+        write_alkane_id(&mut out, &self.input_token);
+        out.extend_from_slice(&self.input_amount.to_le_bytes());
+        write_alkane_id(&mut out, &self.target_token_a);
+        write_alkane_id(&mut out, &self.target_token_b);
+        out.extend_from_slice(&self.split_amount_a.to_le_bytes());
+        out.extend_from_slice(&self.split_amount_b.to_le_bytes());
+        out.extend_from_slice(&self.expected_lp_tokens.to_le_bytes());
+        out.extend_from_slice(&self.minimum_lp_tokens.to_le_bytes());
+        out.extend_from_slice(&self.price_impact.to_le_bytes());
+        out.extend_from_slice(&self.slippage_sensitivity.to_le_bytes());
+        out.extend_from_slice(&self.expected_dust_a.to_le_bytes());
+        out.extend_from_slice(&self.expected_dust_b.to_le_bytes());
+        out.extend_from_slice(&self.expected_deposit_a.to_le_bytes());
+        out.extend_from_slice(&self.expected_deposit_b.to_le_bytes());
+        out.extend_from_slice(&self.computed_at_height.to_le_bytes());
+        out.push(u8::from(self.stale));
+        self.route_a.encode_into(&mut out);
+        self.route_b.encode_into(&mut out);
+
+        let total_len = out.len() as u32;
+        out[2..6].copy_from_slice(&total_len.to_le_bytes());
+        out
+    }
+
+    pub fn deserialize(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let version = reader.read_u16()?;
+        if version != ZAP_QUOTE_SERIALIZED_VERSION {
+            return Err(anyhow!("Unsupported zap quote version {}", version));
+        }
+        let total_len = reader.read_u32()? as usize;
+        if total_len != bytes.len() {
+            return Err(anyhow!("Zap quote length {} does not match payload of {} bytes", total_len, bytes.len()));
+        }
+
+        let quote = Self {
+            input_token: reader.read_alkane_id()?,
+            input_amount: reader.read_u128()?,
+            target_token_a: reader.read_alkane_id()?,
+            target_token_b: reader.read_alkane_id()?,
+            split_amount_a: reader.read_u128()?,
+            split_amount_b: reader.read_u128()?,
+            expected_lp_tokens: reader.read_u128()?,
+            minimum_lp_tokens: reader.read_u128()?,
+            price_impact: reader.read_u128()?,
+            slippage_sensitivity: reader.read_u128()?,
+            expected_dust_a: reader.read_u128()?,
+            expected_dust_b: reader.read_u128()?,
+            expected_deposit_a: reader.read_u128()?,
+            expected_deposit_b: reader.read_u128()?,
+            computed_at_height: reader.read_u128()?,
+            stale: reader.read_u8()? != 0,
+            route_a: RouteInfo::decode_from(&mut reader)?,
+            route_b: RouteInfo::decode_from(&mut reader)?,
+        };
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after zap quote"));
+        }
+        Ok(quote)
+    }
+
+    /// Whether the quote deposits anything; `GetZapQuoteBatch` reports pairs it could not
+    /// quote as a zero-LP sentinel with empty routes
+    pub fn is_routable(&self) -> bool {
+        self.expected_lp_tokens > 0
+    }
+
+    /// Encode one quote per batch pair as a u32 count followed by each `serialize` encoding,
+    /// which carries its own length
+    pub fn serialize_batch(quotes: &[ZapQuote]) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&(quotes.len() as u32).to_le_bytes());
+        for quote in quotes {
+            out.extend_from_slice(&quote.serialize());
+        }
+        out
+    }
+
+    pub fn deserialize_batch(bytes: &[u8]) -> Result<Vec<ZapQuote>> {
+        let mut reader = ByteReader::new(bytes);
+        let count = reader.read_u32()? as usize;
+        let mut quotes = Vec::with_capacity(count.min(MAX_QUOTE_BATCH));
+        for _ in 0..count {
+            // The total length follows the u16 format version in each quote's header
+            let quote_len = reader
+                .remaining()
+                .get(2..6)
+                .map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize)
+                .ok_or_else(|| anyhow!("Zap quote batch truncated"))?;
+            quotes.push(Self::deserialize(reader.take(quote_len)?)?);
+        }
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after zap quote batch"));
+        }
+        Ok(quotes)
+    }
+
     pub fn validate(&self) -> Result<()> {
         if self.input_amount == 0 {
             return Err(anyhow!("Input amount cannot be zero"));
@@ -133,6 +562,135 @@ impl ZapQuote {
     }
 }
 
+/// Input layout of the factory's exact-input swap, which changes between factory versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FactorySwapLayout {
+    /// Opcode 13: path length, path, amount in, minimum out, deadline
+    #[default]
+    V1,
+    /// Opcode 29: minimum out, deadline, path length, path; the amount in is taken from the
+    /// incoming parcel
+    V2,
+}
+
+impl FactorySwapLayout {
+    /// Layout for a factory reporting `version`; factories that predate versioning report 0
+    pub fn from_version(version: u128) -> Self {
+        if version >= 2 {
+            FactorySwapLayout::V2
+        } else {
+            FactorySwapLayout::V1
+        }
+    }
+
+    pub fn version(&self) -> u128 {
+        match self {
+            FactorySwapLayout::V1 => 1,
+            FactorySwapLayout::V2 => 2,
+        }
+    }
+
+    /// Cellpack inputs for swapping `amount_in` of `path[0]` along `path`
+    pub fn swap_inputs(&self, path: &[AlkaneId], amount_in: u128, amount_out_min: u128, deadline: u128) -> Vec<u128> {
+        let mut inputs = Vec::with_capacity(path.len() * 2 + 5);
+        match self {
+            FactorySwapLayout::V1 => {
+                inputs.push(13); // SwapExactTokensForTokens opcode
+                inputs.push(path.len() as u128);
+                for token in path {
+                    inputs.push(token.block);
+                    inputs.push(token.tx);
+                }
+                inputs.push(amount_in);
+                inputs.push(amount_out_min);
+                inputs.push(deadline);
+            }
+            FactorySwapLayout::V2 => {
+                inputs.push(29); // SwapExactTokensForTokensImplicit opcode
+                inputs.push(amount_out_min);
+                inputs.push(deadline);
+                inputs.push(path.len() as u128);
+                for token in path {
+                    inputs.push(token.block);
+                    inputs.push(token.tx);
+                }
+            }
+        }
+        inputs
+    }
+}
+
+/// Pricing curve a pool trades along
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CurveKind {
+    /// Uniswap v2 style x * y = k
+    #[default]
+    ConstantProduct,
+    /// Curve-style StableSwap invariant with amplification coefficient `amp`
+    Stable { amp: u128 },
+}
+
+/// Gas charged for each swap a route makes, used to fill in `RouteInfo::gas_estimate`
+pub trait GasModel {
+    /// Gas for one hop through a pool of `curve_kind`, where `is_cold` marks the first access to
+    /// that pool's storage within the zap, so a pool both legs swap through is cold only once
+    fn cost_for_hop(&self, curve_kind: CurveKind, is_cold: bool) -> u128;
+}
+
+/// Flat gas per hop, regardless of curve or storage warmth
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct DefaultGasModel;
+
+impl DefaultGasModel {
+    pub const GAS_PER_HOP: u128 = 50_000;
+}
+
+impl GasModel for DefaultGasModel {
+    fn cost_for_hop(&self, _curve_kind: CurveKind, _is_cold: bool) -> u128 {
+        Self::GAS_PER_HOP
+    }
+}
+
+/// How `RouteFinder::find_best_route` ranks candidate routes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RouteScoring {
+    /// Highest expected output, regardless of price impact
+    #[default]
+    MaxOutput,
+    /// Lowest price impact, with output breaking ties
+    MinPriceImpact,
+    /// Expected output reduced by `price_impact * impact_weight_bps / BASIS_POINTS` bps of itself,
+    /// so a weight of 10000 counts each bp of impact as a bp of lost output
+    Balanced { impact_weight_bps: u128 },
+}
+
+impl RouteScoring {
+    /// Order `a` relative to `b`, where `Greater` means `a` is the better route. Routes that
+    /// score the same are ordered by `RouteInfo::tie_break`, so the order is total.
+    pub fn compare(&self, a: &RouteInfo, b: &RouteInfo) -> Ordering {
+        self.compare_scores(a, b).then_with(|| a.tie_break(b))
+    }
+
+    fn compare_scores(&self, a: &RouteInfo, b: &RouteInfo) -> Ordering {
+        match self {
+            RouteScoring::MaxOutput => a.expected_output.cmp(&b.expected_output),
+            RouteScoring::MinPriceImpact => b
+                .price_impact
+                .cmp(&a.price_impact)
+                .then(a.expected_output.cmp(&b.expected_output)),
+            RouteScoring::Balanced { impact_weight_bps } => {
+                let score = |route: &RouteInfo| {
+                    let output = U256::from(route.expected_output);
+                    let penalty_bps = U256::from(route.price_impact) * U256::from(*impact_weight_bps);
+                    let scale = U256::from(BASIS_POINTS) * U256::from(BASIS_POINTS);
+                    output * scale.saturating_sub(penalty_bps) / scale
+                };
+                score(a).cmp(&score(b)).then(a.expected_output.cmp(&b.expected_output))
+            }
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct PoolReserves {
     pub token_a: AlkaneId,
@@ -141,6 +699,9 @@ pub struct PoolReserves {
     pub reserve_b: u128,
     pub total_supply: u128,
     pub fee_rate: u128,
+    pub fee_a_to_b: u128, // bps charged swapping token_a in for token_b
+    pub fee_b_to_a: u128, // bps charged swapping token_b in for token_a
+    pub curve: CurveKind,
 }
 
 impl PoolReserves {
@@ -159,6 +720,37 @@ impl PoolReserves {
             reserve_b,
             total_supply,
             fee_rate,
+            fee_a_to_b: fee_rate,
+            fee_b_to_a: fee_rate,
+            curve: CurveKind::ConstantProduct,
+        }
+    }
+
+    pub fn with_curve(mut self, curve: CurveKind) -> Self {
+        self.curve = curve;
+        self
+    }
+
+    /// Charge `fee_a_to_b` on swaps selling token A and `fee_b_to_a` on swaps selling token B,
+    /// for pools whose fee depends on direction
+    pub fn with_directional_fees(mut self, fee_a_to_b: u128, fee_b_to_a: u128) -> Self {
+        self.fee_a_to_b = fee_a_to_b;
+        self.fee_b_to_a = fee_b_to_a;
+        self
+    }
+
+    /// Charge `fee_rate` in both directions
+    pub fn with_fee_rate(mut self, fee_rate: u128) -> Self {
+        self.fee_rate = fee_rate;
+        self.with_directional_fees(fee_rate, fee_rate)
+    }
+
+    /// Fee in bps for a swap selling `token_in` into this pool
+    pub fn fee_for(&self, token_in: AlkaneId) -> u128 {
+        if token_in == self.token_b {
+            self.fee_b_to_a
+        } else {
+            self.fee_a_to_b
         }
     }
 
@@ -172,6 +764,22 @@ impl PoolReserves {
         }
     }
 
+    /// The same pool with `token_a` on the first side, swapping the sides if needed
+    pub fn oriented(&self, token_a: AlkaneId) -> Self {
+        if token_a == self.token_a {
+            return self.clone();
+        }
+        Self {
+            token_a: self.token_b,
+            token_b: self.token_a,
+            reserve_a: self.reserve_b,
+            reserve_b: self.reserve_a,
+            fee_a_to_b: self.fee_b_to_a,
+            fee_b_to_a: self.fee_a_to_b,
+            ..self.clone()
+        }
+    }
+
     pub fn get_price_ratio(&self) -> Result<U256> {
         if self.reserve_b == 0 {
             return Err(anyhow!("Cannot calculate price ratio with zero reserve"));
@@ -180,6 +788,335 @@ impl PoolReserves {
     }
 }
 
+/// Pool metadata registered through `AddPool`, kept in canonical token order
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredPool {
+    pub pool_id: AlkaneId,
+    pub token_a: AlkaneId,
+    pub token_b: AlkaneId,
+    pub reserve_a: u128,
+    pub reserve_b: u128,
+    pub total_supply: u128,
+    pub fee_rate: u128,
+}
+
+impl StoredPool {
+    pub fn new(
+        pool_id: AlkaneId,
+        token_a: AlkaneId,
+        token_b: AlkaneId,
+        reserve_a: u128,
+        reserve_b: u128,
+        total_supply: u128,
+        fee_rate: u128,
+    ) -> Self {
+        let mut pool = Self {
+            pool_id,
+            token_a,
+            token_b,
+            reserve_a,
+            reserve_b,
+            total_supply,
+            fee_rate,
+        };
+        pool.canonicalize();
+        pool
+    }
+
+    pub fn storage_key(token_a: &AlkaneId, token_b: &AlkaneId) -> Vec<u8> {
+        crate::pool_storage_key("/pools", token_a, token_b)
+    }
+
+    fn canonicalize(&mut self) {
+        if crate::canonical_pool_key(self.token_a, self.token_b).0 != self.token_a {
+            std::mem::swap(&mut self.token_a, &mut self.token_b);
+            std::mem::swap(&mut self.reserve_a, &mut self.reserve_b);
+        }
+    }
+
+    /// Replace the reserves, given in the order of `token_a` first
+    pub fn set_reserves(&mut self, token_a: AlkaneId, reserve_a: u128, reserve_b: u128, total_supply: u128) {
+        if token_a == self.token_a {
+            self.reserve_a = reserve_a;
+            self.reserve_b = reserve_b;
+        } else {
+            self.reserve_a = reserve_b;
+            self.reserve_b = reserve_a;
+        }
+        self.total_supply = total_supply;
+    }
+
+    /// Reserves oriented so the first value belongs to `token_a`
+    pub fn reserves_for(&self, token_a: AlkaneId) -> (u128, u128) {
+        if token_a == self.token_a {
+            (self.reserve_a, self.reserve_b)
+        } else {
+            (self.reserve_b, self.reserve_a)
+        }
+    }
+
+    pub fn to_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> PoolReserves {
+        let (reserve_a, reserve_b) = self.reserves_for(token_a);
+        PoolReserves::new(token_a, token_b, reserve_a, reserve_b, self.total_supply, self.fee_rate)
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(160);
+        write_alkane_id(&mut out, &self.pool_id);
+        write_alkane_id(&mut out, &self.token_a);
+        write_alkane_id(&mut out, &self.token_b);
+        out.extend_from_slice(&self.reserve_a.to_le_bytes());
+        out.extend_from_slice(&self.reserve_b.to_le_bytes());
+        out.extend_from_slice(&self.total_supply.to_le_bytes());
+        out.extend_from_slice(&self.fee_rate.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let pool = Self {
+            pool_id: reader.read_alkane_id()?,
+            token_a: reader.read_alkane_id()?,
+            token_b: reader.read_alkane_id()?,
+            reserve_a: reader.read_u128()?,
+            reserve_b: reader.read_u128()?,
+            total_supply: reader.read_u128()?,
+            fee_rate: reader.read_u128()?,
+        };
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after stored pool"));
+        }
+        Ok(pool)
+    }
+}
+
+/// Realized outcome of a zap dry run, reported by `SimulateZap`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZapSimulation {
+    pub lp_tokens: u128,
+    pub consumed_a: u128,
+    pub consumed_b: u128,
+    pub dust_a: u128,
+    pub dust_b: u128,
+}
+
+impl ZapSimulation {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(80);
+        for value in [self.lp_tokens, self.consumed_a, self.consumed_b, self.dust_a, self.dust_b] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let simulation = Self {
+            lp_tokens: reader.read_u128()?,
+            consumed_a: reader.read_u128()?,
+            consumed_b: reader.read_u128()?,
+            dust_a: reader.read_u128()?,
+            dust_b: reader.read_u128()?,
+        };
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after zap simulation"));
+        }
+        Ok(simulation)
+    }
+}
+
+/// Underlying value of an LP position, reported by `EstimateLpValue`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LpValueEstimate {
+    /// The position's pro-rata share of each reserve
+    pub amount_a: u128,
+    pub amount_b: u128,
+    /// Part of that share earned by swap fees since the pool was created
+    pub fees_a: u128,
+    pub fees_b: u128,
+}
+
+impl LpValueEstimate {
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(64);
+        for value in [self.amount_a, self.amount_b, self.fees_a, self.fees_b] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let estimate = Self {
+            amount_a: reader.read_u128()?,
+            amount_b: reader.read_u128()?,
+            fees_a: reader.read_u128()?,
+            fees_b: reader.read_u128()?,
+        };
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after LP value estimate"));
+        }
+        Ok(estimate)
+    }
+}
+
+/// Record of a completed zap, returned as `ExecuteZap` response data so indexers can
+/// reconstruct zap history without re-simulating
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZapReceipt {
+    pub input_token: AlkaneId,
+    pub input_amount: u128,
+    /// Swap path of each leg; an identity leg is just the input token
+    pub path_a: Vec<AlkaneId>,
+    pub path_b: Vec<AlkaneId>,
+    /// Target tokens each leg delivered
+    pub output_a: u128,
+    pub output_b: u128,
+    pub lp_minted: u128,
+    pub dust_a: u128,
+    pub dust_b: u128,
+}
+
+impl ZapReceipt {
+    /// Encode as a u8 layout version, the input token and amount, each leg path as a u32
+    /// count followed by its tokens, then the leg outputs, LP minted and dust
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(1 + 48 + 8 + (self.path_a.len() + self.path_b.len()) * 32 + 80);
+        out.push(ZAP_RECEIPT_VERSION);
+        write_alkane_id(&mut out, &self.input_token);
+        out.extend_from_slice(&self.input_amount.to_le_bytes());
+        for path in [&self.path_a, &self.path_b] {
+            out.extend_from_slice(&(path.len() as u32).to_le_bytes());
+            for token in path {
+                write_alkane_id(&mut out, token);
+            }
+        }
+        for value in [self.output_a, self.output_b, self.lp_minted, self.dust_a, self.dust_b] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let version = reader.read_u8()?;
+        if version != ZAP_RECEIPT_VERSION {
+            return Err(anyhow!("Unsupported zap receipt version {}", version));
+        }
+        let input_token = reader.read_alkane_id()?;
+        let input_amount = reader.read_u128()?;
+        let mut read_path = || -> Result<Vec<AlkaneId>> {
+            let len = reader.read_u32()? as usize;
+            let mut path = Vec::with_capacity(len.min(MAX_HOPS + 1));
+            for _ in 0..len {
+                path.push(reader.read_alkane_id()?);
+            }
+            Ok(path)
+        };
+        let path_a = read_path()?;
+        let path_b = read_path()?;
+        let receipt = Self {
+            input_token,
+            input_amount,
+            path_a,
+            path_b,
+            output_a: reader.read_u128()?,
+            output_b: reader.read_u128()?,
+            lp_minted: reader.read_u128()?,
+            dust_a: reader.read_u128()?,
+            dust_b: reader.read_u128()?,
+        };
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after zap receipt"));
+        }
+        Ok(receipt)
+    }
+}
+
+/// Compact record of the quote a zap executed against, kept in the quote history ring
+/// buffer for indexers. Quote opcodes stay read-only and record nothing.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuoteRecord {
+    pub input_token: AlkaneId,
+    pub input_amount: u128,
+    pub expected_lp_tokens: u128,
+    pub price_impact: u128,
+    pub height: u128,
+}
+
+impl QuoteRecord {
+    pub const ENCODED_LEN: usize = 96;
+
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(Self::ENCODED_LEN);
+        write_alkane_id(&mut out, &self.input_token);
+        for value in [self.input_amount, self.expected_lp_tokens, self.price_impact, self.height] {
+            out.extend_from_slice(&value.to_le_bytes());
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let record = Self {
+            input_token: reader.read_alkane_id()?,
+            input_amount: reader.read_u128()?,
+            expected_lp_tokens: reader.read_u128()?,
+            price_impact: reader.read_u128()?,
+            height: reader.read_u128()?,
+        };
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after quote record"));
+        }
+        Ok(record)
+    }
+}
+
+/// Contract configuration reported by `GetConfig`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ZapConfig {
+    pub factory_id: AlkaneId,
+    pub base_tokens: Vec<AlkaneId>,
+    /// Contract version as (major, minor, patch)
+    pub version: (u128, u128, u128),
+    /// Slippage tolerance (bps) applied when a caller passes zero
+    pub default_slippage_bps: u128,
+}
+
+impl ZapConfig {
+    /// Encode as the factory id, a u32 base-token count followed by each token, the version,
+    /// then the default slippage
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + 4 + self.base_tokens.len() * 32 + 64);
+        write_alkane_id(&mut out, &self.factory_id);
+        out.extend_from_slice(&(self.base_tokens.len() as u32).to_le_bytes());
+        for token in &self.base_tokens {
+            write_alkane_id(&mut out, token);
+        }
+        out.extend_from_slice(&self.version.0.to_le_bytes());
+        out.extend_from_slice(&self.version.1.to_le_bytes());
+        out.extend_from_slice(&self.version.2.to_le_bytes());
+        out.extend_from_slice(&self.default_slippage_bps.to_le_bytes());
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> Result<Self> {
+        let mut reader = ByteReader::new(bytes);
+        let factory_id = reader.read_alkane_id()?;
+        let count = reader.read_u32()? as usize;
+        let mut base_tokens = Vec::with_capacity(count.min(bytes.len() / 32));
+        for _ in 0..count {
+            base_tokens.push(reader.read_alkane_id()?);
+        }
+        let version = (reader.read_u128()?, reader.read_u128()?, reader.read_u128()?);
+        let default_slippage_bps = reader.read_u128()?;
+        if !reader.is_empty() {
+            return Err(anyhow!("Trailing bytes after zap config"));
+        }
+        Ok(Self { factory_id, base_tokens, version, default_slippage_bps })
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct ZapParams {
     pub input_token: AlkaneId,
@@ -187,7 +1124,7 @@ pub struct ZapParams {
     pub target_token_a: AlkaneId,
     pub target_token_b: AlkaneId,
     pub min_lp_tokens: u128,
-    pub deadline: u128,
+    pub deadline: u128, // block height, 0 = none
     pub max_slippage_bps: u128, // basis points, 100 = 1%
 }
 
@@ -216,13 +1153,14 @@ impl ZapParams {
         self
     }
 
-    pub fn validate(&self, current_time: u128) -> Result<()> {
+    /// Check the parameters at block `height`, expiring the deadline as `is_deadline_passed` does
+    pub fn validate(&self, height: u128) -> Result<()> {
         if self.input_amount == 0 {
             return Err(anyhow!("Input amount cannot be zero"));
         }
 
-        if self.deadline <= current_time {
-            return Err(anyhow!("Transaction deadline has passed"));
+        if is_deadline_passed(self.deadline, height) {
+            return Err(ZapError::DeadlineExpired.into());
         }
 
         if self.max_slippage_bps > 10000 {
@@ -241,8 +1179,190 @@ impl ZapParams {
     }
 }
 
+/// Options controlling how a zap quote is computed
+#[derive(Debug, Clone, Default)]
+pub struct QuoteOptions {
+    /// Estimate LP tokens against the target pool reserves after the quoted swaps
+    pub project_target_reserves: bool,
+    /// Split the input with `calculate_optimal_split_exact` instead of the binary search
+    pub exact_split: bool,
+    /// Weight each leg's price impact by the value of its output in this token, rather than
+    /// by the raw amount of input it swaps
+    pub value_reference: Option<AlkaneId>,
+    /// Decimals of the two target tokens, which the binary search split normalizes by
+    pub target_decimals: Option<(u8, u8)>,
+}
+
+impl QuoteOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_projected_reserves(mut self, project_target_reserves: bool) -> Self {
+        self.project_target_reserves = project_target_reserves;
+        self
+    }
+
+    pub fn with_exact_split(mut self, exact_split: bool) -> Self {
+        self.exact_split = exact_split;
+        self
+    }
+
+    pub fn with_value_reference(mut self, base_token: AlkaneId) -> Self {
+        self.value_reference = Some(base_token);
+        self
+    }
+
+    pub fn with_target_decimals(mut self, decimals_a: u8, decimals_b: u8) -> Self {
+        self.target_decimals = Some((decimals_a, decimals_b));
+        self
+    }
+}
+
+/// Structured failure reasons for zap operations. Returned through `anyhow::Result`, so
+/// integrators can recover the variant with `err.downcast_ref::<ZapError>()`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZapError {
+    DeadlineExpired,
+    NoInputTokens,
+    InputMismatch,
+    ZeroInputAmount,
+    SelfRoute,
+    PoolNotFound { token_a: AlkaneId, token_b: AlkaneId },
+    RouteNotFound { from_token: AlkaneId, to_token: AlkaneId },
+    PriceImpactExceeded { impact: u128, max: u128 },
+    PoolTooVolatile { moved_bps: u128, max_bps: u128 },
+    PriceDeviatesFromReference { deviation_bps: u128, tolerance_bps: u128 },
+    SlippageExceeded { realized: u128, minimum: u128, suggested_bps: u128 },
+    DuplicateToken { token: AlkaneId },
+    InsufficientFuel { available: u128, required: u128 },
+    QuoteDrifted { quoted: u128, current: u128 },
+    ReservesDeviated { deviation_bps: u128, max_bps: u128 },
+    InsufficientInput { required: u128, max: u128 },
+    Paused,
+    ReserveKDropped { drop_bps: u128, max_bps: u128 },
+    InputTooLarge { amount: u128, max: u128 },
+    Reentrancy,
+    IdenticalTokens { token: AlkaneId },
+}
+
+impl std::fmt::Display for ZapError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ZapError::DeadlineExpired => write!(f, "Transaction deadline has passed"),
+            ZapError::NoInputTokens => write!(f, "No input tokens provided"),
+            ZapError::InputMismatch => write!(f, "Input token mismatch"),
+            ZapError::ZeroInputAmount => write!(f, "Input amount cannot be zero"),
+            ZapError::SelfRoute => write!(f, "Cannot route from token to itself"),
+            ZapError::PoolNotFound { token_a, token_b } => {
+                write!(f, "Pool not found for tokens {:?} and {:?}", token_a, token_b)
+            }
+            ZapError::RouteNotFound { from_token, to_token } => {
+                write!(f, "No route found from {:?} to {:?}", from_token, to_token)
+            }
+            ZapError::PriceImpactExceeded { impact, max } => {
+                write!(f, "Price impact {} exceeds max {}", impact, max)
+            }
+            ZapError::PoolTooVolatile { moved_bps, max_bps } => write!(
+                f,
+                "Pool too volatile: ratio moved {} bps against recent history (max {})",
+                moved_bps, max_bps
+            ),
+            ZapError::PriceDeviatesFromReference { deviation_bps, tolerance_bps } => write!(
+                f,
+                "Route price deviates {} bps from reference (tolerance {})",
+                deviation_bps, tolerance_bps
+            ),
+            ZapError::SlippageExceeded { realized, minimum, suggested_bps } => write!(
+                f,
+                "Insufficient output received: {} < {} (retry with at least {} bps slippage)",
+                realized, minimum, suggested_bps
+            ),
+            ZapError::DuplicateToken { token } => write!(f, "Token {:?} appears more than once", token),
+            ZapError::InsufficientFuel { available, required } => {
+                write!(f, "Insufficient fuel for all zap hops: {} < {}", available, required)
+            }
+            ZapError::QuoteDrifted { quoted, current } => {
+                write!(f, "Expected LP tokens fell from {} at quote time to {}", quoted, current)
+            }
+            ZapError::ReservesDeviated { deviation_bps, max_bps } => write!(
+                f,
+                "Pool ratio deviates {} bps from its stored reserves (max {})",
+                deviation_bps, max_bps
+            ),
+            ZapError::InsufficientInput { required, max } => {
+                write!(f, "Input required exceeds the maximum: {} > {}", required, max)
+            }
+            ZapError::Paused => write!(f, "Zaps are paused"),
+            ZapError::ReserveKDropped { drop_bps, max_bps } => write!(
+                f,
+                "Pool reserve product drops {} bps from its stored reserves (max {})",
+                drop_bps, max_bps
+            ),
+            ZapError::InputTooLarge { amount, max } => {
+                write!(f, "Zap input exceeds the per-zap maximum: {} > {}", amount, max)
+            }
+            ZapError::Reentrancy => write!(f, "Reentrant call into the zap rejected"),
+            ZapError::IdenticalTokens { token } => {
+                write!(f, "Token {:?} is repeated where distinct tokens are required", token)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ZapError {}
+
+/// Whether `deadline` has passed at block `height`.
+///
+/// A deadline of zero means none was set. The deadline block itself is still on time;
+/// only heights strictly after it are expired.
+pub fn is_deadline_passed(deadline: u128, height: u128) -> bool {
+    deadline != 0 && height > deadline
+}
+
+/// Read a 0/1 opcode input as a flag, rejecting any other value
+pub fn parse_flag(value: u128, name: &str) -> Result<bool> {
+    match value {
+        0 => Ok(false),
+        1 => Ok(true),
+        _ => Err(anyhow!("{} must be 0 or 1", name)),
+    }
+}
+
+/// Reject a pair naming the same token on both sides, which no pool or route can serve
+pub fn ensure_distinct_pair(token_a: AlkaneId, token_b: AlkaneId) -> Result<()> {
+    if token_a == token_b {
+        return Err(ZapError::IdenticalTokens { token: token_a }.into());
+    }
+    Ok(())
+}
+
+/// Reject token lists that name the same token twice, since split and ratio math assumes distinct entries
+pub fn ensure_distinct_tokens(tokens: &[AlkaneId]) -> Result<()> {
+    for (i, token) in tokens.iter().enumerate() {
+        if tokens[..i].contains(token) {
+            return Err(ZapError::DuplicateToken { token: *token }.into());
+        }
+    }
+    Ok(())
+}
+
 // Constants for the zap contract
 pub const DEFAULT_FEE_AMOUNT_PER_1000: u128 = 5; // 0.5% fee
+pub const DEFAULT_FEE_BPS: u128 = DEFAULT_FEE_AMOUNT_PER_1000 * 10; // Default fee in basis points
 pub const MAX_HOPS: usize = 3; // Maximum number of hops in a route
+pub const MAX_ROUTES: usize = 64; // Routes one search enumerates before it stops
+pub const MAX_ROUTE_EXPANSIONS: usize = 1024; // Partial paths one multi-hop search may expand
+pub const MAX_ROUTE_BATCH: usize = 16; // Maximum queries in one GetBestRouteBatch call
+pub const MAX_QUOTE_BATCH: usize = 8; // Maximum target pairs in one GetZapQuoteBatch call
+pub const SPLIT_ROUTE_STEPS: u128 = 20; // Chunks a leg is cut into when allocating it across split routes
 pub const BASIS_POINTS: u128 = 10000; // 100% in basis points
+pub const MAX_TOKEN_DECIMALS: u8 = 18; // Scale amounts are normalized to when token decimals differ
+pub const PRICE_PRECISION: u128 = 1_000_000_000_000_000_000; // 1e18 scale for reference prices
 pub const MINIMUM_LIQUIDITY: u128 = 1000; // Minimum liquidity for new pools
+pub const ZAP_QUOTE_SERIALIZED_VERSION: u16 = 5; // Format version prefixed to GetZapQuoteV2 data
+pub const ZAP_RECEIPT_VERSION: u8 = 1; // Layout version prefixed to ExecuteZap receipts
+pub const SLIPPAGE_SENSITIVITY_STEP_BPS: u128 = 100; // 1% slippage step for quote sensitivity
+pub const RATIO_HISTORY_LENGTH: usize = 8; // Recent pool ratio observations kept for volatility checks
+pub const FUEL_PER_HOP: u128 = 100_000; // Fuel budgeted per swap hop or liquidity call when pre-checking a zap
+pub const QUOTE_HISTORY_LENGTH: u128 = 32; // Executed zaps' quotes kept in the GetQuoteHistory ring buffer
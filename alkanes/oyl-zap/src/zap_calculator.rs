@@ -1,4 +1,4 @@
-use crate::types::{RouteInfo, ZapQuote, PoolReserves, U256, BASIS_POINTS};
+use crate::types::{ensure_distinct_tokens, CurveKind, QuoteOptions, RouteInfo, ZapError, ZapParams, ZapQuote, PoolReserves, U256, BASIS_POINTS, FUEL_PER_HOP, MAX_TOKEN_DECIMALS, PRICE_PRECISION, RATIO_HISTORY_LENGTH, SLIPPAGE_SENSITIVITY_STEP_BPS};
 use crate::pool_provider::PoolProvider;
 use crate::route_finder::RouteFinder;
 use crate::amm_logic;
@@ -29,12 +29,258 @@ impl ZapCalculator {
             route_a,
             route_b,
             pool_ratio,
+            (1, 1),
             route_finder,
         )
     }
 
+    /// `calculate_optimal_split` with both target tokens brought to 18 decimals before ratios
+    /// are compared.
+    ///
+    /// Raw ratios of a pair whose decimals differ widely can truncate to a handful of
+    /// significant digits, leaving the search unable to tell splits apart. The reserves are
+    /// oriented to `route_a`'s target first, so each side is scaled by its own token's decimals
+    /// whichever way round the pool stores them. The split itself stays in input units and
+    /// route outputs in each token's own units.
+    pub fn calculate_optimal_split_with_decimals<P: PoolProvider>(
+        input_amount: u128,
+        route_a: &RouteInfo,
+        route_b: &RouteInfo,
+        target_pool_reserves: &PoolReserves,
+        (decimals_a, decimals_b): (u8, u8),
+        route_finder: &RouteFinder<P>,
+    ) -> Result<(u128, u128)> {
+        if input_amount == 0 {
+            return Err(anyhow!("Input amount cannot be zero"));
+        }
+        let scales = (Self::decimal_scale(decimals_a)?, Self::decimal_scale(decimals_b)?);
+        let target_token_a = *route_a.path.last().ok_or_else(|| anyhow!("Route path cannot be empty"))?;
+        let reserves = target_pool_reserves.oriented(target_token_a);
+        if reserves.reserve_b == 0 {
+            return Err(anyhow!("Pool reserve B cannot be zero"));
+        }
+
+        let pool_ratio = U256::from(reserves.reserve_a) * U256::from(scales.0) * U256::from(PRICE_PRECISION)
+            / (U256::from(reserves.reserve_b) * U256::from(scales.1));
+        Self::binary_search_optimal_split(input_amount, route_a, route_b, pool_ratio, scales, route_finder)
+    }
+
+    /// Factor bringing an amount of a token with `decimals` decimals to 18 decimals
+    fn decimal_scale(decimals: u8) -> Result<u128> {
+        if decimals > MAX_TOKEN_DECIMALS {
+            return Err(anyhow!("Token decimals {} exceed {}", decimals, MAX_TOKEN_DECIMALS));
+        }
+        Ok(10u128.pow(u32::from(MAX_TOKEN_DECIMALS - decimals)))
+    }
+
+    /// Calculate the split whose route outputs land exactly on the target pool's ratio.
+    ///
+    /// Rather than bisecting on the input split and scoring forward outputs, this searches the
+    /// amount of token B to receive, pairs it with the ratio-matched amount of token A, and
+    /// prices both with `calculate_swap_in` along the inverted routes. Only constant-product
+    /// hops can be inverted. Any input left after buying the largest affordable pair goes to B.
+    pub fn calculate_optimal_split_exact<P: PoolProvider>(
+        input_amount: u128,
+        route_a: &RouteInfo,
+        route_b: &RouteInfo,
+        target_pool_reserves: &PoolReserves,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<(u128, u128)> {
+        if input_amount == 0 {
+            return Err(anyhow!("Input amount cannot be zero"));
+        }
+        let target_token_a = *route_a.path.last().ok_or_else(|| anyhow!("Route path cannot be empty"))?;
+        let (reserve_a, reserve_b) = if target_pool_reserves.token_a == target_token_a {
+            (target_pool_reserves.reserve_a, target_pool_reserves.reserve_b)
+        } else {
+            (target_pool_reserves.reserve_b, target_pool_reserves.reserve_a)
+        };
+        if reserve_a == 0 || reserve_b == 0 {
+            return Err(anyhow!("Target pool reserves cannot be zero"));
+        }
+
+        // Input needed for `amount_b` of B plus the ratio-matched amount of A; None if unaffordable
+        let pair_cost = |amount_b: u128| -> Result<Option<u128>> {
+            let amount_a: u128 = (U256::from(amount_b) * U256::from(reserve_a) / U256::from(reserve_b))
+                .try_into()
+                .map_err(|_| anyhow!("Ratio-matched amount exceeds u128"))?;
+            let cost_a = Self::calculate_route_input(amount_a, route_a, route_finder)?;
+            let cost_b = Self::calculate_route_input(amount_b, route_b, route_finder)?;
+            Ok(cost_a.zip(cost_b).and_then(|(a, b)| a.checked_add(b)).filter(|cost| *cost <= input_amount))
+        };
+
+        // Spending everything on B bounds how much B any split can buy
+        let mut low = 0u128;
+        let mut high = Self::calculate_route_output(input_amount, route_b, route_finder)?;
+        while low < high {
+            let mid = low + (high - low).div_ceil(2);
+            if pair_cost(mid)?.is_some() {
+                low = mid;
+            } else {
+                high = mid - 1;
+            }
+        }
+
+        let amount_a: u128 = (U256::from(low) * U256::from(reserve_a) / U256::from(reserve_b))
+            .try_into()
+            .map_err(|_| anyhow!("Ratio-matched amount exceeds u128"))?;
+        let split_a = Self::calculate_route_input(amount_a, route_a, route_finder)?.unwrap_or(0);
+        Ok((split_a, input_amount - split_a))
+    }
+
+    /// Split an input that is itself one of the target tokens so the unswapped remainder and
+    /// the swapped side land on the ratio the target pool will have when liquidity is added.
+    ///
+    /// When the swapped side trades through the target pool itself, the pool moves as it swaps
+    /// and the standard single-sided closed form applies. Otherwise the target ratio is fixed
+    /// and the exact inverse split is used, falling back to the search for curves it cannot invert.
+    pub fn calculate_single_sided_split<P: PoolProvider>(
+        input_amount: u128,
+        route_a: &RouteInfo,
+        route_b: &RouteInfo,
+        target_pool_reserves: &PoolReserves,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<(u128, u128)> {
+        if input_amount == 0 {
+            return Err(anyhow!("Input amount cannot be zero"));
+        }
+        let (kept_is_a, swapped) = match (route_a.hop_count(), route_b.hop_count()) {
+            (0, _) => (true, route_b),
+            (_, 0) => (false, route_a),
+            _ => return Err(anyhow!("Single-sided split needs the input to be one of the targets")),
+        };
+
+        let input_token = swapped.path[0];
+        let through_target_pool = swapped.hop_count() == 1
+            && [target_pool_reserves.token_a, target_pool_reserves.token_b].contains(&swapped.path[1]);
+        if through_target_pool && target_pool_reserves.curve == CurveKind::ConstantProduct {
+            let reserve_in = if target_pool_reserves.token_a == input_token {
+                target_pool_reserves.reserve_a
+            } else {
+                target_pool_reserves.reserve_b
+            };
+            let swap_amount = amm_logic::optimal_swap_fraction(input_amount, reserve_in, target_pool_reserves.fee_for(input_token));
+            let kept_amount = input_amount - swap_amount;
+            return Ok(if kept_is_a { (kept_amount, swap_amount) } else { (swap_amount, kept_amount) });
+        }
+
+        Self::calculate_optimal_split_exact(input_amount, route_a, route_b, target_pool_reserves, route_finder)
+            .or_else(|_| Self::calculate_optimal_split(input_amount, route_a, route_b, target_pool_reserves, route_finder))
+    }
+
+    /// Split of input that buys just enough of each target token to mint `lp_amount_out` LP
+    /// tokens, the inverse of quoting a zap. Both legs are priced with `calculate_swap_in`.
+    /// The deposit rounds the ratio-matched side down, so each side is bought with one unit of
+    /// the other side's worth to spare and the deposit mints at least the requested LP.
+    ///
+    /// A single-sided input swapping through the target pool moves the ratio it deposits at,
+    /// so that case is solved against the post-swap reserves.
+    pub fn calculate_split_for_exact_lp<P: PoolProvider>(
+        lp_amount_out: u128,
+        route_a: &RouteInfo,
+        route_b: &RouteInfo,
+        target_pool_reserves: &PoolReserves,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<(u128, u128)> {
+        if lp_amount_out == 0 {
+            return Err(anyhow!("LP amount cannot be zero"));
+        }
+        if target_pool_reserves.total_supply == 0 {
+            return Err(anyhow!("Exact LP output needs a pool with LP supply"));
+        }
+        let target_token_a = *route_a.path.last().ok_or_else(|| anyhow!("Route path cannot be empty"))?;
+        let (reserve_a, reserve_b) = if target_pool_reserves.token_a == target_token_a {
+            (target_pool_reserves.reserve_a, target_pool_reserves.reserve_b)
+        } else {
+            (target_pool_reserves.reserve_b, target_pool_reserves.reserve_a)
+        };
+        if reserve_a == 0 || reserve_b == 0 {
+            return Err(anyhow!("Target pool reserves cannot be zero"));
+        }
+        let lp_amount = U256::from(lp_amount_out);
+        let total_supply = U256::from(target_pool_reserves.total_supply);
+        let to_u128 = |amount: U256| -> Result<u128> {
+            amount.try_into().map_err(|_| anyhow!("Amount for exact LP output exceeds u128"))
+        };
+        // Amount of a side backing `lp_amount` at `reserve`, plus one unit of the other side
+        let deposit_amount = |reserve: u128, other_reserve: u128| -> Result<u128> {
+            let share = (lp_amount * U256::from(reserve)).div_ceil(total_supply);
+            to_u128(share + U256::from(reserve).div_ceil(U256::from(other_reserve)))
+        };
+
+        let single_sided_leg = match (route_a.hop_count(), route_b.hop_count()) {
+            (0, _) => Some((true, route_b, reserve_a, reserve_b)),
+            (_, 0) => Some((false, route_a, reserve_b, reserve_a)),
+            _ => None,
+        };
+        if let Some((kept_is_a, swapped, reserve_in, reserve_out)) = single_sided_leg {
+            let through_target_pool = swapped.hop_count() == 1
+                && [target_pool_reserves.token_a, target_pool_reserves.token_b].contains(&swapped.path[1]);
+            if through_target_pool && target_pool_reserves.curve == CurveKind::ConstantProduct {
+                // The swapped side must cover its share of the reserve it is drawn from,
+                // out >= lp * (reserve_out - out) / supply, with the same unit to spare. The
+                // swap only lowers the out side's price, so the pre-swap unit is enough.
+                let spare = U256::from(reserve_out).div_ceil(U256::from(reserve_in));
+                let amount_out = to_u128(
+                    (lp_amount * U256::from(reserve_out) + spare * total_supply).div_ceil(total_supply + lp_amount),
+                )?;
+                let swap_amount =
+                    amm_logic::calculate_swap_in(amount_out, reserve_in, reserve_out, target_pool_reserves.fee_for(swapped.path[0]))?;
+                let kept_amount = deposit_amount(
+                    reserve_in.checked_add(swap_amount).ok_or_else(|| anyhow!("Reserve after swap exceeds u128"))?,
+                    reserve_out - amount_out,
+                )?;
+                return Ok(if kept_is_a { (kept_amount, swap_amount) } else { (swap_amount, kept_amount) });
+            }
+        }
+
+        let amount_a = deposit_amount(reserve_a, reserve_b)?;
+        let amount_b = deposit_amount(reserve_b, reserve_a)?;
+        let split_a = Self::calculate_route_input(amount_a, route_a, route_finder)?
+            .ok_or_else(|| anyhow!("Route to token A cannot supply {}", amount_a))?;
+        let split_b = Self::calculate_route_input(amount_b, route_b, route_finder)?
+            .ok_or_else(|| anyhow!("Route to token B cannot supply {}", amount_b))?;
+        Ok((split_a, split_b))
+    }
+
+    /// Input needed along `route` to receive `amount_out`, or None when the route's pools
+    /// cannot supply that much
+    fn calculate_route_input<P: PoolProvider>(
+        amount_out: u128,
+        route: &RouteInfo,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<Option<u128>> {
+        if route.path.is_empty() {
+            return Err(anyhow!("Route path cannot be empty"));
+        }
+        if amount_out == 0 {
+            return Ok(Some(0));
+        }
+
+        let mut current_amount = amount_out;
+        for (i, hop) in route.path.windows(2).enumerate().rev() {
+            let (token_in, token_out) = (hop[0], hop[1]);
+            let pool = Self::scoring_pool_reserves(route_finder, i, token_in, token_out)?;
+            if pool.curve != CurveKind::ConstantProduct {
+                return Err(anyhow!("Exact split requires constant-product hops"));
+            }
+
+            let (reserve_in, reserve_out) = if pool.token_a == token_in {
+                (pool.reserve_a, pool.reserve_b)
+            } else {
+                (pool.reserve_b, pool.reserve_a)
+            };
+            if current_amount >= reserve_out {
+                return Ok(None);
+            }
+            current_amount = amm_logic::calculate_swap_in(current_amount, reserve_in, reserve_out, pool.fee_for(token_in))?;
+        }
+
+        Ok(Some(current_amount))
+    }
+
     /// Get the ratio of token A to token B in the target pool
-    fn get_pool_ratio(pool_reserves: &PoolReserves) -> Result<U256> {
+    pub fn get_pool_ratio(pool_reserves: &PoolReserves) -> Result<U256> {
         if pool_reserves.reserve_b == 0 {
             return Err(anyhow!("Pool reserve B cannot be zero"));
         }
@@ -42,22 +288,133 @@ impl ZapCalculator {
         Ok(U256::from(pool_reserves.reserve_a) * U256::from(1_000_000_000_000_000_000u128) / U256::from(pool_reserves.reserve_b))
     }
 
+    /// Reject quoting against a pool whose ratio has moved more than `max_volatility_bps`
+    /// from any recent observation, a sign of active manipulation or a thin pool.
+    /// A zero threshold or empty history disables the check.
+    pub fn check_ratio_volatility(
+        pool_reserves: &PoolReserves,
+        ratio_history: &[U256],
+        max_volatility_bps: u128,
+    ) -> Result<()> {
+        if max_volatility_bps == 0 || ratio_history.is_empty() {
+            return Ok(());
+        }
+
+        let current_ratio = Self::get_pool_ratio(pool_reserves)?;
+        for past_ratio in ratio_history.iter().filter(|ratio| !ratio.is_zero()) {
+            let difference = if current_ratio > *past_ratio {
+                current_ratio - *past_ratio
+            } else {
+                *past_ratio - current_ratio
+            };
+            let moved_bps = difference * U256::from(BASIS_POINTS) / *past_ratio;
+            if moved_bps > U256::from(max_volatility_bps) {
+                return Err(ZapError::PoolTooVolatile {
+                    moved_bps: moved_bps.try_into().unwrap_or(u128::MAX),
+                    max_bps: max_volatility_bps,
+                }
+                .into());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Reject a reserve update that cannot describe a real pool: reserves without any LP
+    /// supply, or a product per LP share, `sqrt(reserve_a * reserve_b) / total_supply`, more
+    /// than `max_k_drop_bps` below the stored one. Swaps only ever grow it and liquidity moves
+    /// leave it unchanged, so a drop points to a faulty feed. A zero threshold or an empty
+    /// stored pool skips the product check.
+    pub fn check_reserve_update(
+        stored_reserves: &PoolReserves,
+        updated_reserves: &PoolReserves,
+        max_k_drop_bps: u128,
+    ) -> Result<()> {
+        if updated_reserves.total_supply == 0 && (updated_reserves.reserve_a != 0 || updated_reserves.reserve_b != 0) {
+            return Err(anyhow!("Pool reserves cannot be non-zero without LP supply"));
+        }
+        if max_k_drop_bps == 0 || stored_reserves.total_supply == 0 || updated_reserves.total_supply == 0 {
+            return Ok(());
+        }
+
+        let k_per_share = |reserves: &PoolReserves| {
+            let k = U256::from(reserves.reserve_a) * U256::from(reserves.reserve_b);
+            amm_logic::integer_sqrt(k) * U256::from(PRICE_PRECISION) / U256::from(reserves.total_supply)
+        };
+        let (stored, updated) = (k_per_share(stored_reserves), k_per_share(updated_reserves));
+        if stored.is_zero() || updated >= stored {
+            return Ok(());
+        }
+        let drop_bps: u128 = ((stored - updated) * U256::from(BASIS_POINTS) / stored)
+            .try_into()
+            .map_err(|_| anyhow!("Reserve product drop exceeds u128"))?;
+        if drop_bps > max_k_drop_bps {
+            return Err(ZapError::ReserveKDropped { drop_bps, max_bps: max_k_drop_bps }.into());
+        }
+        Ok(())
+    }
+
+    /// Reject trading against a pool whose live ratio has moved more than `max_deviation_bps`
+    /// from the last stored reserves, as it would right after a flash manipulation in the
+    /// same block. A zero threshold or an empty stored pool disables the check.
+    pub fn check_reserve_deviation(
+        stored_reserves: &PoolReserves,
+        fresh_reserves: &PoolReserves,
+        max_deviation_bps: u128,
+    ) -> Result<()> {
+        if max_deviation_bps == 0 || stored_reserves.reserve_a == 0 || stored_reserves.reserve_b == 0 {
+            return Ok(());
+        }
+
+        // Line the fresh reserves up with the stored pool's token order
+        let (fresh_a, fresh_b) = if fresh_reserves.token_a == stored_reserves.token_a {
+            (fresh_reserves.reserve_a, fresh_reserves.reserve_b)
+        } else {
+            (fresh_reserves.reserve_b, fresh_reserves.reserve_a)
+        };
+
+        // Compare a/b ratios by cross-multiplying so neither side is rounded first
+        let baseline = U256::from(stored_reserves.reserve_a) * U256::from(fresh_b);
+        let current = U256::from(fresh_a) * U256::from(stored_reserves.reserve_b);
+        let deviation_bps = if baseline.is_zero() {
+            u128::MAX // the live pool has drained one side entirely
+        } else {
+            let difference = if current > baseline { current - baseline } else { baseline - current };
+            (difference * U256::from(BASIS_POINTS) / baseline).try_into().unwrap_or(u128::MAX)
+        };
+
+        if deviation_bps > max_deviation_bps {
+            return Err(ZapError::ReservesDeviated { deviation_bps, max_bps: max_deviation_bps }.into());
+        }
+        Ok(())
+    }
+
+    /// Append a ratio observation, keeping only the most recent `RATIO_HISTORY_LENGTH` entries
+    pub fn push_ratio_observation(ratio_history: &mut Vec<U256>, ratio: U256) {
+        ratio_history.push(ratio);
+        if ratio_history.len() > RATIO_HISTORY_LENGTH {
+            let excess = ratio_history.len() - RATIO_HISTORY_LENGTH;
+            ratio_history.drain(..excess);
+        }
+    }
+
     /// Use binary search to find the optimal split that results in balanced LP provision
     fn binary_search_optimal_split<P: PoolProvider>(
         input_amount: u128,
         route_a: &RouteInfo,
         route_b: &RouteInfo,
         target_ratio: U256,
+        (scale_a, scale_b): (u128, u128),
         route_finder: &RouteFinder<P>,
     ) -> Result<(u128, u128)> {
         let mut left = 0u128;
         let mut right = input_amount;
-        let mut best_split = (input_amount / 2, input_amount / 2);
+        let mut best_split = (input_amount / 2, input_amount - input_amount / 2);
         let mut best_balance_score = U256::MAX;
 
         // Binary search for optimal split
         for _ in 0..50 { // Limit iterations to prevent infinite loops
-            let mid = (left + right) / 2;
+            let mid = left + (right - left) / 2;
             let split_a = mid;
             let split_b = input_amount - mid;
 
@@ -73,9 +430,9 @@ impl ZapCalculator {
                 continue;
             }
 
-            // Calculate expected outputs
-            let expected_a = Self::calculate_route_output(split_a, route_a, route_finder)?;
-            let expected_b = Self::calculate_route_output(split_b, route_b, route_finder)?;
+            // Calculate expected outputs, on the same scale as the target ratio
+            let expected_a = U256::from(Self::calculate_route_output(split_a, route_a, route_finder)?) * U256::from(scale_a);
+            let expected_b = U256::from(Self::calculate_route_output(split_b, route_b, route_finder)?) * U256::from(scale_b);
 
             // Calculate how balanced this split would be
             let balance_score = Self::calculate_balance_score(expected_a, expected_b, target_ratio)?;
@@ -86,10 +443,10 @@ impl ZapCalculator {
             }
 
             // Adjust search range based on balance
-            let current_ratio = if expected_b == 0 {
+            let current_ratio = if expected_b.is_zero() {
                 U256::MAX
             } else {
-                U256::from(expected_a) * U256::from(1_000_000_000_000_000_000u128) / U256::from(expected_b)
+                expected_a * U256::from(1_000_000_000_000_000_000u128) / expected_b
             };
 
             if current_ratio > target_ratio {
@@ -108,13 +465,60 @@ impl ZapCalculator {
         Ok(best_split)
     }
 
+    /// Split an input across the tokens of a weighted pool in proportion to their weights.
+    ///
+    /// Weights are in basis points and must be non-zero and sum to 100%. Rounding dust is
+    /// assigned to the last token so the splits always sum to the input amount.
+    pub fn calculate_weighted_split(
+        input_amount: u128,
+        target_tokens: &[AlkaneId],
+        weights_bps: &[u128],
+    ) -> Result<Vec<u128>> {
+        if input_amount == 0 {
+            return Err(anyhow!("Input amount cannot be zero"));
+        }
+        if target_tokens.len() != weights_bps.len() {
+            return Err(anyhow!(
+                "Expected one weight per target token: {} tokens, {} weights",
+                target_tokens.len(),
+                weights_bps.len()
+            ));
+        }
+        if target_tokens.len() < 2 {
+            return Err(anyhow!("Weighted zaps need at least two target tokens"));
+        }
+        ensure_distinct_tokens(target_tokens)?;
+        if weights_bps.contains(&0) {
+            return Err(anyhow!("Target token weights cannot be zero"));
+        }
+        let total_weight = weights_bps.iter().fold(0u128, |total, weight| total.saturating_add(*weight));
+        if total_weight != BASIS_POINTS {
+            return Err(anyhow!("Weights must sum to {} bps, got {}", BASIS_POINTS, total_weight));
+        }
+
+        let mut splits = weights_bps
+            .iter()
+            .map(|weight| {
+                (U256::from(input_amount) * U256::from(*weight) / U256::from(BASIS_POINTS))
+                    .try_into()
+                    .map_err(|_| anyhow!("Split amount exceeds u128"))
+            })
+            .collect::<Result<Vec<u128>>>()?;
+        let allocated = splits.iter().sum::<u128>();
+        if let Some(last) = splits.last_mut() {
+            *last += input_amount - allocated;
+        }
+
+        Ok(splits)
+    }
+
     /// Calculate how balanced the outputs are compared to the target ratio
-    fn calculate_balance_score(output_a: u128, output_b: u128, target_ratio: U256) -> Result<U256> {
-        if output_b == 0 {
+    fn calculate_balance_score(output_a: U256, output_b: U256, target_ratio: U256) -> Result<U256> {
+        if output_b.is_zero() {
             return Ok(U256::MAX);
         }
 
-        let actual_ratio = U256::from(output_a) * U256::from(1_000_000_000_000_000_000u128) / U256::from(output_b);
+        let actual_ratio = output_a * U256::from(1_000_000_000_000_000_000u128) / output_b;
         
         let diff = if actual_ratio > target_ratio {
             actual_ratio - target_ratio
@@ -145,14 +549,120 @@ impl ZapCalculator {
         expected_lp_tokens: u128,
         slippage_tolerance_bps: u128,
     ) -> Result<u128> {
-        if slippage_tolerance_bps > BASIS_POINTS {
-            return Err(anyhow!("Slippage tolerance cannot exceed 100%"));
+        amm_logic::apply_slippage(expected_lp_tokens, slippage_tolerance_bps)
+    }
+
+    /// Minimum acceptable output of a swap hop expected to return `expected`
+    pub fn min_out_for_hop(expected: u128, slippage_bps: u128) -> Result<u128> {
+        amm_logic::apply_slippage(expected, slippage_bps)
+    }
+
+    /// Minimum outputs for each hop of `route` when swapping `amount_in` through it.
+    ///
+    /// Each hop's expected output feeds the next, so the final entry is the cumulative
+    /// minimum for the whole path, which is what a single multi-hop factory swap enforces.
+    pub fn hop_min_outputs<P: PoolProvider>(
+        amount_in: u128,
+        route: &RouteInfo,
+        slippage_bps: u128,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<Vec<u128>> {
+        let mut minimums = Vec::with_capacity(route.hop_count());
+        let mut current_amount = amount_in;
+        for (i, hop) in route.path.windows(2).enumerate() {
+            let (token_in, token_out) = (hop[0], hop[1]);
+            let pool = Self::scoring_pool_reserves(route_finder, i, token_in, token_out)?;
+
+            let (reserve_in, reserve_out) = if pool.token_a == token_in {
+                (pool.reserve_a, pool.reserve_b)
+            } else {
+                (pool.reserve_b, pool.reserve_a)
+            };
+
+            current_amount = amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, pool.fee_for(token_in), pool.curve)?;
+            minimums.push(Self::min_out_for_hop(current_amount, slippage_bps)?);
         }
 
-        let slippage_multiplier = BASIS_POINTS - slippage_tolerance_bps;
-        let minimum_lp = U256::from(expected_lp_tokens) * U256::from(slippage_multiplier) / U256::from(BASIS_POINTS);
-        
-        Ok(minimum_lp.try_into().map_err(|_| anyhow!("Minimum LP token amount exceeds u128"))?)
+        Ok(minimums)
+    }
+
+    /// Fuel a zap over these routes needs: one `FUEL_PER_HOP` per swap hop plus one for add_liquidity
+    pub fn estimate_fuel_for_routes(route_a: &RouteInfo, route_b: &RouteInfo) -> u128 {
+        let calls = route_a.hop_count() + route_b.hop_count() + 1;
+        calls as u128 * FUEL_PER_HOP
+    }
+
+    /// Fuel needed to execute `quote` end to end, so a zap can fail before its first swap
+    pub fn estimate_fuel_for_quote(quote: &ZapQuote) -> u128 {
+        Self::estimate_fuel_for_routes(&quote.route_a, &quote.route_b)
+    }
+
+    /// Reject an execution whose freshly quoted LP tokens have fallen more than
+    /// `max_slippage_bps` below the expectation the caller quoted against, so moved reserves
+    /// can't push the zap to a worse rate that still clears a loose minimum
+    pub fn check_quote_drift(quoted_expected_lp: u128, current_expected_lp: u128, max_slippage_bps: u128) -> Result<()> {
+        let floor = Self::calculate_minimum_lp_tokens(quoted_expected_lp, max_slippage_bps)?;
+        if current_expected_lp < floor {
+            return Err(ZapError::QuoteDrifted { quoted: quoted_expected_lp, current: current_expected_lp }.into());
+        }
+        Ok(())
+    }
+
+    /// Marginal change in minimum LP tokens per `SLIPPAGE_SENSITIVITY_STEP_BPS` of slippage.
+    ///
+    /// The minimum is linear in the slippage tolerance, so the derivative is the LP lost
+    /// when the tolerance widens by one step from zero.
+    pub fn calculate_slippage_sensitivity(expected_lp_tokens: u128) -> Result<u128> {
+        let minimum_at_step = Self::calculate_minimum_lp_tokens(expected_lp_tokens, SLIPPAGE_SENSITIVITY_STEP_BPS)?;
+        Ok(expected_lp_tokens - minimum_at_step)
+    }
+
+    /// Loosen a caller's minimum LP tokens by the protocol safety margin.
+    ///
+    /// The margin absorbs small block-to-block reserve drift so executions matching a quote
+    /// don't revert on a rounding-sized shortfall. It is capped at the caller's own slippage
+    /// tolerance, so a caller who allows no slippage always gets exactly their minimum.
+    /// A margin of zero (the default) disables the adjustment.
+    pub fn apply_min_lp_safety_margin(
+        min_lp_tokens: u128,
+        safety_margin_bps: u128,
+        slippage_tolerance_bps: u128,
+    ) -> Result<u128> {
+        let margin_bps = safety_margin_bps.min(slippage_tolerance_bps);
+        if margin_bps == 0 {
+            return Ok(min_lp_tokens);
+        }
+
+        Self::calculate_minimum_lp_tokens(min_lp_tokens, margin_bps)
+    }
+
+    /// Largest expected amount a caller's `minimum` could have been derived from at
+    /// `slippage_tolerance_bps`, for when only the minimum reached the contract
+    pub fn implied_expected_amount(minimum: u128, slippage_tolerance_bps: u128) -> u128 {
+        if minimum == 0 || slippage_tolerance_bps >= BASIS_POINTS {
+            return minimum;
+        }
+
+        // The minimum is floored, so any expectation up to one token's worth above it qualifies
+        let numerator = (U256::from(minimum) + U256::from(1u128)) * U256::from(BASIS_POINTS) - U256::from(1u128);
+        let expected = numerator / U256::from(BASIS_POINTS - slippage_tolerance_bps);
+        expected.try_into().unwrap_or(u128::MAX)
+    }
+
+    /// Slippage tolerance a retry would need for `realized` to clear the minimum derived from
+    /// `expected`, as reported by `ZapError::SlippageExceeded`.
+    ///
+    /// Rounded up, so `calculate_minimum_lp_tokens(expected, suggested)` never lands above the
+    /// realized amount.
+    pub fn suggest_slippage_bps(expected: u128, realized: u128) -> u128 {
+        if realized >= expected {
+            return 0;
+        }
+
+        let shortfall = U256::from(expected - realized) * U256::from(BASIS_POINTS);
+        let expected = U256::from(expected);
+        let suggested = (shortfall + expected - U256::from(1u128)) / expected;
+        suggested.try_into().unwrap_or(BASIS_POINTS).min(BASIS_POINTS)
     }
 
     /// Generate a complete zap quote
@@ -167,24 +677,68 @@ impl ZapCalculator {
         slippage_tolerance_bps: u128,
         route_finder: &RouteFinder<P>,
     ) -> Result<ZapQuote> {
-        // Calculate optimal split
-        let (split_a, split_b) = Self::calculate_optimal_split(
-            input_amount,
-            &route_a,
-            &route_b,
+        let params = ZapParams::new(input_token, input_amount, target_token_a, target_token_b, 0, 0)
+            .with_max_slippage(slippage_tolerance_bps);
+        Self::generate_zap_quote_with_options(
+            &params,
+            route_a,
+            route_b,
             target_pool_reserves,
             route_finder,
-        )?;
+            &QuoteOptions::default(),
+        )
+    }
+
+    /// Generate a complete zap quote with explicit quoting options. Only the tokens, input
+    /// amount and slippage tolerance of `params` are read.
+    pub fn generate_zap_quote_with_options<P: PoolProvider>(
+        params: &ZapParams,
+        route_a: RouteInfo,
+        route_b: RouteInfo,
+        target_pool_reserves: &PoolReserves,
+        route_finder: &RouteFinder<P>,
+        options: &QuoteOptions,
+    ) -> Result<ZapQuote> {
+        let ZapParams {
+            input_token,
+            input_amount,
+            target_token_a,
+            target_token_b,
+            max_slippage_bps: slippage_tolerance_bps,
+            ..
+        } = *params;
+        // Calculate optimal split, solving exactly when the input is one of the targets
+        let single_sided = input_token == target_token_a || input_token == target_token_b;
+        let (split_a, split_b) = if single_sided {
+            Self::calculate_single_sided_split(input_amount, &route_a, &route_b, target_pool_reserves, route_finder)?
+        } else if options.exact_split {
+            Self::calculate_optimal_split_exact(input_amount, &route_a, &route_b, target_pool_reserves, route_finder)?
+        } else if let Some(target_decimals) = options.target_decimals {
+            Self::calculate_optimal_split_with_decimals(input_amount, &route_a, &route_b, target_pool_reserves, target_decimals, route_finder)?
+        } else {
+            Self::calculate_optimal_split(input_amount, &route_a, &route_b, target_pool_reserves, route_finder)?
+        };
 
         // Calculate expected outputs after swaps
         let expected_output_a = Self::calculate_route_output(split_a, &route_a, route_finder)?;
         let expected_output_b = Self::calculate_route_output(split_b, &route_b, route_finder)?;
+        // A leg that yields nothing would only fail at execution, as a shortfall of LP tokens
+        for (expected_output, target_token) in [(expected_output_a, target_token_a), (expected_output_b, target_token_b)] {
+            if expected_output == 0 {
+                return Err(ZapError::RouteNotFound { from_token: input_token, to_token: target_token }.into());
+            }
+        }
 
-        // Calculate expected LP tokens
+        // Calculate expected LP tokens, optionally against the reserves left by the swaps
+        let lp_reserves = if options.project_target_reserves {
+            Self::project_target_reserves(&route_a, split_a, &route_b, split_b, target_pool_reserves, route_finder)?
+        } else {
+            target_pool_reserves.clone()
+        };
         let expected_lp_tokens = Self::calculate_expected_lp_tokens(
             expected_output_a,
             expected_output_b,
-            target_pool_reserves,
+            &lp_reserves,
         )?;
 
         // Calculate minimum LP tokens with slippage protection
@@ -193,18 +747,106 @@ impl ZapCalculator {
             slippage_tolerance_bps,
         )?;
 
-        // Calculate overall price impact
+        // Calculate overall price impact, weighting legs by value when a reference is given
+        let price_impact = match options.value_reference {
+            Some(value_reference) => Self::calculate_value_weighted_price_impact(
+                &route_a,
+                &route_b,
+                split_a,
+                split_b,
+                value_reference,
+                route_finder,
+            )?,
+            None => Self::calculate_overall_price_impact(&route_a, &route_b, split_a, split_b, route_finder)?,
+        };
+
+        let slippage_sensitivity = Self::calculate_slippage_sensitivity(expected_lp_tokens)?;
+
+        let (expected_dust_a, expected_dust_b) =
+            Self::calculate_expected_dust(expected_output_a, expected_output_b, target_token_a, &lp_reserves)?;
+        // What the pool ratio lets into the pool, as opposed to what the swaps return
+        let (expected_deposit_a, expected_deposit_b) =
+            (expected_output_a - expected_dust_a, expected_output_b - expected_dust_b);
+
+        // Routes report what their share of the split is expected to yield
+        let route_a = RouteInfo { expected_output: expected_output_a, ..route_a };
+        let route_b = RouteInfo { expected_output: expected_output_b, ..route_b };
+
+        Ok(ZapQuote::new(input_token, input_amount, target_token_a, target_token_b)
+            .with_routes(route_a, route_b)
+            .with_split(split_a, split_b)
+            .with_lp_estimate(expected_lp_tokens, minimum_lp_tokens)
+            .with_price_impact(price_impact)
+            .with_slippage_sensitivity(slippage_sensitivity)
+            .with_expected_dust(expected_dust_a, expected_dust_b)
+            .with_expected_deposits(expected_deposit_a, expected_deposit_b))
+    }
+
+    /// Quote a zap into a pair that has no pool yet, which the zap creates with its deposit.
+    ///
+    /// With no ratio to match, the input is split evenly and everything the legs return is
+    /// deposited, minting the geometric mean of the deposit less the locked
+    /// `MINIMUM_LIQUIDITY`. Neither leg may be the input itself, since the only pool that
+    /// could supply the other target is the one being created.
+    pub fn generate_new_pool_zap_quote<P: PoolProvider>(
+        params: &ZapParams,
+        route_a: RouteInfo,
+        route_b: RouteInfo,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<ZapQuote> {
+        let ZapParams {
+            input_token,
+            input_amount,
+            target_token_a,
+            target_token_b,
+            max_slippage_bps: slippage_tolerance_bps,
+            ..
+        } = *params;
+        if input_token == target_token_a || input_token == target_token_b {
+            return Err(ZapError::IdenticalTokens { token: input_token }.into());
+        }
+        let (split_a, split_b) = (input_amount / 2, input_amount - input_amount / 2);
+
+        let expected_output_a = Self::calculate_route_output(split_a, &route_a, route_finder)?;
+        let expected_output_b = Self::calculate_route_output(split_b, &route_b, route_finder)?;
+        let expected_lp_tokens = amm_logic::calculate_initial_lp_tokens(expected_output_a, expected_output_b)?;
+        let minimum_lp_tokens = Self::calculate_minimum_lp_tokens(expected_lp_tokens, slippage_tolerance_bps)?;
         let price_impact = Self::calculate_overall_price_impact(&route_a, &route_b, split_a, split_b, route_finder)?;
+        let slippage_sensitivity = Self::calculate_slippage_sensitivity(expected_lp_tokens)?;
+
+        let route_a = RouteInfo { expected_output: expected_output_a, ..route_a };
+        let route_b = RouteInfo { expected_output: expected_output_b, ..route_b };
 
         Ok(ZapQuote::new(input_token, input_amount, target_token_a, target_token_b)
             .with_routes(route_a, route_b)
             .with_split(split_a, split_b)
             .with_lp_estimate(expected_lp_tokens, minimum_lp_tokens)
-            .with_price_impact(price_impact))
+            .with_price_impact(price_impact)
+            .with_slippage_sensitivity(slippage_sensitivity)
+            .with_expected_dust(0, 0)
+            .with_expected_deposits(expected_output_a, expected_output_b))
+    }
+
+    /// Amounts of each target token that won't fit the pool ratio when adding liquidity,
+    /// and so come back to the caller as dust
+    pub fn calculate_expected_dust(
+        amount_a: u128,
+        amount_b: u128,
+        target_token_a: AlkaneId,
+        target_pool_reserves: &PoolReserves,
+    ) -> Result<(u128, u128)> {
+        let (reserve_a, reserve_b) = if target_pool_reserves.token_a == target_token_a {
+            (target_pool_reserves.reserve_a, target_pool_reserves.reserve_b)
+        } else {
+            (target_pool_reserves.reserve_b, target_pool_reserves.reserve_a)
+        };
+        let (consumed_a, consumed_b) =
+            amm_logic::quote_add_liquidity(amount_a, amount_b, reserve_a, reserve_b)?;
+        Ok((amount_a.saturating_sub(consumed_a), amount_b.saturating_sub(consumed_b)))
     }
 
     /// Calculate the actual output for a route given an input amount
-    fn calculate_route_output<P: PoolProvider>(
+    pub fn calculate_route_output<P: PoolProvider>(
         input_amount: u128,
         route: &RouteInfo,
         route_finder: &RouteFinder<P>,
@@ -221,9 +863,7 @@ impl ZapCalculator {
         for i in 0..route.path.len() - 1 {
             let token_in = route.path[i];
             let token_out = route.path[i + 1];
-            let pool = route_finder
-                .pool_provider
-                .get_pool_reserves(token_in, token_out)?;
+            let pool = Self::scoring_pool_reserves(route_finder, i, token_in, token_out)?;
 
             let (reserve_in, reserve_out) = if pool.token_a == token_in {
                 (pool.reserve_a, pool.reserve_b)
@@ -231,14 +871,146 @@ impl ZapCalculator {
                 (pool.reserve_b, pool.reserve_a)
             };
 
-            current_amount = amm_logic::calculate_swap_out(current_amount, reserve_in, reserve_out, pool.fee_rate)?;
+            current_amount = amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, pool.fee_for(token_in), pool.curve)?;
+        }
+
+        Ok(current_amount)
+    }
+
+    /// LP tokens a quoted zap mints if an adversary swaps `adversary_amount` through each
+    /// leg's first pool just before the user does.
+    ///
+    /// The adversary's swap moves that pool's reserves, the user's split is priced against
+    /// what is left and the remaining hops are priced as usual. Legs that need no swap are
+    /// unaffected. The result is a floor for `min_lp_tokens` under adversarial ordering.
+    pub fn calculate_worst_case_lp_tokens<P: PoolProvider>(
+        quote: &ZapQuote,
+        target_pool_reserves: &PoolReserves,
+        adversary_amount: u128,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<u128> {
+        let output_a =
+            Self::calculate_front_run_route_output(quote.split_amount_a, &quote.route_a, adversary_amount, route_finder)?;
+        let output_b =
+            Self::calculate_front_run_route_output(quote.split_amount_b, &quote.route_b, adversary_amount, route_finder)?;
+        Self::calculate_expected_lp_tokens(output_a, output_b, target_pool_reserves)
+    }
+
+    /// Route output after an adversary front-runs the first hop with `adversary_amount`
+    fn calculate_front_run_route_output<P: PoolProvider>(
+        input_amount: u128,
+        route: &RouteInfo,
+        adversary_amount: u128,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<u128> {
+        if route.path.is_empty() {
+            return Err(anyhow!("Route path cannot be empty"));
+        }
+
+        let mut current_amount = input_amount;
+        for (i, hop) in route.path.windows(2).enumerate() {
+            let (token_in, token_out) = (hop[0], hop[1]);
+            let pool = Self::scoring_pool_reserves(route_finder, i, token_in, token_out)?;
+            let fee = pool.fee_for(token_in);
+            let (mut reserve_in, mut reserve_out) = if pool.token_a == token_in {
+                (pool.reserve_a, pool.reserve_b)
+            } else {
+                (pool.reserve_b, pool.reserve_a)
+            };
+
+            if i == 0 && adversary_amount > 0 {
+                let adversary_out =
+                    amm_logic::calculate_swap_out_for_curve(adversary_amount, reserve_in, reserve_out, fee, pool.curve)?;
+                reserve_in = reserve_in.saturating_add(adversary_amount);
+                reserve_out = reserve_out.saturating_sub(adversary_out);
+            }
+
+            current_amount = amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, fee, pool.curve)?;
         }
 
         Ok(current_amount)
     }
 
+    /// Project the target pool reserves after both routes' swaps have executed.
+    ///
+    /// Hops that trade against the target pool move its reserves before liquidity is added;
+    /// all other hops only determine the amount flowing into the next hop.
+    pub fn project_target_reserves<P: PoolProvider>(
+        route_a: &RouteInfo,
+        split_a: u128,
+        route_b: &RouteInfo,
+        split_b: u128,
+        target_pool_reserves: &PoolReserves,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<PoolReserves> {
+        let mut projected = target_pool_reserves.clone();
+
+        for (route, amount_in) in [(route_a, split_a), (route_b, split_b)] {
+            let mut current_amount = amount_in;
+            for (i, hop) in route.path.windows(2).enumerate() {
+                let (token_in, token_out) = (hop[0], hop[1]);
+                let is_target_pool = (token_in == projected.token_a && token_out == projected.token_b)
+                    || (token_in == projected.token_b && token_out == projected.token_a);
+
+                if is_target_pool {
+                    let (reserve_in, reserve_out) = if projected.token_a == token_in {
+                        (projected.reserve_a, projected.reserve_b)
+                    } else {
+                        (projected.reserve_b, projected.reserve_a)
+                    };
+                    let amount_out =
+                        amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, projected.fee_for(token_in), projected.curve)?;
+
+                    if projected.token_a == token_in {
+                        projected.reserve_a = projected.reserve_a.saturating_add(current_amount);
+                        projected.reserve_b = projected.reserve_b.saturating_sub(amount_out);
+                    } else {
+                        projected.reserve_b = projected.reserve_b.saturating_add(current_amount);
+                        projected.reserve_a = projected.reserve_a.saturating_sub(amount_out);
+                    }
+                    current_amount = amount_out;
+                } else {
+                    let pool = Self::scoring_pool_reserves(route_finder, i, token_in, token_out)?;
+                    let (reserve_in, reserve_out) = if pool.token_a == token_in {
+                        (pool.reserve_a, pool.reserve_b)
+                    } else {
+                        (pool.reserve_b, pool.reserve_a)
+                    };
+                    current_amount =
+                        amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, pool.fee_for(token_in), pool.curve)?;
+                }
+            }
+        }
+
+        Ok(projected)
+    }
+
+    /// Fetch reserves for a hop of an already-discovered route.
+    ///
+    /// A pool that resolved during route discovery but fails here has become unavailable
+    /// between discovery and scoring, so the error names the hop to let clients retry.
+    fn scoring_pool_reserves<P: PoolProvider>(
+        route_finder: &RouteFinder<P>,
+        hop: usize,
+        token_in: AlkaneId,
+        token_out: AlkaneId,
+    ) -> Result<PoolReserves> {
+        route_finder
+            .pool_provider
+            .get_pool_reserves(token_in, token_out)
+            .map_err(|e| {
+                anyhow!(
+                    "Pool for hop {} ({:?} -> {:?}) became unavailable during scoring: {}",
+                    hop,
+                    token_in,
+                    token_out,
+                    e
+                )
+            })
+    }
+
     /// Calculate overall price impact from both routes
-    fn calculate_overall_price_impact<P: PoolProvider>(
+    pub fn calculate_overall_price_impact<P: PoolProvider>(
         route_a: &RouteInfo,
         route_b: &RouteInfo,
         split_a: u128,
@@ -258,7 +1030,53 @@ impl ZapCalculator {
         let weighted_impact_b = U256::from(impact_b) * U256::from(split_b) / total_input;
         
         let total_impact = weighted_impact_a + weighted_impact_b;
-        Ok(total_impact.try_into().map_err(|_| anyhow!("Price impact amount exceeds u128"))?)
+        total_impact.try_into().map_err(|_| anyhow!("Price impact amount exceeds u128"))
+    }
+
+    /// Overall price impact with each leg weighted by the value of its output in
+    /// `value_reference`, priced with `RouteFinder::spot_price`.
+    ///
+    /// Raw amounts weight a leg into a cheap token the same as one into a dear token when
+    /// they move the same number of units; valuing both in one token compares like with like.
+    pub fn calculate_value_weighted_price_impact<P: PoolProvider>(
+        route_a: &RouteInfo,
+        route_b: &RouteInfo,
+        split_a: u128,
+        split_b: u128,
+        value_reference: AlkaneId,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<u128> {
+        let value_a = Self::calculate_leg_value(split_a, route_a, value_reference, route_finder)?;
+        let value_b = Self::calculate_leg_value(split_b, route_b, value_reference, route_finder)?;
+        let total_value = value_a + value_b;
+        if total_value.is_zero() {
+            return Ok(0);
+        }
+
+        let impact_a = Self::calculate_route_price_impact(split_a, route_a, route_finder)?;
+        let impact_b = Self::calculate_route_price_impact(split_b, route_b, route_finder)?;
+
+        let total_impact = (U256::from(impact_a) * value_a + U256::from(impact_b) * value_b) / total_value;
+        total_impact.try_into().map_err(|_| anyhow!("Price impact amount exceeds u128"))
+    }
+
+    /// Value of a leg's output in `value_reference`; a leg into the reference is worth its output
+    fn calculate_leg_value<P: PoolProvider>(
+        input_amount: u128,
+        route: &RouteInfo,
+        value_reference: AlkaneId,
+        route_finder: &RouteFinder<P>,
+    ) -> Result<U256> {
+        if input_amount == 0 {
+            return Ok(U256::from(0));
+        }
+        let output = Self::calculate_route_output(input_amount, route, route_finder)?;
+        let output_token = *route.path.last().ok_or_else(|| anyhow!("Route path cannot be empty"))?;
+        if output_token == value_reference {
+            return Ok(U256::from(output));
+        }
+        let price = route_finder.spot_price(output_token, value_reference)?;
+        Ok(U256::from(output) * U256::from(price) / U256::from(PRICE_PRECISION))
     }
 
     fn calculate_route_price_impact<P: PoolProvider>(
@@ -272,7 +1090,7 @@ impl ZapCalculator {
         for i in 0..route.path.len() - 1 {
             let token_in = route.path[i];
             let token_out = route.path[i + 1];
-            let pool = route_finder.pool_provider.get_pool_reserves(token_in, token_out)?;
+            let pool = Self::scoring_pool_reserves(route_finder, i, token_in, token_out)?;
 
             let (reserve_in, reserve_out) = if pool.token_a == token_in {
                 (pool.reserve_a, pool.reserve_b)
@@ -280,13 +1098,17 @@ impl ZapCalculator {
                 (pool.reserve_b, pool.reserve_a)
             };
 
-            let amount_out = amm_logic::calculate_swap_out(current_amount, reserve_in, reserve_out, pool.fee_rate)?;
+            let amount_out = amm_logic::calculate_swap_out_for_curve(current_amount, reserve_in, reserve_out, pool.fee_for(token_in), pool.curve)?;
             let impact = amm_logic::calculate_price_impact(current_amount, reserve_in, amount_out, reserve_out)?;
             total_impact += U256::from(impact);
             current_amount = amount_out;
         }
 
-        Ok(total_impact.try_into()?)
+        // Hop impacts add up, but a route can lose no more than everything
+        total_impact
+            .min(U256::from(BASIS_POINTS))
+            .try_into()
+            .map_err(|_| anyhow!("Price impact exceeds u128"))
     }
 
     /// Validate that a zap quote is reasonable
@@ -460,4 +1282,41 @@ mod tests {
         assert!(quote.minimum_lp_tokens > 0);
         assert!(quote.minimum_lp_tokens <= quote.expected_lp_tokens);
     }
+
+    #[test]
+    fn test_pool_unavailable_during_scoring() {
+        let input_token = AlkaneId { block: 1, tx: 1 };
+        let target_token_a = AlkaneId { block: 2, tx: 2 };
+        let target_token_b = AlkaneId { block: 3, tx: 3 };
+        let pool_reserves = create_mock_pool_reserves();
+        let factory_id = AlkaneId { block: 1, tx: 0 };
+
+        // Discover the route while the pool is available
+        let mut pools = HashMap::new();
+        pools.insert((input_token, target_token_a), pool_reserves.clone());
+        let discovery_provider = MockPoolProvider { pools };
+        let route_a = RouteFinder::new(factory_id, &discovery_provider)
+            .find_best_route(input_token, target_token_a, 1000)
+            .unwrap();
+        let route_b = RouteInfo::new(vec![input_token], 1000);
+
+        // The pool disappears before the quote is scored
+        let scoring_provider = MockPoolProvider { pools: HashMap::new() };
+        let route_finder = RouteFinder::new(factory_id, &scoring_provider);
+        let result = ZapCalculator::generate_zap_quote(
+            input_token,
+            1000,
+            target_token_a,
+            target_token_b,
+            route_a,
+            route_b,
+            &pool_reserves,
+            500,
+            &route_finder,
+        );
+
+        let err = result.unwrap_err().to_string();
+        assert!(err.contains("hop 0"), "unexpected error: {}", err);
+        assert!(err.contains("became unavailable during scoring"), "unexpected error: {}", err);
+    }
 }
@@ -19,35 +19,32 @@ This change directly addresses the core issue identified in the project summary:
 
 use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cell::{Cell, RefCell};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
-use oyl_zap_core::types::{ZapQuote, RouteInfo, PoolReserves, U256};
+use oyl_zap_core::types::{encode_token_list, ZapParams, DEFAULT_FEE_BPS, ensure_distinct_pair, ensure_distinct_tokens, is_deadline_passed, FactorySwapLayout, HopBreakdown, LpValueEstimate, QuoteOptions, ZapQuote, MAX_QUOTE_BATCH, MAX_TOKEN_DECIMALS, ZapError, RouteInfo, PoolReserves, CurveKind, QuoteRecord, StoredPool, ZapConfig, ZapReceipt, ZapSimulation, U256, BASIS_POINTS, MINIMUM_LIQUIDITY, QUOTE_HISTORY_LENGTH};
 use oyl_zap_core::route_finder::RouteFinder;
 use oyl_zap_core::zap_calculator::ZapCalculator;
-use oyl_zap_core::pool_provider::PoolProvider;
+use oyl_zap_core::pool_provider::{CachedPoolProvider, FlashSwapPoolProvider, PoolProvider};
 use oyl_zap_core::amm_logic;
+use oyl_zap_core::{canonical_pool_key, ReentrancyGuard, ZapBase, consumed_liquidity_amounts, contract_version, is_unroutable, received_amount, ReentrancyLock};
 use alkanes_support::id::AlkaneId;
 use alkanes_support::parcel::{AlkaneTransfer, AlkaneTransferParcel};
 use alkanes_support::context::Context;
 use alkanes_support::response::CallResponse;
+use alkanes_runtime::auth::AuthenticatedResponder;
+use alkanes_runtime::runtime::AlkaneResponder;
 use alkanes_runtime::storage::StoragePointer;
 
-/// Helper to create a canonical key for a token pair, ensuring consistent ordering.
-fn get_canonical_key(token_a: AlkaneId, token_b: AlkaneId) -> (AlkaneId, AlkaneId) {
-    // AlkaneId does not derive Ord, so we compare its fields directly.
-    if (token_a.block, token_a.tx) < (token_b.block, token_b.tx) {
-        (token_a, token_b)
-    } else {
-        (token_b, token_a)
-    }
-}
-
 // Test configuration constants
 pub const TEST_PRECISION: u128 = 1_000_000_000_000_000_000;
 pub const TEST_FEE_RATE: u128 = 50; // 0.5% in basis points
 pub const MAX_PRICE_IMPACT: u128 = 5000; // 50% in basis points
 pub const DEFAULT_SLIPPAGE: u128 = 500; // 5% in basis points
 
+/// `/route_cache/{from}/{to}/{height}` entries: the best route found for a pair in a block
+pub type RouteCache = HashMap<(AlkaneId, AlkaneId, u64), RouteInfo>;
+
 // Common test helper functions
 pub fn assert_within_tolerance(actual: u128, expected: u128, tolerance_bps: u128) {
     let tolerance = expected * tolerance_bps / 10000;
@@ -157,12 +154,12 @@ pub fn create_mock_zap() -> MockOylZap {
     MockOylZap::new()
 }
 
-/// Mock OYL Zap implementation for testing
+/// Mock OYL Zap implementation for testing. Quotes and executions run the contract's own
+/// `ZapBase` logic through a `TestZap` over a copy of the factory.
 #[derive(Clone)]
 pub struct MockOylZap {
     pub factory_id: AlkaneId,
     pub base_tokens: Vec<AlkaneId>,
-    pub max_price_impact: u128,
     pub default_slippage: u128,
     pub factory: MockOylFactory,
 }
@@ -170,40 +167,37 @@ pub struct MockOylZap {
 impl MockOylZap {
     pub fn new() -> Self {
         let (factory, base_tokens) = setup_test_environment();
-        Self {
-            factory_id: alkane_id("oyl_factory"),
-            base_tokens,
-            max_price_impact: MAX_PRICE_IMPACT,
-            default_slippage: DEFAULT_SLIPPAGE,
-            factory,
-        }
+        Self::with_factory(factory, base_tokens)
     }
-    
+
     pub fn with_comprehensive_setup() -> Self {
-        let (factory, token_map) = setup_comprehensive_test_environment();
-        let base_tokens = vec![
-            token_map["WBTC"],
-            token_map["ETH"],
-            token_map["USDC"],
-            token_map["USDT"],
-            token_map["DAI"],
-        ];
-        
+        let (factory, base_tokens) = comprehensive_environment();
+        Self::with_factory(factory, base_tokens)
+    }
+
+    pub fn with_factory(factory: MockOylFactory, base_tokens: Vec<AlkaneId>) -> Self {
         Self {
             factory_id: alkane_id("oyl_factory"),
             base_tokens,
-            max_price_impact: MAX_PRICE_IMPACT,
             default_slippage: DEFAULT_SLIPPAGE,
             factory,
         }
     }
-    
+
     pub fn init_zap(&mut self, factory_id: AlkaneId, base_tokens: Vec<AlkaneId>) -> Result<()> {
         self.factory_id = factory_id;
         self.base_tokens = base_tokens;
         Ok(())
     }
-    
+
+    /// The contract over this mock's factory and configuration
+    pub fn contract(&self) -> TestZap {
+        let mut zap = TestZap::with_factory(self.factory.clone(), self.base_tokens.clone());
+        zap.factory_id = self.factory_id;
+        zap.default_slippage = self.default_slippage;
+        zap
+    }
+
     pub fn get_zap_quote(
         &self,
         input_token: AlkaneId,
@@ -212,85 +206,16 @@ impl MockOylZap {
         target_token_b: AlkaneId,
         max_slippage_bps: u128,
     ) -> Result<ZapQuote> {
-        // Find routes to both target tokens, handling direct contributions and excluding the other target token
-        // from the path to prevent the route from cannibalizing the liquidity of its sibling target pool.
-        let route_a = if input_token == target_token_a {
-            Ok(RouteInfo::new(vec![input_token], input_amount / 2))
-        } else {
-            RouteFinder::new(self.factory_id, &self.factory)
-                .with_base_tokens(self.base_tokens.clone())
-                .with_excluded_intermediate_tokens(&[target_token_b])
-                .find_best_route(input_token, target_token_a, input_amount / 2)
-        }?;
-
-        let route_b = if input_token == target_token_b {
-            Ok(RouteInfo::new(vec![input_token], input_amount / 2))
-        } else {
-            RouteFinder::new(self.factory_id, &self.factory)
-                .with_base_tokens(self.base_tokens.clone())
-                .with_excluded_intermediate_tokens(&[target_token_a])
-                .find_best_route(input_token, target_token_b, input_amount / 2)
-        }?;
-        
-        // Get target pool reserves
-        let target_pool = self.factory.get_pool(target_token_a, target_token_b)
-            .ok_or_else(|| anyhow::anyhow!("Target pool not found"))?;
-        
-        let target_pool_reserves = PoolReserves::new(
-            target_token_a,
-            target_token_b,
-            target_pool.reserve_a,
-            target_pool.reserve_b,
-            target_pool.total_supply,
-            target_pool.fee_rate,
-        );
-        
-        // Generate quote
-        ZapCalculator::generate_zap_quote(
-            input_token,
-            input_amount,
-            target_token_a,
-            target_token_b,
-            route_a,
-            route_b,
-            &target_pool_reserves,
-            max_slippage_bps,
-            // The route_finder used here is for post-calculation checks, so a generic one is fine.
-            &RouteFinder::new(self.factory_id, &self.factory),
-        )
+        self.contract()
+            .quote_zap_via(&self.factory, input_token, input_amount, target_token_a, target_token_b, max_slippage_bps)
     }
-    
-    pub fn execute_zap(&mut self, quote: &ZapQuote) -> Result<u128> {
-        // Clone the factory to create an isolated environment for this zap execution.
-        // This prevents race conditions where the execution of one route affects the other.
-        let mut execution_factory = self.factory.clone();
-
-        // Step 1: Execute swaps for both routes within the isolated factory.
-        let amount_a_received =
-            Self::simulate_route_execution_static(&mut execution_factory, &quote.route_a, quote.split_amount_a)?;
-        let amount_b_received =
-            Self::simulate_route_execution_static(&mut execution_factory, &quote.route_b, quote.split_amount_b)?;
-
-        // Step 2: Add liquidity to the target pool within the isolated factory.
-        let target_pool = execution_factory
-            .get_pool_mut(quote.target_token_a, quote.target_token_b)
-            .ok_or_else(|| anyhow::anyhow!("Target pool not found in execution factory"))?;
-        
-        let lp_tokens = target_pool.simulate_add_liquidity(amount_a_received, amount_b_received)?;
-
-        // Step 3: Atomically update the main factory state with the result of the execution.
-        self.factory = execution_factory;
-
-        // Step 4: Verify minimum LP tokens.
-        if lp_tokens < quote.minimum_lp_tokens {
-            return Err(anyhow::anyhow!(
-                "Received {} LP tokens, less than minimum {}",
-                lp_tokens,
-                quote.minimum_lp_tokens
-            ));
-        }
 
-        Ok(lp_tokens)
+    /// Execute `quote` along its own routes and split, returning the LP tokens minted
+    pub fn execute_zap(&mut self, quote: &ZapQuote) -> Result<u128> {
+        let zap = self.contract();
+        let receipt = zap.execute(quote)?;
+        self.factory = zap.state.into_inner().factory;
+        Ok(receipt.lp_minted)
     }
 
     // Refactored to be a static method to make data flow explicit and support isolated execution.
@@ -314,12 +239,12 @@ impl MockOylZap {
 
         Ok(current_amount)
     }
-    
+
     // Keep the old instance method for compatibility or specific tests if needed, but delegate.
     fn simulate_route_execution(&mut self, route: &RouteInfo, amount_in: u128) -> Result<u128> {
         Self::simulate_route_execution_static(&mut self.factory, route, amount_in)
     }
-    
+
     pub fn find_optimal_route(
         &self,
         from_token: AlkaneId,
@@ -328,7 +253,7 @@ impl MockOylZap {
     ) -> Result<RouteInfo> {
         let route_finder = RouteFinder::new(self.factory_id, &self.factory)
             .with_base_tokens(self.base_tokens.clone());
-        
+
         route_finder.find_best_route(from_token, to_token, amount)
     }
 }
@@ -339,6 +264,612 @@ impl Default for MockOylZap {
     }
 }
 
+/// The comprehensive pool network with its majors as base tokens
+fn comprehensive_environment() -> (MockOylFactory, Vec<AlkaneId>) {
+    let (factory, token_map) = setup_comprehensive_test_environment();
+    let base_tokens = ["WBTC", "ETH", "USDC", "USDT", "DAI"].iter().map(|name| token_map[*name]).collect();
+    (factory, base_tokens)
+}
+
+/// Everything an opcode can write, snapshotted so a failed call reverts as it would on chain
+#[derive(Clone, Default)]
+pub struct TestZapState {
+    pub factory: MockOylFactory,
+    pub balances: HashMap<AlkaneId, u128>, // the zap's own holdings
+    pub stored_pools: HashMap<(AlkaneId, AlkaneId), StoredPool>, // registered through AddPool
+    pub pool_update_heights: HashMap<(AlkaneId, AlkaneId), u64>, // height each stored pool was last written
+    pub adjacency: HashMap<AlkaneId, Vec<AlkaneId>>, // `/adjacency/` counterparties of stored pools
+    pub ratio_history: HashMap<AlkaneId, Vec<U256>>, // `/ratio_history/` per pool id
+    pub lp_token_ids: HashMap<(AlkaneId, AlkaneId), AlkaneId>, // canonical pair -> LP token registry
+    pub lp_token_pairs: HashMap<AlkaneId, (AlkaneId, AlkaneId)>, // LP token -> canonical pair
+    pub route_cache: RouteCache,
+    pub quote_history: Vec<QuoteRecord>, // `/quote_history/` ring buffer, oldest first
+    pub locked: bool, // `/locked` reentrancy flag
+}
+
+/// `ZapBase` test double: the contract's opcode logic over in-memory storage, balances and a
+/// `MockOylFactory` standing in for the factory and pool contracts it calls. Configuration
+/// the owner setters would write is held in plain fields.
+pub struct TestZap {
+    pub factory_id: AlkaneId,
+    pub base_tokens: Vec<AlkaneId>,
+    pub myself: AlkaneId,
+    pub owner: AlkaneId, // caller `only_owner` accepts, in place of the auth token check
+    pub user: AlkaneId, // caller of `dispatch`
+    pub height: u64,
+    pub fuel: u64,
+    pub swap_layout: FactorySwapLayout, // probed from the factory version at init
+    pub default_slippage: u128,
+    pub min_lp_safety_margin_bps: u128,
+    pub max_ratio_volatility_bps: u128,
+    pub max_reserve_deviation_bps: u128,
+    pub max_reserve_k_drop_bps: u128,
+    pub max_zap_input: u128,
+    pub paused: bool,
+    pub reference_prices: HashMap<(AlkaneId, AlkaneId), (u128, u128)>, // (price, tolerance_bps)
+    pub fee_on_transfer_tokens: HashSet<AlkaneId>, // owner-flagged through SetFeeOnTransfer
+    pub token_decimals: HashMap<AlkaneId, u8>, // recorded through SetTokenDecimals
+    pub flash_swap_fee_bps: Option<u128>, // set through SetFlashSwapConfig
+    pub transfer_fee_bps: HashMap<AlkaneId, u128>, // share of each transfer a token burns
+    pub state: RefCell<TestZapState>,
+    pub calls: RefCell<Vec<(AlkaneId, u128)>>, // target and opcode of every call and staticcall made
+    context: RefCell<Context>,
+}
+
+impl TestZap {
+    pub fn new() -> Self {
+        let (factory, base_tokens) = setup_test_environment();
+        Self::with_factory(factory, base_tokens)
+    }
+
+    pub fn with_comprehensive_setup() -> Self {
+        let (factory, base_tokens) = comprehensive_environment();
+        Self::with_factory(factory, base_tokens)
+    }
+
+    pub fn with_factory(factory: MockOylFactory, base_tokens: Vec<AlkaneId>) -> Self {
+        let myself = alkane_id("zap_contract");
+        Self {
+            factory_id: alkane_id("oyl_factory"),
+            base_tokens,
+            myself,
+            owner: alkane_id("OWNER"),
+            user: alkane_id("USER"),
+            height: 0,
+            fuel: u64::MAX,
+            swap_layout: FactorySwapLayout::from_version(factory.version),
+            default_slippage: DEFAULT_SLIPPAGE,
+            min_lp_safety_margin_bps: 0,
+            max_ratio_volatility_bps: 0,
+            max_reserve_deviation_bps: 0,
+            max_reserve_k_drop_bps: 0,
+            max_zap_input: 0,
+            paused: false,
+            reference_prices: HashMap::new(),
+            fee_on_transfer_tokens: HashSet::new(),
+            token_decimals: HashMap::new(),
+            flash_swap_fee_bps: None,
+            transfer_fee_bps: HashMap::new(),
+            state: RefCell::new(TestZapState { factory, ..TestZapState::default() }),
+            calls: RefCell::new(Vec::new()),
+            context: RefCell::new(Context { myself, ..Context::default() }),
+        }
+    }
+
+    /// Run an opcode handler as the user with `incoming` attached
+    pub fn dispatch<F>(&self, incoming: Vec<AlkaneTransfer>, opcode: F) -> Result<CallResponse>
+    where
+        F: FnOnce(&Self) -> Result<CallResponse>,
+    {
+        self.dispatch_as(self.user, incoming, opcode)
+    }
+
+    /// Run an owner-gated opcode handler as the owner, with nothing attached
+    pub fn dispatch_as_owner<F>(&self, opcode: F) -> Result<CallResponse>
+    where
+        F: FnOnce(&Self) -> Result<CallResponse>,
+    {
+        self.dispatch_as(self.owner, vec![], opcode)
+    }
+
+    /// Run an opcode handler as the runtime would: `incoming` is credited to the zap for the
+    /// call, the response's alkanes are paid out to the caller, and a failed call reverts
+    /// every write it made
+    pub fn dispatch_as<F>(&self, caller: AlkaneId, incoming: Vec<AlkaneTransfer>, opcode: F) -> Result<CallResponse>
+    where
+        F: FnOnce(&Self) -> Result<CallResponse>,
+    {
+        let snapshot = self.state.borrow().clone();
+        let incoming: Vec<AlkaneTransfer> = incoming
+            .into_iter()
+            .map(|transfer| AlkaneTransfer { id: transfer.id, value: self.after_transfer_fee(transfer.id, transfer.value) })
+            .collect();
+        for transfer in &incoming {
+            self.credit(transfer.id, transfer.value);
+        }
+        *self.context.borrow_mut() = Context {
+            myself: self.myself,
+            caller,
+            incoming_alkanes: AlkaneTransferParcel(incoming),
+            ..Context::default()
+        };
+        let result = opcode(self).and_then(|response| {
+            for transfer in &response.alkanes.0 {
+                self.debit(transfer.id, transfer.value)?;
+            }
+            Ok(response)
+        });
+        if result.is_err() {
+            *self.state.borrow_mut() = snapshot;
+        }
+        result
+    }
+
+    /// Dispatch `ExecuteZap` with `input_amount` of `input_token` attached and no LP minimum
+    /// or deadline, returning the receipt
+    pub fn execute_zap_for(
+        &self,
+        input_token: AlkaneId,
+        input_amount: u128,
+        target_token_a: AlkaneId,
+        target_token_b: AlkaneId,
+        max_slippage_bps: u128,
+    ) -> Result<ZapReceipt> {
+        let input = AlkaneTransfer { id: input_token, value: input_amount };
+        let response = self.dispatch(vec![input], |zap| {
+            zap.execute_zap(input_token, input_amount, target_token_a, target_token_b, 0, 0, max_slippage_bps, 0, 0, 0)
+        })?;
+        ZapReceipt::decode(&response.data)
+    }
+
+    /// Execute `quote` along its own routes and split, as `ExecuteZap` does with the quote it
+    /// computes, returning the receipt
+    pub fn execute(&self, quote: &ZapQuote) -> Result<ZapReceipt> {
+        let params = ZapParams::new(
+            quote.input_token,
+            quote.input_amount,
+            quote.target_token_a,
+            quote.target_token_b,
+            quote.minimum_lp_tokens,
+            0,
+        )
+        .with_max_slippage(self.default_slippage);
+        let input = AlkaneTransfer { id: quote.input_token, value: quote.input_amount };
+        let response = self.dispatch(vec![input], |zap| {
+            zap.ensure_not_paused()?;
+            let _lock = ReentrancyGuard::acquire(zap)?;
+            zap.zap_input(&params, Some(quote.clone()), None, 0, 0)
+        })?;
+        ZapReceipt::decode(&response.data)
+    }
+
+    /// Current state of the factory pool for a pair
+    pub fn pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Option<MockPool> {
+        self.state.borrow().factory.get_pool(token_a, token_b).cloned()
+    }
+
+    /// Change the factory pool for a pair outside any zap call, as other traders would
+    pub fn with_pool<R>(&self, token_a: AlkaneId, token_b: AlkaneId, change: impl FnOnce(&mut MockPool) -> R) -> R {
+        let mut state = self.state.borrow_mut();
+        change(state.factory.get_pool_mut(token_a, token_b).expect("pool exists"))
+    }
+
+    /// The zap's own balance of `token`
+    pub fn held(&self, token: AlkaneId) -> u128 {
+        self.state.borrow().balances.get(&token).copied().unwrap_or(0)
+    }
+
+    /// Tokens that reach the zap outside any call
+    pub fn receive_out_of_band(&self, token: AlkaneId, amount: u128) {
+        self.credit(token, amount);
+    }
+
+    /// Calls and staticcalls made so far with `opcode`
+    pub fn calls_to(&self, opcode: u128) -> usize {
+        self.calls.borrow().iter().filter(|(_, called)| *called == opcode).count()
+    }
+
+    fn trace(&self, target: AlkaneId, opcode: u128) {
+        self.calls.borrow_mut().push((target, opcode));
+    }
+
+    fn credit(&self, token: AlkaneId, amount: u128) {
+        *self.state.borrow_mut().balances.entry(token).or_insert(0) += amount;
+    }
+
+    fn debit(&self, token: AlkaneId, amount: u128) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        let held = state.balances.entry(token).or_insert(0);
+        if *held < amount {
+            return Err(anyhow!("Zap holds {} of {:?}, cannot transfer {}", held, token, amount));
+        }
+        *held -= amount;
+        Ok(())
+    }
+
+    /// What arrives when `amount` of `token` is transferred
+    fn after_transfer_fee(&self, token: AlkaneId, amount: u128) -> u128 {
+        let fee_bps = self.transfer_fee_bps.get(&token).copied().unwrap_or(0);
+        amount - amount * fee_bps / BASIS_POINTS
+    }
+
+    /// Send `amount` of the zap's `token` to the factory, returning what arrives there
+    fn send(&self, token: AlkaneId, amount: u128) -> Result<u128> {
+        self.debit(token, amount)?;
+        Ok(self.after_transfer_fee(token, amount))
+    }
+
+    /// Credit the zap with each transfer of a factory response, rewritten to what arrived
+    fn receive(&self, mut response: CallResponse) -> CallResponse {
+        for transfer in &mut response.alkanes.0 {
+            transfer.value = self.after_transfer_fee(transfer.id, transfer.value);
+            self.credit(transfer.id, transfer.value);
+        }
+        response
+    }
+
+    fn pool_by_id(&self, pool_id: AlkaneId) -> Option<MockPool> {
+        self.state.borrow().factory.pools.values().find(|pool| pool.id == pool_id).cloned()
+    }
+
+    /// The factory's exact-input swap as `execute_swap` sends it, in the probed layout. The
+    /// factory reverts when the output falls short of the minimum.
+    fn factory_swap(&self, factory: &mut MockOylFactory, path: &[AlkaneId], amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
+        let inputs = self.swap_layout.swap_inputs(path, amount_in, amount_out_min, deadline);
+        self.trace(self.factory_id, inputs[0]);
+        let arrived = self.after_transfer_fee(path[0], amount_in);
+        let (path, amount_in, amount_out_min, deadline) = factory.decode_swap_inputs(&inputs, arrived)?;
+        if amount_in > arrived {
+            return Err(anyhow!("Swap of {} exceeds the {} received", amount_in, arrived));
+        }
+        if is_deadline_passed(deadline, u128::from(self.height)) {
+            return Err(anyhow!("Swap deadline passed"));
+        }
+        let mut amount_out = amount_in;
+        for hop in path.windows(2) {
+            let pool = factory
+                .get_pool_mut(hop[0], hop[1])
+                .ok_or(ZapError::PoolNotFound { token_a: hop[0], token_b: hop[1] })?;
+            amount_out = pool.simulate_swap(hop[0], amount_out)?;
+        }
+        if amount_out < amount_out_min {
+            return Err(anyhow!("Swap output {} is below the minimum {}", amount_out, amount_out_min));
+        }
+        let mut response = CallResponse::default();
+        response.alkanes.0.push(AlkaneTransfer { id: path[path.len() - 1], value: amount_out });
+        Ok(response)
+    }
+}
+
+impl Default for TestZap {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl AlkaneResponder for TestZap {
+    fn context(&self) -> Result<Context> {
+        Ok(self.context.borrow().clone())
+    }
+
+    fn height(&self) -> u64 {
+        self.height
+    }
+
+    fn fuel(&self) -> u64 {
+        self.fuel
+    }
+
+    fn balance(&self, who: &AlkaneId, what: &AlkaneId) -> u128 {
+        if *who == self.myself {
+            self.held(*what)
+        } else {
+            0
+        }
+    }
+}
+
+impl AuthenticatedResponder for TestZap {
+    fn only_owner(&self) -> Result<()> {
+        if self.context.borrow().caller != self.owner {
+            return Err(anyhow!("Only the owner can call this opcode"));
+        }
+        Ok(())
+    }
+}
+
+impl ReentrancyLock for TestZap {
+    fn is_locked(&self) -> Result<bool> {
+        Ok(self.state.borrow().locked)
+    }
+
+    fn set_locked(&self, locked: bool) -> Result<()> {
+        self.state.borrow_mut().locked = locked;
+        Ok(())
+    }
+}
+
+impl ZapBase for TestZap {
+    fn calculate_swap_output(&self, amount_in: u128, reserve_in: u128, reserve_out: u128, fee_rate: u128) -> Result<u128> {
+        if amount_in == 0 || reserve_in == 0 || reserve_out == 0 {
+            return Ok(0);
+        }
+        amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, fee_rate)
+    }
+
+    fn execute_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
+        self.debit(path[0], amount_in)?;
+        let mut factory = self.state.borrow().factory.clone();
+        let response = self.factory_swap(&mut factory, &path, amount_in, amount_out_min, deadline)?;
+        self.state.borrow_mut().factory = factory;
+        Ok(self.receive(response))
+    }
+
+    fn simulate_swap(&self, path: Vec<AlkaneId>, amount_in: u128, amount_out_min: u128, deadline: u128) -> Result<CallResponse> {
+        let mut factory = self.state.borrow().factory.clone();
+        self.factory_swap(&mut factory, &path, amount_in, amount_out_min, deadline)
+    }
+
+    fn add_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128, amount_a_min: u128, amount_b_min: u128, _deadline: u128) -> Result<CallResponse> {
+        self.trace(self.factory_id, 11);
+        let arrived_a = self.send(token_a, amount_a)?;
+        let arrived_b = self.send(token_b, amount_b)?;
+        let response = {
+            let mut state = self.state.borrow_mut();
+            let pool = state.factory.get_pool_mut(token_a, token_b)
+                .ok_or(ZapError::PoolNotFound { token_a, token_b })?;
+            let (reserve_a, reserve_b) = if pool.token_a == token_a {
+                (pool.reserve_a, pool.reserve_b)
+            } else {
+                (pool.reserve_b, pool.reserve_a)
+            };
+            let (consumed_a, consumed_b) = amm_logic::quote_add_liquidity(arrived_a, arrived_b, reserve_a, reserve_b)?;
+            if consumed_a < amount_a_min || consumed_b < amount_b_min {
+                return Err(anyhow!(
+                    "Deposit of ({}, {}) is below the minimums ({}, {})",
+                    consumed_a,
+                    consumed_b,
+                    amount_a_min,
+                    amount_b_min
+                ));
+            }
+            if pool.token_a == token_a {
+                pool.simulate_add_liquidity_at_ratio(arrived_a, arrived_b)?
+            } else {
+                pool.simulate_add_liquidity_at_ratio(arrived_b, arrived_a)?
+            }
+        };
+        Ok(self.receive(response))
+    }
+
+    fn create_pool(&self, token_a: AlkaneId, token_b: AlkaneId, amount_a: u128, amount_b: u128) -> Result<CallResponse> {
+        self.trace(self.factory_id, 1);
+        let arrived_a = self.send(token_a, amount_a)?;
+        let arrived_b = self.send(token_b, amount_b)?;
+        let lp_token = {
+            let mut state = self.state.borrow_mut();
+            if state.factory.get_pool(token_a, token_b).is_some() {
+                return Err(anyhow!("Pool already exists"));
+            }
+            let pool_id = state.factory.try_add_pool(token_a, token_b, arrived_a, arrived_b)?;
+            let pool = state.factory.get_pool(token_a, token_b).expect("pool was just created");
+            AlkaneTransfer { id: pool_id, value: pool.total_supply - MINIMUM_LIQUIDITY }
+        };
+        let mut response = CallResponse::default();
+        response.alkanes.0.push(lp_token);
+        Ok(self.receive(response))
+    }
+
+    fn add_liquidity_weighted(&self, tokens: Vec<AlkaneId>, amounts: Vec<u128>, amount_mins: Vec<u128>, _deadline: u128) -> Result<CallResponse> {
+        // The factory mints one LP token per unit deposited into a weighted pool
+        self.trace(self.factory_id, 14);
+        let mut minted = 0u128;
+        for ((token, amount), amount_min) in tokens.iter().zip(&amounts).zip(&amount_mins) {
+            let arrived = self.send(*token, *amount)?;
+            if arrived < *amount_min {
+                return Err(anyhow!("Weighted deposit of {} is below the minimum {}", arrived, amount_min));
+            }
+            minted += arrived;
+        }
+        let mut response = CallResponse::default();
+        response.alkanes.0.push(AlkaneTransfer { id: alkane_id("WEIGHTED_LP"), value: minted });
+        Ok(self.receive(response))
+    }
+
+    fn find_pool_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<AlkaneId> {
+        if let Some(pool) = self.stored_pool(token_a, token_b)? {
+            return Ok(pool.pool_id);
+        }
+        self.trace(self.factory_id, 2);
+        self.state
+            .borrow()
+            .factory
+            .get_pool(token_a, token_b)
+            .map(|pool| pool.id)
+            .ok_or_else(|| ZapError::PoolNotFound { token_a, token_b }.into())
+    }
+
+    fn pool_lp_token_id(&self, pool_id: AlkaneId) -> Result<AlkaneId> {
+        self.trace(pool_id, 1000);
+        Ok(self.pool_by_id(pool_id).map_or(pool_id, |pool| pool.lp_token_id))
+    }
+
+    fn min_lp_safety_margin_bps(&self) -> Result<u128> {
+        Ok(self.min_lp_safety_margin_bps)
+    }
+
+    fn pool_tokens(&self, pool_id: AlkaneId) -> Result<(AlkaneId, AlkaneId)> {
+        // Only pools answer PoolDetails, so an LP token id that differs from its pool's fails here
+        self.trace(pool_id, 999);
+        self.pool_by_id(pool_id)
+            .map(|pool| (pool.token_a, pool.token_b))
+            .ok_or_else(|| anyhow!("Failed to get pool tokens for {:?}", pool_id))
+    }
+
+    fn remove_liquidity(&self, token_a: AlkaneId, token_b: AlkaneId, liquidity: u128, amount_a_min: u128, amount_b_min: u128, _deadline: u128) -> Result<CallResponse> {
+        let lp_token_id = self.lp_token_id_for_pair(token_a, token_b)?;
+        self.trace(self.factory_id, 12);
+        self.debit(lp_token_id, liquidity)?;
+        let response = {
+            let mut state = self.state.borrow_mut();
+            let pool = state.factory.get_pool_mut(token_a, token_b)
+                .ok_or(ZapError::PoolNotFound { token_a, token_b })?;
+            let (withdrawn_a, withdrawn_b) = pool.simulate_remove_liquidity(liquidity)?;
+            let (amount_a, amount_b) = if pool.token_a == token_a {
+                (withdrawn_a, withdrawn_b)
+            } else {
+                (withdrawn_b, withdrawn_a)
+            };
+            if amount_a < amount_a_min || amount_b < amount_b_min {
+                return Err(anyhow!("Withdrawal of ({}, {}) is below the minimums", amount_a, amount_b));
+            }
+            let mut response = CallResponse::default();
+            response.alkanes.0.push(AlkaneTransfer { id: token_a, value: amount_a });
+            response.alkanes.0.push(AlkaneTransfer { id: token_b, value: amount_b });
+            response
+        };
+        Ok(self.receive(response))
+    }
+
+    fn pool_total_supply(&self, pool_id: AlkaneId) -> Result<u128> {
+        self.trace(pool_id, 101);
+        self.pool_by_id(pool_id)
+            .map(|pool| pool.total_supply)
+            .ok_or_else(|| anyhow!("Failed to get pool total supply"))
+    }
+
+    fn live_pool_reserves(&self, pool_id: AlkaneId, token_a: AlkaneId) -> Result<(u128, u128, Option<u128>)> {
+        self.trace(pool_id, 97);
+        let pool = self.pool_by_id(pool_id).ok_or_else(|| anyhow!("Failed to get pool reserves"))?;
+        let (pool_token_0, _) = self.pool_tokens(pool_id)?;
+        if pool_token_0 == token_a {
+            Ok((pool.reserve_a, pool.reserve_b, Some(pool.fee_rate)))
+        } else {
+            Ok((pool.reserve_b, pool.reserve_a, Some(pool.fee_rate)))
+        }
+    }
+
+    fn oyl_factory_id(&self) -> Result<AlkaneId> {
+        Ok(self.factory_id)
+    }
+
+    fn base_tokens(&self) -> Result<Vec<AlkaneId>> {
+        Ok(self.base_tokens.clone())
+    }
+
+    fn pool_ratio_history(&self, pool_id: AlkaneId) -> Result<Vec<U256>> {
+        Ok(self.state.borrow().ratio_history.get(&pool_id).cloned().unwrap_or_default())
+    }
+
+    fn record_pool_ratio(&self, pool_id: AlkaneId, ratio: U256) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        ZapCalculator::push_ratio_observation(state.ratio_history.entry(pool_id).or_default(), ratio);
+        Ok(())
+    }
+
+    fn max_ratio_volatility_bps(&self) -> Result<u128> {
+        Ok(self.max_ratio_volatility_bps)
+    }
+
+    fn max_reserve_deviation_bps(&self) -> Result<u128> {
+        Ok(self.max_reserve_deviation_bps)
+    }
+
+    fn max_reserve_k_drop_bps(&self) -> Result<u128> {
+        Ok(self.max_reserve_k_drop_bps)
+    }
+
+    fn max_zap_input(&self) -> Result<u128> {
+        Ok(self.max_zap_input)
+    }
+
+    fn default_slippage_bps(&self) -> Result<u128> {
+        Ok(self.default_slippage)
+    }
+
+    fn reference_price(&self, token_in: AlkaneId, token_out: AlkaneId) -> Result<Option<(u128, u128)>> {
+        Ok(self.reference_prices.get(&(token_in, token_out)).copied())
+    }
+
+    fn is_fee_on_transfer(&self, token: AlkaneId) -> Result<bool> {
+        Ok(self.fee_on_transfer_tokens.contains(&token))
+    }
+
+    fn token_decimals(&self, token: AlkaneId) -> Result<Option<u8>> {
+        Ok(self.token_decimals.get(&token).copied())
+    }
+
+    fn stored_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<StoredPool>> {
+        Ok(self.state.borrow().stored_pools.get(&canonical_pool_key(token_a, token_b)).cloned())
+    }
+
+    fn store_pool(&self, pool: &StoredPool) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        let pair = canonical_pool_key(pool.token_a, pool.token_b);
+        state.stored_pools.insert(pair, pool.clone());
+        state.pool_update_heights.insert(pair, self.height);
+        for (token, other) in [(pool.token_a, pool.token_b), (pool.token_b, pool.token_a)] {
+            let connected = state.adjacency.entry(token).or_default();
+            if !connected.contains(&other) {
+                connected.push(other);
+            }
+        }
+        Ok(())
+    }
+
+    fn get_connected_tokens_impl(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
+        Ok(self.state.borrow().adjacency.get(&token).cloned().unwrap_or_default())
+    }
+
+    fn flash_swap_fee_bps(&self) -> Result<Option<u128>> {
+        Ok(self.flash_swap_fee_bps)
+    }
+
+    fn registered_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId) -> Result<Option<AlkaneId>> {
+        Ok(self.state.borrow().lp_token_ids.get(&canonical_pool_key(token_a, token_b)).copied())
+    }
+
+    fn register_lp_token_id(&self, token_a: AlkaneId, token_b: AlkaneId, lp_token_id: AlkaneId) -> Result<()> {
+        let pair = canonical_pool_key(token_a, token_b);
+        let mut state = self.state.borrow_mut();
+        state.lp_token_ids.insert(pair, lp_token_id);
+        state.lp_token_pairs.insert(lp_token_id, pair);
+        Ok(())
+    }
+
+    fn registered_lp_token_pair(&self, lp_token_id: AlkaneId) -> Result<Option<(AlkaneId, AlkaneId)>> {
+        Ok(self.state.borrow().lp_token_pairs.get(&lp_token_id).copied())
+    }
+
+    fn cached_route(&self, from_token: AlkaneId, to_token: AlkaneId) -> Result<Option<RouteInfo>> {
+        Ok(self.state.borrow().route_cache.get(&(from_token, to_token, self.height)).cloned())
+    }
+
+    fn cache_route(&self, from_token: AlkaneId, to_token: AlkaneId, route: &RouteInfo) -> Result<()> {
+        self.state.borrow_mut().route_cache.insert((from_token, to_token, self.height), route.clone());
+        Ok(())
+    }
+
+    fn is_paused(&self) -> Result<bool> {
+        Ok(self.paused)
+    }
+
+    fn record_quote(&self, record: &QuoteRecord) -> Result<()> {
+        let mut state = self.state.borrow_mut();
+        state.quote_history.push(record.clone());
+        if state.quote_history.len() > QUOTE_HISTORY_LENGTH as usize {
+            state.quote_history.remove(0);
+        }
+        Ok(())
+    }
+
+    fn quote_history(&self, limit: u128) -> Result<Vec<QuoteRecord>> {
+        let state = self.state.borrow();
+        let retained = (limit.min(QUOTE_HISTORY_LENGTH) as usize).min(state.quote_history.len());
+        Ok(state.quote_history[state.quote_history.len() - retained..].to_vec())
+    }
+}
+
 // Test result validation helpers
 pub fn validate_zap_quote(quote: &ZapQuote) -> Result<()> {
     // Basic validation
@@ -405,6 +936,8 @@ pub fn benchmark_zap_quote_generation(
 pub struct MockOylFactory {
     pub pools: HashMap<(AlkaneId, AlkaneId), MockPool>,
     pub pool_count: u128,
+    pub version: u128, // reported to the zap's version probe; 0 predates versioning
+    pub flash_fee_bps: Option<u128>, // fee for atomic flash swaps, if the factory offers them
 }
 
 impl MockOylFactory {
@@ -412,39 +945,87 @@ impl MockOylFactory {
         Self::default()
     }
     
+    /// Parse an exact-input swap call the way this factory version does, returning the path,
+    /// amount in, minimum out and deadline
+    pub fn decode_swap_inputs(&self, inputs: &[u128], incoming_amount: u128) -> Result<(Vec<AlkaneId>, u128, u128, u128)> {
+        let read_path = |fields: &[u128]| -> Result<Vec<AlkaneId>> {
+            let len = *fields.first().ok_or_else(|| anyhow::anyhow!("Missing path length"))? as usize;
+            if fields.len() < 1 + len * 2 {
+                return Err(anyhow::anyhow!("Truncated swap path"));
+            }
+            Ok(fields[1..1 + len * 2]
+                .chunks_exact(2)
+                .map(|id| AlkaneId { block: id[0], tx: id[1] })
+                .collect())
+        };
+
+        match (self.version >= 2, inputs.first()) {
+            // Original layout: path first, explicit amount in
+            (false, Some(13)) => {
+                let path = read_path(&inputs[1..])?;
+                let rest = &inputs[2 + path.len() * 2..];
+                if rest.len() < 3 {
+                    return Err(anyhow::anyhow!("Truncated swap amounts"));
+                }
+                Ok((path, rest[0], rest[1], rest[2]))
+            }
+            // Versioned layout: limits first, amount in implied by the incoming parcel
+            (true, Some(29)) => {
+                if inputs.len() < 3 {
+                    return Err(anyhow::anyhow!("Truncated swap limits"));
+                }
+                let path = read_path(&inputs[3..])?;
+                Ok((path, incoming_amount, inputs[1], inputs[2]))
+            }
+            (_, opcode) => Err(anyhow::anyhow!(
+                "Factory version {} does not handle swap opcode {:?}",
+                self.version,
+                opcode
+            )),
+        }
+    }
+
     pub fn add_pool(&mut self, token_a: AlkaneId, token_b: AlkaneId, reserve_a: u128, reserve_b: u128) -> AlkaneId {
+        self.try_add_pool(token_a, token_b, reserve_a, reserve_b)
+            .expect("Pool reserves must bootstrap at least MINIMUM_LIQUIDITY")
+    }
+
+    /// Add a pool, surfacing reserves that could not bootstrap a real pool as an error
+    pub fn try_add_pool(&mut self, token_a: AlkaneId, token_b: AlkaneId, reserve_a: u128, reserve_b: u128) -> Result<AlkaneId> {
         let pool_id = AlkaneId {
             block: self.pool_count + 1000,
             tx: self.pool_count + 2000,
         };
         
-        let total_supply = amm_logic::calculate_lp_tokens_minted(reserve_a, reserve_b, 0, 0, 0).unwrap_or(0);
+        let total_supply = amm_logic::calculate_initial_total_supply(reserve_a, reserve_b)?;
 
         let pool = MockPool {
             id: pool_id,
+            lp_token_id: pool_id,
             token_a,
             token_b,
             reserve_a,
             reserve_b,
             total_supply,
             fee_rate: TEST_FEE_RATE,
+            curve: CurveKind::ConstantProduct,
         };
         
         // Store pool with a canonical key to prevent state inconsistencies from duplicate pool objects.
-        let key = get_canonical_key(token_a, token_b);
+        let key = canonical_pool_key(token_a, token_b);
         self.pools.insert(key, pool);
         self.pool_count += 1;
         
-        pool_id
+        Ok(pool_id)
     }
     
     pub fn get_pool(&self, token_a: AlkaneId, token_b: AlkaneId) -> Option<&MockPool> {
-        let key = get_canonical_key(token_a, token_b);
+        let key = canonical_pool_key(token_a, token_b);
         self.pools.get(&key)
     }
 
     pub fn get_pool_mut(&mut self, token_a: AlkaneId, token_b: AlkaneId) -> Option<&mut MockPool> {
-        let key = get_canonical_key(token_a, token_b);
+        let key = canonical_pool_key(token_a, token_b);
         self.pools.get_mut(&key)
     }
 }
@@ -461,7 +1042,9 @@ impl PoolProvider for MockOylFactory {
             pool.reserve_b,
             pool.total_supply,
             pool.fee_rate,
-        ))
+        )
+        .with_curve(pool.curve)
+        .oriented(token_a))
     }
 
     fn get_connected_tokens(&self, token: AlkaneId) -> Result<Vec<AlkaneId>> {
@@ -484,12 +1067,14 @@ impl PoolProvider for MockOylFactory {
 #[derive(Debug, Clone)]
 pub struct MockPool {
     pub id: AlkaneId,
+    pub lp_token_id: AlkaneId, // LP token minted by the pool, may differ from `id`
     pub token_a: AlkaneId,
     pub token_b: AlkaneId,
     pub reserve_a: u128,
     pub reserve_b: u128,
     pub total_supply: u128,
     pub fee_rate: u128, // in basis points
+    pub curve: CurveKind,
 }
 
 impl MockPool {
@@ -502,7 +1087,7 @@ impl MockPool {
             return Err(anyhow::anyhow!("Token not in pool"));
         };
 
-        let amount_out = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, self.fee_rate)?;
+        let amount_out = amm_logic::calculate_swap_out_for_curve(amount_in, reserve_in, reserve_out, self.fee_rate, self.curve)?;
 
         if token_in == self.token_a {
             self.reserve_a = self.reserve_a.saturating_add(amount_in);
@@ -530,6 +1115,46 @@ impl MockPool {
         
         Ok(lp_tokens)
     }
+
+    /// Simulate the parcel the factory returns from `AddLiquidity`
+    pub fn simulate_add_liquidity_response(&mut self, amount_a: u128, amount_b: u128) -> Result<AlkaneTransferParcel> {
+        let lp_tokens = self.simulate_add_liquidity(amount_a, amount_b)?;
+        Ok(AlkaneTransferParcel(vec![AlkaneTransfer {
+            id: self.lp_token_id,
+            value: lp_tokens,
+        }]))
+    }
+
+    /// Simulate the factory's `AddLiquidity` response when only the ratio-matched amounts are
+    /// deposited: the LP tokens and a refund of whatever the ratio left unconsumed
+    pub fn simulate_add_liquidity_at_ratio(&mut self, amount_a: u128, amount_b: u128) -> Result<CallResponse> {
+        let (consumed_a, consumed_b) =
+            amm_logic::quote_add_liquidity(amount_a, amount_b, self.reserve_a, self.reserve_b)?;
+        let lp_tokens = self.simulate_add_liquidity(consumed_a, consumed_b)?;
+
+        let mut response = CallResponse::default();
+        response.alkanes.0.push(AlkaneTransfer { id: self.lp_token_id, value: lp_tokens });
+        for (token, refund) in [(self.token_a, amount_a - consumed_a), (self.token_b, amount_b - consumed_b)] {
+            if refund > 0 {
+                response.alkanes.0.push(AlkaneTransfer { id: token, value: refund });
+            }
+        }
+        Ok(response)
+    }
+
+    pub fn simulate_remove_liquidity(&mut self, lp_amount: u128) -> Result<(u128, u128)> {
+        if lp_amount == 0 || lp_amount > self.total_supply {
+            return Err(anyhow::anyhow!("Invalid LP amount"));
+        }
+
+        let (amount_a, amount_b) = amm_logic::calculate_lp_share(lp_amount, self.reserve_a, self.reserve_b, self.total_supply)?;
+
+        self.reserve_a = self.reserve_a.saturating_sub(amount_a);
+        self.reserve_b = self.reserve_b.saturating_sub(amount_b);
+        self.total_supply = self.total_supply.saturating_sub(lp_amount);
+
+        Ok((amount_a, amount_b))
+    }
 }
 
 // ============================================================================
@@ -546,6 +1171,11 @@ pub fn alkane_id(s: &str) -> AlkaneId {
     AlkaneId { block, tx: 0 }
 }
 
+/// Incoming parcel of a single transfer, as a caller attaches to a zap
+pub fn parcel(token: AlkaneId, amount: u128) -> Vec<AlkaneTransfer> {
+    vec![AlkaneTransfer { id: token, value: amount }]
+}
+
 /// Create a test context with specified caller and incoming alkanes
 pub fn create_test_context(caller: AlkaneId, incoming: Vec<AlkaneTransfer>) -> Context {
     Context {
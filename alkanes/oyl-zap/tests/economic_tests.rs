@@ -23,6 +23,14 @@ All identified architectural and logical flaws in both the core logic (`RouteFin
 
 mod common;
 use common::*;
+use alkanes_support::id::AlkaneId;
+use oyl_zap_core::amm_logic;
+use oyl_zap_core::pool_provider::PoolProvider;
+use oyl_zap_core::route_finder::RouteFinder;
+use oyl_zap_core::types::{LpValueEstimate, PoolReserves, QuoteOptions, QuoteRecord, RouteInfo, ZapParams, ZapQuote, U256, MAX_QUOTE_BATCH, ZAP_QUOTE_SERIALIZED_VERSION};
+use oyl_zap_core::zap_calculator::ZapCalculator;
+use oyl_zap_core::DEFAULT_FEE_BPS;
+use oyl_zap_core::{is_unroutable, OylZap, ZapBase};
 
 #[test]
 fn test_fee_calculation_accuracy() -> anyhow::Result<()> {
@@ -758,3 +766,758 @@ fn test_economic_sustainability() -> anyhow::Result<()> {
     println!("✅ Economic sustainability test passed");
     Ok(())
 }
+
+#[test]
+fn test_quote_slippage_sensitivity() -> anyhow::Result<()> {
+    println!("Testing quote slippage sensitivity...");
+
+    let zap = create_mock_zap();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1e8 as u128; // 1 WBTC
+
+    let quote = zap.get_zap_quote(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let looser_quote = zap.get_zap_quote(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE + 100)?;
+
+    assert!(quote.slippage_sensitivity > 0, "Sensitivity should be positive for a non-empty quote");
+
+    // Moving 1% of slippage changes the minimum by the reported sensitivity (within rounding)
+    let observed_change = quote.minimum_lp_tokens - looser_quote.minimum_lp_tokens;
+    let difference = observed_change.max(quote.slippage_sensitivity) - observed_change.min(quote.slippage_sensitivity);
+    assert!(
+        difference <= 1,
+        "Reported sensitivity {} should match observed change {}",
+        quote.slippage_sensitivity,
+        observed_change
+    );
+
+    println!("✅ Quote slippage sensitivity test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_explanation() -> anyhow::Result<()> {
+    println!("Testing quote explanation output...");
+
+    let zap = MockOylZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let quote = zap.get_zap_quote(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let explanation = quote.explain();
+    println!("{}", explanation);
+
+    let format_path = |path: &[AlkaneId]| {
+        path.iter()
+            .map(|token| format!("{}:{}", token.block, token.tx))
+            .collect::<Vec<_>>()
+            .join(" -> ")
+    };
+    assert!(explanation.contains(&format_path(&quote.route_a.path)), "Route A path missing");
+    assert!(explanation.contains(&format_path(&quote.route_b.path)), "Route B path missing");
+    assert!(explanation.contains(&quote.split_amount_a.to_string()), "Split A missing");
+    assert!(explanation.contains(&quote.split_amount_b.to_string()), "Split B missing");
+    assert!(explanation.contains(&quote.expected_lp_tokens.to_string()), "LP estimate missing");
+
+    println!("✅ Quote explanation test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_serialization_round_trip() -> anyhow::Result<()> {
+    println!("Testing self-describing quote serialization...");
+
+    let zap = MockOylZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let mut quote = zap.get_zap_quote(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    quote.route_a = quote.route_a.clone().with_gas_estimate(42_000);
+    let bytes = quote.serialize();
+
+    // Header: 2-byte version, then the 4-byte total length
+    assert_eq!(u16::from_le_bytes(bytes[0..2].try_into().unwrap()), ZAP_QUOTE_SERIALIZED_VERSION);
+    assert_eq!(u32::from_le_bytes(bytes[2..6].try_into().unwrap()) as usize, bytes.len());
+
+    let decoded = ZapQuote::deserialize(&bytes)?;
+    assert_eq!(decoded, quote, "Quote should survive a round trip unchanged");
+    assert_eq!(decoded.route_a.path, quote.route_a.path);
+    assert_eq!(decoded.route_a.gas_estimate, 42_000);
+
+    // Truncated, padded or unknown-version payloads are rejected
+    assert!(ZapQuote::deserialize(&bytes[..bytes.len() - 1]).is_err());
+    let mut padded = bytes.clone();
+    padded.push(0);
+    assert!(ZapQuote::deserialize(&padded).is_err());
+    let mut future = bytes.clone();
+    future[0..2].copy_from_slice(&(ZAP_QUOTE_SERIALIZED_VERSION + 1).to_le_bytes());
+    assert!(ZapQuote::deserialize(&future).is_err());
+
+    println!("✅ Quote serialization test passed");
+    Ok(())
+}
+
+#[test]
+fn test_zap_quote_batch_skips_unroutable_pairs() -> anyhow::Result<()> {
+    println!("Testing batch zap quotes across target pairs...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let dai = alkane_id("DAI");
+    let orphan = alkane_id("ORPHAN"); // no pool touches ORPHAN
+    let input_amount = 100 * TEST_PRECISION; // 100 UNI
+
+    let quote_batch = |pairs: &[(AlkaneId, AlkaneId)]| {
+        let pairs = pairs.iter().flat_map(|(token_a, token_b)| [*token_a, *token_b]).collect();
+        zap.get_zap_quote_batch(uni, input_amount, pairs, DEFAULT_SLIPPAGE).map(|response| response.data)
+    };
+
+    let pairs = [(eth, usdc), (eth, orphan), (usdc, dai)];
+    let bytes = quote_batch(&pairs)?;
+    let quotes = ZapQuote::deserialize_batch(&bytes)?;
+    assert_eq!(quotes.len(), pairs.len(), "Every pair should be answered in order");
+
+    // Routable pairs match their individual quotes
+    let routable: Vec<_> = quotes.iter().filter(|quote| quote.is_routable()).collect();
+    assert_eq!(routable.len(), 2);
+    assert_eq!(quotes[0], zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?);
+    assert_eq!(quotes[2], zap.quote_zap(uni, input_amount, usdc, dai, DEFAULT_SLIPPAGE)?);
+
+    // The unroutable pair is reported with a zero-LP sentinel that still names it
+    let sentinel = &quotes[1];
+    assert_eq!(sentinel.expected_lp_tokens, 0);
+    assert_eq!((sentinel.target_token_a, sentinel.target_token_b), (eth, orphan));
+    assert!(sentinel.route_a.path.is_empty() && sentinel.route_b.path.is_empty());
+
+    // Only a missing pool or route earns a sentinel; any other failure fails the batch
+    let invalid = [(eth, usdc), (eth, eth)];
+    let err = quote_batch(&invalid).unwrap_err();
+    assert!(!is_unroutable(&err), "got {}", err);
+
+    // Malformed payloads and oversized batches are rejected
+    assert!(ZapQuote::deserialize_batch(&bytes[..bytes.len() - 1]).is_err());
+    let mut padded = bytes.clone();
+    padded.push(0);
+    assert!(ZapQuote::deserialize_batch(&padded).is_err());
+    let oversized = vec![(eth, usdc); MAX_QUOTE_BATCH + 1];
+    assert!(quote_batch(&oversized).is_err());
+
+    println!("✅ Batch zap quote test passed");
+    Ok(())
+}
+
+#[test]
+fn test_swap_output_respects_pool_fee_rate() -> anyhow::Result<()> {
+    println!("Testing swap output against per-pool fee rates...");
+
+    let zap = OylZap::default();
+    let reserve_in = 1_000_000 * TEST_PRECISION;
+    let reserve_out = 2_000_000 * TEST_PRECISION;
+    let amount_in = 1_000 * TEST_PRECISION;
+
+    let output_30_bps = zap.calculate_swap_output(amount_in, reserve_in, reserve_out, 30)?;
+    let output_100_bps = zap.calculate_swap_output(amount_in, reserve_in, reserve_out, 100)?;
+    println!("Output at 0.3% fee: {}, at 1% fee: {}", output_30_bps, output_100_bps);
+
+    // The 0.7% fee difference should show up almost one-for-one in the output
+    assert!(output_100_bps < output_30_bps, "Higher fee should yield less output");
+    let difference_bps = calculate_percentage_difference(output_30_bps, output_100_bps);
+    assert!((60..=80).contains(&difference_bps), "Fee difference was {} bps", difference_bps);
+
+    // The trait path agrees with the shared AMM math
+    assert_eq!(output_100_bps, amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, 100)?);
+
+    println!("✅ Per-pool fee rate test passed");
+    Ok(())
+}
+
+#[test]
+fn test_lp_value_estimate() -> anyhow::Result<()> {
+    println!("Testing LP value estimation...");
+
+    let zap = TestZap::new();
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let estimate_lp_value = |token_a, token_b, lp_amount| -> anyhow::Result<LpValueEstimate> {
+        LpValueEstimate::decode(&zap.estimate_lp_value(token_a, token_b, lp_amount)?.data)
+    };
+
+    let pool = zap.pool(eth, usdc).unwrap();
+    let lp_amount = pool.total_supply / 100;
+
+    // The position is a pro-rata share of each reserve
+    let estimate = estimate_lp_value(eth, usdc, lp_amount)?;
+    let (eth_value, usdc_value) = (estimate.amount_a, estimate.amount_b);
+    assert_eq!(eth_value, lp_amount * pool.reserve_a / pool.total_supply);
+    assert_eq!(usdc_value, lp_amount * pool.reserve_b / pool.total_supply);
+    let combined = lp_amount * (pool.reserve_a + pool.reserve_b) / pool.total_supply;
+    assert!(combined - (eth_value + usdc_value) <= 1, "Combined value should match the pool share");
+
+    // A pool that has never traded has earned no fees
+    assert!(estimate.fees_a <= 1 && estimate.fees_b <= 1, "Fresh pool should carry no fees: {:?}", estimate);
+
+    // Asking with the pair reversed orients the amounts to the caller's order
+    let reversed = estimate_lp_value(usdc, eth, lp_amount)?;
+    assert_eq!((reversed.amount_a, reversed.amount_b), (usdc_value, eth_value));
+
+    // Value scales linearly with the LP amount
+    let doubled = estimate_lp_value(eth, usdc, lp_amount * 2)?;
+    assert!(doubled.amount_a.abs_diff(eth_value * 2) <= 1);
+    assert!(doubled.amount_b.abs_diff(usdc_value * 2) <= 1);
+
+    // Round-trip swaps leave fees in the pool, so the same position is worth more, and the
+    // fee figure is what sets it apart from the position's original geometric value
+    {
+        let mut state = zap.state.borrow_mut();
+        let pool = state.factory.get_pool_mut(eth, usdc).unwrap();
+        let usdc_out = pool.simulate_swap(eth, 10 * TEST_PRECISION)?;
+        pool.simulate_swap(usdc, usdc_out)?;
+    }
+    let after = estimate_lp_value(eth, usdc, lp_amount)?;
+    assert!(after.amount_a + after.amount_b > eth_value + usdc_value, "Accumulated fees should raise LP value");
+    assert!(after.fees_a > 0 && after.fees_b > 0, "Fees should be reported once the pool trades");
+    let principal = U256::from(after.amount_a - after.fees_a) * U256::from(after.amount_b - after.fees_b);
+    let original = U256::from(eth_value) * U256::from(usdc_value);
+    let tolerance = original / U256::from(1_000_000u128);
+    let drift = if principal > original { principal - original } else { original - principal };
+    assert!(drift <= tolerance, "Value net of fees should keep the original geometric value");
+    assert_eq!(LpValueEstimate::decode(&after.encode())?, after, "Estimate should survive the wire format");
+
+    // A position larger than the supply cannot be valued
+    let total_supply = zap.pool(eth, usdc).unwrap().total_supply;
+    assert!(estimate_lp_value(eth, usdc, total_supply + 1).is_err());
+
+    println!("✅ LP value estimation test passed");
+    Ok(())
+}
+
+#[test]
+fn test_contract_quote_mints_lp_like_mock() -> anyhow::Result<()> {
+    println!("Testing contract quote LP estimate against the mock...");
+
+    let zap = MockOylZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * TEST_PRECISION;
+
+    // Quote the way `OylZap::quote_zap` does, straight off the factory's pools
+    let (route_a, route_b) = RouteFinder::new(zap.factory_id, &zap.factory)
+        .with_base_tokens(zap.base_tokens.clone())
+        .find_zap_routes(uni, input_amount, eth, usdc)?;
+    let target_pool_reserves = zap.factory.get_pool_reserves(eth, usdc)?;
+    let contract_quote = ZapCalculator::generate_zap_quote(
+        uni,
+        input_amount,
+        eth,
+        usdc,
+        route_a,
+        route_b,
+        &target_pool_reserves,
+        DEFAULT_SLIPPAGE,
+        &RouteFinder::new(zap.factory_id, &zap.factory),
+    )?;
+    let mock_quote = zap.get_zap_quote(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(contract_quote, mock_quote);
+
+    // Both mint the lesser proportional share of the real LP supply
+    let minted = amm_logic::calculate_lp_tokens_minted(
+        contract_quote.expected_deposit_a,
+        contract_quote.expected_deposit_b,
+        target_pool_reserves.reserve_a,
+        target_pool_reserves.reserve_b,
+        target_pool_reserves.total_supply,
+    )?;
+    assert_eq!(contract_quote.expected_lp_tokens, minted);
+    let summed_supply_estimate = amm_logic::calculate_lp_tokens_minted(
+        contract_quote.expected_deposit_a,
+        contract_quote.expected_deposit_b,
+        target_pool_reserves.reserve_a,
+        target_pool_reserves.reserve_b,
+        target_pool_reserves.reserve_a + target_pool_reserves.reserve_b,
+    )?;
+    assert_ne!(contract_quote.expected_lp_tokens, summed_supply_estimate);
+
+    println!("✅ Contract quote LP estimate test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quoted_dust_matches_execution() -> anyhow::Result<()> {
+    println!("Testing expected dust reporting...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+
+    // Price ETH at twice the market rate in the target pool, so the swapped amounts
+    // cannot both fit its ratio
+    let (pool_reserve_eth, pool_reserve_usdc) = zap.with_pool(eth, usdc, |pool| {
+        pool.reserve_b *= 2;
+        (pool.reserve_a, pool.reserve_b)
+    });
+
+    let quote = zap.quote_zap(uni, 1000 * TEST_PRECISION, eth, usdc, DEFAULT_SLIPPAGE)?;
+    println!("Expected dust: {} ETH, {} USDC", quote.expected_dust_a, quote.expected_dust_b);
+    assert!(
+        quote.expected_dust_a == 0 || quote.expected_dust_b == 0,
+        "The limiting side should be fully deposited"
+    );
+    assert!(quote.explain().contains("Expected dust"));
+
+    // Amounts off the pool ratio leave the excess side as dust
+    let reserves = PoolReserves::new(eth, usdc, pool_reserve_eth, pool_reserve_usdc, 0, DEFAULT_FEE_BPS);
+    let (eth_dust, usdc_dust) = ZapCalculator::calculate_expected_dust(TEST_PRECISION, 1000 * 1_000_000, eth, &reserves)?;
+    assert_eq!((eth_dust, usdc_dust), (TEST_PRECISION * 3 / 4, 0), "1000 USDC only pairs with 0.25 ETH");
+    assert_eq!(
+        ZapCalculator::calculate_expected_dust(1000 * 1_000_000, TEST_PRECISION, usdc, &reserves)?,
+        (0, TEST_PRECISION * 3 / 4),
+        "Dust follows the caller's token order"
+    );
+
+    // The dust refunded by execution matches the quoted amounts within rounding
+    let receipt = zap.execute(&quote)?;
+    let (dust_a, dust_b) = (receipt.dust_a, receipt.dust_b);
+    println!("Actual dust: {} ETH, {} USDC", dust_a, dust_b);
+    assert!(dust_a.abs_diff(quote.expected_dust_a) <= 1, "ETH dust differs from the quote");
+    assert!(dust_b.abs_diff(quote.expected_dust_b) <= 1, "USDC dust differs from the quote");
+
+    // The dust survives the wire encoding
+    assert_eq!(ZapQuote::deserialize(&quote.serialize())?, quote);
+
+    println!("✅ Expected dust test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quoted_deposits_exclude_dust() -> anyhow::Result<()> {
+    println!("Testing deposited amounts reported in zap quotes...");
+
+    let (factory, _) = setup_comprehensive_test_environment();
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let wbtc = alkane_id("WBTC");
+    let input_amount = 20 * TEST_PRECISION; // 20 WBTC
+
+    // Routing the USDC side through ETH swaps through the target pool itself, so the split is
+    // chosen against reserves that the swap then moves, leaving the deposit off the new ratio
+    let pool = factory.get_pool(eth, usdc).unwrap().clone();
+    let target_pool_reserves =
+        PoolReserves::new(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let quote = ZapCalculator::generate_zap_quote_with_options(
+        &ZapParams::new(wbtc, input_amount, eth, usdc, 0, 0).with_max_slippage(DEFAULT_SLIPPAGE),
+        RouteInfo::new(vec![wbtc, eth], 0),
+        RouteInfo::new(vec![wbtc, eth, usdc], 0),
+        &target_pool_reserves,
+        &route_finder,
+        &QuoteOptions::new().with_projected_reserves(true),
+    )?;
+    println!(
+        "Swapped: {} ETH, {} USDC; deposited: {} ETH, {} USDC",
+        quote.route_a.expected_output, quote.route_b.expected_output, quote.expected_deposit_a, quote.expected_deposit_b
+    );
+
+    // Deposits never exceed the swap outputs, and the shortfall is exactly the dust
+    assert!(quote.expected_deposit_a <= quote.route_a.expected_output);
+    assert!(quote.expected_deposit_b <= quote.route_b.expected_output);
+    assert_eq!(quote.route_a.expected_output - quote.expected_deposit_a, quote.expected_dust_a);
+    assert_eq!(quote.route_b.expected_output - quote.expected_deposit_b, quote.expected_dust_b);
+    assert!(quote.expected_dust_a + quote.expected_dust_b > 0, "The moved pool should leave dust");
+
+    // The deposits are reported alongside the dust and survive the wire encoding
+    assert!(quote.explain().contains("Expected deposit"));
+    let decoded = ZapQuote::deserialize(&quote.serialize())?;
+    assert_eq!((decoded.expected_deposit_a, decoded.expected_deposit_b), (quote.expected_deposit_a, quote.expected_deposit_b));
+
+    println!("✅ Quoted deposits test passed");
+    Ok(())
+}
+
+#[test]
+fn test_single_sided_zap_leaves_minimal_dust() -> anyhow::Result<()> {
+    println!("Testing single-sided splits for an input that is one of the targets...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let input_amount = 5 * 100_000_000; // 5 WBTC
+
+    // WBTC/ETH prices WBTC at 15 ETH while the USDC pools imply 10, so the pool is off-market
+    let pool = zap.pool(wbtc, eth).unwrap();
+    assert_eq!(pool.token_a, wbtc);
+    let target_pool_reserves =
+        PoolReserves::new(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate);
+    let kept_route = RouteInfo::new(vec![wbtc], 0);
+    let swapped_route = RouteInfo::new(vec![wbtc, eth], 0);
+
+    // Dust left after swapping `swap_amount` through the target pool and depositing the rest
+    let dust_after_swap = |kept: u128, swap_amount: u128| -> anyhow::Result<(u128, u128)> {
+        let mut execution_pool = pool.clone();
+        let eth_received = execution_pool.simulate_swap(wbtc, swap_amount)?;
+        let (consumed_wbtc, consumed_eth) = amm_logic::quote_add_liquidity(
+            kept,
+            eth_received,
+            execution_pool.reserve_a,
+            execution_pool.reserve_b,
+        )?;
+        Ok((kept - consumed_wbtc, eth_received - consumed_eth))
+    };
+
+    // Swapping through the target pool, the closed form lands on the ratio the swap leaves
+    let factory = zap.state.borrow().factory.clone();
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let (split_a, split_b) = ZapCalculator::calculate_single_sided_split(
+        input_amount,
+        &kept_route,
+        &swapped_route,
+        &target_pool_reserves,
+        &route_finder,
+    )?;
+    assert_eq!(split_a + split_b, input_amount);
+    let (dust_wbtc, dust_eth) = dust_after_swap(split_a, split_b)?;
+    let (half_dust_wbtc, half_dust_eth) = dust_after_swap(input_amount / 2, input_amount / 2)?;
+    println!("Single-sided dust: {} WBTC, {} ETH; even split dust: {} WBTC, {} ETH", dust_wbtc, dust_eth, half_dust_wbtc, half_dust_eth);
+    assert!(dust_wbtc * 10_000 <= split_a, "WBTC dust should be under 1 bp: {}", dust_wbtc);
+    assert!(half_dust_wbtc == 0 && dust_eth * 1_000 < half_dust_eth, "An even split swaps too much away");
+
+    // Quoted and executed by the contract, routed around the target pool, the split is exact
+    let quote = zap.quote_zap(wbtc, input_amount, wbtc, eth, DEFAULT_SLIPPAGE)?;
+    assert_eq!(quote.route_a.path, vec![wbtc]);
+    let receipt = zap.execute(&quote)?;
+    let (dust_wbtc, dust_eth) = (receipt.dust_a, receipt.dust_b);
+    println!("Routed single-sided dust: {} WBTC, {} ETH", dust_wbtc, dust_eth);
+    assert!(dust_wbtc * 10_000 <= quote.split_amount_a, "WBTC dust should be under 1 bp: {}", dust_wbtc);
+    assert!(dust_eth * 10_000 <= quote.route_b.expected_output, "ETH dust should be under 1 bp: {}", dust_eth);
+
+    println!("✅ Single-sided split test passed");
+    Ok(())
+}
+
+#[test]
+fn test_flash_swap_quote_against_sequential_quote() -> anyhow::Result<()> {
+    println!("Testing flash-swap zap quotes...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * TEST_PRECISION;
+    let quote_flash = |zap: &TestZap| -> anyhow::Result<ZapQuote> {
+        ZapQuote::deserialize(&zap.get_flash_zap_quote(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.data)
+    };
+
+    // Flash quotes need the owner to have recorded a flash-capable factory
+    assert!(quote_flash(&zap).is_err(), "Flash quotes are disabled by default");
+
+    let sequential = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+
+    // At the pools' own fee the flash quote matches the sequential one
+    zap.flash_swap_fee_bps = Some(TEST_FEE_RATE);
+    assert_eq!(quote_flash(&zap)?, sequential);
+
+    // A cheaper flash fee yields more of each target token and more LP
+    zap.flash_swap_fee_bps = Some(TEST_FEE_RATE / 3);
+    let cheap = quote_flash(&zap)?;
+    println!("Sequential LP: {}, flash LP: {}", sequential.expected_lp_tokens, cheap.expected_lp_tokens);
+    assert!(cheap.expected_lp_tokens > sequential.expected_lp_tokens);
+    assert!(cheap.price_impact <= sequential.price_impact);
+
+    // A pricier one yields less
+    zap.flash_swap_fee_bps = Some(TEST_FEE_RATE * 3);
+    assert!(quote_flash(&zap)?.expected_lp_tokens < sequential.expected_lp_tokens);
+
+    println!("✅ Flash-swap quote test passed");
+    Ok(())
+}
+
+#[test]
+fn test_preview_dust_matches_refund() -> anyhow::Result<()> {
+    println!("Testing dust previews...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 100_000_000; // 1 WBTC
+
+    // Skew the target pool off the market rate so the swapped amounts cannot both fit it
+    zap.with_pool(eth, usdc, |pool| pool.reserve_b *= 3);
+
+    let preview = zap.preview_dust(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.data;
+    assert_eq!(preview.len(), 32);
+    let preview_a = u128::from_le_bytes(preview[0..16].try_into()?);
+    let preview_b = u128::from_le_bytes(preview[16..32].try_into()?);
+    println!("Previewed dust: {} ETH, {} USDC", preview_a, preview_b);
+    assert!(preview_a + preview_b > 0, "The skewed pool should leave dust");
+
+    // A zap committed afterwards refunds what the preview predicted, within rounding
+    let receipt = zap.execute_zap_for(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let (dust_a, dust_b) = (receipt.dust_a, receipt.dust_b);
+    println!("Refunded dust: {} ETH, {} USDC", dust_a, dust_b);
+    assert!(dust_a.abs_diff(preview_a) <= 1, "ETH dust differs from the preview");
+    assert!(dust_b.abs_diff(preview_b) <= 1, "USDC dust differs from the preview");
+
+    println!("✅ Dust preview test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_history_returns_latest_quotes() -> anyhow::Result<()> {
+    println!("Testing quote history...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let quote_history = |zap: &TestZap, limit| zap.get_quote_history(limit).map(|response| response.data);
+
+    // Quoting is read-only and records nothing
+    zap.get_zap_quote(wbtc, 10_000_000, eth, usdc, DEFAULT_SLIPPAGE, 0)?;
+    assert!(quote_history(&zap, 100)?.is_empty(), "A quote must not write the history");
+
+    // Execute five zaps of distinct sizes at successive heights
+    let amounts: Vec<u128> = (1..=5).map(|i| i * 10_000_000).collect();
+    for (i, amount) in amounts.iter().enumerate() {
+        zap.height = 100 + i as u64;
+        zap.execute_zap_for(wbtc, *amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    }
+
+    let data = quote_history(&zap, 3)?;
+    assert_eq!(data.len(), 3 * QuoteRecord::ENCODED_LEN);
+    let records = data
+        .chunks_exact(QuoteRecord::ENCODED_LEN)
+        .map(QuoteRecord::decode)
+        .collect::<anyhow::Result<Vec<_>>>()?;
+
+    // The three latest quotes come back oldest first
+    for (record, (amount, height)) in records.iter().zip(amounts[2..].iter().zip(102u128..)) {
+        println!("Height {}: {} WBTC -> {} LP", record.height, record.input_amount, record.expected_lp_tokens);
+        assert_eq!(record.input_token, wbtc);
+        assert_eq!(record.input_amount, *amount);
+        assert_eq!(record.height, height);
+        assert!(record.expected_lp_tokens > 0);
+    }
+
+    // A limit past what was recorded returns everything
+    assert_eq!(quote_history(&zap, 100)?.len(), 5 * QuoteRecord::ENCODED_LEN);
+
+    println!("✅ Quote history test passed");
+    Ok(())
+}
+
+#[test]
+fn test_pool_share_matches_hand_computed_bps() -> anyhow::Result<()> {
+    println!("Testing post-zap pool share...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 10 * 100_000_000; // 10 WBTC
+
+    let total_supply = zap.pool(eth, usdc).unwrap().total_supply;
+    let share = zap.get_pool_share(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.data;
+    assert_eq!(share.len(), 32);
+    let lp_minted = u128::from_le_bytes(share[0..16].try_into()?);
+    let share_bps = u128::from_le_bytes(share[16..32].try_into()?);
+    println!("{} LP minted into a supply of {}: {} bps", lp_minted, total_supply, share_bps);
+
+    // The share is the quoted LP over the supply once it is minted
+    let quote = zap.quote_zap(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(lp_minted, quote.expected_lp_tokens);
+    assert_eq!(share_bps, lp_minted * 10_000 / (total_supply + lp_minted));
+    assert!(share_bps > 0 && share_bps < 10_000);
+
+    // Executing the zap lands on the previewed share, within rounding
+    let lp_received = zap.execute_zap_for(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+    let supply_after = zap.pool(eth, usdc).unwrap().total_supply;
+    let realized_bps = lp_received * 10_000 / supply_after;
+    println!("Realized share: {} bps", realized_bps);
+    assert!(realized_bps.abs_diff(share_bps) <= 1, "Realized share strays from the preview");
+
+    // A sole depositor into an empty supply would own the whole pool
+    assert_eq!(amm_logic::calculate_pool_share_bps(lp_minted, 0)?, 10_000);
+    assert!(amm_logic::calculate_pool_share_bps(0, 0).is_err());
+
+    println!("✅ Pool share test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_records_height_and_goes_stale() -> anyhow::Result<()> {
+    println!("Testing quote freshness...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // A quote carries the height it was priced at
+    zap.height = 500;
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(quote.computed_at_height, 500);
+
+    // ... through serialization, where the height round-trips
+    let decoded = ZapQuote::deserialize(&quote.serialize())?;
+    assert_eq!(decoded.computed_at_height, 500);
+    assert_eq!(decoded, quote);
+
+    // It stays fresh for `max_age` blocks and goes stale after
+    assert!(!quote.is_stale(500, 0));
+    assert!(quote.is_stale(501, 0));
+    assert!(!quote.is_stale(505, 5));
+    assert!(quote.is_stale(506, 5));
+    // A height before the quote's own is never stale
+    assert!(!quote.is_stale(499, 0));
+
+    // Re-quoting later refreshes the height
+    zap.height = 510;
+    let requote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(requote.computed_at_height, 510);
+    assert!(!requote.is_stale(510, 5));
+
+    println!("✅ Quote freshness test passed");
+    Ok(())
+}
+
+#[test]
+fn test_worst_case_quote_below_normal_and_scales_with_adversary() -> anyhow::Result<()> {
+    println!("Testing worst-case quote under a front-running adversary...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 10 * 100_000_000; // 10 WBTC
+
+    let decode = |data: Vec<u8>| -> anyhow::Result<(u128, u128)> {
+        assert_eq!(data.len(), 32);
+        Ok((u128::from_le_bytes(data[0..16].try_into()?), u128::from_le_bytes(data[16..32].try_into()?)))
+    };
+
+    // Without an adversary the worst case is the quote itself
+    let quote = zap.quote_zap(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let (expected_lp, unattacked_lp) = decode(zap.get_worst_case_quote(wbtc, input_amount, eth, usdc, 0)?.data)?;
+    assert_eq!(expected_lp, quote.expected_lp_tokens);
+    assert_eq!(unattacked_lp, expected_lp);
+
+    // Each larger front-run leaves strictly less for the user
+    let mut previous_lp = expected_lp;
+    for adversary_amount in [input_amount, 5 * input_amount, 20 * input_amount] {
+        let (normal_lp, worst_case_lp) = decode(zap.get_worst_case_quote(wbtc, input_amount, eth, usdc, adversary_amount)?.data)?;
+        println!("Adversary {}: {} LP against {} expected", adversary_amount, worst_case_lp, normal_lp);
+        assert_eq!(normal_lp, expected_lp);
+        assert!(worst_case_lp < previous_lp, "Worst case should fall as the adversary grows");
+        previous_lp = worst_case_lp;
+    }
+
+    println!("✅ Worst-case quote test passed");
+    Ok(())
+}
+
+#[test]
+fn test_decimals_aware_split_cuts_cross_decimal_dust() -> anyhow::Result<()> {
+    println!("Testing decimals-aware splits into a 6/18-decimal pair...");
+
+    // GOLD has 6 decimals and trades at a million MEME, which has 18, so the pair's raw
+    // reserve ratio is ~1 once scaled by 1e18 and keeps almost no precision
+    let input = alkane_id("DEC_IN");
+    let gold = alkane_id("GOLD6");
+    let meme = alkane_id("MEME18");
+    let gold_unit = 1_000_000;
+    let meme_unit = TEST_PRECISION;
+    let (mut factory, base_tokens) = setup_test_environment();
+    factory.add_pool(input, gold, 1_000_000_000 * TEST_PRECISION, 1_000 * gold_unit);
+    factory.add_pool(input, meme, 1_000_000_000 * TEST_PRECISION, 1_000_000_000 * meme_unit);
+    factory.add_pool(gold, meme, 100 * gold_unit, 100_000_000 * meme_unit);
+    let mut zap = TestZap::with_factory(factory, base_tokens);
+    let input_amount = 1_000_000 * TEST_PRECISION;
+
+    // Dust as a share of what each leg delivers, in bps
+    let dust_bps = |quote: &ZapQuote| {
+        (
+            quote.expected_dust_a * 10_000 / quote.route_a.expected_output,
+            quote.expected_dust_b * 10_000 / quote.route_b.expected_output,
+        )
+    };
+
+    let naive = zap.quote_zap(input, input_amount, gold, meme, DEFAULT_SLIPPAGE)?;
+
+    // Decimals as recorded through SetTokenDecimals
+    zap.token_decimals.insert(gold, 6);
+    zap.token_decimals.insert(meme, 18);
+    let aware = zap.quote_zap(input, input_amount, gold, meme, DEFAULT_SLIPPAGE)?;
+
+    let (naive_dust, aware_dust) = (dust_bps(&naive), dust_bps(&aware));
+    println!(
+        "Naive split {:?} strands {:?} bps; decimals-aware split {:?} strands {:?} bps",
+        (naive.split_amount_a, naive.split_amount_b),
+        naive_dust,
+        (aware.split_amount_a, aware.split_amount_b),
+        aware_dust,
+    );
+
+    // Normalizing lands the split on the pool ratio, stranding far less of either token
+    assert_eq!(aware.split_amount_a + aware.split_amount_b, input_amount);
+    assert!(aware_dust.0.max(aware_dust.1) <= 1);
+    assert!(naive_dust.0.max(naive_dust.1) >= 100 * aware_dust.0.max(aware_dust.1).max(1));
+    assert!(aware.expected_lp_tokens > naive.expected_lp_tokens);
+
+    println!("✅ Decimals-aware split test passed");
+    Ok(())
+}
+
+#[test]
+fn test_decimals_aware_split_orients_target_reserves() -> anyhow::Result<()> {
+    println!("Testing decimals-aware splits against a pool stored in either order...");
+
+    // The target pool lists MEME (18 decimals) first, while the zap's token A is GOLD (6)
+    let mut factory = MockOylFactory::new();
+    let input = alkane_id("ORIENT_IN");
+    let gold = alkane_id("ORIENT_GOLD6");
+    let meme = alkane_id("ORIENT_MEME18");
+    let (gold_unit, meme_unit) = (1_000_000, TEST_PRECISION);
+    factory.add_pool(input, gold, 1_000_000_000 * TEST_PRECISION, 1_000 * gold_unit);
+    factory.add_pool(input, meme, 1_000_000_000 * TEST_PRECISION, 1_000_000_000 * meme_unit);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let input_amount = 1_000_000 * TEST_PRECISION;
+    let route_a = route_finder.find_best_route(input, gold, input_amount / 2)?;
+    let route_b = route_finder.find_best_route(input, meme, input_amount / 2)?;
+
+    let meme_first = PoolReserves::new(meme, gold, 100_000_000 * meme_unit, 100 * gold_unit, 0, DEFAULT_FEE_BPS);
+    let gold_first = meme_first.oriented(gold);
+    let split = |reserves: &PoolReserves| {
+        ZapCalculator::calculate_optimal_split_with_decimals(input_amount, &route_a, &route_b, reserves, (6, 18), &route_finder)
+    };
+    let aware = split(&meme_first)?;
+    assert_eq!(aware, split(&gold_first)?, "The split should not depend on the pool's stored order");
+
+    // Scaled to 18 decimals, the legs land on the pool's million MEME per GOLD
+    let gold_out = ZapCalculator::calculate_route_output(aware.0, &route_a, &route_finder)? * (TEST_PRECISION / gold_unit);
+    let meme_out = ZapCalculator::calculate_route_output(aware.1, &route_b, &route_finder)?;
+    let ratio_bps = meme_out / (gold_out / 10_000);
+    println!("Decimals-aware split {:?} delivers {} MEME per GOLD", aware, ratio_bps / 10_000);
+    assert!(ratio_bps.abs_diff(1_000_000 * 10_000) <= 1_000_000, "Outputs should sit within 0.01% of the pool ratio");
+
+    // The plain split compares raw ratios, which keep almost no precision for this pair
+    let plain = ZapCalculator::calculate_optimal_split(input_amount, &route_a, &route_b, &gold_first, &route_finder)?;
+    println!("Plain split {:?}", plain);
+    assert_ne!(plain, aware);
+
+    println!("✅ Oriented decimals-aware split test passed");
+    Ok(())
+}
@@ -5,6 +5,12 @@
 
 mod common;
 use common::*;
+use oyl_zap_core::pool_provider::PoolProvider;
+use oyl_zap_core::route_finder::RouteFinder;
+use oyl_zap_core::amm_logic;
+use oyl_zap_core::BASIS_POINTS;
+use oyl_zap_core::types::{CurveKind, PoolReserves, QuoteOptions, RouteInfo, ZapParams};
+use oyl_zap_core::zap_calculator::ZapCalculator;
 
 #[test]
 fn test_optimal_split_calculation_verification() -> anyhow::Result<()> {
@@ -335,8 +341,27 @@ fn test_invariant_preservation_across_operations() -> anyhow::Result<()> {
     
     for (i, amount) in operations.iter().enumerate() {
         let quote = zap.get_zap_quote(wbtc, *amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+        let legs_before: Vec<MockPool> = [eth, usdc]
+            .iter()
+            .map(|target| zap.factory.get_pool(wbtc, *target).cloned().ok_or_else(|| anyhow::anyhow!("Pool not found")))
+            .collect::<anyhow::Result<_>>()?;
         let lp_tokens = zap.execute_zap(&quote)?;
         
+        // 0. Each leg's swap kept its pool's constant product
+        for before in &legs_before {
+            let after = zap.factory.get_pool(before.token_a, before.token_b)
+                .ok_or_else(|| anyhow::anyhow!("Pool not found"))?;
+            let (reserve_in, reserve_out, amount_in, amount_out) = if before.token_a == wbtc {
+                (before.reserve_a, before.reserve_b, after.reserve_a - before.reserve_a, before.reserve_b - after.reserve_b)
+            } else {
+                (before.reserve_b, before.reserve_a, after.reserve_b - before.reserve_b, before.reserve_a - after.reserve_a)
+            };
+            assert!(
+                amm_logic::verify_constant_product(reserve_in, reserve_out, amount_in, amount_out, before.fee_rate),
+                "Leg swap should preserve the constant product in operation {}", i + 1
+            );
+        }
+        
         total_lp_issued += lp_tokens;
         
         // Get current pool state
@@ -682,7 +707,7 @@ fn test_mathematical_edge_cases() -> anyhow::Result<()> {
     
     // 3. Test boundary conditions for slippage
     let boundary_slippages = vec![
-        0u128,      // No slippage
+        0u128,      // Stored default slippage
         1u128,      // Minimum slippage
         9999u128,   // Maximum slippage - 1
         10000u128,  // Maximum slippage
@@ -694,7 +719,8 @@ fn test_mathematical_edge_cases() -> anyhow::Result<()> {
         match result {
             Ok(quote) => {
                 // Verify slippage calculation is mathematically correct
-                let expected_minimum = quote.expected_lp_tokens * (10000 - slippage) / 10000;
+                let applied = if slippage == 0 { zap.default_slippage } else { slippage };
+                let expected_minimum = quote.expected_lp_tokens * (10000 - applied) / 10000;
                 assert_within_tolerance(quote.minimum_lp_tokens, expected_minimum, 100); // 1% tolerance
                 
                 validate_zap_quote(&quote)?;
@@ -791,3 +817,669 @@ fn test_mathematical_consistency_across_scenarios() -> anyhow::Result<()> {
     println!("✅ Mathematical consistency across scenarios test passed");
     Ok(())
 }
+
+#[test]
+fn test_optimal_split_outperforms_even_split() -> anyhow::Result<()> {
+    println!("Testing optimal split against a naive 50/50 split...");
+
+    let zap = MockOylZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    // UNI routes into ETH and USDC through pools of very different depth
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let quote = zap.get_zap_quote(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_ne!(quote.split_amount_a, input_amount / 2, "Imbalanced routes should not split evenly");
+
+    let mut even_quote = quote.clone();
+    even_quote.split_amount_a = input_amount / 2;
+    even_quote.split_amount_b = input_amount - input_amount / 2;
+    even_quote.minimum_lp_tokens = 0;
+    even_quote.route_a.expected_output = 0; // quoted outputs belong to the optimal split
+    even_quote.route_b.expected_output = 0;
+
+    // An even split leaves most of one side unconsumed, which the deposit minimums would
+    // refuse, so the naive run is executed without them
+    let mut naive_zap = zap.clone();
+    naive_zap.default_slippage = BASIS_POINTS;
+
+    let optimal_lp = zap.clone().execute_zap(&quote)?;
+    let even_lp = naive_zap.execute_zap(&even_quote)?;
+    println!("Optimal split LP: {}, even split LP: {}", optimal_lp, even_lp);
+    assert!(optimal_lp >= even_lp, "Optimal split should mint at least as many LP tokens");
+
+    println!("✅ Optimal vs even split test passed");
+    Ok(())
+}
+
+#[test]
+fn test_pool_bootstrap_with_near_max_reserves() -> anyhow::Result<()> {
+    println!("Testing pool bootstrap total supply with near-u128 reserves...");
+
+    let mut factory = MockOylFactory::new();
+    let token_a = alkane_id("HUGE_A");
+    let token_b = alkane_id("HUGE_B");
+
+    // The reserve product overflows u128 but the geometric mean does not
+    let reserve = u128::MAX / 2;
+    factory.try_add_pool(token_a, token_b, reserve, reserve)?;
+    let pool = factory.get_pool(token_a, token_b).unwrap();
+    assert_eq!(pool.total_supply, reserve, "Supply of a balanced pool should equal its reserves");
+
+    // Imbalanced huge reserves still land on the geometric mean
+    let token_c = alkane_id("HUGE_C");
+    factory.try_add_pool(token_a, token_c, u128::MAX, 4)?;
+    let pool = factory.get_pool(token_a, token_c).unwrap();
+    assert_within_tolerance(pool.total_supply, 2 * (1u128 << 64), 1);
+
+    // Reserves that cannot cover the locked minimum liquidity are rejected
+    let token_d = alkane_id("DUST_D");
+    assert!(factory.try_add_pool(token_a, token_d, 10, 10).is_err(), "Dust pools should be rejected");
+    assert!(factory.get_pool(token_a, token_d).is_none());
+
+    println!("✅ Near-max reserve bootstrap test passed");
+    Ok(())
+}
+
+#[test]
+fn test_lp_math_with_reserves_near_1e30() -> anyhow::Result<()> {
+    println!("Testing LP math with reserves near 1e30...");
+
+    let huge: u128 = 1_000_000_000_000_000_000_000_000_000_000; // 1e30
+
+    // amount * amount overflows u128, but the geometric mean is exact
+    assert_eq!(amm_logic::calculate_lp_tokens_minted(huge, huge, 0, 0, 0)?, huge);
+    assert_eq!(amm_logic::calculate_lp_tokens_minted(huge * 4, huge, 0, 0, 0)?, huge * 2);
+
+    // amount * total_supply overflows u128, but the pro-rata share does not
+    let minted = amm_logic::calculate_lp_tokens_minted(huge / 2, huge, huge, huge * 2, huge)?;
+    assert_eq!(minted, huge / 2);
+
+    // A result that cannot fit u128 is a clean error rather than a wrap
+    let err = amm_logic::calculate_lp_tokens_minted(u128::MAX, u128::MAX, 1, 1, u128::MAX).unwrap_err();
+    assert!(err.to_string().contains("exceed u128"), "Unexpected error: {}", err);
+    assert!(amm_logic::calculate_lp_tokens_minted(huge, huge, 0, huge, huge).is_err(), "Empty reserves cannot price a deposit");
+
+    // Quoting a zap into a 1e30-deep pool stays within range end to end
+    let mut factory = MockOylFactory::new();
+    let token_a = alkane_id("DEEP_A");
+    let token_b = alkane_id("DEEP_B");
+    factory.try_add_pool(token_a, token_b, huge, huge)?;
+    let pool = factory.get_pool(token_a, token_b).unwrap().clone();
+    let reserves = PoolReserves::new(token_a, token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let route_a = RouteInfo::new(vec![token_a], huge / 2);
+    let route_b = route_finder.find_best_route(token_a, token_b, huge / 2)?;
+    let quote = ZapCalculator::generate_zap_quote(token_a, huge, token_a, token_b, route_a, route_b, &reserves, 100, &route_finder)?;
+    assert!(quote.expected_lp_tokens > 0 && quote.expected_lp_tokens < pool.total_supply);
+
+    println!("✅ 1e30 LP math test passed");
+    Ok(())
+}
+
+#[test]
+fn test_projected_reserves_improve_lp_estimate() -> anyhow::Result<()> {
+    println!("Testing LP estimation against projected post-swap reserves...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 200_000 * 1_000_000; // 200k USDC
+
+    // Zap USDC into ETH/USDC, swapping the ETH half through the target pool itself
+    let pool = factory.get_pool(eth, usdc).unwrap().clone();
+    let target_pool_reserves =
+        PoolReserves::new(eth, usdc, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate);
+    let route_a = RouteInfo::new(vec![usdc, eth], 0);
+    let route_b = RouteInfo::new(vec![usdc], input_amount / 2);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+
+    let quote_with = |options: &QuoteOptions| {
+        ZapCalculator::generate_zap_quote_with_options(
+            &ZapParams::new(usdc, input_amount, eth, usdc, 0, 0).with_max_slippage(DEFAULT_SLIPPAGE),
+            route_a.clone(),
+            route_b.clone(),
+            &target_pool_reserves,
+            &route_finder,
+            options,
+        )
+    };
+    let naive = quote_with(&QuoteOptions::new())?;
+    let projected = quote_with(&QuoteOptions::new().with_projected_reserves(true))?;
+    assert_eq!(naive.split_amount_a, projected.split_amount_a, "Projection should not change the split");
+
+    // Execute against the pool: swap first, then add liquidity at the moved reserves
+    let mut execution_pool = pool.clone();
+    let eth_received = execution_pool.simulate_swap(usdc, naive.split_amount_a)?;
+    let actual_lp = execution_pool.simulate_add_liquidity(eth_received, naive.split_amount_b)?;
+
+    let naive_error = calculate_percentage_difference(naive.expected_lp_tokens, actual_lp);
+    let projected_error = calculate_percentage_difference(projected.expected_lp_tokens, actual_lp);
+    println!(
+        "Actual LP: {}, naive: {} ({} bps off), projected: {} ({} bps off)",
+        actual_lp, naive.expected_lp_tokens, naive_error, projected.expected_lp_tokens, projected_error
+    );
+    assert!(projected_error < naive_error, "Projected estimate should be more accurate");
+    assert!(projected_error <= 1, "Projected estimate should track execution closely");
+
+    println!("✅ Projected reserve LP estimation test passed");
+    Ok(())
+}
+
+#[test]
+fn test_stable_curve_price_impact() -> anyhow::Result<()> {
+    println!("Testing stable swap curve against constant product for stablecoins...");
+
+    let (mut factory, tokens) = setup_comprehensive_test_environment();
+    let usdc = tokens["USDC"];
+    let usdt = tokens["USDT"];
+    let amount_in = 1_000_000 * 1_000_000; // 1M USDC into a balanced 1M/1M pool
+
+    let pool = factory.get_pool(usdc, usdt).unwrap().clone();
+    let (reserve_in, reserve_out) = (pool.reserve_a, pool.reserve_b);
+
+    let product_out = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, pool.fee_rate)?;
+    let stable_out = amm_logic::calculate_swap_out_stable(amount_in, reserve_in, reserve_out, pool.fee_rate, 100)?;
+    let product_impact = amm_logic::calculate_price_impact(amount_in, reserve_in, product_out, reserve_out)?;
+    let stable_impact = amm_logic::calculate_price_impact(amount_in, reserve_in, stable_out, reserve_out)?;
+    println!(
+        "Constant product: {} out ({} bps impact), stable: {} out ({} bps impact)",
+        product_out, product_impact, stable_out, stable_impact
+    );
+
+    assert!(stable_out > product_out, "Stable curve should return more for a pegged pair");
+    assert!(stable_out < reserve_out, "Stable curve cannot drain the pool");
+    assert!(stable_impact * 4 < product_impact, "Stable curve impact should be far lower");
+
+    // Small trades on a stable pool trade at nearly 1:1 after fees
+    let small_out = amm_logic::calculate_swap_out_stable(1_000 * 1_000_000, reserve_in, reserve_out, 0, 100)?;
+    assert_within_tolerance(small_out, 1_000 * 1_000_000, 1);
+
+    // Routing picks up the pool's declared curve
+    factory.get_pool_mut(usdc, usdt).unwrap().curve = CurveKind::Stable { amp: 100 };
+    let route = RouteFinder::new(alkane_id("oyl_factory"), &factory).find_best_route(usdc, usdt, amount_in)?;
+    let stable_route_out = amm_logic::calculate_swap_out_stable(amount_in, reserve_in, reserve_out, 500, 100)?;
+    assert!(route.expected_output >= stable_route_out, "Route should price the stable pool on its curve");
+
+    println!("✅ Stable curve price impact test passed");
+    Ok(())
+}
+
+#[test]
+fn test_price_impact_measured_against_mid_price() -> anyhow::Result<()> {
+    println!("Testing price impact against the pre-trade mid-price...");
+
+    // A trade of 10% of the input reserve on a fee-free 1:2 constant product pool
+    let reserve_in = 1_000_000_000_000;
+    let reserve_out = 2_000_000_000_000;
+    let amount_in = reserve_in / 10;
+    let amount_out = amount_in * reserve_out / (reserve_in + amount_in);
+
+    // 1 - (out / in) / (reserve_out / reserve_in) = amount_in / (reserve_in + amount_in) = 1/11
+    let impact = amm_logic::calculate_price_impact(amount_in, reserve_in, amount_out, reserve_out)?;
+    assert_eq!(impact, 909, "A 10% pool fraction trade should move the price 9.09% from mid");
+
+    // Measuring against the post-trade price would report a different, smaller figure
+    let post_reserve_in = reserve_in + amount_in;
+    let post_reserve_out = reserve_out - amount_out;
+    let against_post_trade = amm_logic::calculate_price_impact(amount_in, post_reserve_in, amount_out, post_reserve_out)?;
+    assert!(against_post_trade < impact, "Post-trade reserves understate impact: {}", against_post_trade);
+
+    // Mid-prices that are not whole numbers are not rounded away before comparing
+    assert_eq!(amm_logic::calculate_price_impact(1, 3, 1, 7)?, 5714, "1 - 3/7 is 57.14%");
+
+    // A trade filled exactly at mid-price has no impact
+    assert_eq!(amm_logic::calculate_price_impact(amount_in, reserve_in, amount_in * 2, reserve_out)?, 0);
+
+    println!("✅ Mid-price impact test passed");
+    Ok(())
+}
+
+#[test]
+fn test_price_impact_edge_reserves() -> anyhow::Result<()> {
+    println!("Testing price impact on empty and drained reserves...");
+
+    let reserve = 1_000_000_000_000u128;
+
+    // An empty output reserve has no mid-price to measure against
+    assert!(amm_logic::calculate_price_impact(1_000, reserve, 0, 0).is_err());
+    assert!(amm_logic::calculate_price_impact(0, 0, 0, 0).is_err());
+
+    // No input-side liquidity is total impact rather than an error
+    assert_eq!(amm_logic::calculate_price_impact(1_000, 0, 0, reserve)?, 10000);
+
+    // A swap that drains over 99% of the output reserve is close to, but never past, 100%
+    let amount_in = reserve * 200;
+    let amount_out = amm_logic::calculate_swap_out(amount_in, reserve, reserve, 0)?;
+    assert!(amount_out * 100 > reserve * 99, "Swap should consume >99% of reserve_out: {}", amount_out);
+    let drained = amm_logic::calculate_price_impact(amount_in, reserve, amount_out, reserve)?;
+    assert!(drained > 9900 && drained <= 10000, "Draining impact out of range: {}", drained);
+
+    // Output reported above mid-price clamps to zero, and nothing escapes [0, 10000]
+    let normal_in = reserve / 1000;
+    let normal_out = amm_logic::calculate_swap_out(normal_in, reserve, reserve, 0)?;
+    for (amount_in, reserve_in, amount_out, reserve_out) in [
+        (normal_in, reserve, normal_out, reserve),
+        (normal_in, reserve, normal_in * 3, reserve),
+        (u128::MAX, reserve, 1, reserve),
+        (1, u128::MAX, 0, 1),
+        (amount_in, reserve, amount_out, reserve),
+    ] {
+        let impact = amm_logic::calculate_price_impact(amount_in, reserve_in, amount_out, reserve_out)?;
+        assert!(impact <= 10000, "Impact {} out of bounds", impact);
+    }
+    let normal = amm_logic::calculate_price_impact(normal_in, reserve, normal_out, reserve)?;
+    assert!(normal > 0 && normal < 100, "A 0.1% trade should move the price by ~0.1%: {}", normal);
+
+    // Routing skips an unpriceable pool instead of failing the whole search
+    let input = alkane_id("IN");
+    let output = alkane_id("OUT");
+    let middle = alkane_id("MID");
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, output, reserve, reserve);
+    factory.get_pool_mut(input, output).unwrap().reserve_b = 0;
+    factory.add_pool(input, middle, reserve, reserve);
+    factory.add_pool(middle, output, reserve, reserve);
+    let route_finder = RouteFinder::new(alkane_id("FACTORY"), &factory).with_min_pool_liquidity(0);
+    let routes = route_finder.find_all_routes(input, output, normal_in)?;
+    assert!(routes.iter().all(|route| route.path != vec![input, output]));
+    assert!(routes.iter().all(|route| route.price_impact <= 10000));
+
+    println!("✅ Price impact edge reserves test passed");
+    Ok(())
+}
+
+#[test]
+fn test_compound_price_impact_stays_in_bounds() -> anyhow::Result<()> {
+    println!("Testing path price impact compounding bounds...");
+
+    // A hop with no impact leaves the full fraction, so the path has none
+    assert_eq!(amm_logic::compound_price_impact(&[0]), 0);
+    assert_eq!(amm_logic::compound_price_impact(&[0, 0, 0]), 0);
+    assert_eq!(amm_logic::compound_price_impact(&[]), 0);
+
+    // Impacts compound multiplicatively: 1 - 0.9 * 0.9 = 19%
+    assert_eq!(amm_logic::compound_price_impact(&[1000, 1000]), 1900);
+    assert_eq!(amm_logic::compound_price_impact(&[1000, 0]), 1000);
+
+    // Near-total impact stays just below 100% and never wraps
+    assert_eq!(amm_logic::compound_price_impact(&[9999]), 9999);
+    assert_eq!(amm_logic::compound_price_impact(&[9999, 9999]), 10000);
+    assert_eq!(amm_logic::compound_price_impact(&[10000]), 10000);
+
+    // Out-of-range hop impacts are clamped rather than underflowing
+    assert_eq!(amm_logic::compound_price_impact(&[u128::MAX]), 10000);
+    assert_eq!(amm_logic::compound_price_impact(&[20000, 0]), 10000);
+
+    // A route draining nearly the whole pool reports close to full impact
+    let mut factory = MockOylFactory::new();
+    let (token_in, token_out) = (alkane_id("THIN_IN"), alkane_id("THIN_OUT"));
+    factory.add_pool(token_in, token_out, 1_000, 1_000);
+    let route = RouteFinder::new(alkane_id("FACTORY"), &factory).find_best_route(token_in, token_out, 1_000_000_000)?;
+    assert!((9990..=10000).contains(&route.price_impact), "Impact was {}", route.price_impact);
+
+    println!("✅ Compound price impact bounds test passed");
+    Ok(())
+}
+
+#[test]
+fn test_swap_in_round_trips_swap_out() -> anyhow::Result<()> {
+    println!("Testing exact-output swap math against exact-input swaps...");
+
+    let pools = [
+        (1_000_000 * 1e18 as u128, 1_000_000 * 1e18 as u128, TEST_FEE_RATE),
+        (5_000 * 1e18 as u128, 5_100 * 1e18 as u128, 30),
+        (1_000_000_000_000, 1_000_000_000_000, 0),
+    ];
+    for (reserve_in, reserve_out, fee_bps) in pools {
+        for amount_out in [1, 1_000, reserve_out / 1_000, reserve_out / 10, reserve_out / 2] {
+            let amount_in = amm_logic::calculate_swap_in(amount_out, reserve_in, reserve_out, fee_bps)?;
+
+            // The input buys the requested output, give or take one unit of rounding
+            let realized = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, fee_bps)?;
+            assert!(
+                realized >= amount_out && realized - amount_out <= 1,
+                "Swapping {} in returned {} for a requested {}",
+                amount_in, realized, amount_out
+            );
+            // ...and is the least input that does
+            if amount_in > 1 {
+                let short = amm_logic::calculate_swap_out(amount_in - 1, reserve_in, reserve_out, fee_bps)?;
+                assert!(short < amount_out, "One unit less input should fall short");
+            }
+        }
+    }
+
+    // The whole output reserve can never be bought
+    assert!(amm_logic::calculate_swap_in(1_000, 1_000, 1_000, 30).is_err());
+    assert!(amm_logic::calculate_swap_in(1_001, 1_000, 1_000, 30).is_err());
+    assert!(amm_logic::calculate_swap_in(0, 1_000, 1_000, 30).is_err());
+
+    println!("✅ Exact-output swap round trip test passed");
+    Ok(())
+}
+
+#[test]
+fn test_exact_split_lands_on_pool_ratio() -> anyhow::Result<()> {
+    println!("Testing exact split against the binary-search split...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let pool = factory.get_pool(eth, usdc).unwrap().clone();
+    let target_pool_reserves =
+        PoolReserves::new(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate);
+    let route_a = RouteInfo::new(vec![uni, eth], 0);
+    let route_b = RouteInfo::new(vec![uni, usdc], 0);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+
+    let quote_with = |options: &QuoteOptions| {
+        ZapCalculator::generate_zap_quote_with_options(
+            &ZapParams::new(uni, input_amount, eth, usdc, 0, 0).with_max_slippage(DEFAULT_SLIPPAGE),
+            route_a.clone(),
+            route_b.clone(),
+            &target_pool_reserves,
+            &route_finder,
+            options,
+        )
+    };
+    let searched = quote_with(&QuoteOptions::new())?;
+    let exact = quote_with(&QuoteOptions::new().with_exact_split(true))?;
+    assert_eq!(exact.split_amount_a + exact.split_amount_b, input_amount);
+    println!(
+        "Searched split {:?} leaves dust {:?}; exact split {:?} leaves dust {:?}",
+        (searched.split_amount_a, searched.split_amount_b),
+        (searched.expected_dust_a, searched.expected_dust_b),
+        (exact.split_amount_a, exact.split_amount_b),
+        (exact.expected_dust_a, exact.expected_dust_b),
+    );
+
+    // The exact split deposits at least as much and strands no more of either token
+    assert!(exact.expected_lp_tokens >= searched.expected_lp_tokens);
+    assert!(exact.expected_dust_a <= searched.expected_dust_a.max(1));
+    assert!(exact.expected_dust_b <= searched.expected_dust_b.max(1));
+
+    // Its outputs sit on the pool ratio to within rounding of the smaller side
+    let (reserve_eth, reserve_usdc) = if pool.token_a == eth { (pool.reserve_a, pool.reserve_b) } else { (pool.reserve_b, pool.reserve_a) };
+    let matched_eth = exact.route_b.expected_output * reserve_eth / reserve_usdc;
+    assert!(exact.route_a.expected_output.abs_diff(matched_eth) <= exact.route_a.expected_output / 1_000_000_000);
+
+    println!("✅ Exact split test passed");
+    Ok(())
+}
+
+#[test]
+fn test_apply_slippage_large_amounts() -> anyhow::Result<()> {
+    println!("Testing slippage minimums on amounts near u128::MAX...");
+
+    // amount * 9900 would overflow u128 if computed directly
+    let amount = u128::MAX / 2;
+    let minimum = amm_logic::apply_slippage(amount, 100)?;
+    assert_eq!(minimum, amount / 100 * 99 + (amount % 100) * 99 / 100);
+    assert_eq!(ZapCalculator::calculate_minimum_lp_tokens(amount, 100)?, minimum);
+
+    assert_eq!(amm_logic::apply_slippage(u128::MAX, 0)?, u128::MAX);
+    assert_eq!(amm_logic::apply_slippage(u128::MAX, 10000)?, 0);
+    assert!(amm_logic::apply_slippage(amount, 10001).is_err());
+
+    println!("✅ Large-amount slippage test passed");
+    Ok(())
+}
+
+#[test]
+fn test_optimal_swap_fraction_leaves_little_dust() -> anyhow::Result<()> {
+    println!("Testing the single-sided swap fraction against the post-swap ratio...");
+
+    let pools = [
+        (1_000 * TEST_PRECISION, 2_000_000 * 1_000_000, 30),
+        (5_000 * TEST_PRECISION, 5_100 * TEST_PRECISION, TEST_FEE_RATE),
+        (1_000_000_000_000, 1_000_000_000_000, 0),
+    ];
+    for (reserve_in, reserve_out, fee_bps) in pools {
+        for amount_in in [reserve_in / 10_000, reserve_in / 100, reserve_in / 2, reserve_in * 3] {
+            let swap_amount = amm_logic::optimal_swap_fraction(amount_in, reserve_in, fee_bps);
+            let swap_out = amm_logic::calculate_swap_out(swap_amount, reserve_in, reserve_out, fee_bps)?;
+
+            // Deposit the remainder and the proceeds against the pool the swap leaves behind
+            let kept = amount_in - swap_amount;
+            let (reserve_in_after, reserve_out_after) = (reserve_in + swap_amount, reserve_out - swap_out);
+            let (consumed_in, consumed_out) =
+                amm_logic::quote_add_liquidity(kept, swap_out, reserve_in_after, reserve_out_after)?;
+
+            // The leftover on either side is worth under 0.1% of the input
+            let dust_in = kept - consumed_in;
+            let dust_out_in_input = (swap_out - consumed_out) * reserve_in_after / reserve_out_after;
+            assert!(
+                (dust_in + dust_out_in_input) * 1_000 < amount_in,
+                "Swapping {} of {} left {} + {} dust",
+                swap_amount, amount_in, dust_in, dust_out_in_input
+            );
+        }
+    }
+
+    // Degenerate inputs swap nothing, and reserves near u128::MAX do not overflow
+    assert_eq!(amm_logic::optimal_swap_fraction(0, 1_000, 30), 0);
+    assert_eq!(amm_logic::optimal_swap_fraction(1_000, 0, 30), 0);
+    assert_eq!(amm_logic::optimal_swap_fraction(1_000, 1_000, 10_000), 0);
+    let huge = u128::MAX / 4;
+    let swap_amount = amm_logic::optimal_swap_fraction(huge, huge, 30);
+    assert!(swap_amount > huge / 3 && swap_amount < huge / 2);
+
+    println!("✅ Single-sided swap fraction test passed");
+    Ok(())
+}
+
+#[test]
+fn test_verify_constant_product() -> anyhow::Result<()> {
+    println!("Testing the constant product invariant checker...");
+    
+    let reserve_in = 1_000_000 * TEST_PRECISION;
+    let reserve_out = 2_000_000 * TEST_PRECISION;
+    let amount_in = 10_000 * TEST_PRECISION;
+    
+    // Outputs priced by the swap formula pass, with or without a fee
+    for fee_bps in [0, 30, 100] {
+        let amount_out = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, fee_bps)?;
+        assert!(
+            amm_logic::verify_constant_product(reserve_in, reserve_out, amount_in, amount_out, fee_bps),
+            "Priced output should pass at {} bps", fee_bps
+        );
+        
+        // A single unit more than the formula allows breaks the invariant
+        assert!(
+            !amm_logic::verify_constant_product(reserve_in, reserve_out, amount_in, amount_out + 1, fee_bps),
+            "Over-generous output should fail at {} bps", fee_bps
+        );
+    }
+    
+    // Without a fee, the fee-free output is exactly the most the pool can pay
+    let fee_free_out = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, 0)?;
+    let with_fee_out = amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, 30)?;
+    assert!(fee_free_out > with_fee_out);
+    assert!(!amm_logic::verify_constant_product(reserve_in, reserve_out, amount_in, fee_free_out, 30));
+    
+    // Nothing in and nothing out keeps K; anything out for nothing in, or draining the pool, does not
+    assert!(amm_logic::verify_constant_product(reserve_in, reserve_out, 0, 0, 0));
+    assert!(!amm_logic::verify_constant_product(reserve_in, reserve_out, 0, 1, 0));
+    assert!(!amm_logic::verify_constant_product(reserve_in, reserve_out, u128::MAX, reserve_out, 0));
+    
+    println!("✅ Constant product invariant checker test passed");
+    Ok(())
+}
+
+/// Deterministic xorshift generator, so failing inputs reproduce from the seed
+struct XorShift(u64);
+
+impl XorShift {
+    fn next_u64(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A value of random magnitude between 2^min_bits and 2^max_bits
+    fn next_magnitude(&mut self, min_bits: u32, max_bits: u32) -> u128 {
+        let bits = min_bits + (self.next_u64() as u32) % (max_bits - min_bits + 1);
+        let raw = (u128::from(self.next_u64()) << 64) | u128::from(self.next_u64());
+        (raw >> (128 - bits)) | (1 << (bits - 1))
+    }
+}
+
+#[test]
+fn test_calculator_bounds_under_random_large_values() -> anyhow::Result<()> {
+    println!("Testing calculator bounds across random large values...");
+
+    let input = alkane_id("FUZZ_IN");
+    let token_a = alkane_id("FUZZ_A");
+    let token_b = alkane_id("FUZZ_B");
+    let route_a = RouteInfo::new(vec![input, token_a], 0);
+    let route_b = RouteInfo::new(vec![input, token_b], 0);
+    let mut rng = XorShift(0x5eed_1804);
+    let mut checked = 0;
+
+    for _ in 0..300 {
+        let mut factory = MockOylFactory::new();
+        let pools = [(input, token_a), (input, token_b), (token_a, token_b)];
+        let added = pools.iter().all(|(x, y)| {
+            let (reserve_x, reserve_y) = (rng.next_magnitude(10, 127), rng.next_magnitude(10, 127));
+            factory.try_add_pool(*x, *y, reserve_x, reserve_y).is_ok()
+        });
+        if !added {
+            continue;
+        }
+        let input_amount = rng.next_magnitude(1, 127);
+        let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+        let target_pool_reserves = factory.get_pool_reserves(token_a, token_b)?;
+
+        // Errors are fine at the extremes; panics and out-of-bounds results are not
+        if let Ok((split_a, split_b)) =
+            ZapCalculator::calculate_optimal_split(input_amount, &route_a, &route_b, &target_pool_reserves, &route_finder)
+        {
+            assert_eq!(split_a.checked_add(split_b), Some(input_amount), "Splits must sum to the input");
+            if let Ok(impact) =
+                ZapCalculator::calculate_overall_price_impact(&route_a, &route_b, split_a, split_b, &route_finder)
+            {
+                assert!(impact <= 10_000, "Impact {} exceeds 100%", impact);
+            }
+            checked += 1;
+        }
+
+        let reserve_in = rng.next_magnitude(1, 127);
+        let reserve_out = rng.next_magnitude(1, 127);
+        if let Ok(amount_out) = amm_logic::calculate_swap_out(input_amount, reserve_in, reserve_out, 30) {
+            assert!(amount_out < reserve_out, "A swap cannot drain the pool");
+            if let Ok(impact) = amm_logic::calculate_price_impact(input_amount, reserve_in, amount_out, reserve_out) {
+                assert!(impact <= 10_000);
+            }
+        }
+        if let Ok((deposit_a, deposit_b)) = amm_logic::quote_add_liquidity(input_amount, reserve_out, reserve_in, reserve_out) {
+            assert!(deposit_a <= input_amount && deposit_b <= reserve_out, "Deposits cannot exceed the amounts offered");
+        }
+    }
+    assert!(checked > 0, "Some random pools should produce a split");
+
+    println!("✅ Calculator bounds test passed ({} splits checked)", checked);
+    Ok(())
+}
+
+#[test]
+fn test_value_weighted_price_impact_reflects_leg_value() -> anyhow::Result<()> {
+    println!("Testing value-weighted price impact...");
+
+    let input = alkane_id("VALUE_IN");
+    let cheap = alkane_id("CHEAP");
+    let dear = alkane_id("DEAR");
+    let usdc = alkane_id("USDC");
+
+    // Both legs swap the same number of input units, but the dear leg trades in a shallow
+    // pool and its output is worth a thousand times more
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, cheap, 1_000 * TEST_PRECISION, 1_000 * TEST_PRECISION);
+    factory.add_pool(input, dear, 10 * TEST_PRECISION, 10 * TEST_PRECISION);
+    factory.add_pool(cheap, usdc, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    factory.add_pool(dear, usdc, 1_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    factory.add_pool(cheap, dear, 100_000 * TEST_PRECISION, 100 * TEST_PRECISION);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let route_a = RouteInfo::new(vec![input, cheap], 0);
+    let route_b = RouteInfo::new(vec![input, dear], 0);
+    let split = TEST_PRECISION;
+
+    let raw = ZapCalculator::calculate_overall_price_impact(&route_a, &route_b, split, split, &route_finder)?;
+    let weighted =
+        ZapCalculator::calculate_value_weighted_price_impact(&route_a, &route_b, split, split, usdc, &route_finder)?;
+    let dear_impact = route_finder.price_path(&route_b.path, split)?.price_impact;
+    println!("Raw weighting: {} bps, value weighting: {} bps, dear leg alone: {} bps", raw, weighted, dear_impact);
+
+    // Equal splits average the legs; valuing them lets the dear leg dominate
+    assert!(weighted > raw, "Value weighting should lean towards the dear leg");
+    assert!(dear_impact - weighted < weighted - raw);
+    assert!(weighted <= dear_impact);
+
+    // Quotes use the value weighting only when given a reference token
+    let target_pool_reserves = factory.get_pool_reserves(cheap, dear)?;
+    let quote_with = |options: &QuoteOptions| {
+        ZapCalculator::generate_zap_quote_with_options(
+            &ZapParams::new(input, 2 * split, cheap, dear, 0, 0).with_max_slippage(DEFAULT_SLIPPAGE),
+            route_a.clone(),
+            route_b.clone(),
+            &target_pool_reserves,
+            &route_finder,
+            options,
+        )
+    };
+    let plain = quote_with(&QuoteOptions::new())?;
+    let valued = quote_with(&QuoteOptions::new().with_value_reference(usdc))?;
+    assert_eq!((plain.split_amount_a, plain.split_amount_b), (valued.split_amount_a, valued.split_amount_b));
+    assert_eq!(
+        plain.price_impact,
+        ZapCalculator::calculate_overall_price_impact(&route_a, &route_b, plain.split_amount_a, plain.split_amount_b, &route_finder)?
+    );
+    assert_eq!(
+        valued.price_impact,
+        ZapCalculator::calculate_value_weighted_price_impact(
+            &route_a,
+            &route_b,
+            valued.split_amount_a,
+            valued.split_amount_b,
+            usdc,
+            &route_finder,
+        )?
+    );
+    assert_ne!(plain.price_impact, valued.price_impact);
+
+    println!("✅ Value-weighted price impact test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_add_liquidity_consumes_at_pool_ratio() -> anyhow::Result<()> {
+    println!("Testing add-liquidity quotes against the pool ratio...");
+
+    // An empty pool takes both sides whole, whatever their ratio
+    assert_eq!(amm_logic::quote_add_liquidity(1_000, 7, 0, 0)?, (1_000, 7));
+    assert_eq!(amm_logic::quote_add_liquidity(1_000, 7, 0, 5_000)?, (1_000, 7));
+
+    // A 1:2 pool offered too much B takes all of A and the B that matches it
+    let (reserve_a, reserve_b) = (1_000_000 * TEST_PRECISION, 2_000_000 * TEST_PRECISION);
+    let (used_a, used_b) = amm_logic::quote_add_liquidity(100 * TEST_PRECISION, 300 * TEST_PRECISION, reserve_a, reserve_b)?;
+    assert_eq!((used_a, used_b), (100 * TEST_PRECISION, 200 * TEST_PRECISION));
+
+    // Offered too much A, it falls back to all of B and the A that matches it
+    let (used_a, used_b) = amm_logic::quote_add_liquidity(100 * TEST_PRECISION, 100 * TEST_PRECISION, reserve_a, reserve_b)?;
+    assert_eq!((used_a, used_b), (50 * TEST_PRECISION, 100 * TEST_PRECISION));
+
+    // The dust preview is whatever the quote leaves unused
+    let pool = PoolReserves::new(alkane_id("A"), alkane_id("B"), reserve_a, reserve_b, reserve_a, 30);
+    let dust = ZapCalculator::calculate_expected_dust(100 * TEST_PRECISION, 100 * TEST_PRECISION, alkane_id("A"), &pool)?;
+    assert_eq!(dust, (50 * TEST_PRECISION, 0));
+
+    println!("✅ Add-liquidity quote test passed");
+    Ok(())
+}
@@ -5,6 +5,161 @@
 
 mod common;
 use common::*;
+use alkanes_support::id::AlkaneId;
+use alkanes_support::response::CallResponse;
+use oyl_zap_core::amm_logic;
+use oyl_zap_core::pool_provider::{CachedPoolProvider, PoolProvider};
+use oyl_zap_core::route_finder::RouteFinder;
+use oyl_zap_core::{canonical_pool_key, contract_version, pool_storage_key, ZapBase, ZapPoolProvider};
+use oyl_zap_core::types::{decode_token_list, CurveKind, DefaultGasModel, GasModel, HopBreakdown, PoolReserves, RouteInfo, RouteScoring, StoredPool, ZapConfig, ZapError, MAX_HOPS, MAX_ROUTES, MAX_ROUTE_BATCH, MINIMUM_LIQUIDITY, PRICE_PRECISION, U256};
+use std::cell::{Cell, RefCell};
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+
+/// Provider that erroneously lists every token as connected to itself
+struct SelfConnectedProvider {
+    factory: MockOylFactory,
+}
+
+impl PoolProvider for SelfConnectedProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        self.factory.get_pool_reserves(token_a, token_b)
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        let mut connected = self.factory.get_connected_tokens(token)?;
+        connected.insert(0, token);
+        Ok(connected)
+    }
+}
+
+/// Gas model charging stable-curve hops for their iterative invariant and cold pools for the
+/// first load of their storage
+struct CurveAwareGasModel;
+
+impl GasModel for CurveAwareGasModel {
+    fn cost_for_hop(&self, curve_kind: CurveKind, is_cold: bool) -> u128 {
+        let swap = match curve_kind {
+            CurveKind::ConstantProduct => 40_000,
+            CurveKind::Stable { .. } => 90_000,
+        };
+        if is_cold { swap + 20_000 } else { swap }
+    }
+}
+
+/// Provider charging one pool a different fee in each direction
+struct DirectionalFeeProvider {
+    factory: MockOylFactory,
+    pair: (AlkaneId, AlkaneId),
+    fees: (u128, u128), // (pair.0 -> pair.1, pair.1 -> pair.0) in bps
+}
+
+impl PoolProvider for DirectionalFeeProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        let reserves = self.factory.get_pool_reserves(token_a, token_b)?;
+        if canonical_pool_key(token_a, token_b) != canonical_pool_key(self.pair.0, self.pair.1) {
+            return Ok(reserves);
+        }
+        Ok(reserves.oriented(self.pair.0).with_directional_fees(self.fees.0, self.fees.1))
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        self.factory.get_connected_tokens(token)
+    }
+}
+
+/// Provider that counts how often the pool graph is queried
+struct CountingProvider {
+    factory: MockOylFactory,
+    lookups: Cell<usize>,
+}
+
+impl PoolProvider for CountingProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        self.lookups.set(self.lookups.get() + 1);
+        self.factory.get_pool_reserves(token_a, token_b)
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        self.lookups.set(self.lookups.get() + 1);
+        self.factory.get_connected_tokens(token)
+    }
+}
+
+/// Provider that counts the reserve lookups made for each pair
+struct PairLookupProvider {
+    factory: MockOylFactory,
+    lookups: RefCell<HashMap<(AlkaneId, AlkaneId), usize>>,
+}
+
+impl PoolProvider for PairLookupProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        *self.lookups.borrow_mut().entry((token_a, token_b)).or_insert(0) += 1;
+        self.factory.get_pool_reserves(token_a, token_b)
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        self.factory.get_connected_tokens(token)
+    }
+}
+
+/// Provider that, like the on-chain one, can look up pools but not list a token's neighbours
+struct DirectPoolsOnlyProvider {
+    factory: MockOylFactory,
+}
+
+impl PoolProvider for DirectPoolsOnlyProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        self.factory.get_pool_reserves(token_a, token_b)
+    }
+
+    fn get_connected_tokens(&self, _token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        Ok(Vec::new())
+    }
+}
+
+/// Provider that lists a token's neighbours in the reverse of the factory's order
+struct ReversedConnectionsProvider {
+    factory: MockOylFactory,
+}
+
+impl PoolProvider for ReversedConnectionsProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        self.factory.get_pool_reserves(token_a, token_b)
+    }
+
+    fn get_connected_tokens(&self, token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        let mut connected = self.factory.get_connected_tokens(token)?;
+        connected.reverse();
+        Ok(connected)
+    }
+}
+
+/// Provider that answers reserve batches in one multicall, counting batched and single lookups
+struct MulticallProvider {
+    factory: MockOylFactory,
+    batches: Cell<usize>,
+    single_lookups: Cell<usize>,
+}
+
+impl PoolProvider for MulticallProvider {
+    fn get_pool_reserves(&self, token_a: AlkaneId, token_b: AlkaneId) -> anyhow::Result<PoolReserves> {
+        self.single_lookups.set(self.single_lookups.get() + 1);
+        self.factory.get_pool_reserves(token_a, token_b)
+    }
+
+    fn get_connected_tokens(&self, _token: AlkaneId) -> anyhow::Result<Vec<AlkaneId>> {
+        Ok(Vec::new())
+    }
+
+    fn get_pool_reserves_batch(&self, pairs: &[(AlkaneId, AlkaneId)]) -> anyhow::Result<Vec<Option<PoolReserves>>> {
+        self.batches.set(self.batches.get() + 1);
+        Ok(pairs
+            .iter()
+            .map(|(token_a, token_b)| self.factory.get_pool_reserves(*token_a, *token_b).ok())
+            .collect())
+    }
+}
 
 #[test]
 fn test_direct_route_discovery() -> anyhow::Result<()> {
@@ -417,3 +572,1389 @@ fn test_route_comparison_and_selection() -> anyhow::Result<()> {
     println!("✅ Route comparison and selection test passed");
     Ok(())
 }
+
+#[test]
+fn test_self_connected_tokens_are_ignored() -> anyhow::Result<()> {
+    println!("Testing route finding with self-connected tokens...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let provider = SelfConnectedProvider { factory };
+
+    let comp = tokens["COMP"];
+    let aave = tokens["AAVE"];
+    let amount = 100 * 1e18 as u128; // 100 COMP
+
+    let route = RouteFinder::new(alkane_id("oyl_factory"), &provider)
+        .find_best_route(comp, aave, amount)?;
+
+    assert_eq!(route.path[0], comp, "Route should start with COMP");
+    assert_eq!(*route.path.last().unwrap(), aave, "Route should end with AAVE");
+    for hop in route.path.windows(2) {
+        assert_ne!(hop[0], hop[1], "Route should never contain a self-hop");
+    }
+    validate_route_info(&route)?;
+
+    println!("✅ Self-connected tokens test passed");
+    Ok(())
+}
+
+#[test]
+fn test_dijkstra_finds_path_through_shared_intermediate() -> anyhow::Result<()> {
+    println!("Testing best-output search on a diamond pool graph...");
+
+    // A -> B is shallow, while A -> C -> B is deep; both legs share B on the way to D.
+    // The BFS marks B visited from A and never explores the deeper A -> C -> B leg.
+    let mut factory = MockOylFactory::new();
+    let token_a = alkane_id("DIAMOND_A");
+    let token_b = alkane_id("DIAMOND_B");
+    let token_c = alkane_id("DIAMOND_C");
+    let token_d = alkane_id("DIAMOND_D");
+    factory.add_pool(token_a, token_b, 1_000 * TEST_PRECISION, 1_000 * TEST_PRECISION);
+    factory.add_pool(token_a, token_c, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    factory.add_pool(token_c, token_b, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    factory.add_pool(token_b, token_d, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+
+    let amount = 100 * TEST_PRECISION;
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+
+    let route = route_finder.find_best_route_dijkstra(token_a, token_d, amount)?;
+    assert_eq!(route.path, vec![token_a, token_c, token_b, token_d], "Should take the deep leg");
+    validate_route_info(&route)?;
+
+    let shallow_output = {
+        let shallow = factory.get_pool(token_a, token_b).unwrap();
+        let deep = factory.get_pool(token_b, token_d).unwrap();
+        let mid = amm_logic::calculate_swap_out(amount, shallow.reserve_a, shallow.reserve_b, 500)?;
+        amm_logic::calculate_swap_out(mid, deep.reserve_a, deep.reserve_b, 500)?
+    };
+    assert!(route.expected_output > shallow_output, "Deep leg should out-produce the shallow leg");
+
+    // find_best_route picks up the better path as well
+    let best = route_finder.find_best_route(token_a, token_d, amount)?;
+    assert_eq!(best.path, route.path);
+
+    // Excluding C as an intermediate forces the shallow leg
+    let excluded = RouteFinder::new(alkane_id("oyl_factory"), &factory)
+        .with_excluded_intermediate_tokens(&[token_c])
+        .find_best_route_dijkstra(token_a, token_d, amount)?;
+    assert_eq!(excluded.path, vec![token_a, token_b, token_d]);
+
+    println!("✅ Diamond graph search test passed");
+    Ok(())
+}
+
+#[test]
+fn test_zap_routes_never_trade_against_target_pool() -> anyhow::Result<()> {
+    println!("Testing zap routes exclude circular trades through the target pool...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let wbtc = tokens["WBTC"];
+    let amount = 10 * 1e18 as u128; // 10 ETH
+
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory)
+        .with_base_tokens(vec![eth, usdc, tokens["USDT"], tokens["DAI"]]);
+
+    // Unrestricted, ETH -> USDC goes straight through the ETH/USDC pool
+    let naive = route_finder.find_best_route(eth, usdc, amount / 2)?;
+    assert_eq!(naive.path, vec![eth, usdc], "Direct pool should be the naive best route");
+
+    // Zapping ETH into ETH/USDC is single-sided, so its swap leg may use the target pool
+    let (route_a, route_b) = route_finder.find_zap_routes(eth, amount, eth, usdc)?;
+    assert_eq!(route_a.path, vec![eth], "ETH side should be a direct contribution");
+    assert_eq!(route_b.path, vec![eth, usdc], "Single-sided leg should swap through the target pool");
+
+    // The same protection holds for routes that would cross through the sibling target
+    let target_guard = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_excluded_pool(eth, usdc);
+    let (route_a, route_b) = route_finder.find_zap_routes(wbtc, amount, eth, usdc)?;
+    for route in [&route_a, &route_b] {
+        assert!(target_guard.is_route_allowed(&route.path), "Route {:?} is circular", route.path);
+    }
+    assert!(!target_guard.is_route_allowed(&[wbtc, usdc, eth]), "Target pool hop should be rejected");
+    assert!(!target_guard.is_route_allowed(&[wbtc, eth, wbtc, eth]), "Cycles should be rejected");
+
+    println!("✅ Circular route exclusion test passed");
+    Ok(())
+}
+
+#[test]
+fn test_single_sided_zap_quotes_through_only_target_pool() -> anyhow::Result<()> {
+    println!("Testing single-sided zap quotes into a pair with no other liquidity...");
+
+    let mut zap = MockOylZap::new();
+    let lone_a = alkane_id("LONE_A");
+    let lone_b = alkane_id("LONE_B");
+    zap.factory.add_pool(lone_a, lone_b, 1_000 * TEST_PRECISION, 2_000 * TEST_PRECISION);
+    let amount = 10 * TEST_PRECISION;
+
+    // The target pool is the pair's only liquidity, so the quote must swap through it
+    let quote = zap.get_zap_quote(lone_a, amount, lone_a, lone_b, DEFAULT_SLIPPAGE)?;
+    assert_eq!(quote.route_a.path, vec![lone_a]);
+    assert_eq!(quote.route_b.path, vec![lone_a, lone_b]);
+    assert!(quote.expected_lp_tokens > 0);
+
+    // Executing the quote follows the same route
+    let lp_tokens = zap.execute_zap(&quote)?;
+    assert!(lp_tokens >= quote.minimum_lp_tokens, "Execution should honor the quote");
+
+    println!("✅ Single-sided target pool quote test passed");
+    Ok(())
+}
+
+#[test]
+fn test_batch_routes_match_individual_lookups() -> anyhow::Result<()> {
+    println!("Testing batch route finding...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let base_tokens = vec![tokens["WBTC"], tokens["ETH"], tokens["USDC"]];
+    let orphan = alkane_id("ORPHAN");
+
+    let queries = vec![
+        (tokens["UNI"], tokens["USDC"], 100 * 1e18 as u128),
+        (tokens["WBTC"], tokens["DAI"], 1e8 as u128),
+        (tokens["ETH"], orphan, 1e18 as u128), // no pool touches ORPHAN
+        (tokens["ETH"], tokens["ETH"], 1e18 as u128), // self-route is never valid
+        (tokens["UNI"], tokens["USDC"], 100 * 1e18 as u128), // repeated query
+    ];
+
+    // Every batch answer matches the corresponding individual lookup
+    let individual_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_base_tokens(base_tokens.clone());
+    let cached = CachedPoolProvider::new(&factory);
+    let batch_finder = RouteFinder::new(alkane_id("oyl_factory"), &cached).with_base_tokens(base_tokens.clone());
+    let batch = batch_finder.find_best_routes(&queries)?;
+    assert_eq!(batch.len(), queries.len());
+    for ((from_token, to_token, amount_in), route) in queries.iter().zip(&batch) {
+        let expected = individual_finder.find_best_route(*from_token, *to_token, *amount_in).ok();
+        assert_eq!(route, &expected, "Batch route for {:?} -> {:?} differs", from_token, to_token);
+    }
+    assert!(batch[0].is_some() && batch[1].is_some());
+    assert!(batch[2].is_none() && batch[3].is_none(), "Unroutable pairs should yield the sentinel");
+
+    // The wire format preserves the sentinel for unroutable pairs
+    assert_eq!(RouteInfo::deserialize_batch(&RouteInfo::serialize_batch(&batch))?, batch);
+
+    // Sharing one snapshot needs fewer pool queries than routing each pair separately
+    let counting = CountingProvider { factory: factory.clone(), lookups: Cell::new(0) };
+    let counting_finder = RouteFinder::new(alkane_id("oyl_factory"), &counting).with_base_tokens(base_tokens.clone());
+    for (from_token, to_token, amount_in) in &queries {
+        let _ = counting_finder.find_best_route(*from_token, *to_token, *amount_in);
+    }
+    let individual_lookups = counting.lookups.replace(0);
+    let cached_counting = CachedPoolProvider::new(&counting);
+    RouteFinder::new(alkane_id("oyl_factory"), &cached_counting)
+        .with_base_tokens(base_tokens.clone())
+        .find_best_routes(&queries)?;
+    println!("Pool queries: {} individually, {} batched", individual_lookups, counting.lookups.get());
+    assert!(counting.lookups.get() < individual_lookups, "Batching should reuse pool lookups");
+
+    // Oversized batches are rejected outright
+    let oversized = vec![queries[0]; MAX_ROUTE_BATCH + 1];
+    assert!(batch_finder.find_best_routes(&oversized).is_err());
+
+    println!("✅ Batch route finding test passed");
+    Ok(())
+}
+
+#[test]
+fn test_find_all_routes_fetches_reserves_in_one_batch() -> anyhow::Result<()> {
+    println!("Testing batched reserve lookups during route discovery...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let base_tokens = vec![tokens["ETH"], tokens["USDC"], tokens["USDT"], tokens["DAI"]];
+    let wbtc = tokens["WBTC"];
+    let dai = tokens["DAI"];
+    let amount = 1e8 as u128; // 1 WBTC
+
+    let multicall = MulticallProvider { factory: factory.clone(), batches: Cell::new(0), single_lookups: Cell::new(0) };
+    let routes = RouteFinder::new(alkane_id("oyl_factory"), &multicall)
+        .with_base_tokens(base_tokens.clone())
+        .find_all_routes(wbtc, dai, amount)?;
+
+    // Every direct and base-token pool came from the one batch, including missing ones
+    assert_eq!(multicall.batches.get(), 1, "Reserves should be fetched in a single batch");
+    assert_eq!(multicall.single_lookups.get(), 0, "No pool should be looked up on its own");
+    assert!(!routes.is_empty());
+
+    // The batch changes how reserves are fetched, not which routes are found
+    let expected = RouteFinder::new(alkane_id("oyl_factory"), &DirectPoolsOnlyProvider { factory: factory.clone() })
+        .with_base_tokens(base_tokens)
+        .find_all_routes(wbtc, dai, amount)?;
+    assert_eq!(routes, expected);
+
+    // Providers without a multicall fall back to one lookup per pair
+    let pairs = [(wbtc, tokens["ETH"]), (wbtc, alkane_id("ORPHAN"))];
+    let batch = factory.get_pool_reserves_batch(&pairs)?;
+    assert_eq!(batch[0].as_ref().map(|pool| pool.reserve_a), Some(factory.get_pool_reserves(wbtc, tokens["ETH"])?.reserve_a));
+    assert!(batch[1].is_none());
+
+    println!("✅ Batched reserve lookup test passed");
+    Ok(())
+}
+
+#[test]
+fn test_route_search_asks_each_pool_once() -> anyhow::Result<()> {
+    println!("Testing that one route search shares a single pool snapshot...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let base_tokens = vec![tokens["ETH"], tokens["USDC"], tokens["USDT"], tokens["DAI"]];
+    let provider = PairLookupProvider { factory: factory.clone(), lookups: RefCell::new(HashMap::new()) };
+    let route = RouteFinder::new(alkane_id("oyl_factory"), &provider)
+        .with_base_tokens(base_tokens.clone())
+        .find_best_route(tokens["UNI"], tokens["DAI"], 100 * TEST_PRECISION)?;
+
+    // The best-first search reads the same snapshot as the enumeration, not the provider
+    let lookups = provider.lookups.borrow();
+    assert!(!lookups.is_empty());
+    for (pair, count) in lookups.iter() {
+        assert_eq!(*count, 1, "Pool {:?} was fetched {} times", pair, count);
+    }
+    assert_eq!(
+        route,
+        RouteFinder::new(alkane_id("oyl_factory"), &factory)
+            .with_base_tokens(base_tokens)
+            .find_best_route(tokens["UNI"], tokens["DAI"], 100 * TEST_PRECISION)?
+    );
+
+    println!("✅ Single snapshot route search test passed");
+    Ok(())
+}
+
+#[test]
+fn test_tied_routes_resolve_deterministically() -> anyhow::Result<()> {
+    println!("Testing deterministic tie-breaking between equal routes...");
+
+    let input = alkane_id("IN");
+    let output = alkane_id("OUT");
+    let (mid_a, mid_b) = (alkane_id("MID_A"), alkane_id("MID_B"));
+    let liquidity = 1_000_000 * 1e18 as u128;
+    let amount_in = 1_000 * 1e18 as u128;
+
+    // Two mirror-image routes through MID_A and MID_B quote exactly the same output
+    let build_factory = || {
+        let mut factory = MockOylFactory::new();
+        for middle in [mid_b, mid_a] {
+            factory.add_pool(input, middle, liquidity, liquidity);
+            factory.add_pool(middle, output, liquidity, liquidity);
+        }
+        factory
+    };
+
+    // Either neighbour order and base token order, with fresh pool maps each time
+    let best_route = |reversed: bool| -> anyhow::Result<RouteInfo> {
+        let factory = build_factory();
+        if reversed {
+            let provider = ReversedConnectionsProvider { factory };
+            RouteFinder::new(alkane_id("oyl_factory"), &provider)
+                .with_base_tokens(vec![mid_a, mid_b])
+                .find_best_route(input, output, amount_in)
+        } else {
+            RouteFinder::new(alkane_id("oyl_factory"), &factory)
+                .with_base_tokens(vec![mid_b, mid_a])
+                .find_best_route(input, output, amount_in)
+        }
+    };
+    let first = best_route(false)?;
+    let smaller_middle = if (mid_a.block, mid_a.tx) < (mid_b.block, mid_b.tx) { mid_a } else { mid_b };
+    assert_eq!(first.path, vec![input, smaller_middle, output], "Ties go to the lexicographically smaller path");
+
+    // Every run picks the byte-identical route
+    let expected = first.to_bytes();
+    for run in 0..100 {
+        assert_eq!(best_route(run % 2 == 1)?.to_bytes(), expected, "Run {} picked a different route", run);
+    }
+
+    // Fewer hops win a tie before the path is compared
+    let direct = RouteInfo::new(vec![input, output], 100);
+    let two_hop = RouteInfo::new(vec![input, smaller_middle, output], 100);
+    assert_eq!(RouteScoring::MaxOutput.compare(&direct, &two_hop), std::cmp::Ordering::Greater);
+    assert_eq!(RouteScoring::MaxOutput.compare(&two_hop, &two_hop), std::cmp::Ordering::Equal);
+
+    println!("✅ Deterministic tie-breaking test passed");
+    Ok(())
+}
+
+#[test]
+fn test_stored_pools_skip_factory_lookups() -> anyhow::Result<()> {
+    println!("Testing quotes against stored pool metadata...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    // USDT pairs with every base token, so routing only probes pools that exist
+    let usdt = alkane_id("USDT");
+    let input_amount = 10_000 * 1_000_000;
+    let factory_lookups = || zap.calls_to(2); // FindExistingPoolId
+    let pool_reads = || zap.calls_to(97) + zap.calls_to(999) + zap.calls_to(101); // GetReserves, PoolDetails, total supply
+
+    // Without stored pools every reserve lookup goes to the factory
+    let factory_quote = zap.quote_zap(usdt, input_amount, eth, usdc, 500)?;
+    assert!(factory_lookups() > 0, "Unregistered pools must be resolved through the factory");
+    zap.calls.borrow_mut().clear();
+
+    // AddPool resolves each pool id once and persists its metadata
+    let pools: Vec<_> = zap.state.borrow().factory.pools.values().cloned().collect();
+    for pool in &pools {
+        zap.dispatch_as_owner(|zap| {
+            zap.add_pool(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate)
+        })?;
+    }
+    assert_eq!(factory_lookups(), pools.len());
+    zap.calls.borrow_mut().clear();
+
+    // A later quote is served from storage, with no factory or pool calls, and matches the
+    // factory-backed one
+    let stored_quote = zap.quote_zap(usdt, input_amount, eth, usdc, 500)?;
+    assert_eq!(factory_lookups(), 0, "Quote should not call the factory after AddPool");
+    assert_eq!(pool_reads(), 0, "Quote should not read stored pools from the pool contracts");
+    assert_eq!(stored_quote, factory_quote);
+
+    // Quotes follow the stored snapshot, not trades against the pool, until it is refreshed
+    zap.with_pool(eth, usdc, |pool| pool.simulate_swap(eth, 10 * 1e18 as u128))?;
+    assert_eq!(zap.quote_zap(usdt, input_amount, eth, usdc, 500)?, stored_quote);
+
+    // UpdatePoolReserves refreshes the stored snapshot, in either token order
+    let target = zap.stored_pool(eth, usdc)?.unwrap();
+    let (reserve_eth, reserve_usdc) = target.reserves_for(eth);
+    zap.dispatch_as_owner(|zap| zap.update_pool_reserves(usdc, eth, reserve_usdc * 2, reserve_eth, target.total_supply))?;
+    assert_eq!(zap.stored_pool(eth, usdc)?.unwrap().reserves_for(eth), (reserve_eth, reserve_usdc * 2));
+    zap.calls.borrow_mut().clear();
+    let updated_quote = zap.quote_zap(usdt, input_amount, eth, usdc, 500)?;
+    assert_eq!((factory_lookups(), pool_reads()), (0, 0));
+    assert_ne!(updated_quote.expected_lp_tokens, stored_quote.expected_lp_tokens);
+
+    // Only registered pools can be updated, and only by the owner
+    let err = zap.dispatch_as_owner(|zap| zap.update_pool_reserves(wbtc, alkane_id("ORPHAN"), 1, 1, 1)).unwrap_err();
+    assert!(matches!(err.downcast_ref::<ZapError>(), Some(ZapError::PoolNotFound { .. })));
+    assert!(zap.dispatch(vec![], |zap| zap.update_pool_reserves(usdc, eth, reserve_usdc, reserve_eth, target.total_supply)).is_err());
+
+    println!("✅ Stored pool lookup test passed");
+    Ok(())
+}
+
+#[test]
+fn test_base_tokens_updated_after_initialization() -> anyhow::Result<()> {
+    println!("Testing base token updates...");
+
+    // AddBaseToken/RemoveBaseToken are owner setters; their dedupe is covered in the
+    // indexer harness. Here the routing side reads the stored set.
+    let mut zap = TestZap::with_comprehensive_setup();
+    let initial = zap.base_tokens.clone();
+
+    // Two new tokens share a pool only with a new hub token
+    let (new_a, hub, new_b) = (alkane_id("NEW_A"), alkane_id("HUB"), alkane_id("NEW_B"));
+    {
+        let mut state = zap.state.borrow_mut();
+        state.factory.add_pool(new_a, hub, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+        state.factory.add_pool(hub, new_b, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    }
+    let amount = 1_000 * TEST_PRECISION;
+    let provider = DirectPoolsOnlyProvider { factory: zap.state.borrow().factory.clone() };
+    let find_route = |base_tokens: &[AlkaneId]| {
+        RouteFinder::new(alkane_id("oyl_factory"), &provider)
+            .with_base_tokens(base_tokens.to_vec())
+            .find_best_route(new_a, new_b, amount)
+    };
+    assert!(find_route(&zap.base_tokens).is_err(), "No base token bridges the new pair yet");
+
+    // Routing picks up the hub once it is a base token, including the contract's own search
+    zap.base_tokens.push(hub);
+    assert_eq!(find_route(&zap.base_tokens)?.path, vec![new_a, hub, new_b]);
+    let contract_route = zap.dispatch(vec![], |zap| zap.get_best_route(new_a, new_b, amount))?;
+    assert_eq!(RouteInfo::from_bytes(&contract_route.data)?.path, vec![new_a, hub, new_b]);
+
+    // Removing it again restores the original set
+    zap.base_tokens.retain(|token| *token != hub);
+    assert_eq!(zap.base_tokens, initial);
+    assert!(find_route(&zap.base_tokens).is_err());
+
+    println!("✅ Base token update test passed");
+    Ok(())
+}
+
+#[test]
+fn test_config_round_trip() -> anyhow::Result<()> {
+    println!("Testing GetConfig payload round trip...");
+
+    let factory_id = AlkaneId { block: 4, tx: 65522 };
+    let base_tokens = vec![alkane_id("USDC"), alkane_id("ETH"), alkane_id("DAI")];
+    let bytes = ZapConfig {
+        factory_id,
+        base_tokens: base_tokens.clone(),
+        version: contract_version(),
+        default_slippage_bps: 100,
+    }
+    .encode();
+    assert_eq!(bytes.len(), 32 + 4 + 3 * 32 + 64);
+    assert_eq!(&bytes[32..36], &3u32.to_le_bytes(), "Base tokens should be length-prefixed");
+
+    let config = ZapConfig::decode(&bytes)?;
+    assert_eq!(config.factory_id, factory_id);
+    assert_eq!(config.base_tokens, base_tokens);
+    assert_eq!(config.version, contract_version());
+    assert_eq!(config.default_slippage_bps, 100);
+
+    // Truncated and padded payloads are refused
+    assert!(ZapConfig::decode(&bytes[..bytes.len() - 1]).is_err());
+    let mut padded = bytes.clone();
+    padded.push(0);
+    assert!(ZapConfig::decode(&padded).is_err());
+
+    println!("✅ GetConfig round trip test passed");
+    Ok(())
+}
+
+#[test]
+fn test_default_slippage_configuration() -> anyhow::Result<()> {
+    println!("Testing the configurable default slippage...");
+
+    // SetDefaultSlippage validation and GetConfig are covered in the indexer harness
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 100 * 1e18 as u128;
+    zap.default_slippage = 250;
+
+    // A zero tolerance quotes at the stored default; an explicit one overrides it
+    let defaulted = zap.quote_zap(uni, input_amount, eth, usdc, 0)?;
+    let explicit = zap.quote_zap(uni, input_amount, eth, usdc, 250)?;
+    assert_eq!(defaulted.minimum_lp_tokens, explicit.minimum_lp_tokens);
+    let tighter = zap.quote_zap(uni, input_amount, eth, usdc, 50)?;
+    assert!(tighter.minimum_lp_tokens > defaulted.minimum_lp_tokens);
+
+    println!("✅ Default slippage test passed");
+    Ok(())
+}
+
+#[test]
+fn test_pool_id_lookup() -> anyhow::Result<()> {
+    println!("Testing GetPoolId for registered and unknown pairs...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let pool_id = zap.pool(eth, usdc).unwrap().id;
+    let decode = |bytes: &[u8]| AlkaneId {
+        block: u128::from_le_bytes(bytes[0..16].try_into().unwrap()),
+        tx: u128::from_le_bytes(bytes[16..32].try_into().unwrap()),
+    };
+    let get_pool_id = |token_a, token_b| zap.dispatch(vec![], |zap| zap.get_pool_id(token_a, token_b)).map(|response| response.data);
+
+    // A pool known to the factory is reported in either token order
+    let bytes = get_pool_id(eth, usdc)?;
+    assert_eq!(bytes.len(), 32);
+    assert_eq!(decode(&bytes), pool_id);
+    assert_eq!(get_pool_id(usdc, eth)?, bytes);
+
+    // A registered pool is answered from storage
+    zap.dispatch_as_owner(|zap| zap.add_pool(eth, usdc, 1000 * 1e18 as u128, 2_000_000 * 1_000_000, 0, 30))?;
+    let factory_lookups = zap.calls_to(2);
+    assert_eq!(decode(&get_pool_id(eth, usdc)?), pool_id);
+    assert_eq!(zap.calls_to(2), factory_lookups);
+
+    // A pair without a pool yields an empty payload rather than an error
+    assert!(get_pool_id(eth, alkane_id("ORPHAN"))?.is_empty());
+
+    println!("✅ Pool id lookup test passed");
+    Ok(())
+}
+
+#[test]
+fn test_ratio_preference_picks_ratio_stable_route_pair() -> anyhow::Result<()> {
+    println!("Testing ratio-preserving route pair selection...");
+
+    let input = alkane_id("IN");
+    let token_a = alkane_id("A");
+    let token_b = alkane_id("B");
+    let middle = alkane_id("MID");
+    let liquidity = 1_000_000 * 1e18 as u128;
+
+    // A is reachable directly or through MID, which pays slightly more A
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, token_a, liquidity, liquidity);
+    factory.add_pool(input, middle, liquidity, liquidity * 101 / 100);
+    factory.add_pool(middle, token_a, liquidity, liquidity);
+    // B trades a little below parity, and the target pool holds A and B at 1:1
+    factory.add_pool(input, token_b, liquidity, liquidity * 98 / 100);
+    factory.add_pool(token_a, token_b, liquidity, liquidity);
+
+    let input_amount = 2000 * 1e18 as u128;
+    let highest_output = RouteFinder::new(alkane_id("FACTORY"), &factory)
+        .find_zap_routes(input, input_amount, token_a, token_b)?;
+    assert_eq!(highest_output.0.path, vec![input, middle, token_a], "Without a preference the richest A route wins");
+
+    let ratio_stable = RouteFinder::new(alkane_id("FACTORY"), &factory)
+        .with_ratio_preference(200)
+        .find_zap_routes(input, input_amount, token_a, token_b)?;
+    assert_eq!(ratio_stable.0.path, vec![input, token_a], "The direct route keeps outputs closer to 1:1");
+    assert_eq!(ratio_stable.1.path, highest_output.1.path);
+
+    // The rejected pair is marginally richer but further from the pool ratio
+    let output_b = ratio_stable.1.expected_output;
+    assert!(highest_output.0.expected_output > ratio_stable.0.expected_output);
+    assert!(highest_output.0.expected_output.abs_diff(output_b) > ratio_stable.0.expected_output.abs_diff(output_b));
+    let richer_value = highest_output.0.expected_output + output_b;
+    let stable_value = ratio_stable.0.expected_output + output_b;
+    assert!((richer_value - stable_value) * 10000 / richer_value <= 200);
+
+    // Outside the tolerance the richer pair is still chosen
+    let strict = RouteFinder::new(alkane_id("FACTORY"), &factory)
+        .with_ratio_preference(1)
+        .find_zap_routes(input, input_amount, token_a, token_b)?;
+    assert_eq!(strict.0.path, highest_output.0.path);
+
+    println!("✅ Ratio preference route selection test passed");
+    Ok(())
+}
+
+#[test]
+fn test_best_route_payload_is_machine_readable() -> anyhow::Result<()> {
+    println!("Testing GetBestRoute payload decoding...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let usdc = tokens["USDC"];
+    let amount_in = 100 * 1e18 as u128; // 100 UNI
+    let get_best_route = |to_token| zap.dispatch(vec![], |zap| zap.get_best_route(uni, to_token, amount_in)).map(|response| response.data);
+
+    let bytes = get_best_route(usdc)?;
+    let route = RouteInfo::from_bytes(&bytes)?;
+    assert_eq!(route.path.first(), Some(&uni));
+    assert_eq!(route.path.last(), Some(&usdc));
+    assert!(route.expected_output > 0, "A connected pair should route to a positive output");
+    assert_eq!(route.to_bytes(), bytes);
+
+    // Payloads with missing or extra bytes are refused
+    assert!(RouteInfo::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    let mut padded = bytes.clone();
+    padded.push(0);
+    assert!(RouteInfo::from_bytes(&padded).is_err());
+
+    // Unroutable pairs fail rather than returning an empty payload
+    assert!(get_best_route(alkane_id("UNLISTED")).is_err());
+
+    println!("✅ GetBestRoute payload test passed");
+    Ok(())
+}
+
+#[test]
+fn test_best_route_cached_within_block() -> anyhow::Result<()> {
+    println!("Testing route caching across blocks...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let usdc = tokens["USDC"];
+    let amount_in = 100 * 1e18 as u128; // 100 UNI
+    zap.height = 840_000;
+
+    // A search reads reserves across the graph; re-pricing a cached path reads only its hops
+    let reserve_reads = |zap: &TestZap, opcode: &dyn Fn(&TestZap) -> anyhow::Result<CallResponse>| -> anyhow::Result<(Vec<u8>, usize)> {
+        let before = zap.calls_to(97);
+        let data = zap.dispatch(vec![], opcode)?.data;
+        Ok((data, zap.calls_to(97) - before))
+    };
+
+    // The first query in a block searches the graph and caches the route it found
+    assert!(zap.cached_route(uni, usdc)?.is_none());
+    let (first, search_reads) = reserve_reads(&zap, &|zap| zap.get_best_route(uni, usdc, amount_in))?;
+    let hops = RouteInfo::from_bytes(&first)?.path.len() - 1;
+    assert!(search_reads > hops);
+    assert_eq!(zap.cached_route(uni, usdc)?.map(|route| route.to_bytes()), Some(first.clone()));
+
+    // The identical query later in the block skips route finding, reading only the path's hops
+    let (second, reads) = reserve_reads(&zap, &|zap| zap.get_best_route(uni, usdc, amount_in))?;
+    assert_eq!(reads, hops, "Route finding should be skipped");
+    assert_eq!(second, first);
+
+    // Another amount for the pair re-prices the cached path for that amount
+    let (doubled, reads) = reserve_reads(&zap, &|zap| zap.get_best_route(uni, usdc, amount_in * 2))?;
+    assert_eq!(reads, hops);
+    let doubled = RouteInfo::from_bytes(&doubled)?;
+    assert_eq!(doubled.path, RouteInfo::from_bytes(&first)?.path);
+    assert!(doubled.expected_output > RouteInfo::from_bytes(&first)?.expected_output);
+
+    // The next block's reserves may differ, so its first query searches again
+    zap.height += 1;
+    assert!(zap.cached_route(uni, usdc)?.is_none());
+    let (next_block, reads) = reserve_reads(&zap, &|zap| zap.get_best_route(uni, usdc, amount_in))?;
+    assert_eq!(next_block, first);
+    assert_eq!(reads, search_reads);
+
+    println!("✅ Route caching test passed");
+    Ok(())
+}
+
+#[test]
+fn test_balanced_scoring_prefers_lower_impact_route() -> anyhow::Result<()> {
+    println!("Testing price-impact-weighted route scoring...");
+
+    let input = alkane_id("IN");
+    let output = alkane_id("OUT");
+    let deep_middle = alkane_id("DEEP");
+    let thin_middle = alkane_id("THIN");
+    let deep = 1_000_000 * 1e18 as u128;
+    let thin = 10_000 * 1e18 as u128;
+
+    // Two routes of two hops each: one through a thin pool priced slightly rich, one all at parity
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, thin_middle, thin, thin * 110 / 100);
+    factory.add_pool(thin_middle, output, deep, deep);
+    factory.add_pool(input, deep_middle, deep, deep);
+    factory.add_pool(deep_middle, output, deep, deep);
+
+    let amount_in = 1000 * 1e18 as u128;
+    let max_output = RouteFinder::new(alkane_id("FACTORY"), &factory).find_best_route(input, output, amount_in)?;
+    let balanced = RouteFinder::new(alkane_id("FACTORY"), &factory)
+        .with_scoring(RouteScoring::Balanced { impact_weight_bps: 10000 })
+        .find_best_route(input, output, amount_in)?;
+    let min_impact = RouteFinder::new(alkane_id("FACTORY"), &factory)
+        .with_scoring(RouteScoring::MinPriceImpact)
+        .find_best_route(input, output, amount_in)?;
+
+    // The default still maximizes output and takes the thin pool
+    assert_eq!(max_output.path, vec![input, thin_middle, output]);
+    assert_eq!(balanced.path, vec![input, deep_middle, output], "Balanced scoring should avoid the thin pool");
+    assert_eq!(min_impact.path, balanced.path);
+
+    // Outputs are within 1% of each other while the thin route moves its price far further
+    assert!(max_output.expected_output > balanced.expected_output);
+    assert!((max_output.expected_output - balanced.expected_output) * 10000 / max_output.expected_output < 100);
+    assert!(max_output.price_impact > balanced.price_impact + 500);
+
+    // A negligible impact weight falls back to ranking by output
+    let lightly_weighted = RouteFinder::new(alkane_id("FACTORY"), &factory)
+        .with_scoring(RouteScoring::Balanced { impact_weight_bps: 1 })
+        .find_best_route(input, output, amount_in)?;
+    assert_eq!(lightly_weighted.path, max_output.path);
+
+    println!("✅ Price-impact-weighted scoring test passed");
+    Ok(())
+}
+
+#[test]
+fn test_find_all_routes_returns_unique_paths() -> anyhow::Result<()> {
+    println!("Testing route deduplication and validation...");
+
+    let input = alkane_id("IN");
+    let output = alkane_id("OUT");
+    let base = alkane_id("BASE");
+    let other = alkane_id("OTHER");
+    let liquidity = 1_000_000 * 1e18 as u128;
+
+    // The path through BASE surfaces both as a base-token route and from the multi-hop search,
+    // and OTHER offers a way back to the input
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, output, liquidity, liquidity);
+    factory.add_pool(input, base, liquidity, liquidity);
+    factory.add_pool(base, output, liquidity, liquidity);
+    factory.add_pool(base, other, liquidity, liquidity);
+    factory.add_pool(other, input, liquidity, liquidity);
+
+    let route_finder = RouteFinder::new(alkane_id("FACTORY"), &factory).with_base_tokens(vec![base, other]);
+    let routes = route_finder.find_all_routes(input, output, 1000 * 1e18 as u128)?;
+    let paths: Vec<_> = routes.iter().map(|route| route.path.clone()).collect();
+    println!("Found {} routes", paths.len());
+
+    // Every path appears once
+    for (i, path) in paths.iter().enumerate() {
+        assert!(!paths[..i].contains(path), "Path {:?} returned more than once", path);
+    }
+    assert!(paths.contains(&vec![input, output]));
+    assert!(paths.contains(&vec![input, base, output]));
+
+    // No path revisits a token or runs past the hop limit
+    for route in &routes {
+        let mut seen = HashSet::new();
+        assert!(route.path.iter().all(|token| seen.insert(*token)));
+        assert!(route.hop_count() <= MAX_HOPS);
+    }
+
+    println!("✅ Route deduplication test passed");
+    Ok(())
+}
+
+#[test]
+fn test_near_empty_pools_excluded_from_routing() -> anyhow::Result<()> {
+    println!("Testing the minimum pool liquidity guard...");
+
+    let input = alkane_id("IN");
+    let output = alkane_id("OUT");
+    let middle = alkane_id("MID");
+    let liquidity = 1_000_000 * 1e18 as u128;
+
+    // IN and OUT are linked by a near-empty direct pool and by a rich route through MID
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, output, 4000, 400);
+    factory.add_pool(input, middle, liquidity, liquidity);
+    factory.add_pool(middle, output, liquidity, liquidity);
+
+    let amount_in = 100;
+    let route_finder = RouteFinder::new(alkane_id("FACTORY"), &factory);
+    assert_eq!(route_finder.min_pool_liquidity, MINIMUM_LIQUIDITY);
+    let paths: Vec<_> = route_finder
+        .find_all_routes(input, output, amount_in)?
+        .into_iter()
+        .map(|route| route.path)
+        .collect();
+    assert_eq!(paths, vec![vec![input, middle, output]], "The near-empty pool should not be a candidate");
+    assert_eq!(route_finder.find_best_route_dijkstra(input, output, amount_in)?.path, vec![input, middle, output]);
+
+    // Without the guard the near-empty pool is routed through again
+    let unguarded = RouteFinder::new(alkane_id("FACTORY"), &factory).with_min_pool_liquidity(0);
+    let paths: Vec<_> = unguarded
+        .find_all_routes(input, output, amount_in)?
+        .into_iter()
+        .map(|route| route.path)
+        .collect();
+    assert!(paths.contains(&vec![input, output]));
+
+    // A threshold above every pool leaves nothing to route through
+    let strict = RouteFinder::new(alkane_id("FACTORY"), &factory).with_min_pool_liquidity(liquidity + 1);
+    assert!(strict.find_best_route(input, output, amount_in).is_err());
+
+    println!("✅ Minimum pool liquidity test passed");
+    Ok(())
+}
+
+#[test]
+fn test_route_within_impact_distinguishes_outcomes() -> anyhow::Result<()> {
+    println!("Testing impact-capped route lookup...");
+
+    let input = alkane_id("CAP_IN");
+    let middle = alkane_id("CAP_MID");
+    let output = alkane_id("CAP_OUT");
+    let island = alkane_id("CAP_ISLAND");
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, middle, 1_000 * TEST_PRECISION, 1_000 * TEST_PRECISION);
+    factory.add_pool(middle, output, 1_000 * TEST_PRECISION, 1_000 * TEST_PRECISION);
+    factory.add_pool(island, alkane_id("CAP_ISLAND_PAIR"), 1_000 * TEST_PRECISION, 1_000 * TEST_PRECISION);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_base_tokens(vec![middle]);
+    let max_impact_bps = 1_500; // both hops' fees plus a little price movement
+
+    // A small trade fits under the cap and matches the unconstrained best route
+    let small = TEST_PRECISION;
+    let best = route_finder.find_best_route(input, output, small)?;
+    let capped = route_finder.find_route_within_impact(input, output, small, max_impact_bps)?;
+    assert_eq!(capped.map(|route| route.path), Some(best.path));
+
+    // A large trade is routable but too expensive for the cap
+    let large = 500 * TEST_PRECISION;
+    assert!(route_finder.find_best_route(input, output, large)?.price_impact > max_impact_bps);
+    assert!(route_finder.find_route_within_impact(input, output, large, max_impact_bps)?.is_none());
+
+    // A disconnected pair is still an error
+    let err = route_finder.find_route_within_impact(input, island, small, max_impact_bps).unwrap_err();
+    assert!(matches!(err.downcast_ref::<ZapError>(), Some(ZapError::RouteNotFound { .. })));
+
+    println!("✅ Impact-capped route lookup test passed");
+    Ok(())
+}
+
+#[test]
+fn test_check_routable_counts_reachable_base_tokens() -> anyhow::Result<()> {
+    println!("Testing base-token reachability...");
+
+    let new_token = alkane_id("FRESH");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let dai = alkane_id("DAI");
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(new_token, eth, 1_000 * TEST_PRECISION, 10 * TEST_PRECISION);
+    factory.add_pool(usdc, dai, 1_000_000 * 1_000_000, 1_000_000 * TEST_PRECISION);
+    let zap = TestZap::with_factory(factory, vec![usdc, eth, dai]);
+    let check_routable = |token| -> anyhow::Result<u128> {
+        let data = zap.dispatch(vec![], |zap| zap.check_routable(token))?.data;
+        Ok(u128::from_le_bytes(data.as_slice().try_into()?))
+    };
+
+    // Only ETH is reachable; the stablecoin pool is disconnected from it
+    let factory = zap.state.borrow().factory.clone();
+    let route_finder = RouteFinder::new(zap.factory_id, &factory).with_base_tokens(zap.base_tokens.clone());
+    assert_eq!(route_finder.reachable_base_tokens(new_token)?, vec![eth]);
+    assert_eq!(check_routable(new_token)?, 1);
+
+    // A token with no pools reaches nothing
+    assert_eq!(check_routable(alkane_id("ORPHAN"))?, 0);
+
+    println!("✅ Base-token reachability test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_fails_on_dry_leg() -> anyhow::Result<()> {
+    println!("Testing quotes with a leg that yields nothing...");
+
+    let mut zap = MockOylZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let dry = alkane_id("DRY");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // UNI reaches DRY only through a pool too thin to return any of it
+    zap.factory.add_pool(uni, dry, 1_000_000_000_000 * TEST_PRECISION, 1_000);
+    zap.factory.add_pool(eth, dry, 1_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+
+    // The A leg alone routes fine
+    let route_finder = RouteFinder::new(zap.factory_id, &zap.factory).with_base_tokens(zap.base_tokens.clone());
+    assert!(route_finder.find_best_route(uni, eth, input_amount / 2)?.expected_output > 0);
+
+    // The quote fails up front, naming the B leg
+    let err = zap.get_zap_quote(uni, input_amount, eth, dry, DEFAULT_SLIPPAGE).unwrap_err();
+    assert_eq!(
+        err.downcast_ref::<ZapError>(),
+        Some(&ZapError::RouteNotFound { from_token: uni, to_token: dry }),
+        "got {}",
+        err
+    );
+
+    println!("✅ Dry leg quote test passed");
+    Ok(())
+}
+
+#[test]
+fn test_stored_pool_provider_routes_through_registered_pools() -> anyhow::Result<()> {
+    println!("Testing routing over registered pools...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let usdc = tokens["USDC"];
+    let new_token = alkane_id("NEW");
+    let mid = alkane_id("MID");
+    {
+        let factory = &mut zap.state.borrow_mut().factory;
+        factory.add_pool(new_token, mid, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+        factory.add_pool(mid, usdc, 1_000_000 * TEST_PRECISION, 2_000_000 * 1_000_000);
+    }
+
+    // Register both hops through `AddPool`, which also persists them as routing edges
+    for (token_a, token_b) in [(new_token, mid), (mid, usdc)] {
+        let pool = zap.pool(token_a, token_b).unwrap();
+        zap.dispatch_as_owner(|zap| zap.add_pool(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate))?;
+    }
+
+    let provider = ZapPoolProvider::new(&zap);
+    assert!(provider.get_connected_tokens(new_token)?.contains(&mid));
+    assert!(provider.get_connected_tokens(mid)?.contains(&usdc));
+
+    // The contract's route search follows the stored edges to the base token
+    let data = zap.dispatch(vec![], |zap| zap.get_best_route(new_token, usdc, 100 * TEST_PRECISION))?.data;
+    let route = RouteInfo::from_bytes(&data)?;
+    assert_eq!(route.path, vec![new_token, mid, usdc]);
+    assert!(route.expected_output > 0);
+
+    println!("✅ Stored pool provider test passed");
+    Ok(())
+}
+
+#[test]
+fn test_connected_tokens_persisted_by_add_pool() -> anyhow::Result<()> {
+    println!("Testing connected-token adjacency...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let token_a = alkane_id("ADJ_A");
+    let token_b = alkane_id("ADJ_B");
+    let token_c = alkane_id("ADJ_C");
+    {
+        let factory = &mut zap.state.borrow_mut().factory;
+        factory.add_pool(token_a, token_b, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+        factory.add_pool(token_b, token_c, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    }
+    let get_connected_tokens = |token| zap.dispatch(vec![], |zap| zap.get_connected_tokens(token)).map(|response| response.data);
+    let add_pool = |token_a, token_b, reserve_a| {
+        zap.dispatch_as_owner(|zap| zap.add_pool(token_a, token_b, reserve_a, 1_000_000 * TEST_PRECISION, 0, 30))
+    };
+    assert!(decode_token_list(&get_connected_tokens(token_b)?)?.is_empty());
+
+    add_pool(token_a, token_b, 1_000_000 * TEST_PRECISION)?;
+    add_pool(token_b, token_c, 1_000_000 * TEST_PRECISION)?;
+
+    // B reaches both counterparties; the ends only reach B
+    assert_eq!(decode_token_list(&get_connected_tokens(token_b)?)?, vec![token_a, token_c]);
+    assert_eq!(decode_token_list(&get_connected_tokens(token_a)?)?, vec![token_b]);
+    assert_eq!(decode_token_list(&get_connected_tokens(token_c)?)?, vec![token_b]);
+
+    // Refreshing a pool does not duplicate its edge
+    add_pool(token_a, token_b, 2_000_000 * TEST_PRECISION)?;
+    assert_eq!(zap.get_connected_tokens_impl(token_b)?, vec![token_a, token_c]);
+
+    // The payload is exact
+    let payload = get_connected_tokens(token_b)?;
+    assert!(decode_token_list(&payload[..payload.len() - 1]).is_err());
+
+    println!("✅ Connected tokens test passed");
+    Ok(())
+}
+
+#[test]
+fn test_split_routes_beat_single_best_route() -> anyhow::Result<()> {
+    println!("Testing leg splits across routes...");
+
+    // A direct pool and a path at the same price through a hub whose pools are twice as deep
+    let mut factory = MockOylFactory::new();
+    let from = alkane_id("SPLIT_IN");
+    let to = alkane_id("SPLIT_OUT");
+    let hub = alkane_id("SPLIT_HUB");
+    factory.add_pool(from, to, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    factory.add_pool(from, hub, 200_000 * TEST_PRECISION, 200_000 * TEST_PRECISION);
+    factory.add_pool(hub, to, 200_000 * TEST_PRECISION, 200_000 * TEST_PRECISION);
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+
+    // A leg large enough to move the direct pool well off its price
+    let amount_in = 20_000 * TEST_PRECISION;
+    let single = route_finder.find_best_route(from, to, amount_in)?;
+    let splits = route_finder.find_split_routes(from, to, amount_in, 2)?;
+    for (route, allocation) in &splits {
+        println!("{} hops <- {}: {} out", route.path.len() - 1, allocation, route.expected_output);
+    }
+
+    assert_eq!(splits.len(), 2, "Both routes should take part of the leg");
+    assert_eq!(splits.iter().map(|(_, allocation)| allocation).sum::<u128>(), amount_in);
+    let split_output: u128 = splits.iter().map(|(route, _)| route.expected_output).sum();
+    assert!(split_output > single.expected_output, "split {} vs single {}", split_output, single.expected_output);
+
+    // Each split route is priced for its own allocation
+    for (route, allocation) in &splits {
+        assert_eq!(route.expected_output, route_finder.price_path(&route.path, *allocation)?.expected_output);
+    }
+
+    // Limited to one route, the whole leg stays on it; a small leg never splits
+    let unsplit = route_finder.find_split_routes(from, to, amount_in, 1)?;
+    assert_eq!(unsplit.len(), 1);
+    assert_eq!(unsplit[0].1, amount_in);
+    assert_eq!(route_finder.find_split_routes(from, to, TEST_PRECISION, 2)?.len(), 1);
+
+    println!("✅ Split route test passed");
+    Ok(())
+}
+
+#[test]
+fn test_fast_route_matches_full_search_with_fewer_lookups() -> anyhow::Result<()> {
+    println!("Benchmarking the base-token fast path against the full route search...");
+
+    let (factory, tokens) = setup_comprehensive_test_environment();
+    let base_tokens = vec![tokens["ETH"], tokens["USDC"], tokens["USDT"], tokens["DAI"], tokens["WBTC"]];
+    let queries = [
+        (tokens["ETH"], tokens["USDC"], 10 * TEST_PRECISION),
+        (tokens["WBTC"], tokens["DAI"], 1e8 as u128),
+        (tokens["UNI"], tokens["USDT"], 100 * TEST_PRECISION),
+        (tokens["WETH"], tokens["UNI"], TEST_PRECISION),
+    ];
+
+    let counting = CountingProvider { factory: factory.clone(), lookups: Cell::new(0) };
+    let finder = RouteFinder::new(alkane_id("oyl_factory"), &counting).with_base_tokens(base_tokens);
+    let (mut full_lookups, mut fast_lookups) = (0, 0);
+    let (mut full_time, mut fast_time) = (std::time::Duration::ZERO, std::time::Duration::ZERO);
+    for (from_token, to_token, amount_in) in queries {
+        let start = std::time::Instant::now();
+        let full = finder.find_best_route(from_token, to_token, amount_in)?;
+        full_time += start.elapsed();
+        full_lookups += counting.lookups.replace(0);
+
+        let start = std::time::Instant::now();
+        let fast = finder.find_best_route_fast(from_token, to_token, amount_in)?;
+        fast_time += start.elapsed();
+        fast_lookups += counting.lookups.replace(0);
+
+        // Every query here is best served directly or through one base token
+        assert!(full.hop_count() <= 2, "{:?} is not a base-token route", full.path);
+        assert_eq!(fast, full, "Fast path for {:?} -> {:?} differs", from_token, to_token);
+    }
+
+    println!("Full search: {} pool queries in {:?}", full_lookups, full_time);
+    println!("Fast path:   {} pool queries in {:?}", fast_lookups, fast_time);
+    assert!(fast_lookups < full_lookups, "The fast path should query fewer pools");
+
+    // Pricing, impact and gas all reuse the reserves the walk read, so no pool is asked twice
+    let provider = PairLookupProvider { factory: factory.clone(), lookups: RefCell::new(HashMap::new()) };
+    RouteFinder::new(alkane_id("oyl_factory"), &provider)
+        .with_base_tokens(vec![tokens["ETH"], tokens["USDC"], tokens["DAI"]])
+        .find_best_route_fast(tokens["UNI"], tokens["USDT"], 100 * TEST_PRECISION)?;
+    let lookups = provider.lookups.borrow();
+    assert!(!lookups.is_empty());
+    for (pair, count) in lookups.iter() {
+        assert_eq!(*count, 1, "Pool {:?} was fetched {} times", pair, count);
+    }
+
+    // A pair no base token connects still gets routed, through the full search
+    let (from_token, to_token) = (tokens["UNI"], tokens["AAVE"]);
+    let fallback = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_base_tokens(vec![tokens["DAI"]]);
+    assert_eq!(
+        fallback.find_best_route_fast(from_token, to_token, TEST_PRECISION)?,
+        fallback.find_best_route(from_token, to_token, TEST_PRECISION)?
+    );
+
+    println!("✅ Fast route benchmark test passed");
+    Ok(())
+}
+
+#[test]
+fn test_pool_storage_is_independent_of_token_order() -> anyhow::Result<()> {
+    println!("Testing pool-keyed storage in both token orders...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let usdt = alkane_id("USDT");
+
+    // Both orders of a pair share one key
+    assert_eq!(canonical_pool_key(eth, usdc), canonical_pool_key(usdc, eth));
+    assert_eq!(StoredPool::storage_key(&eth, &usdc), StoredPool::storage_key(&usdc, &eth));
+    assert_eq!(pool_storage_key("/lp_token_ids", &eth, &usdc), pool_storage_key("/lp_token_ids", &usdc, &eth));
+
+    // A pool stored as (USDC, ETH) reads back the same as (ETH, USDC)
+    let pool = zap.pool(eth, usdc).unwrap();
+    let (reserve_eth, reserve_usdc) = if pool.token_a == eth { (pool.reserve_a, pool.reserve_b) } else { (pool.reserve_b, pool.reserve_a) };
+    zap.height = 42;
+    zap.dispatch_as_owner(|zap| zap.add_pool(usdc, eth, reserve_usdc, reserve_eth, pool.total_supply, pool.fee_rate))?;
+    assert_eq!(zap.stored_pool(eth, usdc)?, zap.stored_pool(usdc, eth)?);
+    assert_eq!(zap.stored_pool(eth, usdc)?.unwrap().reserves_for(eth), (reserve_eth, reserve_usdc));
+    let get_pool_id = |token_a, token_b| zap.dispatch(vec![], |zap| zap.get_pool_id(token_a, token_b)).map(|response| response.data);
+    assert_eq!(get_pool_id(eth, usdc)?, get_pool_id(usdc, eth)?);
+    assert_eq!(zap.state.borrow().pool_update_heights[&canonical_pool_key(usdc, eth)], 42);
+
+    // Zaps into either order record one history, on the same scale
+    let input_amount = 1_000 * 1_000_000;
+    zap.execute_zap_for(usdt, input_amount, usdc, eth, 500)?;
+    zap.execute_zap_for(usdt, input_amount, eth, usdc, 500)?;
+    let history = zap.state.borrow().ratio_history[&pool.id].clone();
+    assert_eq!(history.len(), 2);
+    let drift = if history[0] > history[1] { history[0] - history[1] } else { history[1] - history[0] };
+    assert!(drift * U256::from(10_000u128) <= history[0], "Both orders should observe the same ratio");
+
+    println!("✅ Token order independence test passed");
+    Ok(())
+}
+
+#[test]
+fn test_gas_model_prices_hops_by_curve() -> anyhow::Result<()> {
+    println!("Testing route gas estimates under a curve-aware gas model...");
+
+    let (mut factory, tokens) = setup_comprehensive_test_environment();
+    let (eth, usdc, usdt) = (tokens["ETH"], tokens["USDC"], tokens["USDT"]);
+    factory.get_pool_mut(usdc, usdt).unwrap().curve = CurveKind::Stable { amp: 100 };
+
+    // The default model keeps the flat per-hop estimate whatever the curve
+    let default_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let stable_hop = default_finder.price_path(&[usdc, usdt], 1_000 * 1_000_000)?;
+    let product_hop = default_finder.price_path(&[usdc, eth], 1_000 * 1_000_000)?;
+    assert_eq!(stable_hop.gas_estimate, DefaultGasModel::GAS_PER_HOP);
+    assert_eq!(product_hop.gas_estimate, DefaultGasModel::GAS_PER_HOP);
+    let two_hops = default_finder.price_path(&[eth, usdc, usdt], TEST_PRECISION)?;
+    assert_eq!(two_hops.gas_estimate, 2 * DefaultGasModel::GAS_PER_HOP);
+
+    // A curve-aware model tells the two pools apart
+    let finder = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_gas_model(CurveAwareGasModel);
+    let stable_hop = finder.price_path(&[usdc, usdt], 1_000 * 1_000_000)?;
+    let product_hop = finder.price_path(&[usdc, eth], 1_000 * 1_000_000)?;
+    println!("Stable hop: {} gas, constant product hop: {} gas", stable_hop.gas_estimate, product_hop.gas_estimate);
+    assert_eq!(stable_hop.gas_estimate, 110_000);
+    assert_eq!(product_hop.gas_estimate, 60_000);
+
+    // Searched routes are costed hop by hop through the same model
+    assert_eq!(finder.price_path(&[eth, usdc, usdt], TEST_PRECISION)?.gas_estimate, 170_000);
+    let route = finder.find_best_route(eth, usdt, TEST_PRECISION)?;
+    assert_eq!(route.gas_estimate, finder.price_path(&route.path, TEST_PRECISION)?.gas_estimate);
+
+    // Each hop's curve comes from the reserves read to price it, not from another lookup
+    let provider = PairLookupProvider { factory: factory.clone(), lookups: RefCell::new(HashMap::new()) };
+    let counted_finder = RouteFinder::new(alkane_id("oyl_factory"), &provider).with_gas_model(CurveAwareGasModel);
+    assert_eq!(counted_finder.price_path(&[eth, usdc, usdt], TEST_PRECISION)?.gas_estimate, 170_000);
+    assert_eq!(*provider.lookups.borrow(), HashMap::from([((eth, usdc), 1), ((usdc, usdt), 1)]));
+
+    // Both legs of a zap share their first pool, which only the first leg loads cold
+    let (input, hub, token_a, token_b) = (alkane_id("WARMX"), alkane_id("WARMH"), alkane_id("WARMA"), alkane_id("WARMB"));
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(input, hub, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    factory.add_pool(hub, token_a, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    factory.add_pool(hub, token_b, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    factory.add_pool(token_a, token_b, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    let finder = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_gas_model(CurveAwareGasModel);
+    let (route_a, route_b) = finder.find_zap_routes(input, 10 * TEST_PRECISION, token_a, token_b)?;
+    assert_eq!(route_a.path, vec![input, hub, token_a]);
+    assert_eq!(route_b.path, vec![input, hub, token_b]);
+    assert_eq!(route_a.gas_estimate, 120_000);
+    assert_eq!(route_b.gas_estimate, 100_000);
+
+    println!("✅ Curve-aware gas model test passed");
+    Ok(())
+}
+
+#[test]
+fn test_spot_price_direct_and_through_base_token() -> anyhow::Result<()> {
+    println!("Testing spot prices from direct pools and composed base-token legs...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let usdc = alkane_id("USDC");
+    let (spot_a, spot_b) = (alkane_id("SPOT_A"), alkane_id("SPOT_B"));
+    let (leg_x, leg_y) = (alkane_id("LEG_X"), alkane_id("LEG_Y"));
+    let get_spot_price = |token_a, token_b| -> anyhow::Result<u128> {
+        let data = zap.dispatch(vec![], |zap| zap.get_spot_price(token_a, token_b))?.data;
+        Ok(u128::from_le_bytes(data.as_slice().try_into()?))
+    };
+
+    // A 1:2 pool prices token A at two of token B, and token B at half of token A
+    zap.state.borrow_mut().factory.add_pool(spot_a, spot_b, 1_000 * TEST_PRECISION, 2_000 * TEST_PRECISION);
+    let price = get_spot_price(spot_a, spot_b)?;
+    println!("Spot price of A in B: {}", price);
+    assert_within_tolerance(price, 2 * TEST_PRECISION, 1);
+    assert_within_tolerance(get_spot_price(spot_b, spot_a)?, TEST_PRECISION / 2, 1);
+
+    // Without a direct pool the price is the product of the legs through USDC
+    {
+        let factory = &mut zap.state.borrow_mut().factory;
+        factory.add_pool(leg_x, usdc, 1_000 * TEST_PRECISION, 3_000 * 1_000_000);
+        factory.add_pool(usdc, leg_y, 1_000 * 1_000_000, 5_000 * TEST_PRECISION);
+    }
+    assert!(zap.pool(leg_x, leg_y).is_none());
+    let first_leg = get_spot_price(leg_x, usdc)?;
+    let second_leg = get_spot_price(usdc, leg_y)?;
+    let composed = get_spot_price(leg_x, leg_y)?;
+    println!("Legs: {} x {} -> composed {}", first_leg, second_leg, composed);
+    assert_eq!(composed, first_leg * second_leg / TEST_PRECISION);
+    assert_within_tolerance(composed, 15 * TEST_PRECISION, 1);
+
+    // Pairs no pool or base token connects have no spot price
+    assert!(get_spot_price(spot_a, leg_x).is_err());
+    assert!(get_spot_price(spot_a, spot_a).is_err());
+
+    println!("✅ Spot price test passed");
+    Ok(())
+}
+
+#[test]
+fn test_directional_fees_priced_per_hop() -> anyhow::Result<()> {
+    println!("Testing routing against a pool with a fee per direction...");
+
+    let (token_x, token_y) = (alkane_id("FEE_X"), alkane_id("FEE_Y"));
+    let liquidity = 1_000_000 * TEST_PRECISION;
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(token_x, token_y, liquidity, liquidity);
+
+    // 0.3% selling X, 0.5% selling Y
+    let provider = DirectionalFeeProvider { factory, pair: (token_x, token_y), fees: (30, 50) };
+    let reserves = provider.get_pool_reserves(token_y, token_x)?;
+    assert_eq!((reserves.fee_for(token_x), reserves.fee_for(token_y)), (30, 50));
+    let flipped = reserves.oriented(token_y);
+    assert_eq!((flipped.fee_for(token_x), flipped.fee_for(token_y)), (30, 50), "Orienting keeps each direction's fee");
+
+    let amount_in = 1_000 * TEST_PRECISION;
+    let finder = RouteFinder::new(alkane_id("oyl_factory"), &provider);
+    let x_to_y = finder.find_best_route(token_x, token_y, amount_in)?;
+    let y_to_x = finder.find_best_route(token_y, token_x, amount_in)?;
+    println!("X -> Y: {}, Y -> X: {}", x_to_y.expected_output, y_to_x.expected_output);
+    assert_eq!(x_to_y.expected_output, amm_logic::calculate_swap_out(amount_in, liquidity, liquidity, 30)?);
+    assert_eq!(y_to_x.expected_output, amm_logic::calculate_swap_out(amount_in, liquidity, liquidity, 50)?);
+    assert!(x_to_y.expected_output > y_to_x.expected_output, "The cheaper direction should pay more");
+
+    // Re-pricing a known path charges the same directional fee
+    assert_eq!(finder.price_path(&[token_y, token_x], amount_in)?.expected_output, y_to_x.expected_output);
+
+    println!("✅ Directional fee test passed");
+    Ok(())
+}
+
+#[test]
+fn test_explain_route_chains_hops() -> anyhow::Result<()> {
+    println!("Testing per-hop route explanation...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (factory, tokens) = setup_comprehensive_test_environment();
+
+    let comp = tokens["COMP"];
+    let eth = tokens["ETH"];
+    let aave = tokens["AAVE"];
+    let amount_in = 10 * TEST_PRECISION; // 10 COMP
+
+    // A known two-hop route through ETH
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    let path = [comp, eth, aave];
+    let hops = route_finder.explain(&path, amount_in)?;
+    let priced = route_finder.price_path(&path, amount_in)?;
+    assert_eq!(hops.len(), 2);
+    assert_eq!((hops[0].token_in, hops[0].token_out), (comp, eth));
+    assert_eq!((hops[1].token_in, hops[1].token_out), (eth, aave));
+    assert_eq!(hops[0].amount_in, amount_in);
+    assert_eq!(hops[0].amount_out, hops[1].amount_in, "Hop 1 output must be hop 2 input");
+    assert_eq!(hops[1].amount_out, priced.expected_output);
+
+    // Each hop reports the reserves of the pool it trades against, oriented by direction
+    for hop in &hops {
+        println!("{:?} -> {:?}: {} in, {} out, {} bps impact", hop.token_in, hop.token_out, hop.amount_in, hop.amount_out, hop.price_impact);
+        let pool = factory.get_pool_reserves(hop.token_in, hop.token_out)?.oriented(hop.token_in);
+        assert_eq!((hop.reserve_in, hop.reserve_out), (pool.reserve_a, pool.reserve_b));
+        assert!(hop.price_impact > 0 && hop.price_impact < 10_000);
+    }
+    let impacts: Vec<u128> = hops.iter().map(|hop| hop.price_impact).collect();
+    assert_eq!(amm_logic::compound_price_impact(&impacts), priced.price_impact);
+
+    // `ExplainRoute` breaks down the route `GetBestRoute` picks, chaining every hop
+    let route = RouteInfo::from_bytes(&zap.dispatch(vec![], |zap| zap.get_best_route(comp, aave, amount_in))?.data)?;
+    let bytes = zap.dispatch(vec![], |zap| zap.explain_route(comp, aave, amount_in))?.data;
+    let explained = HopBreakdown::deserialize_list(&bytes)?;
+    assert_eq!(explained.len(), route.hop_count());
+    assert_eq!(explained[0].amount_in, amount_in);
+    for pair in explained.windows(2) {
+        assert_eq!(pair[0].token_out, pair[1].token_in);
+        assert_eq!(pair[0].amount_out, pair[1].amount_in);
+    }
+    assert_eq!(explained.last().map(|hop| hop.amount_out), Some(route.expected_output));
+
+    // The payload is exact: truncated or padded data is refused
+    assert!(HopBreakdown::deserialize_list(&bytes[..bytes.len() - 1]).is_err());
+    let mut padded = bytes.clone();
+    padded.push(0);
+    assert!(HopBreakdown::deserialize_list(&padded).is_err());
+
+    println!("✅ Route explanation test passed");
+    Ok(())
+}
+
+#[test]
+fn test_best_first_search_honours_expansion_cap() -> anyhow::Result<()> {
+    println!("Testing the expansion cap on the best-first search...");
+
+    // A thin direct pool next to a deep two-hop path
+    let (from, via, to) = (alkane_id("CAP_A"), alkane_id("CAP_B"), alkane_id("CAP_C"));
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(from, to, 100 * TEST_PRECISION, 100 * TEST_PRECISION);
+    factory.add_pool(from, via, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    factory.add_pool(via, to, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    let amount_in = 50 * TEST_PRECISION;
+
+    let uncapped = RouteFinder::new(alkane_id("oyl_factory"), &factory)
+        .find_best_route_dijkstra(from, to, amount_in)?;
+    assert_eq!(uncapped.path, vec![from, via, to]);
+
+    // Only the origin is expanded, so the deep path is never reached
+    let capped = RouteFinder::new(alkane_id("oyl_factory"), &factory)
+        .with_max_expansions(1)
+        .find_best_route_dijkstra(from, to, amount_in)?;
+    assert_eq!(capped.path, vec![from, to]);
+
+    println!("✅ Best-first expansion cap test passed");
+    Ok(())
+}
+
+#[test]
+fn test_route_enumeration_halts_at_cap() -> anyhow::Result<()> {
+    println!("Testing the route enumeration cap on a fully connected graph...");
+
+    // Six tokens with a pool between every pair, each priced a little differently
+    let tokens: Vec<AlkaneId> = (0..6).map(|i| alkane_id(&format!("MESH{}", i))).collect();
+    let mut factory = MockOylFactory::new();
+    for i in 0..tokens.len() {
+        for j in i + 1..tokens.len() {
+            let depth = 1_000 * TEST_PRECISION * (i + j + 1) as u128;
+            factory.add_pool(tokens[i], tokens[j], depth * (i + 2) as u128, depth * (j + 2) as u128);
+        }
+    }
+    let (from, to) = (tokens[0], tokens[5]);
+    let amount_in = 10 * TEST_PRECISION;
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+    assert_eq!(route_finder.max_routes, MAX_ROUTES);
+
+    let uncapped = route_finder.find_all_routes(from, to, amount_in)?;
+    println!("Uncapped search found {} routes", uncapped.len());
+    assert!(uncapped.len() > 3);
+
+    // Enumeration stops at the cap, and both searches list their routes best first
+    let capped_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory).with_max_routes(3);
+    let capped = capped_finder.find_all_routes(from, to, amount_in)?;
+    assert_eq!(capped.len(), 3);
+    for routes in [&uncapped, &capped] {
+        assert!(routes.windows(2).all(|pair| route_finder.scoring.compare(&pair[0], &pair[1]) != Ordering::Less));
+    }
+    assert!(capped.iter().all(|route| uncapped.contains(route)));
+
+    // A single expansion only reaches the direct pool
+    let shallow = RouteFinder::new(alkane_id("oyl_factory"), &factory)
+        .with_max_expansions(1)
+        .find_all_routes(from, to, amount_in)?;
+    assert_eq!(shallow.len(), 1);
+    assert_eq!(shallow[0].path, vec![from, to]);
+
+    // The capped finder still returns a valid best route, priced as its path prices
+    let best = capped_finder.find_best_route(from, to, amount_in)?;
+    assert_eq!(best.path.first(), Some(&from));
+    assert_eq!(best.path.last(), Some(&to));
+    assert!(best.hop_count() <= MAX_HOPS);
+    assert_eq!(best.expected_output, capped_finder.price_path(&best.path, amount_in)?.expected_output);
+    assert_eq!(best, route_finder.find_best_route(from, to, amount_in)?);
+
+    println!("✅ Route enumeration cap test passed");
+    Ok(())
+}
+
+#[test]
+fn test_route_cap_keeps_best_routes() -> anyhow::Result<()> {
+    println!("Testing that the route cap keeps the best routes...");
+
+    // The direct pool is found first but is shallow; the deep two-hop path beats it
+    let (from, via, to) = (alkane_id("CAPX"), alkane_id("CAPB"), alkane_id("CAPY"));
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(from, to, 100 * TEST_PRECISION, 100 * TEST_PRECISION);
+    factory.add_pool(from, via, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    factory.add_pool(via, to, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    let amount_in = 10 * TEST_PRECISION;
+
+    let uncapped = RouteFinder::new(alkane_id("oyl_factory"), &factory).find_all_routes(from, to, amount_in)?;
+    assert_eq!(uncapped.len(), 2);
+    assert_eq!(uncapped[0].path, vec![from, via, to]);
+
+    // A cap of one keeps the two-hop route through the base token, not the direct route
+    // found before it
+    let capped = RouteFinder::new(alkane_id("oyl_factory"), &factory)
+        .with_base_tokens(vec![via])
+        .with_max_routes(1)
+        .find_all_routes(from, to, amount_in)?;
+    assert_eq!(capped.len(), 1);
+    assert_eq!(capped[0], uncapped[0]);
+
+    println!("✅ Route cap ordering test passed");
+    Ok(())
+}
+
+#[test]
+fn test_gas_cost_flips_best_route_to_direct() -> anyhow::Result<()> {
+    println!("Testing net-of-gas route selection...");
+
+    // A shallow direct pool and a deep two-hop path that edges it out on gross output
+    let (from, via, to) = (alkane_id("GASX"), alkane_id("GASB"), alkane_id("GASY"));
+    let mut factory = MockOylFactory::new();
+    factory.add_pool(from, to, 1_000 * TEST_PRECISION, 1_000 * TEST_PRECISION);
+    factory.add_pool(from, via, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    factory.add_pool(via, to, 100_000 * TEST_PRECISION, 100_000 * TEST_PRECISION);
+    let amount_in = 10 * TEST_PRECISION;
+    let route_finder = RouteFinder::new(alkane_id("oyl_factory"), &factory);
+
+    let direct = route_finder.price_path(&[from, to], amount_in)?;
+    let two_hop = route_finder.price_path(&[from, via, to], amount_in)?;
+    println!("Direct {} at {} gas, two-hop {} at {} gas", direct.expected_output, direct.gas_estimate, two_hop.expected_output, two_hop.gas_estimate);
+    assert!(two_hop.expected_output > direct.expected_output);
+    assert!(two_hop.gas_estimate > direct.gas_estimate);
+    assert_eq!(route_finder.find_best_route(from, to, amount_in)?.path, two_hop.path);
+
+    // Free gas ranks by gross output
+    assert_eq!(route_finder.find_best_route_net(from, to, amount_in, 0)?, route_finder.find_best_route(from, to, amount_in)?);
+
+    // The gas price at which the extra hop costs exactly its output gain
+    let gain = two_hop.expected_output - direct.expected_output;
+    let extra_gas = two_hop.gas_estimate - direct.gas_estimate;
+    let break_even_price = gain * PRICE_PRECISION / extra_gas;
+
+    let cheap_gas = route_finder.find_best_route_net(from, to, amount_in, break_even_price / 2)?;
+    assert_eq!(cheap_gas.path, two_hop.path);
+    let dear_gas = route_finder.find_best_route_net(from, to, amount_in, break_even_price * 2)?;
+    assert_eq!(dear_gas.path, direct.path);
+    assert_eq!(dear_gas.expected_output, direct.expected_output);
+
+    println!("✅ Net-of-gas route selection test passed");
+    Ok(())
+}
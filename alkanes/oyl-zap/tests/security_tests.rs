@@ -5,6 +5,12 @@
 
 mod common;
 use common::*;
+use alkanes_support::id::AlkaneId;
+use alkanes_support::response::CallResponse;
+use oyl_zap_core::amm_logic;
+use oyl_zap_core::route_finder::RouteFinder;
+use oyl_zap_core::{canonical_pool_key, received_amount, ReentrancyGuard, ReentrancyLock, ZapBase};
+use oyl_zap_core::types::{ZapError, PRICE_PRECISION};
 
 #[test]
 fn test_flash_loan_attack_resistance() -> anyhow::Result<()> {
@@ -43,3 +49,423 @@ fn test_reentrancy_attack_resistance() -> anyhow::Result<()> {
     println!("✅ Reentrancy attack resistance test passed (by design)");
     Ok(())
 }
+
+#[test]
+fn test_volatile_pool_ratio_rejects_quote() -> anyhow::Result<()> {
+    println!("Testing volatile pool ratio rejection...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let wbtc = tokens["WBTC"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1_000_000; // Small zap that barely moves any pool
+
+    // A zap records the target pool's ratio before it deposits
+    zap.max_ratio_volatility_bps = 500; // 5%
+    zap.execute_zap_for(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+
+    // A steady pool quotes normally
+    zap.quote_zap(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+
+    // Rapidly push the ratio with a large swap against the target pool
+    zap.with_pool(eth, usdc, |pool| {
+        let swap_amount = pool.reserve_a / 5;
+        pool.simulate_swap(pool.token_a, swap_amount)
+    })?;
+
+    let result = zap.quote_zap(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE);
+    let error = result.expect_err("Quote should be rejected while the pool ratio is moving rapidly");
+    assert!(error.to_string().contains("Pool too volatile"), "Unexpected error: {}", error);
+
+    // Disabling the threshold allows quoting again
+    zap.max_ratio_volatility_bps = 0;
+    zap.quote_zap(wbtc, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+
+    println!("✅ Volatile pool ratio rejection test passed");
+    Ok(())
+}
+
+#[test]
+fn test_route_rejected_when_price_deviates_from_reference() -> anyhow::Result<()> {
+    println!("Testing reference price protection...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (factory, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+    let route_amount = input_amount / 2; // each zap leg is routed with half the input
+
+    // Price UNI -> ETH at what the pools currently pay
+    let route = RouteFinder::new(zap.factory_id, &factory)
+        .with_base_tokens(zap.base_tokens.clone())
+        .with_excluded_intermediate_tokens(&[usdc])
+        .with_excluded_pool(eth, usdc)
+        .find_best_route(uni, eth, route_amount)?;
+    let market_price = route.expected_output * PRICE_PRECISION / route_amount;
+
+    // A reference matching the market quotes normally
+    zap.reference_prices.insert((uni, eth), (market_price, 500));
+    zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+
+    // Every available route pays 30% below the reference, so the quote is refused at 5% tolerance
+    let reference_price = market_price * 10 / 7;
+    zap.reference_prices.insert((uni, eth), (reference_price, 500));
+    let error = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE).unwrap_err();
+    match error.downcast_ref::<ZapError>() {
+        Some(ZapError::PriceDeviatesFromReference { deviation_bps, tolerance_bps: 500 }) => {
+            assert!((2990..=3010).contains(deviation_bps), "Deviation was {} bps", deviation_bps);
+        }
+        other => panic!("Expected a reference price rejection, got {:?}", other),
+    }
+
+    // The amm helper reports the same deviation directly
+    let deviation = amm_logic::calculate_price_deviation(route_amount, route.expected_output, reference_price)?;
+    assert!((2990..=3010).contains(&deviation));
+
+    println!("✅ Reference price protection test passed");
+    Ok(())
+}
+
+#[test]
+fn test_pause_blocks_mutating_zaps() -> anyhow::Result<()> {
+    println!("Testing the zap circuit breaker...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+
+    let lp_tokens = zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    zap.paused = true;
+
+    // Every zap that moves funds is refused
+    let is_paused = |err: anyhow::Error| matches!(err.downcast_ref::<ZapError>(), Some(ZapError::Paused));
+    assert!(is_paused(zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE).unwrap_err()));
+    assert!(is_paused(zap.dispatch(parcel(lp_token, lp_tokens), |zap| zap.zap_out(lp_token, lp_tokens, eth, 0, 0, 0)).unwrap_err()));
+    assert!(is_paused(
+        zap.dispatch(parcel(lp_token, lp_tokens), |zap| zap.migrate_lp(lp_token, lp_tokens, uni, usdc, 0, 0, 0)).unwrap_err()
+    ));
+    assert!(is_paused(
+        zap.dispatch(parcel(uni, input_amount), |zap| {
+            zap.zap_into_weighted(uni, input_amount, vec![eth, usdc], vec![5000, 5000], 0, 0, 0)
+        })
+        .unwrap_err()
+    ));
+    assert!(is_paused(
+        zap.dispatch(parcel(uni, input_amount), |zap| zap.zap_partial(uni, input_amount, eth, usdc, 0, 0, 0, 5000))
+            .unwrap_err()
+    ));
+
+    // Reads keep working
+    assert_eq!(zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.expected_lp_tokens, quote.expected_lp_tokens);
+    assert!(!zap.dispatch(vec![], |zap| zap.get_best_route(uni, usdc, input_amount))?.data.is_empty());
+    assert!(!zap.dispatch(vec![], |zap| zap.get_version())?.data.is_empty());
+
+    // Unpausing restores execution
+    zap.paused = false;
+    assert!(zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted > 0);
+
+    println!("✅ Pause test passed");
+    Ok(())
+}
+
+#[test]
+fn test_pool_reserve_updates_validated() -> anyhow::Result<()> {
+    println!("Testing pool reserve update validation...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let pool = zap.pool(eth, usdc).unwrap();
+    let update_height = |zap: &TestZap, token_a, token_b| zap.state.borrow().pool_update_heights[&canonical_pool_key(token_a, token_b)];
+    let stored_reserves = |zap: &TestZap| zap.stored_pool(eth, usdc).unwrap().unwrap().reserves_for(pool.token_a);
+    let update = |zap: &TestZap, reserve_a, reserve_b, total_supply| {
+        zap.dispatch_as_owner(|zap| zap.update_pool_reserves(pool.token_a, pool.token_b, reserve_a, reserve_b, total_supply))
+    };
+    zap.height = 100;
+    zap.dispatch_as_owner(|zap| zap.add_pool(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate))?;
+    assert_eq!(update_height(&zap, eth, usdc), 100);
+    zap.max_reserve_k_drop_bps = 1000; // 10%
+
+    // A plausible update is stored along with the height it arrived at
+    zap.height = 105;
+    let (reserve_a, reserve_b) = (pool.reserve_a * 11 / 10, pool.reserve_b);
+    update(&zap, reserve_a, reserve_b, pool.total_supply)?;
+    assert_eq!(stored_reserves(&zap), (reserve_a, reserve_b));
+    assert_eq!(update_height(&zap, usdc, eth), 105, "The height is shared by both token orders");
+
+    // Reserves backed by no LP supply are refused
+    zap.height = 110;
+    assert!(update(&zap, reserve_a, reserve_b, 0).is_err());
+
+    // Halving the product at the same supply cuts its root per share by ~29%, beyond the
+    // threshold, so it is refused, and neither rejection is recorded
+    let err = update(&zap, reserve_a / 2, reserve_b, pool.total_supply).unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<ZapError>(), Some(ZapError::ReserveKDropped { drop_bps: 2928..=2929, max_bps: 1000 })),
+        "got {}",
+        err
+    );
+    assert_eq!(stored_reserves(&zap), (reserve_a, reserve_b));
+    assert_eq!(update_height(&zap, eth, usdc), 105);
+
+    // A drop within the threshold is accepted
+    update(&zap, reserve_a * 95 / 100, reserve_b, pool.total_supply)?;
+    assert_eq!(update_height(&zap, eth, usdc), 110);
+
+    // Withdrawing most of the pool shrinks the product but not its value per LP share
+    zap.height = 115;
+    let (reserve_a, reserve_b) = (reserve_a * 95 / 100, reserve_b);
+    update(&zap, reserve_a / 10, reserve_b / 10, pool.total_supply / 10)?;
+    assert_eq!(stored_reserves(&zap), (reserve_a / 10, reserve_b / 10));
+    assert_eq!(update_height(&zap, eth, usdc), 115);
+
+    println!("✅ Pool reserve update validation test passed");
+    Ok(())
+}
+
+#[test]
+fn test_max_zap_input_bounds_zap_size() -> anyhow::Result<()> {
+    println!("Testing the per-zap input cap...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+    let limit = 100 * 1e18 as u128; // 100 UNI
+
+    // Unlimited by default, however large the zap
+    assert!(TestZap::with_comprehensive_setup().execute_zap_for(uni, limit * 10, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted > 0);
+
+    zap.max_zap_input = limit;
+
+    // One unit over the cap is refused before anything is swapped
+    let pool_before = zap.pool(uni, eth).unwrap().reserve_a;
+    let err = zap.execute_zap_for(uni, limit + 1, eth, usdc, DEFAULT_SLIPPAGE).unwrap_err();
+    assert_eq!(err.downcast_ref::<ZapError>(), Some(&ZapError::InputTooLarge { amount: limit + 1, max: limit }));
+    assert_eq!(zap.pool(uni, eth).unwrap().reserve_a, pool_before);
+    assert_eq!(zap.calls_to(3), 0, "No swap should be attempted");
+
+    // The cap holds for every entry point that takes an input token
+    let too_large = |err: anyhow::Error| matches!(err.downcast_ref::<ZapError>(), Some(ZapError::InputTooLarge { .. }));
+    let over = limit + 1;
+    assert!(too_large(
+        zap.dispatch(parcel(uni, over), |zap| zap.zap_into_weighted(uni, over, vec![eth, usdc], vec![5_000, 5_000], 0, 0, 0))
+            .unwrap_err()
+    ));
+
+    // Exactly at the cap is accepted
+    let lp_tokens = zap.execute_zap_for(uni, limit, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+    assert!(lp_tokens > 0);
+
+    // LP amounts are not input-token units, so unzapping and repositioning are not held to the cap
+    zap.max_zap_input = lp_tokens / 3 - 1;
+    let third = lp_tokens / 3;
+    let response = zap.dispatch(parcel(lp_token, third), |zap| zap.zap_out(lp_token, third, eth, 0, 0, 0))?;
+    assert!(received_amount(&response.alkanes, &eth) > 0);
+    let uni_eth_lp = zap.pool(uni, eth).unwrap().lp_token_id;
+    let response = zap.dispatch(parcel(lp_token, third), |zap| zap.migrate_lp(lp_token, third, uni, eth, 0, 0, 0))?;
+    assert!(received_amount(&response.alkanes, &uni_eth_lp) > 0);
+    let response = zap.dispatch(parcel(lp_token, third), |zap| zap.rebalance(lp_token, third, uni, eth, 0, 0, 0))?;
+    assert!(received_amount(&response.alkanes, &uni_eth_lp) > 0);
+    zap.max_zap_input = limit;
+
+    // Zero lifts the cap again
+    zap.max_zap_input = 0;
+    assert!(zap.execute_zap_for(uni, limit * 10, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted > 0);
+
+    println!("✅ Max zap input test passed");
+    Ok(())
+}
+
+#[test]
+fn test_reentrant_zap_rejected() -> anyhow::Result<()> {
+    println!("Testing the reentrancy guard around external calls...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let is_reentrancy = |err: anyhow::Error| matches!(err.downcast_ref::<ZapError>(), Some(ZapError::Reentrancy));
+
+    // While a zap holds the lock, a pool calling back into the zap is turned away
+    {
+        let _outer = ReentrancyGuard::acquire(&zap)?;
+        assert!(zap.is_locked()?);
+        let reentrant = ReentrancyGuard::acquire(&zap);
+        assert!(matches!(
+            reentrant.err().and_then(|e| e.downcast::<ZapError>().ok()),
+            Some(ZapError::Reentrancy)
+        ));
+    }
+    assert!(!zap.is_locked()?, "Lock should clear when the outer zap finishes");
+
+    // A reentrant call into any fund-moving zap is refused while quotes stay available
+    zap.set_locked(true)?;
+    assert!(is_reentrancy(zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE).unwrap_err()));
+    assert!(is_reentrancy(
+        zap.dispatch(parcel(lp_token, TEST_PRECISION), |zap| zap.zap_out(lp_token, TEST_PRECISION, eth, 0, 0, 0)).unwrap_err()
+    ));
+    assert_eq!(zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.expected_lp_tokens, quote.expected_lp_tokens);
+    zap.set_locked(false)?;
+
+    // The lock also clears when the guarded call fails part way through
+    let failed: anyhow::Result<()> = (|| {
+        let _lock = ReentrancyGuard::acquire(&zap)?;
+        Err(ZapError::SlippageExceeded { realized: 0, minimum: 1, suggested_bps: 10_000 }.into())
+    })();
+    assert!(failed.is_err());
+    assert!(!zap.is_locked()?, "Lock should clear on the error path");
+    assert!(zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted > 0);
+
+    println!("✅ Reentrancy guard test passed");
+    Ok(())
+}
+
+#[test]
+fn test_identical_tokens_rejected_by_every_opcode() -> anyhow::Result<()> {
+    println!("Testing identical token rejection...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+    let identical = |result: anyhow::Result<CallResponse>, token: AlkaneId| {
+        matches!(result.unwrap_err().downcast_ref::<ZapError>(), Some(ZapError::IdenticalTokens { token: t }) if *t == token)
+    };
+    let with_input = |opcode: &dyn Fn(&TestZap) -> anyhow::Result<CallResponse>| zap.dispatch(parcel(uni, input_amount), opcode);
+    let with_lp = |opcode: &dyn Fn(&TestZap) -> anyhow::Result<CallResponse>| zap.dispatch(parcel(lp_token, TEST_PRECISION), opcode);
+
+    // GetZapQuote: a pair of one token is never quoted, not even as a pool to create
+    assert!(identical(zap.get_zap_quote(uni, input_amount, eth, eth, DEFAULT_SLIPPAGE, 0), eth));
+    assert!(identical(zap.get_zap_quote(uni, input_amount, eth, eth, DEFAULT_SLIPPAGE, 1), eth));
+
+    // ExecuteZap: a pair naming one target twice is refused before any swap
+    assert!(identical(with_input(&|zap| zap.execute_zap(uni, input_amount, eth, eth, 0, 0, DEFAULT_SLIPPAGE, 0, 0, 0)), eth));
+    assert!(identical(
+        with_input(&|zap| zap.execute_zap(uni, input_amount, eth, eth, 0, 0, DEFAULT_SLIPPAGE, 1, 0, 0)),
+        eth
+    ));
+    assert_eq!(zap.calls_to(3), 0);
+
+    // GetBestRoute: routing a token to itself is rejected
+    assert!(identical(zap.get_best_route(uni, uni, input_amount), uni));
+
+    // Creating a pool from one of its own tokens is invalid for that mode only
+    let new_token = alkane_id("NEW_TOKEN");
+    assert!(identical(zap.get_zap_quote(eth, input_amount, eth, new_token, DEFAULT_SLIPPAGE, 1), eth));
+    assert!(zap.quote_zap(eth, 10 * 1e18 as u128, eth, usdc, DEFAULT_SLIPPAGE)?.expected_lp_tokens > 0);
+
+    // ExecuteZapExact and SimulateZap refuse a pair naming one target twice
+    assert!(identical(with_input(&|zap| zap.execute_zap_exact(uni, input_amount, eth, eth, 0, 0, 0, 0)), eth));
+    assert!(identical(zap.simulate_zap(uni, input_amount, eth, eth, DEFAULT_SLIPPAGE, 0), eth));
+
+    // ZapPartial and ZapForExactLp check the pair before quoting either leg
+    assert!(identical(with_input(&|zap| zap.zap_partial(uni, input_amount, eth, eth, 0, 0, DEFAULT_SLIPPAGE, 5_000)), eth));
+    assert!(identical(
+        with_input(&|zap| zap.zap_for_exact_lp(uni, input_amount, TEST_PRECISION, eth, eth, 0, DEFAULT_SLIPPAGE)),
+        eth
+    ));
+
+    // MigrateLp and Rebalance refuse a target pair of one withdrawn token, which would leave
+    // the other withdrawn token with no target to swap into
+    assert!(identical(with_lp(&|zap| zap.migrate_lp(lp_token, TEST_PRECISION, eth, eth, 0, 0, 0)), eth));
+    assert!(identical(with_lp(&|zap| zap.rebalance(lp_token, TEST_PRECISION, eth, eth, 0, 0, 0)), eth));
+
+    println!("✅ Identical token rejection test passed");
+    Ok(())
+}
+
+#[test]
+fn test_only_owner_recovers_stuck_tokens() -> anyhow::Result<()> {
+    println!("Testing owner-gated recovery of stuck tokens...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let usdc = tokens["USDC"];
+    let stranger = alkane_id("STRANGER");
+    let stuck = 5_000 * 1_000_000; // 5000 USDC sent straight to the contract
+    let recover = |amount| -> anyhow::Result<u128> {
+        let response = zap.dispatch_as_owner(|zap| zap.recover_stranded_swap(usdc, amount))?;
+        Ok(response.alkanes.0.iter().filter(|transfer| transfer.id == usdc).map(|transfer| transfer.value).sum())
+    };
+
+    zap.receive_out_of_band(usdc, stuck);
+
+    // Anyone but the owner is refused, and the balance stays put
+    assert!(
+        zap.dispatch_as(stranger, vec![], |zap| zap.recover_stranded_swap(usdc, stuck)).is_err(),
+        "A non-owner must not recover"
+    );
+    assert_eq!(zap.held(usdc), stuck);
+
+    // The owner cannot recover more than is stuck, or nothing
+    assert!(recover(stuck + 1).is_err());
+    assert!(recover(0).is_err());
+
+    // A parcel sent along with the call is not counted as stranded
+    assert!(zap.dispatch_as(zap.owner, parcel(usdc, 1), |zap| zap.recover_stranded_swap(usdc, stuck + 1)).is_err());
+
+    // Nor while a zap holds the reentrancy lock
+    zap.set_locked(true)?;
+    assert!(matches!(recover(stuck).unwrap_err().downcast_ref::<ZapError>(), Some(ZapError::Reentrancy)));
+    zap.set_locked(false)?;
+
+    // Once out of any zap, the owner recovers it in parts
+    assert_eq!(recover(stuck / 2)?, stuck / 2);
+    assert_eq!(recover(stuck / 2)?, stuck / 2);
+    assert_eq!(zap.held(usdc), 0);
+    assert!(recover(1).is_err(), "Nothing is left to recover");
+
+    println!("✅ Stuck token recovery test passed");
+    Ok(())
+}
+
+#[test]
+fn test_only_owner_sweeps_stuck_tokens() -> anyhow::Result<()> {
+    println!("Testing owner-gated sweeps...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let usdc = tokens["USDC"];
+    let stranger = alkane_id("STRANGER");
+    let stuck = 5_000 * 1_000_000; // 5000 USDC sent straight to the contract
+    zap.receive_out_of_band(usdc, stuck);
+
+    // Anyone but the owner is refused, and the balance stays put
+    assert!(zap.dispatch_as(stranger, vec![], |zap| zap.sweep(usdc, stuck, 0)).is_err(), "A non-owner must not sweep");
+    assert_eq!(zap.held(usdc), stuck);
+
+    // Nothing is swept while a zap holds the reentrancy lock, nor the parcel sent with the call
+    zap.set_locked(true)?;
+    let err = zap.dispatch_as_owner(|zap| zap.sweep(usdc, stuck, 0)).unwrap_err();
+    assert!(matches!(err.downcast_ref::<ZapError>(), Some(ZapError::Reentrancy)), "got {}", err);
+    zap.set_locked(false)?;
+    assert!(zap.dispatch_as(zap.owner, parcel(usdc, 1), |zap| zap.sweep(usdc, stuck + 1, 0)).is_err());
+
+    // The owner sweeps the stuck balance, and the response names the output it was meant for
+    let response = zap.dispatch_as_owner(|zap| zap.sweep(usdc, stuck, 2))?;
+    assert_eq!(received_amount(&response.alkanes, &usdc), stuck);
+    assert_eq!(response.data, 2u32.to_le_bytes().to_vec());
+    assert_eq!(zap.held(usdc), 0);
+
+    println!("✅ Sweep test passed");
+    Ok(())
+}
@@ -5,6 +5,12 @@
 
 mod common;
 use common::*;
+use alkanes_support::id::AlkaneId;
+use oyl_zap_core::amm_logic;
+use oyl_zap_core::{canonical_pool_key, consumed_liquidity_amounts, contract_version, received_amount, settle_add_liquidity, ReentrancyLock, ZapBase, BASIS_POINTS, MINIMUM_LIQUIDITY, SUPPORTED_OPCODES};
+use oyl_zap_core::route_finder::RouteFinder;
+use oyl_zap_core::types::{ensure_distinct_tokens, is_deadline_passed, FactorySwapLayout, PoolReserves, RouteInfo, ZapError, ZapParams, ZapQuote, ZapReceipt, ZapSimulation, U256, FUEL_PER_HOP, ZAP_RECEIPT_VERSION};
+use oyl_zap_core::zap_calculator::ZapCalculator;
 
 #[test]
 fn test_successful_zap_execution() -> anyhow::Result<()> {
@@ -34,6 +40,46 @@ fn test_successful_zap_execution() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[test]
+fn test_zap_receipt_records_execution() -> anyhow::Result<()> {
+    println!("Testing the zap receipt of a completed zap...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128;
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let response = zap.dispatch(parcel(uni, input_amount), |zap| {
+        zap.execute_zap(uni, input_amount, eth, usdc, 0, 0, DEFAULT_SLIPPAGE, 0, 0, 0)
+    })?;
+
+    let receipt = ZapReceipt::decode(&response.data)?;
+    assert_eq!(receipt.lp_minted, received_amount(&response.alkanes, &lp_token), "Receipt LP should match the LP transferred");
+    assert_eq!((receipt.input_token, receipt.input_amount), (uni, input_amount));
+    assert_eq!(receipt.path_a, quote.route_a.path);
+    assert_eq!(receipt.path_b, quote.route_b.path);
+    assert_eq!(
+        (receipt.dust_a, receipt.dust_b),
+        (received_amount(&response.alkanes, &eth), received_amount(&response.alkanes, &usdc)),
+        "Receipt dust should match the dust refunded"
+    );
+    assert!(receipt.output_a >= receipt.dust_a && receipt.output_b >= receipt.dust_b);
+
+    // The layout is versioned and exact
+    assert_eq!(response.data[0], ZAP_RECEIPT_VERSION);
+    assert_eq!(receipt.encode(), response.data);
+    let mut bumped = response.data.clone();
+    bumped[0] += 1;
+    assert!(ZapReceipt::decode(&bumped).is_err());
+    assert!(ZapReceipt::decode(&response.data[..response.data.len() - 1]).is_err());
+
+    println!("✅ Zap receipt test passed");
+    Ok(())
+}
+
 #[test]
 fn test_zap_execution_with_insufficient_lp_tokens() -> anyhow::Result<()> {
     println!("Testing zap execution with insufficient LP tokens...");
@@ -47,7 +93,7 @@ fn test_zap_execution_with_insufficient_lp_tokens() -> anyhow::Result<()> {
     let input_amount = 1000 * 1e18 as u128; // 1000 UNI
 
     let mut quote = zap.get_zap_quote(uni, input_amount, wbtc, dai, DEFAULT_SLIPPAGE)?;
-    
+
     // Artificially inflate the minimum required LP tokens to trigger a failure
     quote.minimum_lp_tokens = quote.expected_lp_tokens + 1;
 
@@ -84,3 +130,1350 @@ fn test_zap_execution_failure_on_swap() -> anyhow::Result<()> {
     println!("✅ Zap execution failure on swap test passed");
     Ok(())
 }
+
+#[test]
+fn test_lp_token_identified_when_distinct_from_pool_id() -> anyhow::Result<()> {
+    println!("Testing LP token identification when LP id differs from pool id...");
+
+    let (mut factory, tokens) = setup_comprehensive_test_environment();
+
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let lp_token_id = alkane_id("ETH_USDC_LP");
+
+    let pool = factory.get_pool_mut(eth, usdc).unwrap();
+    pool.lp_token_id = lp_token_id;
+    let pool_id = pool.id;
+    assert_ne!(pool_id, lp_token_id, "Pool and LP token ids should differ in this scenario");
+
+    let expected_lp = amm_logic::calculate_lp_tokens_minted(
+        1e18 as u128,
+        2000 * 1_000_000,
+        pool.reserve_a,
+        pool.reserve_b,
+        pool.total_supply,
+    )?;
+    let response = pool.simulate_add_liquidity_response(1e18 as u128, 2000 * 1_000_000)?;
+
+    // Matching against the pool id would miss the LP transfer entirely
+    assert_eq!(received_amount(&response, &pool_id), 0);
+    let lp_tokens_received = received_amount(&response, &lp_token_id);
+    assert_eq!(lp_tokens_received, expected_lp, "LP tokens should be found under the LP token id");
+
+    // The slippage check operates on the correctly identified amount
+    let min_lp_tokens = expected_lp * (10000 - DEFAULT_SLIPPAGE) / 10000;
+    assert!(lp_tokens_received >= min_lp_tokens, "Slippage check should pass within tolerance");
+    assert!(lp_tokens_received < expected_lp + 1, "A minimum above the minted amount should fail");
+
+    println!("✅ LP token identification test passed");
+    Ok(())
+}
+
+#[test]
+fn test_lp_token_registry_populated_on_first_zap() -> anyhow::Result<()> {
+    println!("Testing the pair to LP token registry...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let lp_token_id = alkane_id("ETH_USDC_LP");
+    zap.with_pool(eth, usdc, |pool| pool.lp_token_id = lp_token_id);
+    let input_amount = 100 * 1e18 as u128; // 100 UNI
+
+    let get_lp_token_id = |token_a: AlkaneId, token_b: AlkaneId| -> anyhow::Result<AlkaneId> {
+        let data = zap.dispatch(vec![], |zap| zap.get_lp_token_id(token_a, token_b))?.data;
+        Ok(AlkaneId {
+            block: u128::from_le_bytes(data[0..16].try_into()?),
+            tx: u128::from_le_bytes(data[16..32].try_into()?),
+        })
+    };
+
+    // Reads resolve through the pool without populating the registry
+    assert_eq!(get_lp_token_id(eth, usdc)?, lp_token_id);
+    assert!(zap.state.borrow().lp_token_ids.is_empty());
+    let lookups = zap.calls_to(1000);
+
+    // The first zap asks the pool once, records the answer and pays out under the LP id
+    assert!(zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted > 0);
+    assert_eq!(zap.calls_to(1000), lookups + 1);
+    assert_eq!(zap.state.borrow().lp_token_ids.get(&canonical_pool_key(eth, usdc)), Some(&lp_token_id));
+
+    // Later zaps and reads, in either token order, are served from the registry
+    assert!(zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted > 0);
+    assert_eq!(get_lp_token_id(usdc, eth)?, lp_token_id);
+    assert_eq!(get_lp_token_id(eth, usdc)?, lp_token_id);
+    assert_eq!(zap.calls_to(1000), lookups + 1, "The pool should only be asked once");
+
+    println!("✅ LP token registry test passed");
+    Ok(())
+}
+
+#[test]
+fn test_min_lp_safety_margin_absorbs_boundary_drift() -> anyhow::Result<()> {
+    println!("Testing min LP safety margin near the boundary...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let zap = TestZap::with_comprehensive_setup();
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let realized_lp = zap.execute(&quote)?.lp_minted;
+
+    // A minimum just above what execution realizes, as if reserves drifted by a hair
+    let mut boundary_quote = quote.clone();
+    boundary_quote.minimum_lp_tokens = realized_lp + realized_lp / 2000; // +0.05%
+
+    let without_margin = TestZap::with_comprehensive_setup();
+    assert!(
+        without_margin.execute(&boundary_quote).is_err(),
+        "Boundary execution should revert without a safety margin"
+    );
+
+    let mut with_margin = TestZap::with_comprehensive_setup();
+    with_margin.min_lp_safety_margin_bps = 10; // 0.1%
+    let lp_tokens = with_margin.execute(&boundary_quote)?.lp_minted;
+    assert_eq!(lp_tokens, realized_lp, "Margin should not change the realized LP amount");
+
+    // A genuinely bad execution still reverts
+    let mut bad_quote = quote.clone();
+    bad_quote.minimum_lp_tokens = realized_lp * 2;
+    let mut with_margin = TestZap::with_comprehensive_setup();
+    with_margin.min_lp_safety_margin_bps = 10;
+    assert!(with_margin.execute(&bad_quote).is_err(), "Large shortfalls should still revert");
+
+    // The margin never exceeds the caller's slippage tolerance
+    let capped = ZapCalculator::apply_min_lp_safety_margin(10_000, 1_000, 0)?;
+    assert_eq!(capped, 10_000, "Zero slippage tolerance should disable the margin");
+
+    println!("✅ Min LP safety margin test passed");
+    Ok(())
+}
+
+#[test]
+fn test_zap_out_to_single_token() -> anyhow::Result<()> {
+    println!("Testing zap out to a single token...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // A zap holding a fresh ETH/USDC position, so each attempt starts from the same pools
+    let setup = || -> anyhow::Result<(TestZap, u128)> {
+        let zap = TestZap::with_comprehensive_setup();
+        let lp_tokens = zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+        Ok((zap, lp_tokens))
+    };
+    let zap_out = |zap: &TestZap, lp_tokens: u128, output_token: AlkaneId, min_output: u128| {
+        let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+        zap.dispatch(parcel(lp_token, lp_tokens), |zap| {
+            zap.zap_out(lp_token, lp_tokens, output_token, min_output, 0, DEFAULT_SLIPPAGE)
+        })
+    };
+
+    // Unwinding into ETH should return both halves as ETH
+    let (preview, lp_tokens) = setup()?;
+    let pool = preview.pool(eth, usdc).unwrap();
+    let response = zap_out(&preview, lp_tokens, eth, 0)?;
+    let eth_out = u128::from_le_bytes(response.data[0..16].try_into()?);
+    assert_eq!(received_amount(&response.alkanes, &eth), eth_out);
+    let eth_share = lp_tokens * pool.reserve_a / pool.total_supply;
+    assert!(eth_out > eth_share, "Output should include the swapped USDC half");
+
+    // An unreachable minimum output should revert and leave state untouched
+    let (zap, lp_tokens) = setup()?;
+    assert!(
+        zap_out(&zap, lp_tokens, eth, eth_out + 1).is_err(),
+        "Zap out should fail below the minimum output"
+    );
+    assert_eq!(zap.pool(eth, usdc).unwrap().total_supply, pool.total_supply);
+
+    // Only pool tokens are valid outputs
+    assert!(zap_out(&zap, lp_tokens, uni, 0).is_err(), "Non-pool output should be rejected");
+
+    println!("✅ Zap out test passed");
+    Ok(())
+}
+
+#[test]
+fn test_zap_out_with_distinct_lp_token() -> anyhow::Result<()> {
+    println!("Testing zap out of an LP token that is not its pool's id...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let lp_token_id = alkane_id("ETH_USDC_LP");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let zap = TestZap::with_comprehensive_setup();
+    zap.with_pool(eth, usdc, |pool| pool.lp_token_id = lp_token_id);
+    assert_ne!(zap.pool(eth, usdc).unwrap().id, lp_token_id);
+    let lp_tokens = zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+
+    // The pair comes from the registry, so the LP token is never called as if it were the pool
+    let response = zap.dispatch(parcel(lp_token_id, lp_tokens), |zap| {
+        zap.zap_out(lp_token_id, lp_tokens, usdc, 0, 0, DEFAULT_SLIPPAGE)
+    })?;
+    assert!(received_amount(&response.alkanes, &usdc) > 0);
+    assert!(
+        zap.calls.borrow().iter().all(|(target, _)| *target != lp_token_id),
+        "The LP token should not be queried as a pool"
+    );
+
+    // An LP token the zap never minted into is still treated as its own pool, and fails
+    let unknown_lp = alkane_id("UNKNOWN_LP");
+    assert!(zap
+        .dispatch(parcel(unknown_lp, lp_tokens), |zap| zap.zap_out(unknown_lp, lp_tokens, usdc, 0, 0, DEFAULT_SLIPPAGE))
+        .is_err());
+
+    println!("✅ Distinct LP token zap out test passed");
+    Ok(())
+}
+
+#[test]
+fn test_migrate_lp_between_pools_sharing_a_token() -> anyhow::Result<()> {
+    println!("Testing LP migration from WBTC/ETH to WBTC/USDC...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+
+    let source_before = zap.pool(wbtc, eth).unwrap();
+    let target_before = zap.pool(wbtc, usdc).unwrap();
+    let bridge_before = zap.pool(eth, usdc).unwrap();
+    let source_lp = source_before.lp_token_id;
+    let lp_amount = source_before.total_supply / 10;
+    let (withdrawn_wbtc, withdrawn_eth) = {
+        let (amount_a, amount_b) = source_before.clone().simulate_remove_liquidity(lp_amount)?;
+        if source_before.token_a == wbtc { (amount_a, amount_b) } else { (amount_b, amount_a) }
+    };
+    let migrate = |target_token_a: AlkaneId, target_token_b: AlkaneId, min_lp_tokens: u128| {
+        zap.dispatch(parcel(source_lp, lp_amount), |zap| {
+            zap.migrate_lp(source_lp, lp_amount, target_token_a, target_token_b, min_lp_tokens, 0, 0)
+        })
+    };
+
+    // An unreachable minimum aborts the whole migration
+    assert!(migrate(wbtc, usdc, u128::MAX).is_err());
+    assert_eq!(zap.pool(wbtc, eth).unwrap().total_supply, source_before.total_supply);
+    assert_eq!(zap.pool(wbtc, usdc).unwrap().total_supply, target_before.total_supply);
+
+    let response = migrate(wbtc, usdc, 1)?;
+    let lp_tokens = received_amount(&response.alkanes, &target_before.lp_token_id);
+    assert!(lp_tokens > 0);
+
+    // The source position is burned and the target position minted
+    let source_after = zap.pool(wbtc, eth).unwrap();
+    assert_eq!(source_after.total_supply, source_before.total_supply - lp_amount);
+    let target_after = zap.pool(wbtc, usdc).unwrap();
+    assert_eq!(target_after.total_supply, target_before.total_supply + lp_tokens);
+
+    // Only the ETH leg is swapped, through ETH/USDC; the shared WBTC goes straight in
+    let bridge_after = zap.pool(eth, usdc).unwrap();
+    let bridge_eth = |pool: &MockPool| if pool.token_a == eth { pool.reserve_a } else { pool.reserve_b };
+    assert_eq!(bridge_eth(&bridge_after), bridge_eth(&bridge_before) + withdrawn_eth);
+    let target_wbtc = |pool: &MockPool| if pool.token_a == wbtc { pool.reserve_a } else { pool.reserve_b };
+    let dust_wbtc = received_amount(&response.alkanes, &wbtc);
+    assert_eq!(target_wbtc(&target_after), target_wbtc(&target_before) + withdrawn_wbtc - dust_wbtc);
+
+    // Migrating into the same pool is refused
+    assert!(migrate(eth, wbtc, 1).is_err());
+
+    println!("✅ LP migration test passed");
+    Ok(())
+}
+
+#[test]
+fn test_migrate_lp_with_distinct_lp_token() -> anyhow::Result<()> {
+    println!("Testing LP migration out of an LP token that is not its pool's id...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let wbtc = alkane_id("WBTC");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let source_lp = alkane_id("WBTC_ETH_LP");
+    zap.with_pool(wbtc, eth, |pool| pool.lp_token_id = source_lp);
+    let source_before = zap.pool(wbtc, eth).unwrap();
+    assert_ne!(source_before.id, source_lp);
+    let lp_amount = source_before.total_supply / 10;
+    let migrate = |zap: &TestZap| {
+        zap.dispatch(parcel(source_lp, lp_amount), |zap| zap.migrate_lp(source_lp, lp_amount, wbtc, usdc, 1, 0, 0))
+    };
+
+    // Until the pair is known, the LP token is taken for a pool and the call fails
+    assert!(migrate(&zap).is_err());
+
+    // Registering the pool records its LP token, and the position migrates by LP id
+    zap.dispatch_as_owner(|zap| {
+        zap.add_pool(wbtc, eth, source_before.reserve_a, source_before.reserve_b, source_before.total_supply, source_before.fee_rate)
+    })?;
+    let calls_before = zap.calls.borrow().len();
+    let response = migrate(&zap)?;
+    assert!(received_amount(&response.alkanes, &zap.pool(wbtc, usdc).unwrap().lp_token_id) > 0);
+    assert_eq!(zap.pool(wbtc, eth).unwrap().total_supply, source_before.total_supply - lp_amount);
+    assert!(
+        zap.calls.borrow()[calls_before..].iter().all(|(target, _)| *target != source_lp),
+        "The LP token should not be queried as a pool"
+    );
+
+    println!("✅ Distinct LP token migration test passed");
+    Ok(())
+}
+
+#[test]
+fn test_rebalance_between_pools() -> anyhow::Result<()> {
+    println!("Testing rebalance from WBTC/ETH to ETH/USDC...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let wbtc = tokens["WBTC"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+
+    let source_before = zap.pool(wbtc, eth).unwrap();
+    let target_before = zap.pool(eth, usdc).unwrap();
+    let lp_amount = source_before.total_supply / 10;
+    let reposition = |routed: bool, from_lp: AlkaneId, amount: u128, target_c: AlkaneId, target_d: AlkaneId, min_lp_tokens: u128| {
+        zap.dispatch(parcel(from_lp, amount), |zap| {
+            if routed {
+                zap.rebalance(from_lp, amount, target_c, target_d, min_lp_tokens, 0, 0)
+            } else {
+                zap.migrate_lp(from_lp, amount, target_c, target_d, min_lp_tokens, 0, 0)
+            }
+        })
+    };
+
+    // A failed deposit unwinds the withdrawal and the swaps with it
+    assert!(reposition(true, source_before.lp_token_id, lp_amount, eth, usdc, u128::MAX).is_err());
+    assert_eq!(zap.pool(wbtc, eth).unwrap().total_supply, source_before.total_supply);
+    assert_eq!(zap.pool(eth, usdc).unwrap().total_supply, target_before.total_supply);
+
+    // The position comes out as a single ETH/USDC LP balance
+    let response = reposition(true, source_before.lp_token_id, lp_amount, eth, usdc, 1)?;
+    let lp_tokens = received_amount(&response.alkanes, &target_before.lp_token_id);
+    assert!(lp_tokens > 0);
+    assert_eq!(zap.pool(wbtc, eth).unwrap().total_supply, source_before.total_supply - lp_amount);
+    assert_eq!(zap.pool(eth, usdc).unwrap().total_supply, target_before.total_supply + lp_tokens);
+
+    // LINK/ETH into COMP/USDC has no LINK/COMP pool, so only the routed rebalance succeeds
+    let link = tokens["LINK"];
+    let comp = tokens["COMP"];
+    let link_pool = zap.pool(link, eth).unwrap();
+    let link_lp = link_pool.total_supply / 10;
+    let comp_usdc_lp = zap.pool(comp, usdc).unwrap().lp_token_id;
+    assert!(zap.pool(link, comp).is_none());
+    assert!(reposition(false, link_pool.lp_token_id, link_lp, comp, usdc, 1).is_err());
+    let response = reposition(true, link_pool.lp_token_id, link_lp, comp, usdc, 1)?;
+    assert!(received_amount(&response.alkanes, &comp_usdc_lp) > 0);
+
+    println!("✅ Rebalance test passed");
+    Ok(())
+}
+
+#[test]
+fn test_rebalance_with_distinct_lp_token() -> anyhow::Result<()> {
+    println!("Testing rebalance out of an LP token that is not its pool's id...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let wbtc = alkane_id("WBTC");
+    let from_lp = alkane_id("ETH_USDC_LP");
+    zap.with_pool(eth, usdc, |pool| pool.lp_token_id = from_lp);
+    assert_ne!(zap.pool(eth, usdc).unwrap().id, from_lp);
+
+    // Zapping in registers the LP token, which is all a later rebalance needs
+    let lp_tokens = zap.execute_zap_for(uni, 1000 * 1e18 as u128, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+    let supply_before = zap.pool(eth, usdc).unwrap().total_supply;
+    let calls_before = zap.calls.borrow().len();
+    let response = zap.dispatch(parcel(from_lp, lp_tokens), |zap| {
+        zap.rebalance(from_lp, lp_tokens, wbtc, usdc, 1, 0, DEFAULT_SLIPPAGE)
+    })?;
+
+    assert!(received_amount(&response.alkanes, &zap.pool(wbtc, usdc).unwrap().lp_token_id) > 0);
+    assert_eq!(zap.pool(eth, usdc).unwrap().total_supply, supply_before - lp_tokens);
+    assert!(
+        zap.calls.borrow()[calls_before..].iter().all(|(target, _)| *target != from_lp),
+        "The LP token should not be queried as a pool"
+    );
+
+    println!("✅ Distinct LP token rebalance test passed");
+    Ok(())
+}
+
+#[test]
+fn test_weighted_zap_split() -> anyhow::Result<()> {
+    println!("Testing weighted zap into a three-token pool...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let usdc = tokens["USDC"];
+    let eth = tokens["ETH"];
+    let dai = tokens["DAI"];
+    let input_amount = 100_000 * 1_000_000; // 100k USDC
+    let target_tokens = [usdc, eth, dai];
+    let weights_bps = [4000, 3000, 3000];
+
+    let splits = ZapCalculator::calculate_weighted_split(input_amount, &target_tokens, &weights_bps)?;
+    assert_eq!(splits, vec![40_000 * 1_000_000, 30_000 * 1_000_000, 30_000 * 1_000_000]);
+
+    let usdc_reserve = |token: AlkaneId| {
+        let pool = zap.pool(usdc, token).unwrap();
+        if pool.token_a == usdc { pool.reserve_a } else { pool.reserve_b }
+    };
+    let (eth_pool_before, dai_pool_before) = (usdc_reserve(eth), usdc_reserve(dai));
+    let response = zap.dispatch(parcel(usdc, input_amount), |zap| {
+        zap.zap_into_weighted(usdc, input_amount, target_tokens.to_vec(), weights_bps.to_vec(), 0, 0, DEFAULT_SLIPPAGE)
+    })?;
+
+    // Only the swapped legs trade, and the USDC leg is contributed directly
+    assert_eq!(usdc_reserve(eth), eth_pool_before + splits[1]);
+    assert_eq!(usdc_reserve(dai), dai_pool_before + splits[2]);
+    let lp_tokens = received_amount(&response.alkanes, &alkane_id("WEIGHTED_LP"));
+    assert!(lp_tokens > splits[0], "Swapped legs should produce output on top of the direct leg");
+
+    // Rounding dust lands on the last leg so nothing is lost
+    let odd_splits = ZapCalculator::calculate_weighted_split(10_001, &target_tokens, &weights_bps)?;
+    assert_eq!(odd_splits.iter().sum::<u128>(), 10_001);
+
+    println!("✅ Weighted zap split test passed");
+    Ok(())
+}
+
+#[test]
+fn test_weighted_zap_rejects_invalid_weights() -> anyhow::Result<()> {
+    println!("Testing weighted zap weight validation...");
+
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let target_tokens = [tokens["USDC"], tokens["ETH"], tokens["DAI"]];
+    let amount = 1_000_000;
+
+    let mismatched_sum = ZapCalculator::calculate_weighted_split(amount, &target_tokens, &[4000, 3000, 2000]);
+    assert!(mismatched_sum.is_err(), "Weights not summing to 100% should be rejected");
+
+    let mismatched_len = ZapCalculator::calculate_weighted_split(amount, &target_tokens, &[5000, 5000]);
+    assert!(mismatched_len.is_err(), "Weight count must match token count");
+
+    let zero_weight = ZapCalculator::calculate_weighted_split(amount, &target_tokens, &[10000, 0, 0]);
+    assert!(zero_weight.is_err(), "Zero weights should be rejected");
+
+    println!("✅ Weighted zap validation test passed");
+    Ok(())
+}
+
+#[test]
+fn test_duplicate_tokens_rejected() -> anyhow::Result<()> {
+    println!("Testing duplicate token rejection in token lists...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let usdc = tokens["USDC"];
+    let eth = tokens["ETH"];
+    let dai = tokens["DAI"];
+    let zap_into_weighted = |target_tokens: Vec<AlkaneId>| {
+        zap.dispatch(parcel(usdc, 1_000_000), |zap| {
+            zap.zap_into_weighted(usdc, 1_000_000, target_tokens, vec![4000, 3000, 3000], 0, 0, DEFAULT_SLIPPAGE)
+        })
+    };
+
+    // A repeated weighted target is refused before any leg swaps
+    let swap_opcode = zap.swap_layout.swap_inputs(&[usdc, eth], 0, 0, 0)[0];
+    let error = zap_into_weighted(vec![usdc, eth, eth]).unwrap_err();
+    assert!(matches!(error.downcast_ref::<ZapError>(), Some(ZapError::DuplicateToken { token }) if *token == eth));
+    assert_eq!(zap.calls_to(swap_opcode), 0, "No leg should execute");
+
+    // A repeated base token is refused by the check InitializeZap runs before storing the set
+    let error = ensure_distinct_tokens(&[usdc, dai, usdc]).unwrap_err();
+    assert!(matches!(error.downcast_ref::<ZapError>(), Some(ZapError::DuplicateToken { token }) if *token == usdc));
+
+    // Distinct lists are accepted
+    assert!(received_amount(&zap_into_weighted(vec![usdc, eth, dai])?.alkanes, &alkane_id("WEIGHTED_LP")) > 0);
+    ensure_distinct_tokens(&[usdc, eth, dai])?;
+
+    println!("✅ Duplicate token rejection test passed");
+    Ok(())
+}
+
+#[test]
+fn test_stranded_swap_recovery() -> anyhow::Result<()> {
+    println!("Testing recovery of swap output stranded mid-zap...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // A single-call zap whose second leg fails reverts without moving any pool
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let mut broken_quote = quote.clone();
+    broken_quote.route_b = RouteInfo::new(vec![uni, alkane_id("MISSING")], 0);
+    let reserves_before = zap.pool(uni, eth).unwrap().reserve_a;
+    assert!(zap.execute(&broken_quote).is_err(), "Broken zap should fail");
+    assert_eq!(zap.pool(uni, eth).unwrap().reserve_a, reserves_before, "Atomic zap should fully revert");
+    assert_eq!((zap.held(uni), zap.held(eth)), (0, 0), "Atomic zap should never strand tokens");
+
+    // An orchestrated zap that stops after its first leg leaves the output with the zap
+    let eth_out = quote.route_a.expected_output;
+    zap.receive_out_of_band(eth, eth_out);
+    let recover = |amount: u128| zap.dispatch_as_owner(|zap| zap.recover_stranded_swap(eth, amount));
+
+    // Recovery cannot run inside an in-flight zap
+    zap.set_locked(true)?;
+    assert!(matches!(
+        recover(eth_out).unwrap_err().downcast_ref::<ZapError>(),
+        Some(ZapError::Reentrancy)
+    ));
+    zap.set_locked(false)?;
+    assert_eq!(zap.held(eth), eth_out, "A refused recovery should leave the balance");
+
+    // Recovery cannot exceed what is held and returns the stranded tokens
+    assert!(recover(eth_out + 1).is_err(), "Over-recovery should fail");
+    assert_eq!(received_amount(&recover(eth_out)?.alkanes, &eth), eth_out);
+    assert_eq!(zap.held(eth), 0);
+
+    println!("✅ Stranded swap recovery test passed");
+    Ok(())
+}
+
+#[test]
+fn test_price_impact_ceiling_rejects_thin_pool_zap() -> anyhow::Result<()> {
+    println!("Testing price impact ceiling on a thin pool...");
+
+    let zap = TestZap::new();
+    let wbtc = alkane_id("WBTC");
+    let usdc = alkane_id("USDC");
+    let thin = alkane_id("THIN");
+
+    // THIN trades at 1 USDC but is only reachable through a shallow WBTC/THIN pool
+    zap.state.borrow_mut().factory.add_pool(wbtc, thin, 1_000_000, 300 * TEST_PRECISION);
+    zap.state.borrow_mut().factory.add_pool(thin, usdc, 1_000_000 * TEST_PRECISION, 1_000_000 * 1_000_000);
+
+    let input_amount = 100_000_000; // 1 WBTC, far deeper than the whole WBTC/THIN pool
+    zap.quote_zap(wbtc, input_amount, thin, usdc, DEFAULT_SLIPPAGE)?;
+
+    let error = zap
+        .dispatch(parcel(wbtc, input_amount), |zap| {
+            zap.execute_zap(wbtc, input_amount, thin, usdc, 0, 0, DEFAULT_SLIPPAGE, 0, 0, 1000) // 10%
+        })
+        .expect_err("Large zap through a thin pool should trip the ceiling");
+    assert!(
+        matches!(error.downcast_ref::<ZapError>(), Some(ZapError::PriceImpactExceeded { max: 1000, .. })),
+        "Unexpected error: {}",
+        error
+    );
+
+    // Rejection happens before any swap or liquidity call
+    let swap_opcode = zap.swap_layout.swap_inputs(&[wbtc, thin], 0, 0, 0)[0];
+    assert_eq!(zap.calls_to(swap_opcode), 0);
+    assert_eq!(zap.calls_to(11), 0);
+
+    println!("✅ Price impact ceiling test passed");
+    Ok(())
+}
+
+#[test]
+fn test_unconsumed_liquidity_refunded_to_caller() -> anyhow::Result<()> {
+    println!("Testing dust refund after add_liquidity...");
+
+    let (mut factory, tokens) = setup_comprehensive_test_environment();
+
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+
+    // Offer twice the USDC the pool ratio can absorb alongside 1 ETH
+    let pool = factory.get_pool_mut(eth, usdc).unwrap();
+    let lp_token_id = pool.lp_token_id;
+    let amount_eth = 1e18 as u128;
+    let amount_usdc = 2 * (amount_eth * pool.reserve_b / pool.reserve_a);
+    let reserve_usdc_before = pool.reserve_b;
+    let liquidity_result = pool.simulate_add_liquidity_at_ratio(amount_eth, amount_usdc)?;
+    let consumed_usdc = pool.reserve_b - reserve_usdc_before;
+    let lp_tokens = received_amount(&liquidity_result.alkanes, &lp_token_id);
+
+    // What the pool took is read off the zap's balances, not the factory's response data:
+    // it held the full amounts before the call and only the refunds after it
+    let held_after = (received_amount(&liquidity_result.alkanes, &eth), received_amount(&liquidity_result.alkanes, &usdc));
+    let (consumed_a, consumed_b) = consumed_liquidity_amounts((amount_eth, amount_usdc), held_after);
+    assert_eq!((consumed_a, consumed_b), (amount_eth, consumed_usdc));
+
+    let response = settle_add_liquidity(liquidity_result, eth, usdc, amount_eth - consumed_a, amount_usdc - consumed_b);
+
+    // The LP tokens pass through and the excess USDC comes back as dust
+    assert_eq!(received_amount(&response.alkanes, &lp_token_id), lp_tokens);
+    assert_eq!(received_amount(&response.alkanes, &eth), 0, "The limiting side is fully consumed");
+    assert_eq!(received_amount(&response.alkanes, &usdc), amount_usdc - consumed_usdc);
+    assert!(amount_usdc - consumed_usdc >= amount_usdc / 2 - 1, "About half the USDC should be refunded");
+
+    println!("✅ Dust refund test passed");
+    Ok(())
+}
+
+#[test]
+fn test_failure_paths_report_zap_error_variants() -> anyhow::Result<()> {
+    println!("Testing structured zap errors...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let orphan = alkane_id("ORPHAN");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let variant = |error: anyhow::Error| error.downcast_ref::<ZapError>().cloned();
+
+    // Routing failures
+    let factory = zap.state.borrow().factory.clone();
+    let route_finder = RouteFinder::new(zap.factory_id, &factory).with_base_tokens(zap.base_tokens.clone());
+    assert_eq!(
+        variant(route_finder.find_best_route(eth, orphan, input_amount).unwrap_err()),
+        Some(ZapError::RouteNotFound { from_token: eth, to_token: orphan })
+    );
+    assert_eq!(variant(route_finder.find_best_route(eth, eth, input_amount).unwrap_err()), Some(ZapError::SelfRoute));
+    assert_eq!(variant(route_finder.find_best_route(eth, usdc, 0).unwrap_err()), Some(ZapError::ZeroInputAmount));
+
+    // Quoting into a pool that does not exist
+    assert_eq!(
+        variant(zap.quote_zap(uni, input_amount, eth, orphan, DEFAULT_SLIPPAGE).unwrap_err()),
+        Some(ZapError::RouteNotFound { from_token: uni, to_token: orphan })
+    );
+    zap.state.borrow_mut().factory.add_pool(uni, orphan, 1_000_000 * TEST_PRECISION, 1_000_000 * TEST_PRECISION);
+    assert_eq!(
+        variant(zap.quote_zap(uni, input_amount, eth, orphan, DEFAULT_SLIPPAGE).unwrap_err()),
+        Some(ZapError::PoolNotFound { token_a: eth, token_b: orphan })
+    );
+
+    // Execution below the LP minimum
+    let mut quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    quote.minimum_lp_tokens = quote.expected_lp_tokens * 2;
+    assert!(matches!(
+        variant(zap.execute(&quote).unwrap_err()),
+        Some(ZapError::SlippageExceeded { minimum, .. }) if minimum == quote.expected_lp_tokens * 2
+    ));
+
+    // Execution above the price impact ceiling
+    let error = zap
+        .dispatch(parcel(uni, input_amount), |zap| {
+            zap.execute_zap(uni, input_amount, eth, usdc, 0, 0, DEFAULT_SLIPPAGE, 0, 0, 1)
+        })
+        .unwrap_err();
+    assert!(matches!(variant(error), Some(ZapError::PriceImpactExceeded { max: 1, .. })));
+
+    // Zap out below the minimum output
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+    let lp_tokens = zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?.lp_minted;
+    let error = zap
+        .dispatch(parcel(lp_token, lp_tokens), |zap| {
+            zap.zap_out(lp_token, lp_tokens, eth, u128::MAX, 0, DEFAULT_SLIPPAGE)
+        })
+        .unwrap_err();
+    assert!(matches!(variant(error), Some(ZapError::SlippageExceeded { minimum: u128::MAX, .. })));
+
+    // Quoting against a volatile pool
+    let pool = zap.pool(eth, usdc).unwrap();
+    let reserves = PoolReserves::new(eth, usdc, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate);
+    let doubled_ratio = ZapCalculator::get_pool_ratio(&reserves)? * U256::from(2);
+    assert!(matches!(
+        variant(ZapCalculator::check_ratio_volatility(&reserves, &[doubled_ratio], 500).unwrap_err()),
+        Some(ZapError::PoolTooVolatile { max_bps: 500, .. })
+    ));
+
+    // Contract-side validation keeps its established messages
+    assert_eq!(ZapError::DeadlineExpired.to_string(), "Transaction deadline has passed");
+    assert_eq!(ZapError::InputMismatch.to_string(), "Input token mismatch");
+    assert_eq!(ZapError::NoInputTokens.to_string(), "No input tokens provided");
+
+    println!("✅ Structured zap error test passed");
+    Ok(())
+}
+
+#[test]
+fn test_deadline_boundaries() -> anyhow::Result<()> {
+    println!("Testing deadline checks at boundary heights...");
+
+    // The deadline block itself is on time; only later blocks are expired
+    assert!(!is_deadline_passed(100, 99));
+    assert!(!is_deadline_passed(100, 100));
+    assert!(is_deadline_passed(100, 101));
+
+    // Zero means no deadline, however high the chain gets
+    assert!(!is_deadline_passed(0, 0));
+    assert!(!is_deadline_passed(0, u128::from(u64::MAX)));
+
+    // Heights past u64 compare without wrapping
+    assert!(is_deadline_passed(u128::from(u64::MAX), u128::from(u64::MAX) + 1));
+    assert!(!is_deadline_passed(u128::MAX, u128::from(u64::MAX)));
+
+    // Parameter validation applies the same rule
+    let (_, tokens) = setup_comprehensive_test_environment();
+    let params = ZapParams::new(tokens["UNI"], 1000, tokens["ETH"], tokens["USDC"], 1, 100);
+    assert!(params.validate(100).is_ok());
+    let error = params.validate(101).unwrap_err();
+    assert!(matches!(error.downcast_ref::<ZapError>(), Some(ZapError::DeadlineExpired)));
+
+    println!("✅ Deadline boundary test passed");
+    Ok(())
+}
+
+#[test]
+fn test_quote_flagged_stale_past_deadline() -> anyhow::Result<()> {
+    println!("Testing quote deadlines...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 100 * TEST_PRECISION;
+    zap.height = 100;
+    let quote_by = |deadline: u128| -> anyhow::Result<ZapQuote> {
+        let response = zap.dispatch(vec![], |zap| {
+            zap.get_zap_quote_v2(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE, deadline)
+        })?;
+        ZapQuote::deserialize(&response.data)
+    };
+
+    // Quotes up to and including the deadline block are fresh, as is one with no deadline
+    assert!(!quote_by(100)?.stale);
+    assert!(!quote_by(0)?.stale);
+
+    // A deadline at a past height still returns the quote, flagged stale
+    let late = quote_by(99)?;
+    assert!(late.stale);
+    assert!(late.is_stale(u128::from(zap.height), u128::MAX));
+    assert_eq!(late.expected_lp_tokens, quote_by(0)?.expected_lp_tokens);
+
+    // Parameter validation draws the same line at the same height
+    let height = u128::from(zap.height);
+    for deadline in [0, 99, 100, 101] {
+        let params = ZapParams::new(uni, input_amount, eth, usdc, 1, deadline);
+        assert_eq!(params.validate(height).is_ok(), !quote_by(deadline)?.stale, "deadline {}", deadline);
+    }
+
+    println!("✅ Quote deadline test passed");
+    Ok(())
+}
+
+#[test]
+fn test_get_zap_quote_keeps_legacy_layout() -> anyhow::Result<()> {
+    println!("Testing the GetZapQuote response layout...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 100 * TEST_PRECISION;
+
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let response = zap.dispatch(vec![], |zap| zap.get_zap_quote(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE, 0))?;
+
+    // Five little-endian u128 words with no version prefix, as existing clients decode them
+    let mut expected = Vec::new();
+    for word in [
+        quote.split_amount_a,
+        quote.route_a.expected_output,
+        quote.route_b.expected_output,
+        quote.expected_lp_tokens,
+        quote.minimum_lp_tokens,
+    ] {
+        expected.extend_from_slice(&word.to_le_bytes());
+    }
+    assert_eq!(response.data, expected);
+
+    println!("✅ GetZapQuote layout test passed");
+    Ok(())
+}
+
+#[test]
+fn test_swap_layout_follows_factory_version() -> anyhow::Result<()> {
+    println!("Testing swap layout selection per factory version...");
+
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let amount_in = 1e18 as u128; // 1 ETH
+    let swap = |zap: &TestZap| {
+        zap.dispatch(parcel(eth, amount_in), |zap| zap.execute_swap(vec![eth, usdc], amount_in, 1, 100))
+    };
+
+    for (version, expected_layout, expected_opcode) in [
+        (0, FactorySwapLayout::V1, 13),
+        (1, FactorySwapLayout::V1, 13),
+        (2, FactorySwapLayout::V2, 29),
+    ] {
+        let (mut factory, base_tokens) = setup_test_environment();
+        factory.version = version;
+        let zap = TestZap::with_factory(factory, base_tokens);
+        assert_eq!(zap.swap_layout, expected_layout, "Factory version {} picked the wrong layout", version);
+
+        let inputs = zap.swap_layout.swap_inputs(&[eth, usdc], amount_in, 1, 100);
+        assert_eq!(inputs[0], expected_opcode);
+
+        // The factory parses the zap's encoding back into the same swap
+        let (path, decoded_amount_in, decoded_min_out, decoded_deadline) =
+            zap.state.borrow().factory.decode_swap_inputs(&inputs, amount_in)?;
+        assert_eq!(path, vec![eth, usdc]);
+        assert_eq!((decoded_amount_in, decoded_min_out, decoded_deadline), (amount_in, 1, 100));
+
+        let pool = zap.pool(eth, usdc).unwrap();
+        let expected_out = amm_logic::calculate_swap_out(amount_in, pool.reserve_a, pool.reserve_b, pool.fee_rate)?;
+        assert_eq!(received_amount(&swap(&zap)?.alkanes, &usdc), expected_out);
+        assert_eq!(zap.calls_to(expected_opcode), 1, "The swap should go out under the probed opcode");
+    }
+
+    // A zap left on the wrong layout is rejected rather than misparsed
+    let (mut factory, base_tokens) = setup_test_environment();
+    factory.version = 2;
+    let mut zap = TestZap::with_factory(factory, base_tokens);
+    zap.swap_layout = FactorySwapLayout::V1;
+    assert!(swap(&zap).is_err());
+
+    println!("✅ Swap layout selection test passed");
+    Ok(())
+}
+
+#[test]
+fn test_sandwiched_hop_aborts_zap() -> anyhow::Result<()> {
+    println!("Testing per-hop slippage minimums...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // Hop minimums are the expected output less slippage
+    assert_eq!(ZapCalculator::min_out_for_hop(10_000, DEFAULT_SLIPPAGE)?, 9_500);
+    assert!(ZapCalculator::min_out_for_hop(10_000, 10_001).is_err());
+
+    // Multi-hop paths carry the cumulative minimum on their final hop
+    let factory = zap.state.borrow().factory.clone();
+    let route_finder = RouteFinder::new(zap.factory_id, &factory);
+    let multi_hop = RouteInfo::new(vec![uni, eth, usdc], 0);
+    let minimums = ZapCalculator::hop_min_outputs(input_amount, &multi_hop, DEFAULT_SLIPPAGE, &route_finder)?;
+    assert_eq!(minimums.len(), 2);
+    let hop_out = |token_in: AlkaneId, token_out: AlkaneId, amount_in: u128| {
+        let pool = factory.get_pool(token_in, token_out).unwrap();
+        let (reserve_in, reserve_out) = if pool.token_a == token_in {
+            (pool.reserve_a, pool.reserve_b)
+        } else {
+            (pool.reserve_b, pool.reserve_a)
+        };
+        amm_logic::calculate_swap_out(amount_in, reserve_in, reserve_out, pool.fee_rate)
+    };
+    let expected_usdc = hop_out(eth, usdc, hop_out(uni, eth, input_amount)?)?;
+    assert!(minimums[1].abs_diff(ZapCalculator::min_out_for_hop(expected_usdc, DEFAULT_SLIPPAGE)?) <= 1);
+
+    // A swap that cannot meet its minimum makes the factory call revert
+    let swap_min = ZapCalculator::min_out_for_hop(expected_usdc, 0)? + 1;
+    let err = zap
+        .dispatch(parcel(uni, input_amount), |zap| zap.execute_swap(vec![uni, eth, usdc], input_amount, swap_min, 0))
+        .unwrap_err();
+    assert!(err.to_string().contains("below the minimum"), "got {}", err);
+
+    // Front-run the ETH leg after quoting, moving its pool well past the slippage tolerance
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(quote.route_a.path, vec![uni, eth]);
+    let reserves_before = zap.pool(eth, usdc).unwrap();
+    zap.with_pool(uni, eth, |pool| pool.simulate_swap(uni, 5_000 * 1e18 as u128))?;
+
+    let err = zap.execute(&quote).unwrap_err();
+    println!("Sandwiched hop reverted: {}", err);
+    assert!(err.to_string().contains("below the minimum"), "Expected the hop minimum to trip, got {}", err);
+
+    // The whole zap reverted, leaving the target pool as it was
+    let reserves_after = zap.pool(eth, usdc).unwrap();
+    assert_eq!((reserves_after.reserve_a, reserves_after.reserve_b), (reserves_before.reserve_a, reserves_before.reserve_b));
+    assert_eq!(reserves_after.total_supply, reserves_before.total_supply);
+
+    println!("✅ Per-hop slippage test passed");
+    Ok(())
+}
+
+#[test]
+fn test_reserve_drift_between_quote_and_execute() -> anyhow::Result<()> {
+    println!("Testing re-quote on reserve drift before execution...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+    let execute_expecting = |zap: &TestZap, expected_lp_tokens: u128| {
+        zap.dispatch(parcel(uni, input_amount), |zap| {
+            zap.execute_zap(uni, input_amount, eth, usdc, 0, 0, DEFAULT_SLIPPAGE, 0, expected_lp_tokens, 0)
+        })
+    };
+
+    let quote = TestZap::with_comprehensive_setup().quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(quote.route_a.path, vec![uni, eth]);
+
+    // A small move in the ETH leg's pool stays within tolerance and the zap goes ahead
+    let nudged = TestZap::with_comprehensive_setup();
+    nudged.with_pool(uni, eth, |pool| pool.simulate_swap(uni, 10 * 1e18 as u128))?;
+    assert!(!execute_expecting(&nudged, quote.expected_lp_tokens)?.alkanes.0.is_empty());
+
+    // A large move drops the fresh expectation past the slippage tolerance
+    let drifted = TestZap::with_comprehensive_setup();
+    drifted.with_pool(uni, eth, |pool| pool.simulate_swap(uni, 5_000 * 1e18 as u128))?;
+    let fresh = drifted.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert!(fresh.expected_lp_tokens < ZapCalculator::calculate_minimum_lp_tokens(quote.expected_lp_tokens, DEFAULT_SLIPPAGE)?);
+    let err = execute_expecting(&drifted, quote.expected_lp_tokens).unwrap_err();
+    match err.downcast_ref::<ZapError>() {
+        Some(ZapError::QuoteDrifted { quoted, current }) => {
+            assert_eq!(*quoted, quote.expected_lp_tokens);
+            assert_eq!(*current, fresh.expected_lp_tokens);
+        }
+        other => panic!("Expected a quote drift rejection, got {:?}", other),
+    }
+    let swap_opcode = drifted.swap_layout.swap_inputs(&quote.route_a.path, 0, 0, 0)[0];
+    assert_eq!(drifted.calls_to(swap_opcode), 0, "The drift check should run before any swap");
+
+    // The check compares against the quoted expectation, not the caller's minimum
+    assert!(ZapCalculator::check_quote_drift(10_000, 9_500, DEFAULT_SLIPPAGE).is_ok());
+    assert!(ZapCalculator::check_quote_drift(10_000, 9_499, DEFAULT_SLIPPAGE).is_err());
+
+    println!("✅ Reserve drift re-quote test passed");
+    Ok(())
+}
+
+#[test]
+fn test_manipulated_pool_fails_reserve_sanity_check() -> anyhow::Result<()> {
+    println!("Testing the stored reserve sanity check...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // Store the target pool's current reserves as the baseline
+    let setup = || -> anyhow::Result<TestZap> {
+        let mut zap = TestZap::with_comprehensive_setup();
+        let pool = zap.pool(eth, usdc).unwrap();
+        zap.dispatch_as_owner(|zap| {
+            zap.add_pool(pool.token_a, pool.token_b, pool.reserve_a, pool.reserve_b, pool.total_supply, pool.fee_rate)
+        })?;
+        zap.max_reserve_deviation_bps = 500; // 5%
+        Ok(zap)
+    };
+
+    // Live reserves matching the baseline pass
+    let zap = setup()?;
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert!(zap.execute(&quote)?.lp_minted > 0);
+
+    // Tripling one side within the block moves the ratio far past the threshold
+    let mut manipulated = setup()?;
+    manipulated.with_pool(eth, usdc, |pool| pool.reserve_a *= 3);
+    let err = manipulated.execute(&quote).unwrap_err();
+    match err.downcast_ref::<ZapError>() {
+        Some(ZapError::ReservesDeviated { deviation_bps, max_bps }) => {
+            assert!(*deviation_bps > 5000, "Deviation should be large: {}", deviation_bps);
+            assert_eq!(*max_bps, 500);
+        }
+        other => panic!("Expected a reserve deviation rejection, got {:?}", other),
+    }
+    let swap_opcode = manipulated.swap_layout.swap_inputs(&quote.route_a.path, 0, 0, 0)[0];
+    assert_eq!(manipulated.calls_to(swap_opcode), 0, "No swap should have run");
+
+    // A zero threshold disables the check, leaving the manipulated pool to the other guards
+    manipulated.max_reserve_deviation_bps = 0;
+    if let Err(err) = manipulated.execute(&quote) {
+        assert!(!matches!(err.downcast_ref::<ZapError>(), Some(ZapError::ReservesDeviated { .. })));
+    }
+
+    // Deviation is symmetric in the stored token order and ignores pools with no baseline
+    let stored = PoolReserves::new(eth, usdc, 1_000, 2_000, 0, 0);
+    let fresh = PoolReserves::new(usdc, eth, 2_000, 1_049, 0, 0);
+    assert!(ZapCalculator::check_reserve_deviation(&stored, &fresh, 500).is_ok());
+    let fresh = PoolReserves::new(usdc, eth, 2_000, 1_051, 0, 0);
+    assert!(ZapCalculator::check_reserve_deviation(&stored, &fresh, 500).is_err());
+    let empty = PoolReserves::new(eth, usdc, 0, 0, 0, 0);
+    assert!(ZapCalculator::check_reserve_deviation(&empty, &fresh, 500).is_ok());
+
+    println!("✅ Reserve sanity check test passed");
+    Ok(())
+}
+
+#[test]
+fn test_simulated_zap_matches_execution() -> anyhow::Result<()> {
+    println!("Testing dry-run zap against real execution...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    let target_before = zap.pool(eth, usdc).unwrap();
+    let leg_before = zap.pool(uni, eth).unwrap();
+
+    // The dry run leaves every pool untouched
+    let response = zap.dispatch(vec![], |zap| zap.simulate_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE, 0))?;
+    let simulation = ZapSimulation::decode(&response.data)?;
+    let target_after = zap.pool(eth, usdc).unwrap();
+    assert_eq!((target_after.reserve_a, target_after.reserve_b), (target_before.reserve_a, target_before.reserve_b));
+    assert_eq!(target_after.total_supply, target_before.total_supply);
+    assert_eq!(zap.pool(uni, eth).unwrap().reserve_a, leg_before.reserve_a);
+    assert_eq!(zap.calls_to(11), 0);
+
+    // Executing against the same reserves realizes exactly what the dry run reported
+    let receipt = zap.execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    assert_eq!(receipt.lp_minted, simulation.lp_tokens);
+    assert_eq!((receipt.dust_a, receipt.dust_b), (simulation.dust_a, simulation.dust_b));
+    assert!(simulation.consumed_a > 0 && simulation.consumed_b > 0);
+
+    // The SimulateZap payload round-trips
+    let bytes = simulation.encode();
+    assert_eq!(bytes.len(), 80);
+    assert_eq!(ZapSimulation::decode(&bytes)?, simulation);
+    assert!(ZapSimulation::decode(&bytes[..79]).is_err());
+
+    println!("✅ Dry-run zap test passed");
+    Ok(())
+}
+
+#[test]
+fn test_low_fuel_aborts_before_first_swap() -> anyhow::Result<()> {
+    println!("Testing fuel budgeting for multi-hop zaps...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let (_, tokens) = setup_comprehensive_test_environment();
+
+    let uni = tokens["UNI"];
+    let eth = tokens["ETH"];
+    let usdc = tokens["USDC"];
+    let input_amount = 100 * 1e18 as u128; // 100 UNI
+
+    // One budgeted call per swap hop plus the add_liquidity call
+    let quote = zap.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let hops = quote.route_a.hop_count() + quote.route_b.hop_count();
+    let required = ZapCalculator::estimate_fuel_for_quote(&quote);
+    assert_eq!(required, (hops as u128 + 1) * FUEL_PER_HOP);
+
+    // A fuel budget one short of the estimate is refused with no swap executed
+    zap.fuel = (required - 1) as u64;
+    let error = zap.execute(&quote).unwrap_err();
+    assert!(matches!(
+        error.downcast_ref::<ZapError>(),
+        Some(ZapError::InsufficientFuel { available, required: needed }) if *available == required - 1 && *needed == required
+    ));
+    let swap_opcode = zap.swap_layout.swap_inputs(&quote.route_a.path, 0, 0, 0)[0];
+    assert_eq!(zap.calls_to(swap_opcode), 0, "No hop should execute");
+
+    // Exactly the estimate is enough
+    zap.fuel = required as u64;
+    assert!(zap.execute(&quote)?.lp_minted > 0);
+
+    println!("✅ Fuel budgeting test passed");
+    Ok(())
+}
+
+#[test]
+fn test_fee_on_transfer_target_token() -> anyhow::Result<()> {
+    println!("Testing zaps into a pair with a fee-on-transfer token...");
+
+    let mut zap = TestZap::with_comprehensive_setup();
+    let fot = alkane_id("FOT");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    zap.state.borrow_mut().factory.add_pool(fot, eth, 100_000 * TEST_PRECISION, 100 * TEST_PRECISION);
+    zap.state.borrow_mut().factory.add_pool(fot, usdc, 100_000 * TEST_PRECISION, 200_000 * 1_000_000);
+
+    // FOT burns 2% of every transfer, more than the 1% the zap allows per side
+    zap.transfer_fee_bps.insert(fot, 200);
+    zap.default_slippage = 100;
+    let mut quote = zap.quote_zap(usdc, 10_000 * 1_000_000, fot, eth, 100)?;
+    quote.minimum_lp_tokens = quote.expected_lp_tokens * 95 / 100;
+
+    // Unflagged, the factory refuses a deposit that misses the FOT side's minimum
+    let err = zap.execute(&quote).unwrap_err();
+    assert!(err.to_string().contains("below the minimums"), "got {}", err);
+
+    // Flagged, the zap deposits what it received and only the LP minimum applies
+    zap.fee_on_transfer_tokens.insert(fot);
+    let lp_tokens = zap.execute(&quote)?.lp_minted;
+    assert!(lp_tokens >= quote.minimum_lp_tokens);
+    assert!(lp_tokens < quote.expected_lp_tokens, "The transfer fee still costs LP");
+
+    println!("✅ Fee-on-transfer test passed");
+    Ok(())
+}
+
+#[test]
+fn test_partial_zap_returns_remainder() -> anyhow::Result<()> {
+    println!("Testing partial zaps...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * TEST_PRECISION;
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+    let zap_partial = |fraction_bps: u128| {
+        zap.dispatch(parcel(uni, input_amount), |zap| {
+            zap.zap_partial(uni, input_amount, eth, usdc, 0, 0, DEFAULT_SLIPPAGE, fraction_bps)
+        })
+    };
+
+    // A quarter of the parcel is zapped and the other three quarters come back
+    let response = zap_partial(2500)?;
+    assert_eq!(received_amount(&response.alkanes, &uni), 750 * TEST_PRECISION);
+    let receipt = ZapReceipt::decode(&response.data)?;
+    assert_eq!(receipt.input_amount, 250 * TEST_PRECISION, "Only the fraction should be zapped");
+    assert!(receipt.lp_minted > 0);
+    assert_eq!(received_amount(&response.alkanes, &lp_token), receipt.lp_minted);
+
+    // The whole parcel is a valid fraction; nothing and more than everything are not
+    assert_eq!(received_amount(&zap_partial(BASIS_POINTS)?.alkanes, &uni), 0);
+    assert!(zap_partial(0).is_err());
+    assert!(zap_partial(BASIS_POINTS + 1).is_err());
+
+    println!("✅ Partial zap test passed");
+    Ok(())
+}
+
+#[test]
+fn test_zap_for_exact_lp_output() -> anyhow::Result<()> {
+    println!("Testing zaps to an exact LP amount...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let max_input = 1000 * TEST_PRECISION;
+    let lp_token = zap.pool(eth, usdc).unwrap().lp_token_id;
+
+    // Ask for what the forward calculation mints from 400 UNI
+    let forward_input = 400 * TEST_PRECISION;
+    let lp_amount_out = zap.quote_zap(uni, forward_input, eth, usdc, DEFAULT_SLIPPAGE)?.expected_lp_tokens;
+    let zap_for_exact_lp = |max_input: u128| {
+        zap.dispatch(parcel(uni, max_input), |zap| {
+            zap.zap_for_exact_lp(uni, max_input, lp_amount_out, eth, usdc, 0, DEFAULT_SLIPPAGE)
+        })
+    };
+
+    let response = zap_for_exact_lp(max_input)?;
+    let lp_tokens = received_amount(&response.alkanes, &lp_token);
+    let consumed = max_input - received_amount(&response.alkanes, &uni);
+    assert!(lp_tokens >= lp_amount_out, "The requested LP must be minted");
+    assert!(lp_tokens - lp_amount_out <= lp_amount_out / 1_000_000, "Only rounding should be over-minted");
+
+    // The input consumed is the forward calculation's, within rounding
+    assert!(consumed.abs_diff(forward_input) <= forward_input / 1_000_000, "consumed {}", consumed);
+
+    // A cap below the required input is refused
+    let err = zap_for_exact_lp(consumed / 2).unwrap_err();
+    assert!(matches!(err.downcast_ref::<ZapError>(), Some(ZapError::InsufficientInput { .. })), "got {}", err);
+
+    println!("✅ Exact LP output test passed");
+    Ok(())
+}
+
+#[test]
+fn test_exact_minimums_abort_zap_bps_would_allow() -> anyhow::Result<()> {
+    println!("Testing zaps with absolute deposit minimums...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 100 * TEST_PRECISION;
+    let execute_exact = |zap: &TestZap, amount_a_min: u128, amount_b_min: u128| {
+        zap.dispatch(parcel(uni, input_amount), |zap| {
+            zap.execute_zap_exact(uni, input_amount, eth, usdc, amount_a_min, amount_b_min, 0, 0)
+        })
+    };
+
+    // The bps path clears the zap with room to spare
+    let receipt = TestZap::with_comprehensive_setup().execute_zap_for(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+
+    // One unit more of ETH than the legs deliver aborts the same zap
+    let zap = TestZap::with_comprehensive_setup();
+    let err = execute_exact(&zap, receipt.output_a + 1, receipt.output_b).unwrap_err();
+    assert!(
+        matches!(err.downcast_ref::<ZapError>(), Some(ZapError::SlippageExceeded { minimum, .. }) if *minimum == receipt.output_a + 1),
+        "got {}",
+        err
+    );
+
+    // Minimums the legs meet exactly go through
+    let response = execute_exact(&zap, receipt.output_a, receipt.output_b)?;
+    assert_eq!(ZapReceipt::decode(&response.data)?.lp_minted, receipt.lp_minted);
+
+    println!("✅ Absolute minimums test passed");
+    Ok(())
+}
+
+#[test]
+fn test_zap_creates_missing_target_pool_only_when_allowed() -> anyhow::Result<()> {
+    println!("Testing zaps into a pair with no pool...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let usdc = alkane_id("USDC");
+    let uni = alkane_id("UNI");
+    let link = alkane_id("LINK");
+    let input_amount = 1000 * 1_000_000; // 1000 USDC
+    assert!(zap.pool(uni, link).is_none());
+
+    // Without the flag the missing pool is reported as such, both quoting and executing
+    let err = zap
+        .dispatch(vec![], |zap| zap.get_zap_quote(usdc, input_amount, uni, link, DEFAULT_SLIPPAGE, 0))
+        .unwrap_err();
+    assert!(matches!(err.downcast_ref::<ZapError>(), Some(ZapError::PoolNotFound { .. })), "got {}", err);
+    zap.dispatch(vec![], |zap| zap.get_zap_quote(usdc, input_amount, uni, link, DEFAULT_SLIPPAGE, 1))?;
+    let err = zap
+        .dispatch(parcel(usdc, input_amount), |zap| zap.execute_zap(usdc, input_amount, uni, link, 0, 0, DEFAULT_SLIPPAGE, 0, 0, 0))
+        .unwrap_err();
+    assert!(matches!(err.downcast_ref::<ZapError>(), Some(ZapError::PoolNotFound { .. })), "got {}", err);
+    assert!(zap.pool(uni, link).is_none());
+
+    // The flag is 0 or 1, nothing else
+    assert!(zap.dispatch(vec![], |zap| zap.get_zap_quote(usdc, input_amount, uni, link, DEFAULT_SLIPPAGE, 2)).is_err());
+    assert!(zap
+        .dispatch(parcel(usdc, input_amount), |zap| zap.execute_zap(usdc, input_amount, uni, link, 0, 0, DEFAULT_SLIPPAGE, 2, 0, 0))
+        .is_err());
+    assert!(zap.pool(uni, link).is_none());
+
+    // With the flag the zap creates the pool, minting the new-pool LP amount
+    let quote = zap.quote_new_pool_zap(usdc, input_amount, uni, link, DEFAULT_SLIPPAGE)?;
+    println!("Quoted {} LP from {} UNI and {} LINK", quote.expected_lp_tokens, quote.expected_deposit_a, quote.expected_deposit_b);
+    assert_eq!(
+        quote.expected_lp_tokens,
+        amm_logic::calculate_initial_lp_tokens(quote.route_a.expected_output, quote.route_b.expected_output)?
+    );
+    assert_eq!((quote.expected_dust_a, quote.expected_dust_b), (0, 0));
+    let response = zap.dispatch(parcel(usdc, input_amount), |zap| {
+        zap.execute_zap(usdc, input_amount, uni, link, 0, 0, DEFAULT_SLIPPAGE, 1, 0, 0)
+    })?;
+
+    let pool = zap.pool(uni, link).expect("The zap should have created the pool");
+    let lp_tokens = received_amount(&response.alkanes, &pool.lp_token_id);
+    assert_eq!(lp_tokens, quote.expected_lp_tokens);
+    let (reserve_uni, reserve_link) = if pool.token_a == uni { (pool.reserve_a, pool.reserve_b) } else { (pool.reserve_b, pool.reserve_a) };
+    assert_eq!((reserve_uni, reserve_link), (quote.route_a.expected_output, quote.route_b.expected_output));
+    assert_eq!(pool.total_supply, lp_tokens + MINIMUM_LIQUIDITY);
+    assert_eq!((received_amount(&response.alkanes, &uni), received_amount(&response.alkanes, &link)), (0, 0));
+
+    println!("✅ Pool creation zap test passed");
+    Ok(())
+}
+
+#[test]
+fn test_lp_shortfall_suggests_clearing_slippage() -> anyhow::Result<()> {
+    println!("Testing the slippage suggested by an LP shortfall...");
+
+    let uni = alkane_id("UNI");
+    let eth = alkane_id("ETH");
+    let usdc = alkane_id("USDC");
+    let input_amount = 1000 * 1e18 as u128; // 1000 UNI
+
+    // Quote with no slippage allowance, then let a small move in the ETH leg's pool land first
+    let fresh = TestZap::with_comprehensive_setup();
+    let quote = fresh.quote_zap(uni, input_amount, eth, usdc, DEFAULT_SLIPPAGE)?;
+    let front_run = || -> anyhow::Result<TestZap> {
+        let zap = TestZap::with_comprehensive_setup();
+        zap.with_pool(uni, eth, |pool| pool.simulate_swap(uni, 10 * 1e18 as u128))?;
+        Ok(zap)
+    };
+    let execute_quoted = |zap: &TestZap, min_lp_tokens: u128| {
+        zap.dispatch(parcel(uni, input_amount), |zap| {
+            zap.execute_zap(uni, input_amount, eth, usdc, min_lp_tokens, 0, DEFAULT_SLIPPAGE, 0, quote.expected_lp_tokens, 0)
+        })
+    };
+
+    let err = execute_quoted(&front_run()?, quote.expected_lp_tokens).unwrap_err();
+    let (realized, suggested_bps) = match err.downcast_ref::<ZapError>() {
+        Some(ZapError::SlippageExceeded { realized, minimum, suggested_bps }) => {
+            assert_eq!(*minimum, quote.expected_lp_tokens);
+            (*realized, *suggested_bps)
+        }
+        other => panic!("Expected an LP shortfall, got {:?}", other),
+    };
+    println!("Realized {} of {} LP, suggested {} bps", realized, quote.expected_lp_tokens, suggested_bps);
+    assert!(err.to_string().contains(&format!("retry with at least {} bps", suggested_bps)));
+    assert!(suggested_bps > 0 && suggested_bps < DEFAULT_SLIPPAGE);
+
+    // The suggestion is the tightest tolerance whose minimum the realized LP clears
+    assert!(ZapCalculator::calculate_minimum_lp_tokens(quote.expected_lp_tokens, suggested_bps)? <= realized);
+    assert!(ZapCalculator::calculate_minimum_lp_tokens(quote.expected_lp_tokens, suggested_bps - 1)? > realized);
+
+    // Retrying against the same reserves at the suggested tolerance goes through
+    let retry_minimum = ZapCalculator::calculate_minimum_lp_tokens(quote.expected_lp_tokens, suggested_bps)?;
+    let response = execute_quoted(&front_run()?, retry_minimum)?;
+    assert_eq!(ZapReceipt::decode(&response.data)?.lp_minted, realized);
+
+    // Leg minimums set by the caller suggest a tolerance against the quoted leg output
+    let leg_minimum = quote.route_a.expected_output * 2;
+    let exact_err = fresh
+        .dispatch(parcel(uni, input_amount), |zap| {
+            zap.execute_zap_exact(uni, input_amount, eth, usdc, leg_minimum, 0, 0, 0)
+        })
+        .unwrap_err();
+    match exact_err.downcast_ref::<ZapError>() {
+        Some(ZapError::SlippageExceeded { realized, minimum, suggested_bps }) => {
+            assert_eq!(*minimum, leg_minimum);
+            assert_eq!(*suggested_bps, ZapCalculator::suggest_slippage_bps(quote.route_a.expected_output, *realized));
+        }
+        other => panic!("Expected a leg shortfall, got {:?}", other),
+    }
+
+    // Without the quote, the expectation is recovered from the minimum and its tolerance
+    let implied = ZapCalculator::implied_expected_amount(9_500, DEFAULT_SLIPPAGE);
+    assert_eq!(ZapCalculator::calculate_minimum_lp_tokens(implied, DEFAULT_SLIPPAGE)?, 9_500);
+    assert_eq!(ZapCalculator::suggest_slippage_bps(10_000, 10_000), 0);
+    assert_eq!(ZapCalculator::suggest_slippage_bps(10_000, 9_000), 1_000);
+
+    println!("✅ LP shortfall slippage suggestion test passed");
+    Ok(())
+}
+
+#[test]
+fn test_supported_opcodes_match_message_enum() -> anyhow::Result<()> {
+    println!("Testing the supported opcode list against the message enum...");
+
+    // Every `#[opcode(N)]` attribute on `OylZapMessage`, read from the contract source
+    let mut declared: Vec<u128> = include_str!("../src/lib.rs")
+        .lines()
+        .filter_map(|line| line.trim().strip_prefix("#[opcode(")?.strip_suffix(")]")?.parse().ok())
+        .collect();
+    declared.sort_unstable();
+
+    let mut supported = SUPPORTED_OPCODES.to_vec();
+    supported.sort_unstable();
+    supported.dedup();
+    assert_eq!(supported.len(), SUPPORTED_OPCODES.len(), "SUPPORTED_OPCODES should not repeat an opcode");
+    assert_eq!(declared, supported, "SUPPORTED_OPCODES should list exactly the dispatched opcodes");
+
+    let expected: Vec<u128> = (0..=48).chain([50]).collect();
+    assert_eq!(supported, expected);
+
+    println!("✅ Supported opcodes test passed");
+    Ok(())
+}
+
+#[test]
+fn test_get_version_reports_supported_opcodes() -> anyhow::Result<()> {
+    println!("Testing GetVersion...");
+
+    let zap = TestZap::with_comprehensive_setup();
+    let data = zap.dispatch(vec![], |zap| zap.get_version())?.data;
+    let words: Vec<u128> = data.chunks(16).map(|word| u128::from_le_bytes(word.try_into().unwrap())).collect();
+    assert_eq!(data.len() % 16, 0);
+
+    // Major, minor and patch, then each supported opcode
+    let (major, minor, patch) = contract_version();
+    assert_eq!(words[..3], [major, minor, patch]);
+    assert_eq!(words[3..], *SUPPORTED_OPCODES);
+
+    println!("✅ GetVersion test passed");
+    Ok(())
+}
@@ -47,7 +47,7 @@ fn main() {
     println!("   Max slippage: {}%", max_slippage_bps as f64 / 100.0);
 
     // Get quote (this would return serialized data in practice)
-    match zap.get_zap_quote(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps) {
+    match zap.get_zap_quote(input_token, input_amount, target_token_a, target_token_b, max_slippage_bps, 0) {
         Ok(_) => println!("   ✓ Quote generated successfully"),
         Err(e) => println!("   ✗ Failed to get quote: {}", e),
     }
@@ -81,6 +81,9 @@ fn main() {
         min_lp_tokens,
         deadline,
         max_slippage_bps,
+        0, // fail rather than create a missing pool
+        0, // no quoted LP expectation to check drift against
+        0, // no price impact ceiling
     ) {
         Ok(_) => println!("   ✓ Zap executed successfully!"),
         Err(e) => println!("   ✗ Zap execution failed: {}", e),
@@ -145,6 +145,27 @@ mod zap_tests {
         assert_eq!(BASIS_POINTS, 10000);
         assert_eq!(MINIMUM_LIQUIDITY, 1000);
     }
+
+    #[test]
+    fn test_contract_version() {
+        use oyl_zap_core::{contract_version, version_data, SUPPORTED_OPCODES};
+
+        let (major, minor, patch) = contract_version();
+        assert_ne!((major, minor, patch), (0, 0, 0), "Version should be reported");
+
+        let data = version_data();
+        assert_eq!(data.len(), (3 + SUPPORTED_OPCODES.len()) * 16);
+        let words: Vec<u128> = data
+            .chunks(16)
+            .map(|chunk| u128::from_le_bytes(chunk.try_into().unwrap()))
+            .collect();
+        assert_eq!(&words[0..3], &[major, minor, patch]);
+
+        // Core opcodes are always advertised
+        for opcode in [0, 3, 4, 5, 50] {
+            assert!(words[3..].contains(&opcode), "Missing opcode {}", opcode);
+        }
+    }
 }
 
 // MockPoolProvider for testing RouteFinder
@@ -9,6 +9,7 @@ use std::fmt::Write;
 use alkanes::message::AlkaneMessageContext;
 use alkanes_support::cellpack::Cellpack;
 use alkanes_support::id::AlkaneId;
+use alkanes_support::trace::{Trace, TraceEvent};
 use alkanes::tests::helpers as alkane_helpers;
 use protorune::{balance_sheet::{load_sheet}, tables::RuneTable, message::MessageContext};
 use protorune_support::balance_sheet::BalanceSheetOperations;
@@ -22,6 +23,7 @@ use protorune_support::{balance_sheet::ProtoruneRuneId, protostone::{Protostone,
 use protorune::protostone::Protostones;
 use metashrew_core::{println, stdio::stdout};
 use protobuf::Message;
+use oyl_zap_core::types::ZapConfig;
 
 // Use the precompiled build from the main project
 use crate::precompiled::oyl_zap_build;
@@ -312,6 +314,7 @@ fn perform_zap_with_traces(
                                     target_token_a.block, target_token_a.tx,
                                     target_token_b.block, target_token_b.tx,
                                     max_slippage_bps,
+                                    0u128, // No pool creation
                                 ]).encipher(),
                                 protocol_tag: AlkaneMessageContext::protocol_tag() as u128,
                                 pointer: Some(0),
@@ -386,6 +389,9 @@ fn perform_zap_with_traces(
                                     min_lp_tokens,
                                     deadline,
                                     max_slippage_bps,
+                                    0u128, // No pool creation
+                                    0u128, // No expected LP drift check
+                                    0u128, // No price impact ceiling
                                 ]).encipher(),
                                 protocol_tag: AlkaneMessageContext::protocol_tag() as u128,
                                 pointer: Some(0),
@@ -873,6 +879,7 @@ fn test_zap_edge_cases() -> Result<()> {
                                     target_token_a.block, target_token_a.tx,
                                     target_token_b.block, target_token_b.tx,
                                     500u128, // 5% slippage
+                                    0u128, // No pool creation
                                 ]).encipher(),
                                 protocol_tag: AlkaneMessageContext::protocol_tag() as u128,
                                 pointer: Some(0),
@@ -944,6 +951,9 @@ fn test_zap_edge_cases() -> Result<()> {
                                     50u128, // Min LP tokens
                                     1u128, // Expired deadline (block 1)
                                     500u128, // 5% slippage
+                                    0u128, // No pool creation
+                                    0u128, // No expected LP drift check
+                                    0u128, // No price impact ceiling
                                 ]).encipher(),
                                 protocol_tag: AlkaneMessageContext::protocol_tag() as u128,
                                 pointer: Some(0),
@@ -999,3 +1009,219 @@ fn test_zap_edge_cases() -> Result<()> {
     
     Ok(())
 }
+
+// Block with one transaction calling `cellpack`, spending `previous_output` and sending `edicts`
+fn zap_call_block(cellpack: Vec<u128>, previous_output: OutPoint, edicts: Vec<ProtostoneEdict>) -> Result<Block> {
+    Ok(protorune_helpers::create_block_with_txs(vec![Transaction {
+        version: Version::ONE,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: vec![TxIn {
+            previous_output,
+            script_sig: ScriptBuf::new(),
+            sequence: Sequence::MAX,
+            witness: Witness::new()
+        }],
+        output: vec![
+            TxOut {
+                script_pubkey: Address::from_str(ADDRESS1().as_str())
+                    .unwrap()
+                    .require_network(get_btc_network())
+                    .unwrap()
+                    .script_pubkey(),
+                value: Amount::from_sat(546),
+            },
+            TxOut {
+                script_pubkey: (Runestone {
+                    edicts: vec![],
+                    etching: None,
+                    mint: None,
+                    pointer: None,
+                    protocol: Some(
+                        vec![
+                            Protostone {
+                                message: into_cellpack(cellpack).encipher(),
+                                protocol_tag: AlkaneMessageContext::protocol_tag() as u128,
+                                pointer: Some(0),
+                                refund: Some(0),
+                                from: None,
+                                burn: None,
+                                edicts,
+                            }
+                        ].encipher()?
+                    )
+                }).encipher(),
+                value: Amount::from_sat(546)
+            }
+        ],
+    }]))
+}
+
+// Trace events recorded for the block's call
+fn zap_call_events(block: &Block) -> Result<Vec<TraceEvent>> {
+    let mut events = Vec::new();
+    for vout in 0..5 {
+        let trace_data = &view::trace(&OutPoint {
+            txid: block.txdata[0].compute_txid(),
+            vout,
+        })?;
+        let trace_result: Trace = alkanes_support::proto::alkanes::AlkanesTrace::parse_from_bytes(trace_data)?.into();
+        events.extend(trace_result.0.lock().unwrap().iter().cloned());
+    }
+    Ok(events)
+}
+
+fn zap_call_reverted(block: &Block) -> Result<bool> {
+    Ok(zap_call_events(block)?.iter().any(|event| matches!(event, TraceEvent::RevertContext(_))))
+}
+
+// Data the zap returned from the block's call
+fn zap_call_data(block: &Block) -> Result<Vec<u8>> {
+    zap_call_events(block)?
+        .into_iter()
+        .rev()
+        .find_map(|event| match event {
+            TraceEvent::ReturnContext(response) => Some(response.inner.data),
+            _ => None,
+        })
+        .ok_or_else(|| anyhow::anyhow!("zap call did not return"))
+}
+
+// Deploy a fresh zap and initialize it with `base_tokens`, returning the zap id and the init block
+fn deploy_zap_with_base_tokens(base_tokens: &[AlkaneId]) -> Result<(AlkaneId, Block)> {
+    clear();
+    let template_block = alkane_helpers::init_with_multiple_cellpacks_with_tx(
+        [oyl_zap_build::get_bytes()].into(),
+        [into_cellpack(vec![3u128, 0x100, 0u128])].into(),
+    );
+    index_block(&template_block, 0)?;
+
+    let zap_contract_id = AlkaneId { block: 4, tx: 0x100 };
+    let mut cellpack = vec![zap_contract_id.block, zap_contract_id.tx, 0u128, 2u128, 0x200, base_tokens.len() as u128];
+    for token in base_tokens {
+        cellpack.extend([token.block, token.tx]);
+    }
+    let init_block = zap_call_block(cellpack, OutPoint::null(), vec![])?;
+    index_block(&init_block, 1)?;
+    Ok((zap_contract_id, init_block))
+}
+
+// Calls an owner-only opcode by spending the auth token InitializeZap minted, moving `auth` to
+// wherever the token lands afterwards
+fn owner_call(
+    zap_contract_id: &AlkaneId,
+    auth: &mut (OutPoint, ProtoruneRuneId),
+    inputs: Vec<u128>,
+    height: u32
+) -> Result<Block> {
+    let mut cellpack = vec![zap_contract_id.block, zap_contract_id.tx];
+    cellpack.extend(inputs);
+    let block = zap_call_block(cellpack, auth.0, vec![ProtostoneEdict { id: auth.1, amount: 1, output: 1 }])?;
+    index_block(&block, height)?;
+    auth.0 = OutPoint { txid: block.txdata[0].compute_txid(), vout: 0 };
+    Ok(block)
+}
+
+fn zap_config(zap_contract_id: &AlkaneId, height: u32) -> Result<ZapConfig> {
+    let block = zap_call_block(vec![zap_contract_id.block, zap_contract_id.tx, 21u128], OutPoint::null(), vec![])?;
+    index_block(&block, height)?;
+    ZapConfig::decode(&zap_call_data(&block)?)
+}
+
+#[wasm_bindgen_test]
+fn test_initialize_rejects_duplicate_base_tokens() -> Result<()> {
+    println!("\n🚀 INITIALIZE DUPLICATE BASE TOKENS TEST");
+    println!("=========================================");
+
+    let token_a = AlkaneId { block: 6, tx: 0x300 };
+    let token_b = AlkaneId { block: 4, tx: 0x400 };
+    let (zap_contract_id, init_block) = deploy_zap_with_base_tokens(&[token_a, token_b, token_a])?;
+    assert!(zap_call_reverted(&init_block)?, "duplicate base tokens must be rejected");
+    println!("✅ Duplicate base tokens rejected");
+
+    // The rejected call must not have left the zap initialized
+    let retry_block = zap_call_block(
+        vec![zap_contract_id.block, zap_contract_id.tx, 0u128, 2u128, 0x200, 2u128, token_a.block, token_a.tx, token_b.block, token_b.tx],
+        OutPoint::null(),
+        vec![],
+    )?;
+    index_block(&retry_block, 2)?;
+    assert!(!zap_call_reverted(&retry_block)?);
+    assert_eq!(zap_config(&zap_contract_id, 3)?.base_tokens, vec![token_a, token_b]);
+    println!("✅ Distinct base tokens accepted afterwards");
+
+    Ok(())
+}
+
+#[wasm_bindgen_test]
+fn test_owner_configuration_opcodes() -> Result<()> {
+    println!("\n🚀 OWNER CONFIGURATION TEST");
+    println!("===========================");
+
+    let token_a = AlkaneId { block: 6, tx: 0x300 };
+    let token_b = AlkaneId { block: 4, tx: 0x400 };
+    let extra_token = AlkaneId { block: 5, tx: 0x500 };
+    let (zap_contract_id, init_block) = deploy_zap_with_base_tokens(&[token_a, token_b])?;
+    assert!(!zap_call_reverted(&init_block)?);
+
+    let init_outpoint = OutPoint { txid: init_block.txdata[0].compute_txid(), vout: 0 };
+    let init_sheet = load_sheet(&RuneTable::for_protocol(AlkaneMessageContext::protocol_tag())
+        .OUTPOINT_TO_RUNES.select(&consensus_encode(&init_outpoint)?));
+    let auth_token = *init_sheet.balances().keys().next().ok_or_else(|| anyhow::anyhow!("no auth token minted"))?;
+    let mut auth = (init_outpoint, auth_token);
+    println!("🔑 Auth token: {:?}", auth_token);
+
+    // Without the auth token the setters are refused
+    let unauthorized = zap_call_block(vec![zap_contract_id.block, zap_contract_id.tx, 29u128, 300u128], OutPoint::null(), vec![])?;
+    index_block(&unauthorized, 10)?;
+    assert!(zap_call_reverted(&unauthorized)?);
+    println!("✅ Setter without auth token rejected");
+
+    // SetDefaultSlippage(29) is capped at 100%
+    assert!(zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![29u128, 10_001u128], 11)?)?);
+    assert!(!zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![29u128, 300u128], 12)?)?);
+    assert_eq!(zap_config(&zap_contract_id, 13)?.default_slippage_bps, 300);
+    println!("✅ Default slippage above 100% rejected, 3% applied");
+
+    // AddBaseToken(17) and RemoveBaseToken(18) are idempotent
+    for height in [14, 15] {
+        assert!(!zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![17u128, extra_token.block, extra_token.tx], height)?)?);
+    }
+    assert_eq!(zap_config(&zap_contract_id, 16)?.base_tokens, vec![token_a, token_b, extra_token]);
+    for height in [17, 18] {
+        assert!(!zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![18u128, extra_token.block, extra_token.tx], height)?)?);
+    }
+    assert_eq!(zap_config(&zap_contract_id, 19)?.base_tokens, vec![token_a, token_b]);
+    println!("✅ Base token add/remove idempotent");
+
+    // SetTokenDecimals(47) stops at 18
+    assert!(zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![47u128, extra_token.block, extra_token.tx, 19u128], 20)?)?);
+    assert!(!zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![47u128, extra_token.block, extra_token.tx, 18u128], 21)?)?);
+    let decimals_block = zap_call_block(vec![zap_contract_id.block, zap_contract_id.tx, 48u128, extra_token.block, extra_token.tx], OutPoint::null(), vec![])?;
+    index_block(&decimals_block, 22)?;
+    assert_eq!(zap_call_data(&decimals_block)?, 18u128.to_le_bytes().to_vec());
+    println!("✅ Token decimals above 18 rejected, 18 recorded");
+
+    // SetPaused(35) only takes 0 or 1, and a pause stops zaps
+    assert!(zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![35u128, 2u128], 23)?)?);
+    assert!(!zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![35u128, 1u128], 24)?)?);
+    let paused_zap = zap_call_block(
+        vec![
+            zap_contract_id.block, zap_contract_id.tx, 4u128,
+            extra_token.block, extra_token.tx, 100u128,
+            token_a.block, token_a.tx, token_b.block, token_b.tx,
+            0u128, 0u128, 500u128, 0u128, 0u128, 0u128,
+        ],
+        OutPoint::null(),
+        vec![],
+    )?;
+    index_block(&paused_zap, 25)?;
+    let paused_events = zap_call_events(&paused_zap)?;
+    assert!(paused_events.iter().any(|event| match event {
+        TraceEvent::RevertContext(response) => String::from_utf8_lossy(&response.inner.data).contains("Zaps are paused"),
+        _ => false,
+    }));
+    assert!(!zap_call_reverted(&owner_call(&zap_contract_id, &mut auth, vec![35u128, 0u128], 26)?)?);
+    println!("✅ Pause flag validated and enforced");
+
+    Ok(())
+}